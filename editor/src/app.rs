@@ -15,8 +15,10 @@ use super::code_validation;
 use super::editor;
 use super::editor::CommandBuffer;
 use super::editor::Controller;
+use super::project;
 use super::save_state;
 use super::ui_toolkit::UiToolkit;
+use super::undo;
 
 // TODO: this is a mess, but not as bad as it was before (the part about the builtins)
 fn init_controller(_interpreter: &env::Interpreter) -> Controller {
@@ -42,9 +44,20 @@ impl App {
 
         // this is usually commented out
         let mut controller = init_controller(&interpreter);
-        _load_saved_code_from_disk(&mut controller, &mut interpreter.env.borrow_mut());
-
-        init_save_state(&mut command_buffer, &mut interpreter.env.borrow_mut());
+        // selections have to be there before the code gets loaded, so the code editors can pick
+        // them up when they're made
+        let loaded_state = save_state::load();
+        controller.load_saved_selections(&loaded_state.open_code_editors);
+        controller.load_recent_projects(loaded_state.recent_projects.clone());
+        // opening a project clears out the recovery file, so grab it first
+        let recovered_work = save_state::load_recovery();
+        open_startup_project(&mut controller, &mut interpreter.env.borrow_mut());
+        controller.offer_recovery(recovered_work);
+
+        init_save_state(loaded_state,
+                        &mut command_buffer,
+                        &mut interpreter.env.borrow_mut());
+        controller.restore_world_history(&interpreter.env.borrow());
 
         let command_buffer = Rc::new(RefCell::new(command_buffer));
         Self { interpreter,
@@ -69,7 +82,15 @@ impl App {
     }
 
     pub fn flush_commands(&mut self, mut async_executor: &mut async_executor::AsyncExecutor) {
+        project::autosave_if_due(&mut self.controller, &self.interpreter.env().borrow());
         let mut command_buffer = self.command_buffer.borrow_mut();
+        if !command_buffer.has_queued_commands() {
+            return;
+        }
+        let world_before = match self.controller.take_last_world_snapshot() {
+            Some(snapshot) => snapshot,
+            None => undo::take_snapshot(&self.controller, &self.interpreter.env().borrow()),
+        };
         while command_buffer.has_queued_commands() {
             //println!("some queued commands, flushing");
             command_buffer.flush_to_controller(&mut self.controller);
@@ -81,25 +102,35 @@ impl App {
                                               &self.controller,
                                               &mut command_buffer);
         }
+        self.controller
+            .record_world_history(&world_before, &self.interpreter.env().borrow());
+        // catches selections changing and windows getting scrolled
+        self.controller.save_state();
+    }
+
+    // for closing the window, which asks about unsaved changes the same way File > Exit does
+    #[allow(unused)] // unused in wasm
+    pub fn request_exit(&mut self) {
+        self.command_buffer
+            .borrow_mut()
+            .request_project_action(project::ProjectAction::Exit)
     }
 }
 
-fn init_save_state(command_buffer: &mut CommandBuffer, env: &mut env::ExecutionEnvironment) {
-    let loaded_state = save_state::load();
+fn init_save_state(loaded_state: save_state::StateDeserialize,
+                   command_buffer: &mut CommandBuffer,
+                   env: &mut env::ExecutionEnvironment) {
     let env_genie = env_genie::EnvGenie::new(env);
-    for code_location in loaded_state.open_code_editors.iter() {
-        match code_location {
+    for open_code_editor in loaded_state.open_code_editors.iter() {
+        match &open_code_editor.location {
             CodeLocation::Function(id) => {
                 env_genie.get_code_func(*id)
                          .map(|code_func| command_buffer.load_code_func(code_func.clone()));
             }
 
-            CodeLocation::Script(_id) => {
-                // lazy, no support for scripts yet
-            }
-            CodeLocation::Test(_id) => {
-                // lazy, no support for tests yet
-            }
+            // scripts and tests get their code editors when they're loaded from disk, and their
+            // windows come back along with the window positions
+            CodeLocation::Script(_id) | CodeLocation::Test(_id) => {}
             CodeLocation::JSONHTTPClientURLParams(id)
             | CodeLocation::JSONHTTPClientURL(id)
             | CodeLocation::JSONHTTPClientTestSection(id)
@@ -112,30 +143,39 @@ fn init_save_state(command_buffer: &mut CommandBuffer, env: &mut env::ExecutionE
                     .get_chat_program(*id)
                     .map(|chat_program| command_buffer.load_chat_program(chat_program.clone()));
             }
+            CodeLocation::WebhookProgram(id) => {
+                env_genie.get_webhook_program(*id)
+                         .map(|program| command_buffer.load_webhook_program(program.clone()));
+            }
+            CodeLocation::ScheduledProgram(id) => {
+                env_genie.get_scheduled_program(*id)
+                         .map(|program| command_buffer.load_scheduled_program(program.clone()));
+            }
         }
     }
 
     let window_positions = loaded_state.window_positions;
+    let recent_opener_items = loaded_state.recent_opener_items;
     command_buffer.add_controller_command(move |controller| {
                       controller.load_serialized_window_positions(window_positions);
+                      controller.load_recent_opener_items(recent_opener_items);
                   })
 }
 
-pub fn _load_saved_code_from_disk(controller: &mut Controller, env: &mut ExecutionEnvironment) {
-    let codestring = include_str!("../../codesample.json");
-    let the_world: code_loading::TheWorld = code_loading::deserialize(codestring).unwrap();
-    for script in the_world.scripts {
-        controller.load_script(script)
-    }
-    for test in the_world.tests {
-        controller.load_test(test);
+// the project that was open last time, or the sample code if there isn't one or it won't open
+fn open_startup_project(controller: &mut Controller, env: &mut ExecutionEnvironment) {
+    let last_project = controller.recent_projects().first().cloned();
+    if let Some(path) = last_project {
+        // a pending merge doesn't load anything until it's resolved, so there still has to be
+        // something open behind the merge window
+        if project::open(path, controller, env).is_ok() && controller.merge_view().is_none() {
+            return;
+        }
     }
+    project::open_untitled(sample_world(), controller, env)
+}
 
-    // TODO: this is duped in irctest.rs
-    for function in the_world.functions {
-        env.add_function_box(function);
-    }
-    for typespec in the_world.typespecs {
-        env.add_typespec_box(typespec);
-    }
+fn sample_world() -> code_loading::TheWorld {
+    let codestring = include_str!("../../codesample.json");
+    code_loading::deserialize(codestring).unwrap()
 }
@@ -1,6 +1,6 @@
 use crate::code_generation::{new_function_call, new_string_literal};
 use cs::builtins;
-use cs::chat_program::ChatProgram;
+use cs::chat_program::{ChatProgram, ChatTrigger};
 use cs::lang;
 use lazy_static::lazy_static;
 use rand::rngs::OsRng;
@@ -19,6 +19,7 @@ pub fn example_chat_program() -> ChatProgram {
                                                       Box::new(new_string_literal("Hello".into())) });
     ChatProgram { id: lang::new_id(),
                   prefix: format!("!{}", verb_me()),
+                  trigger: ChatTrigger::Prefix,
                   code: lang::Block { expressions:
                                           vec![new_function_call(*builtins::CHAT_REPLY_FUNC_ID,
                                                                  vec![arg])],
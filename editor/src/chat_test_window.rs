@@ -1,3 +1,5 @@
+use cs::chat_format;
+use cs::chat_format::{Flavor, OutgoingChatMessage};
 use itertools::Itertools;
 
 pub struct ChatTestWindow {
@@ -19,6 +21,11 @@ impl ChatTestWindow {
     pub fn add_message(&mut self, sender: String, text: String) {
         self.messages.push(Message { sender, text })
     }
+
+    pub fn add_reply(&mut self, sender: String, reply: &OutgoingChatMessage) {
+        self.add_message(sender,
+                         chat_format::render_with_target(reply, Flavor::Plain))
+    }
 }
 
 struct Message {
@@ -25,6 +25,8 @@ use cs::code_function;
 use cs::enums::EnumVariant;
 use cs::env::ExecutionEnvironment;
 use cs::env_genie::EnvGenie;
+use cs::external_func::ModifyableFunc;
+use cs::function::SettableArgs;
 use cs::lang;
 use cs::lang::{arg_typ_for_anon_func, typ_for_anonymous_function, CodeNode, Function};
 use cs::{builtins, env};
@@ -48,7 +50,7 @@ pub struct CodeEditor {
     pub show_output: bool,
 }
 
-#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum CodeLocation {
     Function(lang::ID),
     Script(lang::ID),
@@ -58,6 +60,195 @@ pub enum CodeLocation {
     JSONHTTPClientTestSection(lang::ID),
     JSONHTTPClientTransform(lang::ID),
     ChatProgram(lang::ID),
+    WebhookProgram(lang::ID),
+    ScheduledProgram(lang::ID),
+}
+
+// a hotkey, what the command palette calls it, and when it does anything. handle_keypress and the
+// command palette both go off of these, so they can't disagree about what the keys do
+struct KeyboardAction {
+    // the first one is what the command palette shows
+    keypresses: Vec<editor::Keypress>,
+    works_while_editing: bool,
+    // None for the ones that don't belong in the command palette, like moving the selection around
+    label: fn(&CodeEditor) -> Option<&'static str>,
+    is_available: fn(&CodeEditor) -> bool,
+    perform: fn(&mut CodeEditor, &mut env::Interpreter),
+}
+
+impl KeyboardAction {
+    fn is_available_in(&self, code_editor: &CodeEditor) -> bool {
+        (self.works_while_editing || !code_editor.editing) && (self.is_available)(code_editor)
+    }
+}
+
+fn keyboard_actions() -> Vec<KeyboardAction> {
+    use super::editor::{Key, Keypress};
+
+    let any_shift = |key| {
+        vec![Keypress::new(key, false, false),
+             Keypress::new(key, false, true)]
+    };
+    let any_modifiers = |key| {
+        vec![Keypress::new(key, false, false),
+             Keypress::new(key, false, true),
+             Keypress::new(key, true, false),
+             Keypress::new(key, true, true)]
+    };
+    let always = |_: &CodeEditor| true;
+    let has_selection = |code_editor: &CodeEditor| code_editor.get_last_selected_node().is_some();
+    // TODO: ctrl+shift+R should run the code, but that doesn't work right now
+    vec![KeyboardAction { keypresses: vec![Keypress::new(Key::O, false, false)],
+                          works_while_editing: false,
+                          label: |_| Some("Insert code on the line below"),
+                          is_available: always,
+                          perform: |code_editor, _| {
+                              code_editor.set_insertion_point_on_next_line_in_block()
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::O, false, true)],
+                          works_while_editing: false,
+                          label: |_| Some("Insert code on the line above"),
+                          is_available: always,
+                          perform: |code_editor, _| {
+                              code_editor.set_insertion_point_on_previous_line_in_block()
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::C, false, false)],
+                          works_while_editing: false,
+                          label: |code_editor| {
+                              code_editor.get_last_selected_node()
+                                         .map(|node| code_editor.edit_menu_text(node))
+                          },
+                          is_available: |code_editor| {
+                              code_editor.get_last_selected_node()
+                                         .map_or(false, |node| code_editor.can_be_edited(node))
+                          },
+                          // TODO: this needs to delete everything that's selected first
+                          perform: |code_editor, _| {
+                              if let Some(id) = code_editor.get_last_selected_node_id() {
+                                  code_editor.mark_as_editing(InsertionPoint::Editing(id));
+                              }
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::R, false, false)],
+                          works_while_editing: false,
+                          label: |_| Some("Replace"),
+                          is_available: |code_editor| {
+                              code_editor.get_last_selected_node_id()
+                                         .map_or(false, |id| code_editor.can_be_replaced(id))
+                          },
+                          perform: |code_editor, _| {
+                              code_editor.try_enter_replace_edit_for_selected_node();
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::A, false, false)],
+                          works_while_editing: false,
+                          label: |_| Some("Append to list"),
+                          is_available: |code_editor| {
+                              code_editor.get_last_selected_node()
+                                         .map_or(false, |node| code_editor.can_be_appended_to(node))
+                          },
+                          perform: |code_editor, _| {
+                              code_editor.try_append_in_selected_node();
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::W, false, true)],
+                          works_while_editing: false,
+                          label: |_| Some("Wrap with..."),
+                          is_available: has_selection,
+                          perform: |code_editor, _| {
+                              code_editor.try_enter_wrap_for_selected_node();
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::U, false, true)],
+                          works_while_editing: false,
+                          label: |_| Some("Unwrap..."),
+                          is_available: |code_editor| {
+                              code_editor.get_last_selected_node_id()
+                                         .map_or(false, |id| code_editor.can_be_unwrapped(id))
+                          },
+                          perform: |code_editor, _| {
+                              code_editor.try_enter_unwrap_for_selected_node();
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::E, false, true)],
+                          works_while_editing: false,
+                          label: |_| Some("Extract into variable"),
+                          is_available: has_selection,
+                          perform: |code_editor, _| {
+                              code_editor.extract_selected_code_into_variable();
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::V, false, true)],
+                          works_while_editing: false,
+                          label: |_| Some("Select the whole line"),
+                          is_available: has_selection,
+                          perform: |code_editor, _| {
+                              code_editor.select_current_line();
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::C, true, false)],
+                          works_while_editing: true,
+                          label: |_| Some("Copy"),
+                          is_available: has_selection,
+                          perform: |code_editor, interp| code_editor.copy_selection(interp) },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::V, true, false)],
+                          works_while_editing: true,
+                          label: |_| Some("Paste over"),
+                          is_available: has_selection,
+                          perform: |code_editor, _| code_editor.paste_selection() },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::D, false, false)],
+                          works_while_editing: false,
+                          label: |_| Some("Delete"),
+                          is_available: has_selection,
+                          perform: |code_editor, _| {
+                              println!("deleting selected code via hotkey");
+                              code_editor.delete_selected_code();
+                          } },
+         // the delete key does the same thing, and works even while editing
+         KeyboardAction { keypresses: any_modifiers(Key::Delete),
+                          works_while_editing: true,
+                          label: |_| None,
+                          is_available: has_selection,
+                          perform: |code_editor, _| {
+                              println!("deleting selected code via hotkey");
+                              code_editor.delete_selected_code();
+                          } },
+         KeyboardAction { keypresses: any_modifiers(Key::Escape),
+                          works_while_editing: false,
+                          label: |_| Some("Deselect code"),
+                          is_available: has_selection,
+                          perform: |code_editor, _| {
+                              code_editor.deselect_selected_code();
+                          } },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::U, false, false)],
+                          works_while_editing: false,
+                          label: |_| Some("Undo"),
+                          is_available: always,
+                          perform: |code_editor, _| code_editor.undo() },
+         KeyboardAction { keypresses: vec![Keypress::new(Key::R, true, false)],
+                          works_while_editing: false,
+                          label: |_| Some("Redo"),
+                          is_available: always,
+                          perform: |code_editor, _| code_editor.redo() },
+         KeyboardAction { keypresses: [vec![Keypress::new(Key::J, false, false)],
+                                       any_shift(Key::DownArrow)].concat(),
+                          works_while_editing: false,
+                          label: |_| None,
+                          is_available: always,
+                          perform: |code_editor, _| code_editor.try_select_down_one_node() },
+         KeyboardAction { keypresses: [vec![Keypress::new(Key::K, false, false)],
+                                       any_shift(Key::UpArrow)].concat(),
+                          works_while_editing: false,
+                          label: |_| None,
+                          is_available: always,
+                          perform: |code_editor, _| code_editor.try_select_up_one_node() },
+         KeyboardAction { keypresses: [vec![Keypress::new(Key::B, false, false),
+                                            Keypress::new(Key::H, false, false)],
+                                       any_shift(Key::LeftArrow)].concat(),
+                          works_while_editing: false,
+                          label: |_| None,
+                          is_available: always,
+                          perform: |code_editor, _| code_editor.try_select_back_one_node() },
+         KeyboardAction { keypresses: [vec![Keypress::new(Key::W, false, false),
+                                            Keypress::new(Key::L, false, false)],
+                                       any_shift(Key::RightArrow)].concat(),
+                          works_while_editing: false,
+                          label: |_| None,
+                          is_available: always,
+                          perform: |code_editor, _| code_editor.try_select_forward_one_node() },]
 }
 
 impl CodeEditor {
@@ -95,76 +286,21 @@ impl CodeEditor {
     }
 
     pub fn handle_keypress(&mut self, keypress: editor::Keypress, interp: &mut env::Interpreter) {
-        use super::editor::Key;
-
-        // don't perform any commands when in edit mode
-        match (self.editing, &keypress.key, &keypress.shift, &keypress.ctrl) {
-            (_, Key::C, false, true) => {
-                self.copy_selection(&interp);
-            }
-            (_, Key::V, false, true) => {
-                self.paste_selection();
-            }
-            (false, Key::Escape, _, _) => {
-                self.deselect_selected_code();
-            }
-            (false, Key::J, false, false) | (false, Key::DownArrow, _, false) => {
-                self.try_select_down_one_node()
-            }
-            (false, Key::K, false, false) | (false, Key::UpArrow, _, false) => {
-                self.try_select_up_one_node()
-            }
-            (false, Key::B, false, false)
-            | (false, Key::LeftArrow, _, false)
-            | (false, Key::H, false, false) => self.try_select_back_one_node(),
-            (false, Key::W, false, false)
-            | (false, Key::RightArrow, _, false)
-            | (false, Key::L, false, false) => self.try_select_forward_one_node(),
-            (false, Key::C, false, false) => {
-                // TODO: this needs to delete everything that's selected first
-                if let Some(node) = self.get_last_selected_node() {
-                    #[allow(mutable_borrow_reservation_conflict)]
-                    if self.can_be_edited(node) {
-                        self.mark_as_editing(InsertionPoint::Editing(node.id()));
-                    }
-                }
-            }
-            (false, Key::D, false, false) | (_, Key::Delete, _, _) => {
-                println!("deleting selected code via hotkey");
-                self.delete_selected_code();
-            }
-            (false, Key::A, false, false) => {
-                self.try_append_in_selected_node();
-            }
-            (false, Key::E, true, false) => {
-                self.extract_selected_code_into_variable();
-            }
-            (false, Key::W, true, false) => {
-                self.try_enter_wrap_for_selected_node();
-            }
-            (false, Key::U, true, false) => {
-                self.try_enter_unwrap_for_selected_node();
-            }
-            (false, Key::R, false, false) => {
-                self.try_enter_replace_edit_for_selected_node();
-            }
-            (false, Key::R, true, true) => {
-                // TODO: this doesn't work right now
-                //self.run(&self.get_code().clone());
-            }
-            (false, Key::R, false, true) => {
-                self.redo();
-            }
-            (false, Key::O, true, false) => self.set_insertion_point_on_previous_line_in_block(),
-            (false, Key::O, false, false) => self.set_insertion_point_on_next_line_in_block(),
-            (false, Key::U, false, false) => self.undo(),
-            (false, Key::V, true, false) => {
-                self.select_current_line();
-            }
-            _ => {}
+        let actions = keyboard_actions();
+        let mut actions = actions.iter().filter(|action| action.is_available_in(self));
+        if let Some(action) = actions.find(|action| action.keypresses.contains(&keypress)) {
+            (action.perform)(self, interp)
         }
     }
 
+    // the hotkeys that would do something to the code right now, for the command palette
+    pub fn available_keyboard_actions(&self) -> Vec<(&'static str, editor::Keypress)> {
+        keyboard_actions().into_iter()
+                          .filter(|action| action.is_available_in(self))
+                          .filter_map(|action| Some(((action.label)(self)?, action.keypresses[0])))
+                          .collect()
+    }
+
     pub fn hide_insert_code_menu(&mut self) {
         self.insert_code_menu = None;
         self.editing = false
@@ -409,6 +545,17 @@ impl CodeEditor {
         Some(())
     }
 
+    // whether try_append_in_selected_node would do anything with this node selected
+    fn can_be_appended_to(&self, node: &CodeNode) -> bool {
+        match node {
+            CodeNode::ListLiteral(_) => true,
+            _ => match self.code_genie.find_parent(node.id()) {
+                Some(CodeNode::ListLiteral(_)) => true,
+                _ => false,
+            },
+        }
+    }
+
     // TODO: factor duplicate code between this method and the next
     fn set_insertion_point_on_previous_line_in_block(&mut self) {
         if self.no_node_selected() {
@@ -515,6 +662,44 @@ impl CodeEditor {
         Some(())
     }
 
+    // hands back the new function, which still needs to be added to the env
+    pub fn extract_into_function(&mut self,
+                                 node_ids: &[lang::ID],
+                                 env_genie: &EnvGenie)
+                                 -> Option<code_function::CodeFunction> {
+        let (mutation_result, code_func) =
+            self.mutation_master
+                .extract_into_function(node_ids, &self.code_genie, env_genie)?;
+        self.apply_mutation_result(mutation_result);
+        Some(code_func)
+    }
+
+    pub fn can_be_extracted_into_function(&self,
+                                          node_ids: &[lang::ID],
+                                          env_genie: &EnvGenie)
+                                          -> bool {
+        self.mutation_master
+            .extract_into_function(node_ids, &self.code_genie, env_genie)
+            .is_some()
+    }
+
+    pub fn inline_function_call(&mut self,
+                                function_call_id: lang::ID,
+                                env_genie: &EnvGenie)
+                                -> Option<()> {
+        let mutation_result =
+            self.mutation_master
+                .inline_function_call(function_call_id, &self.code_genie, env_genie)?;
+        self.apply_mutation_result(mutation_result);
+        Some(())
+    }
+
+    pub fn can_be_inlined(&self, id: lang::ID, env_genie: &EnvGenie) -> bool {
+        self.mutation_master
+            .find_inlinable_function_call(id, &self.code_genie, env_genie)
+            .is_some()
+    }
+
     pub fn can_be_deleted(&self, id: lang::ID) -> bool {
         // TODO: this should be deleting a range
         self.mutation_master
@@ -1657,6 +1842,167 @@ impl MutationMaster {
                          new_cursor_position: Some(assignment_expression_id) }
     }
 
+    // pulls a run of expressions out of a block and into a brand new function, leaving a call to
+    // it behind. variables the expressions use from outside of themselves become the function's
+    // arguments
+    pub fn extract_into_function(&self,
+                                 node_ids: &[lang::ID],
+                                 code_genie: &CodeGenie,
+                                 env_genie: &EnvGenie)
+                                 -> Option<(MutationResult, code_function::CodeFunction)> {
+        let orig_block = code_genie.find_parent(*node_ids.first()?)?.as_block()?;
+        let positions = node_ids.iter()
+                                .map(|id| orig_block.find_position(*id))
+                                .collect::<Option<Vec<_>>>()?;
+        let start = *positions.iter().min()?;
+        let end = *positions.iter().max()? + 1;
+        // has to be one unbroken run of expressions from the same block
+        if positions.iter().unique().count() != end - start {
+            return None;
+        }
+        let extracted = orig_block.expressions[start..end].to_vec();
+        let extracted_ids = extracted.iter()
+                                     .flat_map(|code| code.self_with_all_children_dfs())
+                                     .map(|code| code.id())
+                                     .collect::<HashSet<_>>();
+
+        let mut arg_id_by_variable_id = HashMap::new();
+        let mut arg_defs = vec![];
+        let mut call_arg_exprs = vec![];
+        for code in extracted.iter()
+                             .flat_map(|code| code.self_with_all_children_dfs())
+        {
+            match code {
+                CodeNode::VariableReference(vr) => {
+                    if arg_id_by_variable_id.contains_key(&vr.assignment_id) {
+                        continue;
+                    }
+                    let antecedent = locals::find_antecedent_for_variable_reference(vr, false,
+                                                                                    code_genie,
+                                                                                    env_genie)?;
+                    let defined_by = antecedent.place.defined_by_code_id();
+                    if defined_by.map_or(false, |id| extracted_ids.contains(&id)) {
+                        continue;
+                    }
+                    let typ = code_genie.guess_type_for_variable(antecedent.place, env_genie)
+                                        .ok()?;
+                    let arg_def = lang::ArgumentDefinition::new(typ, antecedent.name);
+                    arg_id_by_variable_id.insert(vr.assignment_id, arg_def.id);
+                    call_arg_exprs.push(code_generation::new_variable_reference(vr.assignment_id));
+                    arg_defs.push(arg_def);
+                }
+                // the new function would only be changing its own copy of the variable
+                CodeNode::Reassignment(lang::Reassignment { assignment_id, .. })
+                | CodeNode::ReassignListIndex(lang::ReassignListIndex { assignment_id, .. })
+                    if !extracted_ids.contains(assignment_id) =>
+                {
+                    return None
+                }
+                // this would return out of the new function instead of the one it's in now
+                CodeNode::EarlyReturn(_) => return None,
+                _ => (),
+            }
+        }
+
+        let return_type = code_genie.guess_type(extracted.last()?, env_genie).ok()?;
+        let mut body = CodeNode::Block(code_generation::new_block(extracted));
+        remap_variable_references(&mut body, &arg_id_by_variable_id);
+        let mut code_func = code_function::CodeFunction::new();
+        code_func.name = "Extracted function".to_owned();
+        code_func.set_args(arg_defs);
+        code_func.set_return_type(return_type);
+        code_func.set_code(body.as_block()?.clone());
+
+        let function_call =
+            code_generation::new_function_call_with_arg_exprs(&code_func,
+                                                              call_arg_exprs.into_iter());
+        let function_call_id = function_call.id();
+        let mut new_block = orig_block.clone();
+        new_block.expressions
+                 .splice(start..end, iter::once(function_call));
+        let mut new_root = code_genie.root().clone();
+        new_root.replace(CodeNode::Block(new_block));
+
+        // variables made in the extracted code can't be used by whatever comes after it anymore
+        if find_assignment_ids_referenced_in_code(&new_root).any(|id| extracted_ids.contains(&id)) {
+            return None;
+        }
+        Some((MutationResult::new(new_root, Some(function_call_id), false), code_func))
+    }
+
+    // only calls that are a whole line of their own can be inlined. if the call were inside of
+    // a bigger expression, the function's lines would have to run before that whole expression,
+    // which changes what happens for loop conditions and for anything evaluated before the call
+    pub fn find_inlinable_function_call<'a>(
+        &self,
+        function_call_id: lang::ID,
+        code_genie: &'a CodeGenie,
+        env_genie: &'a EnvGenie)
+        -> Option<(&'a lang::FunctionCall, &'a code_function::CodeFunction)> {
+        code_genie.find_parent(function_call_id)?.as_block()?;
+        let function_call = code_genie.find_node(function_call_id)?
+                                      .as_function_call()
+                                      .ok()?;
+        let code_func = env_genie.get_code_func(function_call.function_reference().function_id)?;
+        let body = code_func.code();
+        // an early return would return out of the caller instead, and a function that calls
+        // itself can't be inlined all the way
+        let cant_inline = body.all_children_dfs_iter().any(|code| match code {
+                                                          CodeNode::EarlyReturn(_) => true,
+                                                          CodeNode::FunctionCall(fc) => {
+                                                              fc.function_reference().function_id
+                                                              == code_func.id()
+                                                          }
+                                                          _ => false,
+                                                      });
+        if cant_inline {
+            return None;
+        }
+        Some((function_call, code_func))
+    }
+
+    // the reverse of extract_into_function: swaps out a call to a code function for the
+    // function's code, with each argument turned into a variable
+    pub fn inline_function_call(&self,
+                                function_call_id: lang::ID,
+                                code_genie: &CodeGenie,
+                                env_genie: &EnvGenie)
+                                -> Option<MutationResult> {
+        let (function_call, code_func) =
+            self.find_inlinable_function_call(function_call_id, code_genie, env_genie)?;
+        let arg_defs = code_func.takes_args();
+        let mut new_exprs = vec![];
+        let mut assignment_id_by_arg_id = HashMap::new();
+        for arg in &function_call.args {
+            let arg = match arg {
+                CodeNode::Argument(arg) => arg,
+                _ => return None,
+            };
+            let arg_def = arg_defs.iter()
+                                  .find(|arg_def| arg_def.id == arg.argument_definition_id)?;
+            let assignment =
+                code_generation::new_assignment(arg_def.short_name.clone(), (*arg.expr).clone());
+            assignment_id_by_arg_id.insert(arg_def.id, assignment.id);
+            new_exprs.push(CodeNode::Assignment(assignment));
+        }
+        let mut body = with_new_ids(&code_func.code());
+        remap_variable_references(&mut body, &assignment_id_by_arg_id);
+        match body {
+            CodeNode::Block(block) => new_exprs.extend(block.expressions),
+            _ => return None,
+        }
+
+        // the function's lines go right where the call was
+        let orig_block = code_genie.find_parent(function_call_id)?.as_block()?;
+        let pos = orig_block.find_position(function_call_id)?;
+        let new_cursor_position = new_exprs.first().map(|code| code.id());
+        let mut new_block = orig_block.clone();
+        new_block.expressions.splice(pos..=pos, new_exprs);
+        let mut new_root = code_genie.root().clone();
+        new_root.replace(CodeNode::Block(new_block));
+        Some(MutationResult::new(new_root, new_cursor_position, false))
+    }
+
     fn delete_code(&self,
                    node_ids: impl ExactSizeIterator<Item = lang::ID>,
                    genie: CodeGenie,
@@ -1980,6 +2326,22 @@ pub fn update_code_in_env(location: CodeLocation,
             chat_program.code = code.as_block().unwrap().clone();
             env.add_function(chat_program);
         }
+        CodeLocation::WebhookProgram(webhook_program_id) => {
+            let env_genie = EnvGenie::new(&env);
+            let mut webhook_program = env_genie.get_webhook_program(webhook_program_id)
+                                               .unwrap()
+                                               .clone();
+            webhook_program.code = code.as_block().unwrap().clone();
+            env.add_function(webhook_program);
+        }
+        CodeLocation::ScheduledProgram(scheduled_program_id) => {
+            let env_genie = EnvGenie::new(&env);
+            let mut scheduled_program = env_genie.get_scheduled_program(scheduled_program_id)
+                                                 .unwrap()
+                                                 .clone();
+            scheduled_program.code = code.as_block().unwrap().clone();
+            env.add_function(scheduled_program);
+        }
     }
 }
 
@@ -2000,13 +2362,88 @@ pub fn required_return_type(location: CodeLocation, env_genie: &EnvGenie) -> Opt
         }
         CodeLocation::JSONHTTPClientURLParams(_) => Some(LIST_OF_FORM_PARAMS.clone()),
         CodeLocation::JSONHTTPClientURL(_) => Some(lang::Type::from_spec(&*lang::STRING_TYPESPEC)),
+        CodeLocation::WebhookProgram(webhook_program_id) => {
+            Some(env_genie.get_webhook_program(webhook_program_id).unwrap().returns())
+        }
         CodeLocation::ChatProgram(_)
+        | CodeLocation::ScheduledProgram(_)
         | CodeLocation::Script(_)
         | CodeLocation::Test(_)
         | CodeLocation::JSONHTTPClientTestSection(_) => None,
     }
 }
 
+// points variable references (and reassignments) at different variables
+fn remap_variable_references(code: &mut CodeNode, new_id_by_old_id: &HashMap<lang::ID, lang::ID>) {
+    let assignment_id = match code {
+        CodeNode::VariableReference(lang::VariableReference { assignment_id, .. })
+        | CodeNode::Reassignment(lang::Reassignment { assignment_id, .. })
+        | CodeNode::ReassignListIndex(lang::ReassignListIndex { assignment_id, .. }) => {
+            Some(assignment_id)
+        }
+        _ => None,
+    };
+    if let Some(assignment_id) = assignment_id {
+        if let Some(new_id) = new_id_by_old_id.get(assignment_id) {
+            *assignment_id = *new_id;
+        }
+    }
+    for child in code.children_mut() {
+        remap_variable_references(child, new_id_by_old_id);
+    }
+}
+
+// a copy of the code where every node has a new ID, and the variables inside of it are referred to
+// by their new IDs, so the copy can live in the same place as the original
+fn with_new_ids(code: &CodeNode) -> CodeNode {
+    let mut new_id_by_old_id = HashMap::new();
+    for code in code.self_with_all_children_dfs() {
+        let new_id = lang::new_id();
+        new_id_by_old_id.insert(code.id(), new_id);
+        match code {
+            CodeNode::AnonymousFunction(anon_func) => {
+                new_id_by_old_id.insert(anon_func.takes_arg.id, lang::new_id());
+            }
+            CodeNode::Match(mach) => {
+                for variant_id in mach.branch_by_variant_id.keys() {
+                    new_id_by_old_id.insert(mach.variable_id(*variant_id),
+                                            lang::Match::make_variable_id(new_id, *variant_id));
+                }
+            }
+            _ => (),
+        }
+    }
+    // IDs show up in all kinds of places in the different nodes, but in JSON they're all strings
+    let mut json = serde_json::to_value(code).unwrap();
+    replace_ids_in_json(&mut json, &new_id_by_old_id);
+    serde_json::from_value(json).unwrap()
+}
+
+fn replace_ids_in_json(json: &mut serde_json::Value,
+                       new_id_by_old_id: &HashMap<lang::ID, lang::ID>) {
+    match json {
+        serde_json::Value::String(s) => {
+            if let Some(new_id) = s.parse::<lang::ID>()
+                                   .ok()
+                                   .and_then(|id| new_id_by_old_id.get(&id))
+            {
+                *s = new_id.to_string();
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                replace_ids_in_json(value, new_id_by_old_id);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for value in map.values_mut() {
+                replace_ids_in_json(value, new_id_by_old_id);
+            }
+        }
+        _ => (),
+    }
+}
+
 pub fn find_assignment_ids_referenced_in_codes<'a>(codes: impl Iterator<Item = &'a lang::CodeNode>)
                                                    -> impl Iterator<Item = lang::ID> + 'a {
     codes.flat_map(find_assignment_ids_referenced_in_code)
@@ -34,6 +34,22 @@ pub enum VariableAntecedentPlace {
     },
 }
 
+impl VariableAntecedentPlace {
+    // the code node the variable comes from. function arguments don't come from any code
+    pub fn defined_by_code_id(&self) -> Option<lang::ID> {
+        match self {
+            VariableAntecedentPlace::Assignment { assignment_id } => Some(*assignment_id),
+            VariableAntecedentPlace::ForLoop { for_loop_id } => Some(*for_loop_id),
+            VariableAntecedentPlace::AnonFuncArgument { anonymous_function_id,
+                                                        .. } => Some(*anonymous_function_id),
+            VariableAntecedentPlace::FunctionArgument { .. } => None,
+            VariableAntecedentPlace::MatchVariant { match_statement_id, .. } => {
+                Some(*match_statement_id)
+            }
+        }
+    }
+}
+
 impl VariableAntecedent {
     pub fn assignment_id(&self) -> lang::ID {
         match &self.place {
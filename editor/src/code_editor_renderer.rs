@@ -23,6 +23,11 @@ use crate::editor::drag_drop::DragDropPayload;
 use crate::editor::value_renderer::ValueRenderer;
 use crate::editor::{CommandBuffer, Key, Keypress};
 use crate::insert_code_menu::{CodeSearchParams, InsertCodeMenuOptionsGroup};
+use crate::references;
+use crate::references::ReferenceTarget;
+use crate::rename;
+use crate::rename::RenameTarget;
+use crate::structural_diff::{NodeChange, StructuralDiff};
 use crate::ui_toolkit::{
     ChildRegionFrameStyle, ChildRegionHeight, ChildRegionStyle, ChildRegionTopPadding,
     ChildRegionWidth, DrawFnRef,
@@ -62,6 +67,9 @@ pub struct CodeEditorRenderer<'a, T> {
     // mode and clicks also shouldn't do anything. surely a cleaner way to do this but whatever RN
     is_rendering_menu: RefCell<bool>,
     render_menu_when_next_possible: RefCell<bool>,
+    // when showing a diff, the code being rendered is the new version, and the old version's
+    // editor is used for drawing in whatever got deleted
+    diff: Option<(&'a StructuralDiff, &'a code_editor::CodeEditor)>,
 }
 
 // ok stupid but all the methods on this take &self instead of &mut self because the ImGui closures
@@ -79,7 +87,16 @@ impl<'a, T: UiToolkit> CodeEditorRenderer<'a, T> {
                arg_nesting_level: RefCell::new(0),
                env_genie,
                is_rendering_menu: RefCell::new(false),
-               render_menu_when_next_possible: RefCell::new(false) }
+               render_menu_when_next_possible: RefCell::new(false),
+               diff: None }
+    }
+
+    pub fn with_diff(mut self,
+                     diff: &'a StructuralDiff,
+                     old_code_editor: &'a code_editor::CodeEditor)
+                     -> Self {
+        self.diff = Some((diff, old_code_editor));
+        self
     }
 
     pub fn render(&self, height: ChildRegionHeight) -> T::DrawResult {
@@ -948,7 +965,7 @@ impl<'a, T: UiToolkit> CodeEditorRenderer<'a, T> {
 
         let draw = || self.render_context_menu(code_node, &draw);
 
-        let draw_fn = &|| {
+        let draw = || {
             if self.is_part_of_selection(code_node.id()) {
                 self.draw_selected(self.code_node_cursor_scroll_hash(code_node), &draw)
             } else {
@@ -956,6 +973,8 @@ impl<'a, T: UiToolkit> CodeEditorRenderer<'a, T> {
             }
         };
 
+        let draw_fn = &|| self.draw_diff_around(code_node, &draw);
+
         // this is good for debugging
         // self.ui_toolkit
         //     .draw_all_on_same_line(&[draw_fn, &|| {
@@ -1150,6 +1169,70 @@ impl<'a, T: UiToolkit> CodeEditorRenderer<'a, T> {
                             })
                         })
                 },
+                &|| {
+                    let code_node = self.code_editor
+                                        .code_genie
+                                        .find_node(code_node_id_to_act_on)
+                                        .unwrap();
+                    let target = rename::target_for_code_node(code_node,
+                                                              self.code_editor.id(),
+                                                              &self.code_editor.code_genie,
+                                                              self.env_genie);
+                    if let Some(target) = target {
+                        let cmd_buffer = Rc::clone(&self.command_buffer);
+                        self.ui_toolkit.draw_menu_item("Rename...", move || {
+                            cmd_buffer.borrow_mut().open_rename_dialog(target);
+                        })
+                    } else {
+                        self.ui_toolkit.draw_all(&[])
+                    }
+                },
+                &|| {
+                    let code_node = self.code_editor
+                                        .code_genie
+                                        .find_node(code_node_id_to_act_on)
+                                        .unwrap();
+                    if let Some(target) = references::target_for_code_node(code_node, self.env_genie) {
+                        let cmd_buffer = Rc::clone(&self.command_buffer);
+                        self.ui_toolkit.draw_menu_item("Find references", move || {
+                            cmd_buffer.borrow_mut().show_references(target);
+                        })
+                    } else {
+                        self.ui_toolkit.draw_all(&[])
+                    }
+                },
+                &|| {
+                    let node_ids = self.code_to_drag_drop(code_node_id_to_act_on)
+                                       .map(|code_node| code_node.id())
+                                       .collect_vec();
+                    if self.code_editor
+                           .can_be_extracted_into_function(&node_ids, self.env_genie)
+                    {
+                        let cmd_buffer = Rc::clone(&self.command_buffer);
+                        self.ui_toolkit.draw_menu_item("Extract into function", move || {
+                            cmd_buffer.borrow_mut().extract_into_function(node_ids.clone());
+                        })
+                    } else {
+                        self.ui_toolkit.draw_all(&[])
+                    }
+                },
+                &|| {
+                    if self.code_editor.can_be_inlined(code_node_id_to_act_on, self.env_genie) {
+                        let cmd_buffer = Rc::clone(&self.command_buffer);
+                        self.ui_toolkit.draw_menu_item("Inline function", move || {
+                            cmd_buffer.borrow_mut().add_editor_and_interp_command(
+                                move |editor, interpreter| {
+                                    let env = interpreter.env.borrow();
+                                    let env_genie = EnvGenie::new(&env);
+                                    editor.inline_function_call(code_node_id_to_act_on,
+                                                                &env_genie);
+                                },
+                            )
+                        })
+                    } else {
+                        self.ui_toolkit.draw_all(&[])
+                    }
+                },
                 &|| {
                     let cmd_buffer = Rc::clone(&self.command_buffer);
                     self.ui_toolkit.draw_menu_item("Wrap with...", move || {
@@ -1355,11 +1438,63 @@ impl<'a, T: UiToolkit> CodeEditorRenderer<'a, T> {
 
     // TODO: combine the insertion point stuff with the insertion point stuff elsewhere, mainly
     // the is_insertion_point_before_or_after stuff
+    // marks the node up with how it changed, and draws in anything deleted from where it is
+    fn draw_diff_around(&self,
+                        code_node: &CodeNode,
+                        draw: &dyn Fn() -> T::DrawResult)
+                        -> T::DrawResult {
+        let diff = match self.diff {
+            Some((diff, _)) => diff,
+            None => return draw(),
+        };
+        let draw = &|| match diff.change(code_node.id()) {
+            Some(change) => self.ui_toolkit.draw_box_around(diff_color(change), draw),
+            None => draw(),
+        };
+        let deleted = diff.deleted_beside(code_node.id());
+        if deleted.is_empty() {
+            return draw();
+        }
+        self.ui_toolkit
+            .draw_all_on_same_line(&[&|| self.render_deleted_code(&deleted), draw])
+    }
+
+    fn render_deleted_in_block(&self,
+                               block_id: lang::ID,
+                               after: Option<lang::ID>)
+                               -> T::DrawResult {
+        match self.diff {
+            Some((diff, _)) => self.render_deleted_code(&diff.deleted_in_block(block_id, after)),
+            None => self.ui_toolkit.draw_all(&[]),
+        }
+    }
+
+    fn render_deleted_code(&self, deleted: &[&CodeNode]) -> T::DrawResult {
+        let old_code_editor = match self.diff {
+            Some((_, old_code_editor)) => old_code_editor,
+            None => return self.ui_toolkit.draw_all(&[]),
+        };
+        let command_buffer_that_does_nothing = Rc::new(RefCell::new(CommandBuffer::new()));
+        let old_renderer = &CodeEditorRenderer::new(self.ui_toolkit,
+                                                    old_code_editor,
+                                                    command_buffer_that_does_nothing,
+                                                    self.env_genie);
+        let removing_color = transparency(colorscheme!(removing_color), 0.4);
+        draw_all_iter!(T::self.ui_toolkit,
+                       deleted.iter().map(|code_node| move || {
+                                         self.ui_toolkit.draw_box_around(removing_color, &|| {
+                                                            old_renderer.render_code(code_node)
+                                                        })
+                                     }))
+    }
+
     fn render_block(&self, block: &lang::Block) -> T::DrawResult {
         // TODO: i think i could move the is_insertion_point_before_or_after crapola to here
-        let not_inserting_code = self.insertion_point().is_none();
+        // diffs can't be edited, so there's nowhere to add code while showing one
+        let not_inserting_code = self.insertion_point().is_none() && self.diff.is_none();
 
         self.ui_toolkit.draw_all(&[
+            &|| self.render_deleted_in_block(block.id, None),
             &|| match self.code_editor.insertion_point_for_menu() {
                 Some(InsertionPoint::BeginningOfBlock(block_id)) if block.id == block_id => {
                     self.render_insert_code_node()
@@ -1397,6 +1532,7 @@ impl<'a, T: UiToolkit> CodeEditorRenderer<'a, T> {
                                            self.ui_toolkit.draw_all(&[
                                               &|| self.render_code_insertion_menu_here_if_it_was_requested(),
                                               &|| self.render_code_line_in_block(code),
+                                              &|| self.render_deleted_in_block(block.id, Some(code.id())),
                                               &|| self.render_code_insertion_menu_here_if_it_was_requested(),
                                            ])
                                        }
@@ -2314,6 +2450,16 @@ impl<'a, T: UiToolkit> CodeEditorRenderer<'a, T> {
     }
 }
 
+// see-through, because it gets drawn over the code
+fn diff_color(change: NodeChange) -> Color {
+    let color = match change {
+        NodeChange::Inserted => colorscheme!(adding_color),
+        NodeChange::Moved => colorscheme!(cool_color),
+        NodeChange::Changed => colorscheme!(warning_color),
+    };
+    transparency(color, 0.4)
+}
+
 struct PerEditorCommandBuffer {
     overlapped_code_node_ids: Vec<lang::ID>,
     actual_command_buffer: Rc<RefCell<editor::CommandBuffer>>,
@@ -2329,6 +2475,24 @@ impl PerEditorCommandBuffer {
                overlapped_code_node_ids: vec![] }
     }
 
+    pub fn show_references(&mut self, target: ReferenceTarget) {
+        self.actual_command_buffer
+            .borrow_mut()
+            .add_controller_command(move |controller| controller.show_references(target));
+    }
+
+    pub fn extract_into_function(&mut self, node_ids: Vec<lang::ID>) {
+        self.actual_command_buffer
+            .borrow_mut()
+            .extract_into_function(self.editor_id, node_ids);
+    }
+
+    pub fn open_rename_dialog(&mut self, target: RenameTarget) {
+        self.actual_command_buffer
+            .borrow_mut()
+            .open_rename_dialog(target);
+    }
+
     pub fn add_overlapped_code_node_id(&mut self, id: lang::ID) {
         self.overlapped_code_node_ids.push(id);
     }
@@ -2370,6 +2534,7 @@ impl PerEditorCommandBuffer {
     }
 
     pub fn add_editor_command<F: FnOnce(&mut code_editor::CodeEditor) + 'static>(&mut self, f: F) {
+        self.focus_editor();
         self.actual_command_buffer
             .borrow_mut()
             .add_editor_command(self.editor_id, |editor, _| f(editor));
@@ -2380,8 +2545,17 @@ impl PerEditorCommandBuffer {
                                                  + 'static>(
         &mut self,
         f: F) {
+        self.focus_editor();
         self.actual_command_buffer
             .borrow_mut()
             .add_editor_command(self.editor_id, f);
     }
+
+    // anything the user does inside of a code editor makes it the one the command palette acts on
+    fn focus_editor(&self) {
+        let editor_id = self.editor_id;
+        self.actual_command_buffer
+            .borrow_mut()
+            .add_controller_command(move |controller| controller.focus_code_editor(editor_id));
+    }
 }
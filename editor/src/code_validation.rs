@@ -22,15 +22,21 @@ pub fn validate_and_fix(env: &mut env::ExecutionEnvironment,
     Validator::new(env, controller, cmd_buffer).validate_and_fix_all_code();
 }
 
-fn all_code<'a>(env_genie: &'a EnvGenie,
+pub fn all_code<'a>(env_genie: &'a EnvGenie,
                 controller: &'a Controller)
                 -> impl Iterator<Item = (CodeLocation, &'a lang::Block)> + 'a {
     let chat_programs = env_genie.list_chat_programs()
                                  .map(|cp| (CodeLocation::ChatProgram(cp.id()), &cp.code));
+    let webhook_programs =
+        env_genie.list_webhook_programs()
+                 .map(|wp| (CodeLocation::WebhookProgram(wp.id()), &wp.code));
+    let scheduled_programs =
+        env_genie.list_scheduled_programs()
+                 .map(|sp| (CodeLocation::ScheduledProgram(sp.id()), &sp.code));
 
     env_genie.list_code_funcs().map(|code_func| {
         (CodeLocation::Function(code_func.id()), &code_func.block)
-    }).chain(chat_programs).chain(
+    }).chain(chat_programs).chain(webhook_programs).chain(scheduled_programs).chain(
         env_genie.list_json_http_clients().flat_map(|json_http_client| {
             once((CodeLocation::JSONHTTPClientURLParams(json_http_client.id()), &json_http_client.gen_url_params_code))
                 .chain(once((CodeLocation::JSONHTTPClientURL(json_http_client.id()), &json_http_client.gen_url_code)))
@@ -224,6 +230,17 @@ impl<'a> Validator<'a> {
                 chat_program.code = block;
                 self.cmd_buffer.load_chat_program(chat_program)
             }
+            CodeLocation::WebhookProgram(id) => {
+                let mut webhook_program = self.env_genie.get_webhook_program(id).unwrap().clone();
+                webhook_program.code = block;
+                self.cmd_buffer.load_webhook_program(webhook_program)
+            }
+            CodeLocation::ScheduledProgram(id) => {
+                let mut scheduled_program =
+                    self.env_genie.get_scheduled_program(id).unwrap().clone();
+                scheduled_program.code = block;
+                self.cmd_buffer.load_scheduled_program(scheduled_program)
+            }
         }
     }
 }
@@ -275,9 +292,11 @@ impl<'a> FixableProblemFinder<'a> {
                 let field_ids_in_code_literal = strukt_literal.fields()
                                                               .map(|field| field.struct_field_id)
                                                               .collect::<HashSet<_>>();
-                let strukt = self.env_genie
-                                 .find_struct(strukt_literal.struct_id)
-                                 .unwrap();
+                // the struct could've been deleted out from under the literal
+                let strukt = match self.env_genie.find_struct(strukt_literal.struct_id) {
+                    Some(strukt) => strukt,
+                    None => continue,
+                };
                 let fields_defined_in_struct = strukt.fields
                                                      .iter()
                                                      .map(|field| (field.id, field))
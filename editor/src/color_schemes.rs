@@ -37,6 +37,9 @@ pub struct ColorScheme {
     pub action_color: Color,
     // traditionally a green, like green in diffs
     pub adding_color: Color,
+    // and a red, like red in diffs. themes from before there was one get the default
+    #[serde(default = "default_removing_color")]
+    pub removing_color: Color,
     // TODO: rename this, not sure what to call it. but i'm just mass replacing
     // blue right now. and the places i'm using blue don't seem to be super
     // related in function. so for now i'll call it cool_color
@@ -58,6 +61,10 @@ pub struct ColorScheme {
     pub input_bg_color: Color,
 }
 
+fn default_removing_color() -> Color {
+    IMGUI_CLASSIC_COLOR_SCHEME.removing_color
+}
+
 impl ColorScheme {
     pub fn from_json(json_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
         Ok(serde_json::from_str(json_str)?)
@@ -67,6 +74,7 @@ impl ColorScheme {
 pub static IMGUI_CLASSIC_COLOR_SCHEME: ColorScheme =
     ColorScheme { text_color: [1., 1., 1., 1.],
                   adding_color: [80. / 255., 161. / 255., 78. / 255., 1.],
+                  removing_color: [190. / 255., 70. / 255., 70. / 255., 1.],
                   button_active_color: [0.46, 0.54, 0.8, 0.6],
                   // pulled out from imgui classic theme, style.colors[ImGuiCol::ButtonHovered]
                   button_hover_color: [0.4, 0.48, 0.71, 0.6],
@@ -0,0 +1,222 @@
+use crate::chat::example_chat_program;
+use crate::code_editor::{CodeEditor, CodeLocation};
+use crate::editor::{Controller, Key, Keypress};
+use crate::opener::MenuItem;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::project::ProjectAction;
+use crate::references;
+use crate::references::ReferenceTarget;
+use crate::rename;
+use crate::window_positions::THEME_EDITOR_WINDOW_ID;
+use cs::builtins::ParseJSON;
+use cs::code_function;
+use cs::enums;
+use cs::env_genie::EnvGenie;
+use cs::json_http_client::JSONHTTPClient;
+use cs::lang;
+use cs::scheduled_program::ScheduledProgram;
+use cs::scripts;
+use cs::structs;
+use cs::webhook_program::WebhookProgram;
+use itertools::Itertools;
+
+// everything that can be done from the command palette right now, grouped under headings. the
+// code editor the user was last in decides what shows up for the selected code and the window
+pub fn sections(controller: &Controller,
+                env_genie: &EnvGenie)
+                -> Vec<(&'static str, Vec<MenuItem>)> {
+    let mut sections = vec![];
+    if let Some(code_editor) = controller.focused_code_editor() {
+        sections.push(("Selected code", selected_code_commands(code_editor, env_genie)));
+        sections.push(("This window", window_commands(code_editor, controller)));
+    }
+    sections.push(("File", file_commands(controller)));
+    sections.push(("Edit", edit_commands(controller)));
+    sections.push(("View", view_commands()));
+    sections
+}
+
+// the hotkeys go through handle_keypress, so they do exactly what pressing the keys would. the
+// rest are from the code's right click menu
+fn selected_code_commands(code_editor: &CodeEditor, env_genie: &EnvGenie) -> Vec<MenuItem> {
+    let editor_id = code_editor.id();
+    let mut commands =
+        code_editor.available_keyboard_actions()
+                   .into_iter()
+                   .map(|(label, keypress)| {
+                       MenuItem::command(label, Some(keypress), move |cmd_buffer| {
+                           cmd_buffer.add_editor_command(editor_id, move |code_editor, interp| {
+                                         code_editor.handle_keypress(keypress, interp)
+                                     })
+                       })
+                   })
+                   .collect_vec();
+    if code_editor.editing {
+        return commands;
+    }
+    let code_node = match code_editor.get_last_selected_node_id()
+                                     .and_then(|id| code_editor.code_genie.find_node(id))
+    {
+        Some(code_node) => code_node,
+        None => return commands,
+    };
+    let code_node_id = code_node.id();
+
+    let rename_target =
+        rename::target_for_code_node(code_node, editor_id, &code_editor.code_genie, env_genie);
+    if let Some(target) = rename_target {
+        commands.push(MenuItem::command("Rename...", None, move |cmd_buffer| {
+                          cmd_buffer.open_rename_dialog(target)
+                      }));
+    }
+    if let Some(target) = references::target_for_code_node(code_node, env_genie) {
+        commands.push(MenuItem::command("Find references", None, move |cmd_buffer| {
+                          cmd_buffer.add_controller_command(move |controller| {
+                                        controller.show_references(target)
+                                    })
+                      }));
+    }
+    let node_ids = code_editor.selected_node_ids.clone();
+    if code_editor.can_be_extracted_into_function(&node_ids, env_genie) {
+        commands.push(MenuItem::command("Extract into function", None, move |cmd_buffer| {
+                          cmd_buffer.extract_into_function(editor_id, node_ids.clone())
+                      }));
+    }
+    if code_editor.can_be_inlined(code_node_id, env_genie) {
+        commands.push(MenuItem::command("Inline function", None, move |cmd_buffer| {
+                          cmd_buffer.add_editor_command(editor_id, move |code_editor, interp| {
+                                        let env = interp.env.borrow();
+                                        let env_genie = EnvGenie::new(&env);
+                                        code_editor.inline_function_call(code_node_id, &env_genie);
+                                    })
+                      }));
+    }
+    commands
+}
+
+fn window_commands(code_editor: &CodeEditor, controller: &Controller) -> Vec<MenuItem> {
+    let location = match code_editor.location {
+        Some(location) => location,
+        None => return vec![],
+    };
+    let mut commands = vec![];
+    if let CodeLocation::Function(function_id) = location {
+        let target = ReferenceTarget::Function(function_id);
+        commands.push(MenuItem::command("Find references to this function",
+                                        None,
+                                        move |cmd_buffer| {
+                                            cmd_buffer.add_controller_command(move |controller| {
+                                                          controller.show_references(target)
+                                                      })
+                                        }));
+    }
+    if controller.project.path.is_some() {
+        let owner_id = references::owner_id(location);
+        commands.push(MenuItem::command("Changes since last save", None, move |cmd_buffer| {
+                          cmd_buffer.compare_with_saved(Some(owner_id))
+                      }));
+    }
+    if let Some(window_id) = references::window_id_for_location(location) {
+        commands.push(MenuItem::command("Close window", None, move |cmd_buffer| {
+                          cmd_buffer.add_controller_command(move |controller| {
+                                        controller.close_window(window_id)
+                                    })
+                      }));
+    }
+    commands
+}
+
+fn file_commands(controller: &Controller) -> Vec<MenuItem> {
+    let mut commands = vec![MenuItem::command("Open...",
+                                              Some(Keypress::new(Key::O, true, false)),
+                                              |cmd_buffer| {
+                                                  cmd_buffer.add_controller_command(|controller| {
+                                                                controller.open_opener()
+                                                            })
+                                              })];
+    commands.extend(project_commands(controller));
+    commands.extend(vec![MenuItem::command("Add new chat program", None, |cmd_buffer| {
+                             cmd_buffer.load_chat_program(example_chat_program())
+                         }),
+                         MenuItem::command("Add new webhook program", None, |cmd_buffer| {
+                             cmd_buffer.load_webhook_program(WebhookProgram::new())
+                         }),
+                         MenuItem::command("Add new scheduled program", None, |cmd_buffer| {
+                             cmd_buffer.load_scheduled_program(ScheduledProgram::new())
+                         }),
+                         MenuItem::command("Add new JSON HTTP client", None, |cmd_buffer| {
+                             cmd_buffer.load_json_http_client(JSONHTTPClient::new())
+                         }),
+                         MenuItem::command("Add new JSON parser", None, |cmd_buffer| {
+                             cmd_buffer.load_parse_json(ParseJSON::new(lang::Type::from_spec(&*lang::STRING_TYPESPEC)))
+                         }),
+                         MenuItem::command("Add new script", None, |cmd_buffer| {
+                             cmd_buffer.add_controller_command(|controller| {
+                                           controller.load_script(scripts::Script::new())
+                                       })
+                         }),
+                         MenuItem::command("Add new function", None, |cmd_buffer| {
+                             cmd_buffer.load_code_func(code_function::CodeFunction::new())
+                         }),
+                         MenuItem::command("Add Struct", None, |cmd_buffer| {
+                             cmd_buffer.load_typespec(structs::Struct::new())
+                         }),
+                         MenuItem::command("Add Enum", None, |cmd_buffer| {
+                             cmd_buffer.load_typespec(enums::Enum::new())
+                         }),]);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.push(MenuItem::command("Exit", None, |cmd_buffer| {
+                      cmd_buffer.request_project_action(ProjectAction::Exit)
+                  }));
+    commands
+}
+
+// opening a project and saving one somewhere new need a file picker, so those are only in the
+// File menu
+#[cfg(not(target_arch = "wasm32"))]
+fn project_commands(controller: &Controller) -> Vec<MenuItem> {
+    let mut commands = vec![MenuItem::command("New project", None, |cmd_buffer| {
+                                cmd_buffer.request_project_action(ProjectAction::New)
+                            })];
+    if let Some(path) = controller.project.path.clone() {
+        commands.push(MenuItem::command("Save", None, move |cmd_buffer| {
+                          cmd_buffer.save_project(path.clone(), None)
+                      }));
+    }
+    commands
+}
+
+// project files only make sense where there's a filesystem
+#[cfg(target_arch = "wasm32")]
+fn project_commands(_controller: &Controller) -> Vec<MenuItem> {
+    vec![]
+}
+
+fn edit_commands(controller: &Controller) -> Vec<MenuItem> {
+    let mut commands = vec![];
+    if let Some(edit) = controller.next_world_undo() {
+        commands.push(MenuItem::command(&format!("Undo {}", edit.describe()),
+                                        None,
+                                        |cmd_buffer| cmd_buffer.undo_world_edit()));
+    }
+    if let Some(edit) = controller.next_world_redo() {
+        commands.push(MenuItem::command(&format!("Redo {}", edit.describe()),
+                                        None,
+                                        |cmd_buffer| cmd_buffer.redo_world_edit()));
+    }
+    commands.push(MenuItem::command("Undo history", None, |cmd_buffer| {
+                      cmd_buffer.open_undo_tree_window()
+                  }));
+    commands
+}
+
+fn view_commands() -> Vec<MenuItem> {
+    vec![MenuItem::command("Theme editor", None, |cmd_buffer| {
+             cmd_buffer.add_controller_command(|controller| {
+                           controller.open_window(*THEME_EDITOR_WINDOW_ID)
+                       })
+         }),
+         MenuItem::command("Reset layout", None, |cmd_buffer| {
+             cmd_buffer.add_controller_command(|controller| controller.reset_layout())
+         }),]
+}
@@ -0,0 +1,202 @@
+// deleting functions and typespecs without leaving anything behind that points at them. code that
+// uses the thing being deleted can have those usages swapped out for placeholders, but things like
+// a struct field or a function argument of a deleted type can't be patched up that way, so those
+// block the deletion until the user changes them by hand
+use cs::code_generation;
+use cs::env::ExecutionEnvironment;
+use cs::env_genie::EnvGenie;
+use cs::json_http_client::JSONHTTPClient;
+use cs::lang;
+use cs::lang::{CodeNode, Function};
+
+use crate::code_editor::update_code_in_env;
+use crate::editor::Controller;
+use crate::references;
+use crate::references::{Reference, ReferenceTarget};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeletionTarget {
+    Function(lang::ID),
+    TypeSpec(lang::ID),
+}
+
+impl DeletionTarget {
+    fn reference_target(self) -> ReferenceTarget {
+        match self {
+            DeletionTarget::Function(id) => ReferenceTarget::Function(id),
+            DeletionTarget::TypeSpec(id) => ReferenceTarget::TypeSpec(id),
+        }
+    }
+
+    // getting a field or matching on a variant doesn't mention the type itself, but those nodes
+    // can't be rendered once the type is gone, so they have to go too
+    fn reference_targets(self, env_genie: &EnvGenie) -> Vec<ReferenceTarget> {
+        let mut targets = vec![self.reference_target()];
+        if let DeletionTarget::TypeSpec(id) = self {
+            if let Some(strukt) = env_genie.find_struct(id) {
+                targets.extend(strukt.fields
+                                     .iter()
+                                     .map(|field| ReferenceTarget::StructField(field.id)));
+            }
+            if let Some(eneom) = env_genie.find_enum(id) {
+                targets.extend(eneom.variants
+                                    .iter()
+                                    .map(|variant| ReferenceTarget::EnumVariant(variant.id)));
+            }
+        }
+        targets
+    }
+}
+
+pub struct Dependents {
+    // code that'll get placeholders put in
+    pub references: Vec<Reference>,
+    // everything else, which has to be fixed by hand before the deletion can go through
+    pub blockers: Vec<String>,
+}
+
+impl Dependents {
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty() && self.blockers.is_empty()
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        !self.blockers.is_empty()
+    }
+}
+
+pub fn describe_target(target: DeletionTarget, env_genie: &EnvGenie) -> String {
+    references::describe_target(target.reference_target(), env_genie)
+}
+
+pub fn find_dependents(target: DeletionTarget,
+                       controller: &Controller,
+                       env_genie: &EnvGenie)
+                       -> Dependents {
+    let references = target.reference_targets(env_genie)
+                           .into_iter()
+                           .flat_map(|reference_target| {
+                               references::find_references(reference_target, controller, env_genie)
+                           })
+                           .collect();
+    let blockers = match target {
+        DeletionTarget::Function(_) => vec![],
+        DeletionTarget::TypeSpec(typespec_id) => find_type_users(typespec_id, env_genie),
+    };
+    Dependents { references,
+                 blockers }
+}
+
+// struct fields, enum variants and function signatures that mention the type. the type's own
+// fields and variants go along with it, so they don't count, or a recursive type could never be
+// deleted
+fn find_type_users(typespec_id: lang::ID, env_genie: &EnvGenie) -> Vec<String> {
+    let mut users = vec![];
+    for strukt in env_genie.list_structs()
+                           .filter(|strukt| strukt.id != typespec_id)
+    {
+        for field in &strukt.fields {
+            if references::type_mentions(&field.field_type, typespec_id) {
+                users.push(format!("Field {}.{}", strukt.name, field.name));
+            }
+        }
+    }
+    for eneom in env_genie.list_enums()
+                          .filter(|eneom| eneom.id != typespec_id)
+    {
+        for variant in &eneom.variants {
+            let variant_type = match &variant.variant_type {
+                Some(variant_type) => variant_type,
+                None => continue,
+            };
+            if references::type_mentions(variant_type, typespec_id) {
+                users.push(format!("Variant {}.{}", eneom.name, variant.name));
+            }
+        }
+    }
+    for func in env_genie.all_functions() {
+        let takes_it = func.takes_args()
+                           .iter()
+                           .any(|arg| references::type_mentions(&arg.arg_type, typespec_id));
+        if takes_it || references::type_mentions(&func.returns(), typespec_id) {
+            users.push(format!("Function {}", func.name()));
+        }
+    }
+    users
+}
+
+// puts a placeholder in place of every piece of code that uses the target, in every code
+// location, including the code editors showing them
+pub fn replace_with_placeholders(target: DeletionTarget,
+                                 controller: &mut Controller,
+                                 env: &mut ExecutionEnvironment) {
+    let replacements_by_location = {
+        let env_genie = EnvGenie::new(env);
+        let reference_targets = target.reference_targets(&env_genie);
+        let description = format!("Was {}", describe_target(target, &env_genie));
+        references::all_code_including_tests(controller, &env_genie)
+            .into_iter()
+            .filter_map(|(location, code)| {
+                let replacements =
+                    code.all_children_dfs_iter()
+                        .filter(|code_node| {
+                            reference_targets.iter()
+                                             .any(|&reference_target| {
+                                                 references::refers_to(code_node, reference_target)
+                                             })
+                        })
+                        .map(|code_node| {
+                            (code_node.id(),
+                             placeholder_for(code_node, description.clone(), &env_genie))
+                        })
+                        .collect::<Vec<_>>();
+                if replacements.is_empty() {
+                    None
+                } else {
+                    Some((location, code, replacements))
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    for (location, mut code, replacements) in replacements_by_location {
+        // a usage nested inside of another one is already gone by the time we get to it, and
+        // replacing it does nothing
+        for (node_id, placeholder) in replacements {
+            code.replace_with(node_id, placeholder);
+        }
+        controller.load_code(code.clone(), location);
+        update_code_in_env(location, code, controller, env);
+    }
+}
+
+// function calls keep their return type so the surrounding code still lines up. anything else
+// was made from the deleted type, so there's nothing better than Any to give it
+fn placeholder_for(code_node: &CodeNode, description: String, env_genie: &EnvGenie) -> CodeNode {
+    let typ = match code_node {
+        CodeNode::FunctionCall(function_call) => {
+            env_genie.find_function(function_call.function_reference().function_id)
+                     .map(|func| func.returns())
+        }
+        _ => None,
+    };
+    code_generation::new_placeholder(description,
+                                     typ.unwrap_or_else(|| {
+                                            lang::Type::from_spec(&*lang::ANY_TYPESPEC)
+                                        }))
+}
+
+pub fn delete(target: DeletionTarget, controller: &mut Controller, env: &mut ExecutionEnvironment) {
+    match target {
+        DeletionTarget::Function(id) => {
+            let is_json_http_client = env.find_function(id)
+                                         .map_or(false, |func| {
+                                             func.downcast_ref::<JSONHTTPClient>().is_some()
+                                         });
+            if is_json_http_client {
+                controller.remove_json_http_client_builder(id)
+            }
+            env.delete_function(id)
+        }
+        DeletionTarget::TypeSpec(id) => env.delete_typespec(id),
+    }
+}
@@ -2,6 +2,7 @@ use std::cell::RefCell;
 //use debug_cell::RefCell;
 use std::collections::HashMap;
 use std::iter;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use http;
@@ -16,26 +17,42 @@ use super::json_http_client_builder::JSONHTTPClientBuilder;
 use super::save_state;
 use super::ui_toolkit::{SelectableItem, UiToolkit};
 use super::window_positions::{
-    WindowPositions, CHAT_TEST_WINDOW_ID, QUICK_START_GUIDE_WINDOW_ID, THEME_EDITOR_WINDOW_ID,
+    WindowPositions, CHAT_TEST_WINDOW_ID, DIFF_WINDOW_ID, MERGE_WINDOW_ID,
+    QUICK_START_GUIDE_WINDOW_ID, REFERENCES_WINDOW_ID, THEME_EDITOR_WINDOW_ID, UNDO_TREE_WINDOW_ID,
 };
 use crate::chat::example_chat_program;
 use crate::chat_test_window::ChatTestWindow;
 use crate::colorscheme;
+use crate::deletion;
+use crate::deletion::DeletionTarget;
 use crate::draw_all_iter;
 use crate::json_http_client_builder::{HTTPResponseIntermediateValue, NAME_OF_ROOT};
+use crate::merge;
+use crate::merge::MergeView;
 use crate::opener::MenuItem;
 use crate::opener::Opener;
+use crate::project;
+use crate::project::{Project, ProjectAction};
+use crate::references;
+use crate::references::{CallDirection, Reference, ReferenceTarget, ReferencesView};
+use crate::rename;
+use crate::rename::RenameTarget;
 use crate::schema_builder::{Indent, IndentRef, SchemaType, ALL_FIELD_TYPES};
 use crate::send_to_server_overlay::{SendToServerOverlay, SendToServerOverlayStatus};
+use crate::structural_diff::{CodeDiff, Comparison, DiffView};
 use crate::theme_editor_renderer::ThemeEditorRenderer;
 use crate::ui_toolkit::{ChildRegionHeight, DrawFnRef};
+use crate::undo;
+use crate::undo::{WorldEdit, WorldHistoryChange, WorldSnapshot, WorldUndoHistory};
 use crate::window_positions::Window;
 use cs::builtins;
-use cs::chat_program::{flush_reply_buffer, message_received, ChatProgram};
+use cs::builtins::ParseJSON;
+use cs::chat_program::{flush_reply_buffer, message_received, ChatProgram, CHAT_TRIGGER_LIST};
 use cs::code_function;
 use cs::code_loading;
 use cs::code_loading::TheWorld;
 use cs::config;
+use cs::edit_token::{EditScope, EditTokenInfo};
 use cs::enums;
 use cs::env;
 use cs::env::Interpreter;
@@ -43,6 +60,8 @@ use cs::env_genie;
 use cs::external_func;
 use cs::function;
 use cs::http_client;
+use cs::http_request;
+use cs::json_codec::{EnumTagging, JSONCodec, ENUM_TAGGING_LIST, NAME_STYLE_LIST};
 use cs::json_http_client::{JSONHTTPClient, HTTP_METHOD_LIST};
 use cs::jsstuff;
 use cs::lang;
@@ -50,7 +69,10 @@ use cs::lang::{CodeNode, Function, Value, ID};
 use cs::pystuff;
 use cs::scripts;
 use cs::structs;
+use cs::scheduled_program::ScheduledProgram;
 use cs::tests;
+use cs::webhook_program::WebhookProgram;
+use cs::world_merge;
 use cs::{await_eval_result, EnvGenie};
 
 pub mod drag_drop;
@@ -63,7 +85,11 @@ use cs::validation::{find_problems_for_code, ProblemPreventingRun};
 use std::hash::{Hash, Hasher};
 use value_renderer::ValueRenderer;
 
-#[derive(Debug, Copy, Clone)]
+// how many things the opener remembers having opened, for its recent section
+const MAX_RECENT_OPENER_ITEMS: usize = 10;
+const MAX_RECENT_PROJECTS: usize = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Keypress {
     pub key: Key,
     pub ctrl: bool,
@@ -74,6 +100,13 @@ impl Keypress {
     pub fn new(key: Key, ctrl: bool, shift: bool) -> Keypress {
         Keypress { key, ctrl, shift }
     }
+
+    // for showing the user which keys to press, like Ctrl+Shift+P
+    pub fn describe(&self) -> String {
+        let ctrl = if self.ctrl { "Ctrl+" } else { "" };
+        let shift = if self.shift { "Shift+" } else { "" };
+        format!("{}{}{:?}", ctrl, shift, self.key)
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -91,6 +124,7 @@ pub enum Key {
     X,
     R,
     O,
+    P,
     U,
     V,
     Delete,
@@ -114,6 +148,11 @@ impl TestResult {
     }
 }
 
+pub struct RenameDialog {
+    pub target: RenameTarget,
+    pub new_name: String,
+}
+
 pub struct Controller {
     // these actually would need to get persisted to the filesystem
     script_by_id: HashMap<ID, scripts::Script>,
@@ -121,6 +160,8 @@ pub struct Controller {
     // this is purely ephemeral GUI state for display only:
     selected_test_id_by_subject: HashMap<tests::TestSubject, ID>,
     code_editor_by_id: HashMap<ID, code_editor::CodeEditor>,
+    // the code editor the user did something in last, for the command palette to act on
+    focused_code_editor_id: Option<ID>,
     test_result_by_func_id: HashMap<ID, TestResult>,
     json_client_builder_by_func_id: HashMap<ID, JSONHTTPClientBuilder>,
     // a record of the builtins kept here so we know which things are builtins
@@ -128,7 +169,29 @@ pub struct Controller {
     builtins: builtins::Builtins,
 
     pub opener: Option<Opener>,
+    pub rename_dialog: Option<RenameDialog>,
+    // the thing the user asked to delete, while we're asking what to do about everything using it
+    pub deletion_dialog: Option<DeletionTarget>,
+    references_view: Option<ReferencesView>,
+    // every change to functions, typespecs and scripts, from any editor
+    world_history: WorldUndoHistory,
+    // what the world looked like after the last batch of commands. the world only changes when
+    // commands get flushed, so this doubles as the before for the next batch
+    last_world_snapshot: Option<WorldSnapshot>,
     window_positions: WindowPositions,
+    // most recently opened first
+    recent_opener_items: Vec<lang::ID>,
+    // selections from last session, waiting on their code editors to get loaded
+    saved_selections: Vec<(code_editor::CodeLocation, Vec<lang::ID>)>,
+    pub project: Project,
+    // most recently opened first
+    recent_projects: Vec<PathBuf>,
+    // what the user wanted to do when we stopped to ask about their unsaved changes
+    pub unsaved_changes_dialog: Option<ProjectAction>,
+    // unsaved changes from last session, while we're asking whether to bring them back
+    pub recovered_work: Option<save_state::RecoveryDeserialize>,
+    merge_view: Option<MergeView>,
+    diff_view: Option<DiffView>,
     pub send_to_server_overlay: Rc<RefCell<SendToServerOverlay>>,
     chat_test_window: Rc<RefCell<ChatTestWindow>>,
 }
@@ -137,13 +200,27 @@ impl<'a> Controller {
     pub fn new(builtins: builtins::Builtins) -> Controller {
         Controller { test_result_by_func_id: HashMap::new(),
                      code_editor_by_id: HashMap::new(),
+                     focused_code_editor_id: None,
                      script_by_id: HashMap::new(),
                      test_by_id: HashMap::new(),
                      selected_test_id_by_subject: HashMap::new(),
                      json_client_builder_by_func_id: HashMap::new(),
                      builtins,
                      opener: None,
+                     rename_dialog: None,
+                     deletion_dialog: None,
+                     references_view: None,
+                     world_history: WorldUndoHistory::new(),
+                     last_world_snapshot: None,
                      window_positions: WindowPositions::default(),
+                     recent_opener_items: vec![],
+                     saved_selections: vec![],
+                     project: Project::new(None, 0),
+                     recent_projects: vec![],
+                     unsaved_changes_dialog: None,
+                     recovered_work: None,
+                     merge_view: None,
+                     diff_view: None,
                      send_to_server_overlay: Rc::new(RefCell::new(SendToServerOverlay::new())),
                      chat_test_window: Rc::new(RefCell::new(ChatTestWindow::new())) }
     }
@@ -179,12 +256,148 @@ impl<'a> Controller {
         self.save_state();
     }
 
-    fn save_state(&self) {
-        let open_code_editors = self.code_editor_by_id
-                                    .values()
-                                    .map(|editor| editor.location.unwrap())
-                                    .collect_vec();
-        save_state::save(&self.window_positions, &open_code_editors)
+    pub fn set_window_scroll(&mut self, window_id: lang::ID, scroll_y: f32) {
+        self.window_positions.set_window_scroll(window_id, scroll_y);
+    }
+
+    pub fn reset_layout(&mut self) {
+        self.window_positions.reset_layout();
+        self.save_state();
+    }
+
+    pub fn load_recent_opener_items(&mut self, recent_opener_items: Vec<lang::ID>) {
+        self.recent_opener_items = recent_opener_items;
+    }
+
+    pub fn recent_opener_items(&self) -> &[lang::ID] {
+        &self.recent_opener_items
+    }
+
+    pub fn remember_opened(&mut self, id: lang::ID) {
+        self.recent_opener_items
+            .retain(|recent_id| *recent_id != id);
+        self.recent_opener_items.insert(0, id);
+        self.recent_opener_items.truncate(MAX_RECENT_OPENER_ITEMS);
+    }
+
+    pub fn load_recent_projects(&mut self, recent_projects: Vec<PathBuf>) {
+        self.recent_projects = recent_projects;
+    }
+
+    pub fn recent_projects(&self) -> &[PathBuf] {
+        &self.recent_projects
+    }
+
+    pub fn remember_project(&mut self, path: PathBuf) {
+        self.recent_projects
+            .retain(|recent_path| *recent_path != path);
+        self.recent_projects.insert(0, path);
+        self.recent_projects.truncate(MAX_RECENT_PROJECTS);
+        self.save_state();
+    }
+
+    pub fn open_unsaved_changes_dialog(&mut self, action: ProjectAction) {
+        self.unsaved_changes_dialog = Some(action);
+    }
+
+    pub fn close_unsaved_changes_dialog(&mut self) {
+        self.unsaved_changes_dialog = None;
+    }
+
+    pub fn offer_recovery(&mut self, recovered_work: Option<save_state::RecoveryDeserialize>) {
+        self.recovered_work = recovered_work;
+    }
+
+    pub fn discard_recovered_work(&mut self) {
+        self.recovered_work = None;
+        if !self.project.is_dirty() {
+            save_state::remove_recovery();
+        }
+    }
+
+    pub fn start_merge(&mut self, merge_view: MergeView) {
+        self.merge_view = Some(merge_view);
+        self.open_window(*MERGE_WINDOW_ID);
+    }
+
+    pub fn merge_view(&self) -> Option<&MergeView> {
+        self.merge_view.as_ref()
+    }
+
+    pub fn resolve_merge_conflict(&mut self, conflict_id: String, side: world_merge::Side) {
+        if let Some(merge_view) = self.merge_view.as_mut() {
+            merge_view.resolve(conflict_id, side)
+        }
+    }
+
+    pub fn close_merge(&mut self) {
+        self.merge_view = None;
+        self.close_window(*MERGE_WINDOW_ID);
+    }
+
+    pub fn show_diff_view(&mut self, diff_view: DiffView) {
+        self.diff_view = Some(diff_view);
+        self.open_window(*DIFF_WINDOW_ID);
+    }
+
+    pub fn diff_view(&self) -> Option<&DiffView> {
+        self.diff_view.as_ref()
+    }
+
+    // gets rid of everything that isn't a builtin, so a different world can be loaded in its place
+    pub fn clear_world(&mut self, env: &mut env::ExecutionEnvironment) {
+        let function_ids = env.functions
+                              .keys()
+                              .filter(|id| !self.is_builtin(**id))
+                              .cloned()
+                              .collect_vec();
+        for id in function_ids {
+            env.delete_function(id);
+        }
+        let typespec_ids = env.typespecs
+                              .keys()
+                              .filter(|id| !self.is_builtin(**id))
+                              .cloned()
+                              .collect_vec();
+        for id in typespec_ids {
+            env.delete_typespec(id);
+        }
+        self.script_by_id.clear();
+        self.test_by_id.clear();
+        self.selected_test_id_by_subject.clear();
+        self.code_editor_by_id.clear();
+        self.focused_code_editor_id = None;
+        self.test_result_by_func_id.clear();
+        self.json_client_builder_by_func_id.clear();
+        self.opener = None;
+        self.rename_dialog = None;
+        self.deletion_dialog = None;
+        self.references_view = None;
+        self.window_positions = WindowPositions::default();
+        self.world_history.start_over();
+    }
+
+    pub fn load_saved_selections(&mut self, open_code_editors: &[save_state::OpenCodeEditor]) {
+        self.saved_selections = open_code_editors.iter()
+                                                 .map(|open_code_editor| {
+                                                     (open_code_editor.location,
+                                                      open_code_editor.selected_node_ids.clone())
+                                                 })
+                                                 .collect();
+    }
+
+    pub fn save_state(&self) {
+        let open_code_editors =
+            self.code_editor_by_id
+                .values()
+                .map(|editor| save_state::OpenCodeEditor { location: editor.location.unwrap(),
+                                                           selected_node_ids:
+                                                               editor.selected_node_ids.clone() })
+                .collect_vec();
+        save_state::save(&self.window_positions,
+                         &open_code_editors,
+                         &self.recent_opener_items,
+                         &self.recent_projects)
     }
 
     pub fn handle_global_keypress(&mut self, keypress: Keypress) {
@@ -192,6 +405,9 @@ impl<'a> Controller {
             Keypress { key: Key::O,
                        ctrl: true,
                        shift: false, } => self.open_opener(),
+            Keypress { key: Key::P,
+                       ctrl: true,
+                       shift: true, } => self.open_command_palette(),
             _ => (),
         }
     }
@@ -208,6 +424,10 @@ impl<'a> Controller {
         self.opener = Some(Opener::new());
     }
 
+    pub fn open_command_palette(&mut self) {
+        self.opener = Some(Opener::command_palette());
+    }
+
     pub fn close_opener(&mut self) {
         self.opener = None;
     }
@@ -226,6 +446,109 @@ impl<'a> Controller {
         self.opener.as_mut().map(move |opener| opener.select_prev());
     }
 
+    pub fn open_rename_dialog(&mut self, target: RenameTarget, current_name: String) {
+        self.rename_dialog = Some(RenameDialog { target,
+                                                 new_name: current_name });
+    }
+
+    pub fn close_rename_dialog(&mut self) {
+        self.rename_dialog = None;
+    }
+
+    pub fn set_rename_input(&mut self, new_name: String) {
+        if let Some(rename_dialog) = self.rename_dialog.as_mut() {
+            rename_dialog.new_name = new_name;
+        }
+    }
+
+    pub fn open_deletion_dialog(&mut self, target: DeletionTarget) {
+        self.deletion_dialog = Some(target);
+    }
+
+    pub fn close_deletion_dialog(&mut self) {
+        self.deletion_dialog = None;
+    }
+
+    pub fn show_references(&mut self, target: ReferenceTarget) {
+        self.references_view = Some(ReferencesView::new(target));
+        self.open_window(*REFERENCES_WINDOW_ID);
+    }
+
+    pub fn toggle_call_tree_entry(&mut self, direction: CallDirection, path: Vec<lang::ID>) {
+        if let Some(references_view) = self.references_view.as_mut() {
+            references_view.toggle_expanded(direction, path);
+        }
+    }
+
+    // brings up the window the code lives in, and selects the node inside of it if there is one
+    pub fn navigate_to(&mut self, location: CodeLocation, node_id: Option<lang::ID>) {
+        if let Some(window_id) = references::window_id_for_location(location) {
+            self.open_window(window_id);
+        }
+        let editor = self.code_editor_by_id
+                         .values_mut()
+                         .find(|editor| editor.location == Some(location));
+        if let (Some(editor), Some(node_id)) = (editor, node_id) {
+            editor.set_selected_node_id(Some(node_id));
+        }
+    }
+
+    pub fn next_world_undo(&self) -> Option<&WorldEdit> {
+        self.world_history.next_undo()
+    }
+
+    pub fn next_world_redo(&self) -> Option<&WorldEdit> {
+        self.world_history.next_redo()
+    }
+
+    pub fn world_history(&self) -> &WorldUndoHistory {
+        &self.world_history
+    }
+
+    // called after each batch of commands, with what the world looked like before it. this is
+    // also where the project finds out it's been changed
+    pub fn record_world_history(&mut self,
+                                before: &WorldSnapshot,
+                                env: &env::ExecutionEnvironment) {
+        let after = undo::take_snapshot(self, env);
+        if let Some(change) = self.world_history.record(before, &after) {
+            let fingerprint = undo::fingerprint(&after);
+            save_state::save_world_history(fingerprint, &self.world_history, change);
+            self.project.world_changed(fingerprint);
+            project::autosave(self, env);
+        }
+        self.last_world_snapshot = Some(after);
+    }
+
+    pub fn take_last_world_snapshot(&mut self) -> Option<WorldSnapshot> {
+        self.last_world_snapshot.take()
+    }
+
+    // picks up the undo history from last time, as long as it's for the world we've got now. it
+    // gets written back out whole, so the log of changes on top of it starts over. otherwise the
+    // world that just got loaded is where the history starts
+    pub fn restore_world_history(&mut self, env: &env::ExecutionEnvironment) {
+        let snapshot = undo::take_snapshot(self, env);
+        let fingerprint = undo::fingerprint(&snapshot);
+        match save_state::load_world_history() {
+            Some(saved) if saved.world_fingerprint == fingerprint => {
+                self.world_history = saved.history;
+                save_state::save_world_history(fingerprint,
+                                               &self.world_history,
+                                               WorldHistoryChange::Rewritten);
+            }
+            _ => {
+                if let Some(change) = self.world_history.record(&snapshot, &snapshot) {
+                    save_state::save_world_history(fingerprint, &self.world_history, change);
+                }
+            }
+        }
+    }
+
+    pub fn remove_script(&mut self, id: lang::ID) {
+        self.script_by_id.remove(&id);
+    }
+
     pub fn list_tests(&self, subject: tests::TestSubject) -> impl Iterator<Item = &tests::Test> {
         self.test_by_id
             .values()
@@ -242,6 +565,10 @@ impl<'a> Controller {
             })
     }
 
+    pub fn list_all_tests(&self) -> impl Iterator<Item = &tests::Test> {
+        self.test_by_id.values()
+    }
+
     pub fn get_test(&self, test_id: lang::ID) -> Option<&tests::Test> {
         self.test_by_id.get(&test_id)
     }
@@ -254,6 +581,19 @@ impl<'a> Controller {
         self.code_editor_by_id.get(&id)
     }
 
+    pub fn focus_code_editor(&mut self, id: lang::ID) {
+        self.focused_code_editor_id = Some(id);
+    }
+
+    // None once the window it was in has been closed
+    pub fn focused_code_editor(&self) -> Option<&code_editor::CodeEditor> {
+        let code_editor = self.get_editor(self.focused_code_editor_id?)?;
+        match references::window_id_for_location(code_editor.location?) {
+            Some(window_id) if self.window_positions.get_open_window(&window_id).is_none() => None,
+            _ => Some(code_editor),
+        }
+    }
+
     fn get_test_result(&self, func: &dyn lang::Function) -> String {
         let test_result = self.test_result_by_func_id.get(&func.id());
         if let Some(test_result) = test_result {
@@ -286,8 +626,20 @@ impl<'a> Controller {
     pub fn load_code(&mut self, code_node: CodeNode, location: code_editor::CodeLocation) {
         let id = code_node.id();
         if !self.code_editor_by_id.contains_key(&id) {
-            self.code_editor_by_id
-                .insert(id, code_editor::CodeEditor::new(code_node, location));
+            let mut code_editor = code_editor::CodeEditor::new(code_node, location);
+            if let Some(i) = self.saved_selections
+                                 .iter()
+                                 .position(|(saved_location, _)| *saved_location == location)
+            {
+                let (_, selection) = self.saved_selections.remove(i);
+                // the code could've changed out from under the selection since it was saved
+                let selection =
+                    selection.into_iter()
+                             .filter(|id| code_editor.get_code().find_node(*id).is_some())
+                             .collect();
+                code_editor.set_selection(selection);
+            }
+            self.code_editor_by_id.insert(id, code_editor);
         } else {
             let code_editor = self.code_editor_by_id.get_mut(&id).unwrap();
             code_editor.replace_code(code_node);
@@ -359,6 +711,22 @@ impl CommandBuffer {
 
                 async_executor.exec(async move {
                                   overlay.borrow_mut().mark_as_submitting();
+                                  // find out if the edit link is still good before sending
+                                  // anything, so we can say why if it isn't
+                                  match fetch_token_info().await {
+                                      Err(e) => {
+                                          overlay.borrow_mut().mark_error(e.to_string());
+                                          return Ok(());
+                                      }
+                                      Ok(token_info) => {
+                                          let problem = token_info.problem.clone();
+                                          overlay.borrow_mut().set_token_info(token_info);
+                                          if let Some(problem) = problem {
+                                              overlay.borrow_mut().mark_error(problem);
+                                              return Ok(());
+                                          }
+                                      }
+                                  }
                                   let resp = postthecode(&theworld).await;
                                   match resp {
                                       Err(e) => overlay.borrow_mut().mark_error(e.to_string()),
@@ -367,8 +735,8 @@ impl CommandBuffer {
                                           if status == 200 {
                                               overlay.borrow_mut().mark_as_success();
                                           } else {
-                                              overlay.borrow_mut()
-                                       .mark_error(format!("Invalid status code: {}", status));
+                                              let error = format!("{}: {}", status, resp.body());
+                                              overlay.borrow_mut().mark_error(error);
                                           }
                                       }
                                   }
@@ -377,11 +745,122 @@ impl CommandBuffer {
             })
     }
 
+    // and then goes on to do whatever the save was standing in the way of
+    pub fn save_project(&mut self, path: PathBuf, then: Option<ProjectAction>) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let mut env = interpreter.env.borrow_mut();
+                let display_path = path.display().to_string();
+                match project::save(path, controller, &env) {
+                    Ok(()) => {
+                        if let Some(action) = then {
+                            project::perform(action, controller, &mut env)
+                        }
+                    }
+                    Err(e) => {
+                        controller.close_unsaved_changes_dialog();
+                        controller.project.error =
+                            Some(format!("Couldn't save {}: {}", display_path, e));
+                    }
+                }
+            })
+    }
+
+    // asks about unsaved changes first, if there are any
     #[allow(unused)] // unused in wasm
-    pub fn save(&mut self) {
+    pub fn request_project_action(&mut self, action: ProjectAction) {
         self.add_integrating_command(move |controller, interpreter, _, _| {
-                let theworld = save_world(controller, &interpreter.env().borrow());
-                code_loading::save("codesample.json", &theworld).unwrap();
+                if controller.project.is_dirty() {
+                    controller.open_unsaved_changes_dialog(action)
+                } else {
+                    project::perform(action, controller, &mut interpreter.env.borrow_mut())
+                }
+            })
+    }
+
+    pub fn perform_project_action(&mut self, action: ProjectAction) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                project::perform(action, controller, &mut interpreter.env.borrow_mut())
+            })
+    }
+
+    pub fn finish_merge(&mut self) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let merge_view = match controller.merge_view() {
+                    Some(merge_view) => merge_view,
+                    None => return,
+                };
+                match merge_view.finish() {
+                    Ok(world) => {
+                        let path = merge_view.path.clone();
+                        controller.close_merge();
+                        project::finish_merge(world,
+                                              path,
+                                              controller,
+                                              &mut interpreter.env.borrow_mut())
+                    }
+                    Err(e) => {
+                        controller.project.error = Some(format!("Couldn't finish merging: {}", e))
+                    }
+                }
+            })
+    }
+
+    // only narrows it down to the code belonging to one function, script or test
+    pub fn compare_with_saved(&mut self, only: Option<lang::ID>) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let description = "Changes since last save".to_owned();
+                let diff_view = match project::read_saved_world(&controller.project) {
+                    Ok(saved_world) => {
+                        let current_world = save_world(controller, &interpreter.env.borrow());
+                        DiffView::between(description, &saved_world, &current_world, only)
+                    }
+                    Err(e) => DiffView::failed(description, e.to_string()),
+                };
+                controller.show_diff_view(diff_view)
+            })
+    }
+
+    pub fn compare_with_history(&mut self, node: usize) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let snapshot = undo::take_snapshot(controller, &interpreter.env.borrow());
+                let world_history = controller.world_history();
+                let (before, after) = world_history.worlds_around_edit(node, &snapshot);
+                let description =
+                    format!("Changes from {}", world_history.node(node).edit.describe());
+                let diff_view = DiffView::between(description, &before, &after, None);
+                controller.show_diff_view(diff_view)
+            })
+    }
+
+    pub fn show_upload_diff(&mut self, server_world: Result<TheWorld, String>) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                // the user could've moved on to a different diff by now
+                if !controller.diff_view().map_or(false, DiffView::is_loading) {
+                    return;
+                }
+                let description = UPLOAD_DIFF_DESCRIPTION.to_owned();
+                let diff_view = match server_world {
+                    Ok(server_world) => {
+                        let current_world = save_world(controller, &interpreter.env.borrow());
+                        DiffView::between(description, &server_world, &current_world, None)
+                    }
+                    Err(e) => DiffView::failed(description, e),
+                };
+                controller.show_diff_view(diff_view)
+            })
+    }
+
+    pub fn recover_unsaved_work(&mut self) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                if let Some(recovered_work) = controller.recovered_work.take() {
+                    if let Err(e) = project::recover(recovered_work,
+                                                     controller,
+                                                     &mut interpreter.env.borrow_mut())
+                    {
+                        controller.project.error =
+                            Some(format!("Couldn't recover unsaved changes: {}", e));
+                    }
+                }
             })
     }
 
@@ -408,6 +887,34 @@ impl CommandBuffer {
             })
     }
 
+    pub fn change_webhook_program(&mut self,
+                                  webhook_program_id: lang::ID,
+                                  change: impl Fn(&mut WebhookProgram) + 'static) {
+        self.add_integrating_command(move |_controller, interpreter, _, _| {
+                let mut env = interpreter.env.borrow_mut();
+                let env_genie = env_genie::EnvGenie::new(&env);
+                let mut webhook_program = env_genie.get_webhook_program(webhook_program_id)
+                                                   .unwrap()
+                                                   .clone();
+                change(&mut webhook_program);
+                env.add_function(webhook_program);
+            })
+    }
+
+    pub fn change_scheduled_program(&mut self,
+                                    scheduled_program_id: lang::ID,
+                                    change: impl Fn(&mut ScheduledProgram) + 'static) {
+        self.add_integrating_command(move |_controller, interpreter, _, _| {
+                let mut env = interpreter.env.borrow_mut();
+                let env_genie = env_genie::EnvGenie::new(&env);
+                let mut scheduled_program = env_genie.get_scheduled_program(scheduled_program_id)
+                                                     .unwrap()
+                                                     .clone();
+                change(&mut scheduled_program);
+                env.add_function(scheduled_program);
+            })
+    }
+
     pub fn change_http_client(&mut self,
                               http_client_id: lang::ID,
                               change: impl Fn(&mut JSONHTTPClient) + 'static) {
@@ -433,6 +940,44 @@ impl CommandBuffer {
             })
     }
 
+    pub fn load_parse_json(&mut self, parse_json: ParseJSON) {
+        let parse_json_id = parse_json.id();
+        self.add_controller_command(move |controller| controller.open_window(parse_json_id));
+        self.load_function(parse_json)
+    }
+
+    pub fn change_parse_json(&mut self,
+                             parse_json_id: lang::ID,
+                             change: impl Fn(&mut ParseJSON) + 'static) {
+        self.add_integrating_command(move |_controller, interpreter, _, _| {
+                let mut env = interpreter.env.borrow_mut();
+                let env_genie = env_genie::EnvGenie::new(&env);
+                let mut parse_json = env_genie.get_parse_json(parse_json_id).unwrap().clone();
+                change(&mut parse_json);
+                env.add_function(parse_json);
+            })
+    }
+
+    pub fn load_webhook_program(&mut self, webhook_program: WebhookProgram) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let mut env = interpreter.env.borrow_mut();
+                controller.load_code(lang::CodeNode::Block(webhook_program.code.clone()),
+                                     code_editor::CodeLocation::WebhookProgram(webhook_program.id()));
+                controller.open_window(webhook_program.id());
+                env.add_function(webhook_program);
+            })
+    }
+
+    pub fn load_scheduled_program(&mut self, scheduled_program: ScheduledProgram) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let mut env = interpreter.env.borrow_mut();
+                controller.load_code(lang::CodeNode::Block(scheduled_program.code.clone()),
+                                     code_editor::CodeLocation::ScheduledProgram(scheduled_program.id()));
+                controller.open_window(scheduled_program.id());
+                env.add_function(scheduled_program);
+            })
+    }
+
     pub fn load_json_http_client(&mut self, json_http_client: JSONHTTPClient) {
         self.add_integrating_command(move |controller, interpreter, _, _| {
                 let mut env = interpreter.env.borrow_mut();
@@ -467,6 +1012,79 @@ impl CommandBuffer {
             })
     }
 
+    pub fn extract_into_function(&mut self, editor_id: lang::ID, node_ids: Vec<lang::ID>) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let mut env = interpreter.env.borrow_mut();
+                let editor = controller.get_editor_mut(editor_id).unwrap();
+                let code_func = {
+                    let env_genie = env_genie::EnvGenie::new(&env);
+                    editor.extract_into_function(&node_ids, &env_genie)
+                };
+                let code_func = match code_func {
+                    Some(code_func) => code_func,
+                    None => return,
+                };
+                let code = editor.get_code().clone();
+                let location = editor.location.unwrap();
+                let func_id = code_func.id();
+                let func_code = code_func.code();
+                // the function has to be there before the code that calls it gets updated
+                env.add_function(code_func);
+                code_editor::update_code_in_env(location, code, controller, &mut env);
+                controller.open_window(func_id);
+                controller.load_code(func_code, code_editor::CodeLocation::Function(func_id));
+            });
+    }
+
+    pub fn open_rename_dialog(&mut self, target: RenameTarget) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let env = interpreter.env.borrow();
+                let env_genie = env_genie::EnvGenie::new(&env);
+                if let Some(current_name) = rename::current_name(target, controller, &env_genie) {
+                    controller.open_rename_dialog(target, current_name);
+                }
+            });
+    }
+
+    pub fn rename(&mut self, target: RenameTarget, new_name: String) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let mut env = interpreter.env.borrow_mut();
+                if let Some(old_name) =
+                    rename::rename(target, new_name.clone(), controller, &mut env)
+                {
+                    controller.world_history
+                              .label_next_edit(format!("Rename {} to {}", old_name, new_name));
+                }
+                controller.close_rename_dialog();
+            });
+    }
+
+    pub fn undo_world_edit(&mut self) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let steps = controller.world_history.undo();
+                undo::take_steps(steps, controller, &mut interpreter.env.borrow_mut());
+            });
+    }
+
+    pub fn redo_world_edit(&mut self) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let steps = controller.world_history.redo();
+                undo::take_steps(steps, controller, &mut interpreter.env.borrow_mut());
+            });
+    }
+
+    pub fn open_undo_tree_window(&mut self) {
+        self.add_controller_command(|controller| controller.open_window(*UNDO_TREE_WINDOW_ID));
+    }
+
+    // None is all the way back at the beginning
+    pub fn go_to_world_history_node(&mut self, node: Option<usize>) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
+                let steps = controller.world_history.go_to(node);
+                undo::take_steps(steps, controller, &mut interpreter.env.borrow_mut());
+            });
+    }
+
     pub fn remove_struct_field(&mut self, strukt_id: lang::ID, field_index: usize) {
         self.add_environment_command(move |env| {
                 let mut new_strukt = env.find_struct(strukt_id).unwrap().clone();
@@ -475,22 +1093,35 @@ impl CommandBuffer {
             });
     }
 
-    pub fn remove_typespec(&mut self, id: lang::ID) {
-        self.add_integrating_command(move |_controller, interpreter, _, _| {
+    // deletes right away if nothing depends on the target, otherwise asks the user what to do
+    pub fn request_deletion(&mut self, target: DeletionTarget) {
+        self.add_integrating_command(move |controller, interpreter, _, _| {
                 let mut env = interpreter.env.borrow_mut();
-                env.delete_typespec(id)
+                let dependents = {
+                    let env_genie = env_genie::EnvGenie::new(&env);
+                    deletion::find_dependents(target, controller, &env_genie)
+                };
+                if dependents.is_empty() {
+                    deletion::delete(target, controller, &mut env)
+                } else {
+                    controller.open_deletion_dialog(target)
+                }
             });
     }
 
-    pub fn remove_function(&mut self, id: lang::ID) {
+    pub fn delete_replacing_with_placeholders(&mut self, target: DeletionTarget) {
         self.add_integrating_command(move |controller, interpreter, _, _| {
                 let mut env = interpreter.env.borrow_mut();
-
-                let func = env.find_function(id).unwrap();
-                if let Some(json_http_client) = func.downcast_ref::<JSONHTTPClient>() {
-                    controller.remove_json_http_client_builder(json_http_client.id())
+                // things could've changed since the dialog was drawn
+                let is_blocked = {
+                    let env_genie = env_genie::EnvGenie::new(&env);
+                    deletion::find_dependents(target, controller, &env_genie).is_blocked()
+                };
+                if !is_blocked {
+                    deletion::replace_with_placeholders(target, controller, &mut env);
+                    deletion::delete(target, controller, &mut env);
                 }
-                env.delete_function(id)
+                controller.close_deletion_dialog();
             });
     }
 
@@ -604,6 +1235,28 @@ async fn postthecode(theworld: &TheWorld)
     Ok(http_client::post_json(post_url.as_str(), theworld).await?)
 }
 
+const UPLOAD_DIFF_DESCRIPTION: &str = "Changes since last upload";
+
+async fn fetch_the_code() -> Result<TheWorld, Box<dyn std::error::Error>> {
+    let postcodetoken = config::get_or_err("SERVER_POST_TOKEN")?;
+    let the_code_url = config::the_code_url(postcodetoken)?;
+    let response = http_client::fetch(http_request::get(the_code_url.as_str())?).await?;
+    if !response.status().is_success() {
+        return Err(format!("{}: {}", response.status(), response.body()).into());
+    }
+    Ok(serde_json::from_str(response.body())?)
+}
+
+async fn fetch_token_info() -> Result<EditTokenInfo, Box<dyn std::error::Error>> {
+    let postcodetoken = config::get_or_err("SERVER_POST_TOKEN")?;
+    let token_info_url = config::token_info_url(postcodetoken)?;
+    let response = http_client::fetch(http_request::get(token_info_url.as_str())?).await?;
+    if !response.status().is_success() {
+        return Err(format!("{}: {}", response.status(), response.body()).into());
+    }
+    Ok(serde_json::from_str(response.body())?)
+}
+
 pub struct Renderer<'a, T> {
     ui_toolkit: &'a mut T,
     // TODO: take this through the constructor, but now we'll let ppl peek in here
@@ -663,9 +1316,20 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                    &|| self.render_edit_structs(),
                                    &|| self.render_edit_enums(),
                                    &|| self.render_json_http_client_builders(),
+                                   &|| self.render_edit_parse_jsons(),
                                    &|| self.render_chat_programs(),
+                                   &|| self.render_webhook_programs(),
+                                   &|| self.render_scheduled_programs(),
                                    &|| self.render_status_bar(),
                                    &|| self.render_opener(),
+                                   &|| self.render_rename_dialog(),
+                                   &|| self.render_deletion_dialog(),
+                                   &|| self.render_unsaved_changes_dialog(),
+                                   &|| self.render_recovery_dialog(),
+                                   &|| self.render_references_window(),
+                                   &|| self.render_undo_tree_window(),
+                                   &|| self.render_merge_window(),
+                                   &|| self.render_diff_window(),
                                    &|| self.render_send_to_server_overlay(false)])
     }
 
@@ -697,6 +1361,17 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                                 colorscheme!(action_color),
                                                 move || cmd_buffer.borrow_mut().save_to_net())
                 },
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.ui_toolkit
+                        .draw_button("See what uploading would change",
+                                     colorscheme!(cool_color),
+                                     move || compare_with_server(Rc::clone(&cmd_buffer)))
+                },
+                &|| match &self.controller.send_to_server_overlay.borrow().token_info {
+                    Some(token_info) => self.ui_toolkit.draw_text(&describe_edit_token(token_info)),
+                    None => self.ui_toolkit.draw_all(&[]),
+                },
                 &|| match &self.controller.send_to_server_overlay.borrow().status {
                     SendToServerOverlayStatus::Ready => self.ui_toolkit.draw_text("Status: Ready"),
                     SendToServerOverlayStatus::Error(e) => {
@@ -738,10 +1413,16 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                 self.ui_toolkit
                     .draw_menu("File", &|| self.render_file_menu())
             },
+            &|| {
+                self.ui_toolkit
+                    .draw_menu("Edit", &|| self.render_edit_menu())
+            },
             &|| {
                 self.ui_toolkit
                     .draw_menu("View", &|| self.render_view_menu())
             },
+            #[cfg(not(target_arch = "wasm32"))]
+            &|| self.render_project_name(),
             &|| {
                 self.ui_toolkit.draw_all_on_same_line(&[
                     &|| {
@@ -758,15 +1439,42 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
         ])
     }
 
-    fn render_file_menu(&self) -> T::DrawResult {
-        self.ui_toolkit.draw_all(&[
-            #[cfg(not(target_arch = "wasm32"))]
-            &|| {
-                let cmd_buffer = Rc::clone(&self.command_buffer);
-                self.ui_toolkit.draw_menu_item("Save", move || {
-                                   cmd_buffer.borrow_mut().save();
-                               })
-            },
+    // undo and redo for changes that aren't inside of one code editor. undoing inside of a code
+    // editor still happens from its own keyboard shortcuts
+    fn render_edit_menu(&self) -> T::DrawResult {
+        let undo_label = match self.controller.next_world_undo() {
+            Some(edit) => format!("Undo {}", edit.describe()),
+            None => "Nothing to undo".to_owned(),
+        };
+        let redo_label = match self.controller.next_world_redo() {
+            Some(edit) => format!("Redo {}", edit.describe()),
+            None => "Nothing to redo".to_owned(),
+        };
+        self.ui_toolkit.draw_all(&[&|| {
+                                       let cmd_buffer = Rc::clone(&self.command_buffer);
+                                       self.ui_toolkit.draw_menu_item(&undo_label, move || {
+                                                          cmd_buffer.borrow_mut().undo_world_edit();
+                                                      })
+                                   },
+                                   &|| {
+                                       let cmd_buffer = Rc::clone(&self.command_buffer);
+                                       self.ui_toolkit.draw_menu_item(&redo_label, move || {
+                                                          cmd_buffer.borrow_mut().redo_world_edit();
+                                                      })
+                                   },
+                                   &|| {
+                                       let cmd_buffer = Rc::clone(&self.command_buffer);
+                                       self.ui_toolkit
+                                           .draw_menu_item("Undo history", move || {
+                                               cmd_buffer.borrow_mut().open_undo_tree_window();
+                                           })
+                                   }])
+    }
+
+    fn render_file_menu(&self) -> T::DrawResult {
+        self.ui_toolkit.draw_all(&[
+            #[cfg(not(target_arch = "wasm32"))]
+            &|| self.render_project_menu_items(),
             &|| {
                 let cmd_buffer = Rc::clone(&self.command_buffer);
                 self.ui_toolkit
@@ -775,6 +1483,22 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                   .load_chat_program(example_chat_program());
                     })
             },
+            &|| {
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                self.ui_toolkit
+                    .draw_menu_item("Add new webhook program", move || {
+                        cmd_buffer.borrow_mut()
+                                  .load_webhook_program(WebhookProgram::new());
+                    })
+            },
+            &|| {
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                self.ui_toolkit
+                    .draw_menu_item("Add new scheduled program", move || {
+                        cmd_buffer.borrow_mut()
+                                  .load_scheduled_program(ScheduledProgram::new());
+                    })
+            },
             &|| {
                 let cmd_buffer = Rc::clone(&self.command_buffer);
                 self.ui_toolkit
@@ -783,6 +1507,14 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                   .load_json_http_client(JSONHTTPClient::new());
                     })
             },
+            &|| {
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                self.ui_toolkit
+                    .draw_menu_item("Add new JSON parser", move || {
+                        cmd_buffer.borrow_mut()
+                                  .load_parse_json(ParseJSON::new(lang::Type::from_spec(&*lang::STRING_TYPESPEC)));
+                    })
+            },
             &|| {
                 let cmd_buffer = Rc::clone(&self.command_buffer);
                 self.ui_toolkit.draw_menu_item("Add new script", move || {
@@ -814,14 +1546,123 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
             },
             #[cfg(not(target_arch = "wasm32"))]
             &|| {
-                self.ui_toolkit.draw_menu_item("Exit", || {
-                                   std::process::exit(0);
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                self.ui_toolkit.draw_menu_item("Exit", move || {
+                                   cmd_buffer.borrow_mut()
+                                             .request_project_action(ProjectAction::Exit);
                                })
             },
         ])
     }
 
+    // project files only make sense where there's a filesystem
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_project_menu_items(&self) -> T::DrawResult {
+        let project_path = self.controller.project.path.clone();
+        self.ui_toolkit.draw_all(&[
+            &|| {
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                self.ui_toolkit.draw_menu_item("New project", move || {
+                                   cmd_buffer.borrow_mut()
+                                             .request_project_action(ProjectAction::New);
+                               })
+            },
+            &|| {
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                self.ui_toolkit.draw_menu_item("Open project...", move || {
+                                   let cmd_buffer = Rc::clone(&cmd_buffer);
+                                   T::choose_file_to_open(move |path| {
+                                       cmd_buffer.borrow_mut()
+                                                 .request_project_action(ProjectAction::Open(path));
+                                   })
+                               })
+            },
+            &|| {
+                self.ui_toolkit
+                    .draw_menu("Open recent project", &|| self.render_recent_projects())
+            },
+            &|| {
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                let project_path = project_path.clone();
+                self.ui_toolkit.draw_menu_item("Save", move || {
+                                   save_project::<T>(Rc::clone(&cmd_buffer),
+                                                     project_path.clone(),
+                                                     None)
+                               })
+            },
+            &|| {
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                self.ui_toolkit.draw_menu_item("Save as...", move || {
+                                   save_project::<T>(Rc::clone(&cmd_buffer), None, None)
+                               })
+            },
+            &|| {
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                self.ui_toolkit
+                    .draw_menu_item("Changes since last save", move || {
+                        cmd_buffer.borrow_mut().compare_with_saved(None)
+                    })
+            },
+            &|| self.ui_toolkit.draw_separator(),
+        ])
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_recent_projects(&self) -> T::DrawResult {
+        let recent_projects = self.controller.recent_projects();
+        if recent_projects.is_empty() {
+            return self.ui_toolkit.draw_text("No recent projects");
+        }
+        draw_all_iter!(T::self.ui_toolkit,
+                       recent_projects.iter().map(|path| move || {
+                           let cmd_buffer = Rc::clone(&self.command_buffer);
+                           let path = path.clone();
+                           self.ui_toolkit.draw_menu_item(&path.display().to_string(), move || {
+                               cmd_buffer.borrow_mut()
+                                         .request_project_action(ProjectAction::Open(path.clone()));
+                           })
+                       }))
+    }
+
+    // which project is open, and whether it's got unsaved changes
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_project_name(&self) -> T::DrawResult {
+        let project = &self.controller.project;
+        let name = if project.is_dirty() {
+            format!("{} *", project.name())
+        } else {
+            project.name()
+        };
+        self.ui_toolkit.draw_all_on_same_line(&[
+            &|| {
+                self.ui_toolkit
+                    .draw_buttony_text(&name, colorscheme!(text_color))
+            },
+            &|| match &project.error {
+                Some(error) => self.ui_toolkit
+                                   .draw_buttony_text(error, colorscheme!(danger_color)),
+                None => self.ui_toolkit.draw_all(&[]),
+            },
+        ])
+    }
+
     fn render_view_menu(&self) -> T::DrawResult {
+        self.ui_toolkit
+            .draw_all(&[&|| self.render_command_palette_menu_item(),
+                        &|| self.render_theme_editor_menu_item(),
+                        &|| self.render_reset_layout_menu_item()])
+    }
+
+    fn render_command_palette_menu_item(&self) -> T::DrawResult {
+        let cmd_buffer = Rc::clone(&self.command_buffer);
+        self.ui_toolkit
+            .draw_menu_item("Command palette (Ctrl+Shift+P)", move || {
+                cmd_buffer.borrow_mut()
+                          .add_controller_command(|cont| cont.open_command_palette());
+            })
+    }
+
+    fn render_theme_editor_menu_item(&self) -> T::DrawResult {
         let cmd_buffer = Rc::clone(&self.command_buffer);
         self.ui_toolkit.draw_menu_item("Theme editor", move || {
                            let cmd_buffer = Rc::clone(&cmd_buffer);
@@ -831,6 +1672,14 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                        })
     }
 
+    fn render_reset_layout_menu_item(&self) -> T::DrawResult {
+        let cmd_buffer = Rc::clone(&self.command_buffer);
+        self.ui_toolkit.draw_menu_item("Reset layout", move || {
+                           cmd_buffer.borrow_mut()
+                                     .add_controller_command(|cont| cont.reset_layout());
+                       })
+    }
+
     fn _render_quick_start_guide(&self) -> T::DrawResult {
         let open_window = self.controller
                               .window_positions
@@ -880,32 +1729,686 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                                      cmd_buffer.add_integrating_command(move |cont, interp, async_executor, _cmd_buffer| {
                                                         cont.chat_test_window.borrow_mut().add_message("\u{f406}".to_string(), entered_text.clone()) ;
 
-                                                         let interp = interp.new_stack_frame();
-                                                         let chat_test_window = Rc::clone(&cont.chat_test_window);
-                                                         async_executor.exec(async move {
-                                                             message_received(&interp, "\u{f406}".to_string(), entered_text).await;
+                                                         let interp = interp.new_stack_frame();
+                                                         let chat_test_window = Rc::clone(&cont.chat_test_window);
+                                                         async_executor.exec(async move {
+                                                             let replies = message_received(&interp, "\u{f406}".to_string(), entered_text).await;
+
+                                                             // TODO: probably not the best place for this, but it'll work. if there's any unflushed
+                                                             // bot output , stick it in the chat test window
+                                                             let env = interp.env.borrow();
+                                                             let env_genie = EnvGenie::new(&env);
+                                                             let mut chat_test_window = chat_test_window.borrow_mut();
+                                                             for reply in replies.into_iter().chain(flush_reply_buffer(&env_genie)) {
+                                                                 chat_test_window.add_reply("\u{f544}".to_string(), &reply);
+                                                             }
+
+                                                             let ok: Result<(), ()> = Ok(());
+                                                             ok
+                                                         });
+                                                     });
+                                                 })
+                                             }
+                                         )
+                                     },
+                                     None::<fn(Keypress)>)
+        } else {
+            self.ui_toolkit.draw_all(&[])
+        }
+    }
+
+    fn render_undo_tree_window(&self) -> T::DrawResult {
+        let window = match self.controller
+                               .window_positions
+                               .get_open_window(&*UNDO_TREE_WINDOW_ID)
+        {
+            Some(window) => window,
+            None => return self.ui_toolkit.draw_all(&[]),
+        };
+        self.draw_managed_window(&window,
+                                 "Undo history###undo_history",
+                                 &|| self.render_undo_tree(),
+                                 None::<fn(Keypress)>)
+    }
+
+    fn render_undo_tree(&self) -> T::DrawResult {
+        let rows = self.controller.world_history().tree_rows();
+        draw_all_iter!(T::self.ui_toolkit,
+                       rows.into_iter()
+                           .map(|(depth, node)| move || self.render_undo_tree_row(depth, node)))
+    }
+
+    // clicking on a row takes the world back (or forward) to how it was right after that edit
+    fn render_undo_tree_row(&self, depth: usize, node: Option<usize>) -> T::DrawResult {
+        let world_history = self.controller.world_history();
+        let description = match node {
+            Some(index) => world_history.node(index).edit.describe(),
+            None => "Beginning".to_owned(),
+        };
+        let marker = if world_history.current() == node {
+            "> "
+        } else {
+            "  "
+        };
+        let label = format!("{}{}{}", "  ".repeat(depth), marker, description);
+        let cmd_buffer = Rc::clone(&self.command_buffer);
+        self.ui_toolkit.draw_all_on_same_line(&[
+            &|| {
+                let cmd_buffer = Rc::clone(&cmd_buffer);
+                self.ui_toolkit
+                    .draw_button(&label, colorscheme!(action_color), move || {
+                        cmd_buffer.borrow_mut().go_to_world_history_node(node)
+                    })
+            },
+            &|| match node {
+                Some(node) => {
+                    let cmd_buffer = Rc::clone(&cmd_buffer);
+                    self.ui_toolkit
+                        .draw_small_button("Diff", colorscheme!(cool_color), move || {
+                            cmd_buffer.borrow_mut().compare_with_history(node)
+                        })
+                }
+                None => self.ui_toolkit.draw_all(&[]),
+            },
+        ])
+    }
+
+    fn render_diff_window(&self) -> T::DrawResult {
+        let open_window = self.controller
+                              .window_positions
+                              .get_open_window(&*DIFF_WINDOW_ID);
+        let (window, diff_view) = match (open_window, self.controller.diff_view()) {
+            (Some(window), Some(diff_view)) => (window, diff_view),
+            _ => return self.ui_toolkit.draw_all(&[]),
+        };
+        let title = format!("{}###diff", diff_view.description);
+        self.draw_managed_window(&window,
+                                 &title,
+                                 &|| self.render_diff_view(diff_view),
+                                 None::<fn(Keypress)>)
+    }
+
+    fn render_diff_view(&self, diff_view: &DiffView) -> T::DrawResult {
+        match &diff_view.comparison {
+            Comparison::Loading => {
+                self.ui_toolkit
+                    .draw_all_on_same_line(&[&|| self.ui_toolkit.draw_text("Loading..."), &|| {
+                                               self.ui_toolkit.draw_spinner()
+                                           }])
+            }
+            Comparison::Failed(e) => self.ui_toolkit
+                                         .draw_wrapped_text(colorscheme!(danger_color), e),
+            Comparison::Done(code_diffs) if code_diffs.is_empty() => {
+                self.ui_toolkit.draw_text("No changes")
+            }
+            Comparison::Done(code_diffs) => {
+                let draw_diffs = || self.render_code_diffs(code_diffs);
+                self.ui_toolkit
+                    .draw_all(&[&|| self.render_diff_legend(), &draw_diffs])
+            }
+        }
+    }
+
+    fn render_code_diffs(&self, code_diffs: &[CodeDiff]) -> T::DrawResult {
+        draw_all_iter!(T::self.ui_toolkit,
+                       code_diffs.iter()
+                                 .map(|code_diff| move || self.render_code_diff(code_diff)))
+    }
+
+    fn render_diff_legend(&self) -> T::DrawResult {
+        self.ui_toolkit.draw_all_on_same_line(&[
+            &|| self.ui_toolkit.draw_buttony_text("Inserted", colorscheme!(adding_color)),
+            &|| self.ui_toolkit.draw_buttony_text("Deleted", colorscheme!(removing_color)),
+            &|| self.ui_toolkit.draw_buttony_text("Moved", colorscheme!(cool_color)),
+            &|| self.ui_toolkit.draw_buttony_text("Changed", colorscheme!(warning_color)),
+        ])
+    }
+
+    // the code in a diff can't be edited, so clicking around in it doesn't do anything
+    fn render_code_diff(&self, code_diff: &CodeDiff) -> T::DrawResult {
+        let heading = format!("{}: {}",
+                              references::describe_location(code_diff.location,
+                                                            self.controller,
+                                                            self.env_genie),
+                              code_diff.diff.summary());
+        self.ui_toolkit
+            .draw_all(&[&|| self.ui_toolkit.draw_separator(),
+                        &|| self.ui_toolkit.draw_text(&heading),
+                        &|| {
+                            let command_buffer_that_does_nothing =
+                                Rc::new(RefCell::new(CommandBuffer::new()));
+                            CodeEditorRenderer::new(self.ui_toolkit,
+                                        &code_diff.new_editor,
+                                        command_buffer_that_does_nothing,
+                                        self.env_genie).with_diff(&code_diff.diff,
+                                                                  &code_diff.old_editor)
+                                                       .render(ChildRegionHeight::FitContent)
+                        }])
+    }
+
+    fn render_merge_window(&self) -> T::DrawResult {
+        let open_window = self.controller
+                              .window_positions
+                              .get_open_window(&*MERGE_WINDOW_ID);
+        let (window, merge_view) = match (open_window, self.controller.merge_view()) {
+            (Some(window), Some(merge_view)) => (window, merge_view),
+            _ => return self.ui_toolkit.draw_all(&[]),
+        };
+        let title = format!("Merging {}###merge",
+                            merge_view.path
+                                      .file_name()
+                                      .unwrap_or_default()
+                                      .to_string_lossy());
+        self.draw_managed_window(&window,
+                                 &title,
+                                 &|| self.render_merge(merge_view),
+                                 None::<fn(Keypress)>)
+    }
+
+    fn render_merge(&self, merge_view: &MergeView) -> T::DrawResult {
+        let num_unresolved = merge_view.num_unresolved();
+        let status = format!("{} of {} conflict(s) left to resolve",
+                             num_unresolved,
+                             merge_view.conflicts().len());
+        self.ui_toolkit.draw_all(&[
+            &|| self.ui_toolkit.draw_text(&status),
+            &|| {
+                draw_all_iter!(T::self.ui_toolkit,
+                               merge_view.conflicts()
+                                         .iter()
+                                         .map(|conflict| move || {
+                                             self.render_merge_conflict(merge_view, conflict)
+                                         }))
+            },
+            &|| {
+                self.ui_toolkit.draw_all_on_same_line(&[
+                    &|| {
+                        if num_unresolved > 0 {
+                            return self.ui_toolkit
+                                       .draw_disabled_button("Finish merge",
+                                                             colorscheme!(action_color));
+                        }
+                        let cmd_buffer = Rc::clone(&self.command_buffer);
+                        self.ui_toolkit.draw_button("Finish merge",
+                                                    colorscheme!(action_color),
+                                                    move || cmd_buffer.borrow_mut().finish_merge())
+                    },
+                    &|| {
+                        let cmd_buffer = Rc::clone(&self.command_buffer);
+                        self.ui_toolkit
+                            .draw_button("Cancel", colorscheme!(cool_color), move || {
+                                cmd_buffer.borrow_mut()
+                                          .add_controller_command(|controller| {
+                                              controller.close_merge()
+                                          })
+                            })
+                    },
+                ])
+            },
+        ])
+    }
+
+    // the side that's been picked is highlighted, and picking it again is a no-op
+    fn render_merge_conflict(&self,
+                             merge_view: &MergeView,
+                             conflict: &world_merge::Conflict)
+                             -> T::DrawResult {
+        let resolution = merge_view.resolution(&conflict.id);
+        let ours = format!("Ours: {}",
+                           merge::preview(conflict, world_merge::Side::Ours));
+        let theirs = format!("Theirs: {}",
+                             merge::preview(conflict, world_merge::Side::Theirs));
+        let render_side = |label: &'static str, side: world_merge::Side| {
+            let color = if resolution == Some(side) {
+                colorscheme!(action_color)
+            } else {
+                colorscheme!(cool_color)
+            };
+            let cmd_buffer = Rc::clone(&self.command_buffer);
+            let conflict_id = conflict.id.clone();
+            self.ui_toolkit.draw_button(label, color, move || {
+                               let conflict_id = conflict_id.clone();
+                               cmd_buffer.borrow_mut()
+                                         .add_controller_command(move |controller| {
+                                             controller.resolve_merge_conflict(conflict_id, side)
+                                         })
+                           })
+        };
+        self.ui_toolkit.draw_all(&[
+            &|| self.ui_toolkit.draw_separator(),
+            &|| {
+                self.ui_toolkit
+                    .draw_wrapped_text(colorscheme!(warning_color), &conflict.describe())
+            },
+            &|| {
+                self.ui_toolkit.draw_all_on_same_line(&[&|| {
+                                                            render_side("Keep ours",
+                                                                        world_merge::Side::Ours)
+                                                        },
+                                                        &|| {
+                                                            render_side("Keep theirs",
+                                                                        world_merge::Side::Theirs)
+                                                        }])
+            },
+            &|| {
+                self.ui_toolkit
+                    .draw_wrapped_text(colorscheme!(text_color), &ours)
+            },
+            &|| {
+                self.ui_toolkit
+                    .draw_wrapped_text(colorscheme!(text_color), &theirs)
+            },
+        ])
+    }
+
+    fn render_references_window(&self) -> T::DrawResult {
+        let open_window = self.controller
+                              .window_positions
+                              .get_open_window(&*REFERENCES_WINDOW_ID);
+        let (window, target) = match (open_window, self.controller.references_view.as_ref()) {
+            (Some(window), Some(references_view)) => (window, references_view.target),
+            _ => return self.ui_toolkit.draw_all(&[]),
+        };
+        let references = references::find_references(target, self.controller, self.env_genie);
+        let title = format!("References to {}###references",
+                            references::describe_target(target, self.env_genie));
+        self.draw_managed_window(&window,
+                                 &title,
+                                 &|| self.render_references(target, &references),
+                                 None::<fn(Keypress)>)
+    }
+
+    fn render_references(&self,
+                         target: ReferenceTarget,
+                         references: &[Reference])
+                         -> T::DrawResult {
+        let count = format!("{} reference(s)", references.len());
+        self.ui_toolkit.draw_all(&[
+            &|| self.ui_toolkit.draw_text(&count),
+            &|| {
+                draw_all_iter!(T::self.ui_toolkit,
+                               references.iter().map(|reference| move || {
+                                                    self.render_reference(*reference)
+                                                }))
+            },
+            &|| match target {
+                ReferenceTarget::Function(function_id) => self.render_call_hierarchy(function_id),
+                _ => self.ui_toolkit.draw_all(&[]),
+            },
+        ])
+    }
+
+    fn render_reference(&self, reference: Reference) -> T::DrawResult {
+        let label =
+            references::describe_location(reference.location, self.controller, self.env_genie);
+        let cmd_buffer = Rc::clone(&self.command_buffer);
+        self.ui_toolkit
+            .draw_button(&label, colorscheme!(action_color), move || {
+                cmd_buffer.borrow_mut()
+                          .add_controller_command(move |controller| {
+                              controller.navigate_to(reference.location, Some(reference.node_id))
+                          })
+            })
+    }
+
+    fn render_call_hierarchy(&self, function_id: lang::ID) -> T::DrawResult {
+        self.ui_toolkit
+            .draw_all(&[&|| self.ui_toolkit.draw_text("Called by:"),
+                        &|| self.render_call_tree(CallDirection::Callers, vec![function_id]),
+                        &|| self.ui_toolkit.draw_text("Calls:"),
+                        &|| self.render_call_tree(CallDirection::Callees, vec![function_id])])
+    }
+
+    // the callers or callees of the function at the end of the path
+    fn render_call_tree(&self, direction: CallDirection, path: Vec<lang::ID>) -> T::DrawResult {
+        let children = references::call_tree_children(direction,
+                                                      *path.last().unwrap(),
+                                                      self.controller,
+                                                      self.env_genie);
+        if children.is_empty() {
+            return self.ui_toolkit.draw_text("Nothing");
+        }
+        draw_all_iter!(T::self.ui_toolkit,
+                       children.into_iter().map(|child_id| {
+                                               let mut child_path = path.clone();
+                                               child_path.push(child_id);
+                                               move || {
+                                                   self.render_call_tree_entry(direction,
+                                                                               child_path.clone())
+                                               }
+                                           }))
+    }
+
+    fn render_call_tree_entry(&self,
+                              direction: CallDirection,
+                              path: Vec<lang::ID>)
+                              -> T::DrawResult {
+        let id = *path.last().unwrap();
+        // a function that ends up calling itself would go on forever
+        let is_recursive = path[..path.len() - 1].contains(&id);
+        let is_expanded = !is_recursive
+                          && self.controller
+                                 .references_view
+                                 .as_ref()
+                                 .map_or(false, |view| view.is_expanded(direction, &path));
+        let name = references::describe_call_tree_entry(id, self.controller, self.env_genie);
+        let location = references::location_for_owner(id, self.controller, self.env_genie);
+        self.ui_toolkit.draw_all(&[
+            &|| {
+                self.ui_toolkit.draw_all_on_same_line(&[
+                    &|| {
+                        if is_recursive {
+                            return self.ui_toolkit.draw_text("(calls itself)");
+                        }
+                        let cmd_buffer = Rc::clone(&self.command_buffer);
+                        let path = path.clone();
+                        let label = if is_expanded { "-" } else { "+" };
+                        self.ui_toolkit.draw_small_button(label, colorscheme!(cool_color), move || {
+                            let path = path.clone();
+                            cmd_buffer.borrow_mut().add_controller_command(move |controller| {
+                                controller.toggle_call_tree_entry(direction, path)
+                            })
+                        })
+                    },
+                    &|| match location {
+                        Some(location) => {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            self.ui_toolkit.draw_button(&name, colorscheme!(action_color), move || {
+                                cmd_buffer.borrow_mut().add_controller_command(move |controller| {
+                                    controller.navigate_to(location, None)
+                                })
+                            })
+                        }
+                        // builtins don't have any code to go to
+                        None => self.ui_toolkit.draw_text(&name),
+                    },
+                ])
+            },
+            &|| {
+                if is_expanded {
+                    self.ui_toolkit
+                        .indent(10, &|| self.render_call_tree(direction, path.clone()))
+                } else {
+                    self.ui_toolkit.draw_all(&[])
+                }
+            },
+        ])
+    }
+
+    fn render_rename_dialog(&self) -> T::DrawResult {
+        let rename_dialog = match self.controller.rename_dialog.as_ref() {
+            Some(rename_dialog) => rename_dialog,
+            None => return self.ui_toolkit.draw_all(&[]),
+        };
+        let target = rename_dialog.target;
+        let new_name = rename_dialog.new_name.clone();
+        let usages = rename::find_usages(target, self.controller, self.env_genie);
+        let mut usage_count_by_location = std::collections::BTreeMap::new();
+        for usage in &usages {
+            let location =
+                references::describe_location(usage.location, self.controller, self.env_genie);
+            *usage_count_by_location.entry(location).or_insert(0) += 1;
+        }
+        let wire_name_changes = rename::wire_name_changes(target, &new_name, self.env_genie);
+
+        let cmd_buffer = Rc::clone(&self.command_buffer);
+        let handle_keypress = move |keypress: Keypress| {
+            if keypress.key == Key::Escape {
+                cmd_buffer.borrow_mut()
+                          .add_controller_command(|controller| controller.close_rename_dialog());
+            }
+        };
+        self.ui_toolkit.draw_centered_popup(&|| {
+            self.ui_toolkit.draw_all(&[
+                &|| self.ui_toolkit.draw_text("Rename to:"),
+                &|| {
+                    self.ui_toolkit.focused(&|| {
+                        let cmd_buffer1 = Rc::clone(&self.command_buffer);
+                        let cmd_buffer2 = Rc::clone(&self.command_buffer);
+                        let cmd_buffer3 = Rc::clone(&self.command_buffer);
+                        let new_name = new_name.clone();
+                        self.ui_toolkit.draw_text_input(
+                            &new_name.clone(),
+                            false,
+                            move |input: &str| {
+                                let input = input.to_owned();
+                                cmd_buffer1.borrow_mut()
+                                           .add_controller_command(move |controller| {
+                                               controller.set_rename_input(input)
+                                           })
+                            },
+                            move || cmd_buffer2.borrow_mut().rename(target, new_name.clone()),
+                            move |keypress| {
+                                if keypress.key == Key::Escape {
+                                    cmd_buffer3.borrow_mut()
+                                               .add_controller_command(|controller| {
+                                                   controller.close_rename_dialog()
+                                               });
+                                }
+                            })
+                    })
+                },
+                &|| {
+                    self.ui_toolkit
+                        .draw_text(&format!("{} usage(s) will show the new name:", usages.len()))
+                },
+                &|| {
+                    draw_all_iter!(T::self.ui_toolkit,
+                                   usage_count_by_location.iter().map(|(location, count)| {
+                                       move || {
+                                           self.ui_toolkit
+                                               .draw_text(&format!("  {} ({})", location, count))
+                                       }
+                                   }))
+                },
+                &|| {
+                    draw_all_iter!(T::self.ui_toolkit,
+                                   wire_name_changes.iter().map(|change| {
+                                       move || {
+                                           let warning =
+                                               format!("{} will use the JSON key \"{}\" instead \
+                                                        of \"{}\"",
+                                                       change.mapped_by,
+                                                       change.new_wire_name,
+                                                       change.old_wire_name);
+                                           self.ui_toolkit
+                                               .draw_wrapped_text(colorscheme!(warning_color),
+                                                                  &warning)
+                                       }
+                                   }))
+                },
+                &|| {
+                    self.ui_toolkit.draw_all_on_same_line(&[
+                        &|| {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            let new_name = new_name.clone();
+                            self.ui_toolkit.draw_button("Rename",
+                                                        colorscheme!(action_color),
+                                                        move || {
+                                                            cmd_buffer.borrow_mut()
+                                                                      .rename(target,
+                                                                              new_name.clone())
+                                                        })
+                        },
+                        &|| {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            self.ui_toolkit.draw_button("Cancel", colorscheme!(cool_color), move || {
+                                cmd_buffer.borrow_mut().add_controller_command(|controller| {
+                                    controller.close_rename_dialog()
+                                })
+                            })
+                        },
+                    ])
+                },
+            ])
+        }, Some(handle_keypress))
+    }
+
+    fn render_deletion_dialog(&self) -> T::DrawResult {
+        let target = match self.controller.deletion_dialog {
+            Some(target) => target,
+            None => return self.ui_toolkit.draw_all(&[]),
+        };
+        let dependents = deletion::find_dependents(target, self.controller, self.env_genie);
+        let title = format!("Delete {}?",
+                            deletion::describe_target(target, self.env_genie));
+
+        let cmd_buffer = Rc::clone(&self.command_buffer);
+        let handle_keypress = move |keypress: Keypress| {
+            if keypress.key == Key::Escape {
+                cmd_buffer.borrow_mut()
+                          .add_controller_command(|controller| controller.close_deletion_dialog());
+            }
+        };
+        self.ui_toolkit.draw_centered_popup(&|| {
+            self.ui_toolkit.draw_all(&[
+                &|| self.ui_toolkit.draw_text(&title),
+                &|| {
+                    if dependents.references.is_empty() {
+                        return self.ui_toolkit.draw_all(&[]);
+                    }
+                    self.ui_toolkit.draw_text(&format!("{} usage(s) in code:",
+                                                       dependents.references.len()))
+                },
+                &|| {
+                    draw_all_iter!(T::self.ui_toolkit,
+                                   dependents.references.iter().map(|reference| move || {
+                                                                   self.render_reference(*reference)
+                                                               }))
+                },
+                &|| {
+                    if !dependents.is_blocked() {
+                        return self.ui_toolkit.draw_all(&[]);
+                    }
+                    self.ui_toolkit.draw_wrapped_text(colorscheme!(warning_color),
+                                                      "These have to be changed by hand first:")
+                },
+                &|| {
+                    draw_all_iter!(T::self.ui_toolkit,
+                                   dependents.blockers.iter().map(|blocker| {
+                                       move || self.ui_toolkit.draw_text(&format!("  {}", blocker))
+                                   }))
+                },
+                &|| {
+                    self.ui_toolkit.draw_all_on_same_line(&[
+                        &|| {
+                            if dependents.is_blocked() {
+                                return self.ui_toolkit.draw_all(&[]);
+                            }
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            let label = "Replace usages with placeholders and delete";
+                            self.ui_toolkit.draw_button(label, colorscheme!(danger_color), move || {
+                                cmd_buffer.borrow_mut()
+                                          .delete_replacing_with_placeholders(target)
+                            })
+                        },
+                        &|| {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            self.ui_toolkit.draw_button("Cancel", colorscheme!(cool_color), move || {
+                                cmd_buffer.borrow_mut().add_controller_command(|controller| {
+                                    controller.close_deletion_dialog()
+                                })
+                            })
+                        },
+                    ])
+                },
+            ])
+        }, Some(handle_keypress))
+    }
+
+    fn render_unsaved_changes_dialog(&self) -> T::DrawResult {
+        let action = match &self.controller.unsaved_changes_dialog {
+            Some(action) => action.clone(),
+            None => return self.ui_toolkit.draw_all(&[]),
+        };
+        let project_path = self.controller.project.path.clone();
+        let title = format!("Save changes to {}?", self.controller.project.name());
 
-                                                             // TODO: probably not the best place for this, but it'll work. if there's any unflushed
-                                                             // bot output , stick it in the chat test window
-                                                             let env = interp.env.borrow();
-                                                             let env_genie = EnvGenie::new(&env);
-                                                             let mut chat_test_window = chat_test_window.borrow_mut();
-                                                             for reply in flush_reply_buffer(&env_genie) {
-                                                                 chat_test_window.add_message("\u{f544}".to_string(), reply);
-                                                             }
+        let cmd_buffer = Rc::clone(&self.command_buffer);
+        let handle_keypress = move |keypress: Keypress| {
+            if keypress.key == Key::Escape {
+                cmd_buffer.borrow_mut()
+                          .add_controller_command(|controller| {
+                              controller.close_unsaved_changes_dialog()
+                          });
+            }
+        };
+        self.ui_toolkit.draw_centered_popup(&|| {
+            self.ui_toolkit.draw_all(&[
+                &|| self.ui_toolkit.draw_text(&title),
+                &|| {
+                    self.ui_toolkit.draw_wrapped_text(colorscheme!(text_color),
+                                                      "Your changes will be lost if you don't save them.")
+                },
+                &|| {
+                    self.ui_toolkit.draw_all_on_same_line(&[
+                        &|| {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            let project_path = project_path.clone();
+                            let action = action.clone();
+                            self.ui_toolkit.draw_button("Save", colorscheme!(action_color), move || {
+                                save_project::<T>(Rc::clone(&cmd_buffer),
+                                                  project_path.clone(),
+                                                  Some(action.clone()))
+                            })
+                        },
+                        &|| {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            let action = action.clone();
+                            self.ui_toolkit.draw_button("Don't save", colorscheme!(danger_color), move || {
+                                cmd_buffer.borrow_mut().perform_project_action(action.clone())
+                            })
+                        },
+                        &|| {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            self.ui_toolkit.draw_button("Cancel", colorscheme!(cool_color), move || {
+                                cmd_buffer.borrow_mut().add_controller_command(|controller| {
+                                    controller.close_unsaved_changes_dialog()
+                                })
+                            })
+                        },
+                    ])
+                },
+            ])
+        }, Some(handle_keypress))
+    }
 
-                                                             let ok: Result<(), ()> = Ok(());
-                                                             ok
-                                                         });
-                                                     });
-                                                 })
-                                             }
-                                         )
-                                     },
-                                     None::<fn(Keypress)>)
-        } else {
-            self.ui_toolkit.draw_all(&[])
-        }
+    // shows up at startup if there were unsaved changes when the editor last closed
+    fn render_recovery_dialog(&self) -> T::DrawResult {
+        let recovered_work = match &self.controller.recovered_work {
+            Some(recovered_work) => recovered_work,
+            None => return self.ui_toolkit.draw_all(&[]),
+        };
+        let project_name = recovered_work.project_path
+                                         .as_ref()
+                                         .map(|path| path.display().to_string())
+                                         .unwrap_or_else(|| "an untitled project".to_owned());
+        let message = format!("There are unsaved changes to {} from last time. Recovering them replaces what's open now.",
+                              project_name);
+        self.ui_toolkit.draw_centered_popup(&|| {
+            self.ui_toolkit.draw_all(&[
+                &|| self.ui_toolkit.draw_text("Recover unsaved changes?"),
+                &|| self.ui_toolkit.draw_wrapped_text(colorscheme!(text_color), &message),
+                &|| {
+                    self.ui_toolkit.draw_all_on_same_line(&[
+                        &|| {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            self.ui_toolkit.draw_button("Recover", colorscheme!(action_color), move || {
+                                cmd_buffer.borrow_mut().recover_unsaved_work()
+                            })
+                        },
+                        &|| {
+                            let cmd_buffer = Rc::clone(&self.command_buffer);
+                            self.ui_toolkit.draw_button("Discard", colorscheme!(danger_color), move || {
+                                cmd_buffer.borrow_mut().add_controller_command(|controller| {
+                                    controller.discard_recovered_work()
+                                })
+                            })
+                        },
+                    ])
+                },
+            ])
+        }, None::<fn(Keypress)>)
     }
 
     fn render_opener(&self) -> T::DrawResult {
@@ -994,9 +2497,17 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                                       SelectableItem::GroupHeader(heading)
                                                   }
                                                   MenuItem::Selectable { ref label,
+                                                                         keybinding,
                                                                          is_selected,
                                                                          .. } => {
-                                                      let label = label.clone();
+                                                      let label = match keybinding {
+                                                          Some(keypress) => {
+                                                              format!("{}    {}",
+                                                                      label,
+                                                                      keypress.describe())
+                                                          }
+                                                          None => label.clone(),
+                                                      };
                                                       SelectableItem::Selectable { item:
                                                                                        menu_item,
                                                                                    label,
@@ -1388,6 +2899,156 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
         )
     }
 
+    fn render_edit_parse_jsons(&self) -> T::DrawResult {
+        let parse_jsons =
+            self.list_open_functions()
+                .filter_map(|(func, window)| Some((func.downcast_ref::<ParseJSON>()?, window)));
+        draw_all_iter!(T::self.ui_toolkit,
+                       parse_jsons.map(|(parse_json, window)| {
+                                      move || self.render_edit_parse_json(parse_json, &window)
+                                  }))
+    }
+
+    fn render_edit_parse_json(&self, parse_json: &ParseJSON, window: &Window) -> T::DrawResult {
+        let parse_json_id = parse_json.id;
+        self.draw_managed_window(window,
+                                 &format!("Edit JSON parser: {}", parse_json_id),
+                                 &|| {
+                                     self.ui_toolkit.draw_all(&[
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.ui_toolkit.draw_text_input_with_label("Function name",
+                                                               parse_json.name(),
+                                                               move |newvalue| {
+                                                                   let newvalue = newvalue.to_owned();
+                                                                   cmd_buffer.borrow_mut().change_parse_json(parse_json_id, move |parse_json| {
+                                                                       parse_json.name = newvalue.clone();
+                                                                   })
+                                                               },
+                                                               || {})
+                },
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.render_type_change_combo("Parses into", &parse_json.into_type, move |newtype| {
+                        cmd_buffer.borrow_mut().change_parse_json(parse_json_id, move |parse_json| {
+                            parse_json.into_type = newtype.clone();
+                        })
+                    })
+                },
+                &|| self.ui_toolkit.draw_separator(),
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.render_json_codec_editor(&parse_json.json_codec, move |json_codec| {
+                        cmd_buffer.borrow_mut().change_parse_json(parse_json_id, move |parse_json| {
+                            parse_json.json_codec = json_codec.clone();
+                        })
+                    })
+                },
+                &|| self.ui_toolkit.draw_separator(),
+                &|| self.render_general_function_menu(parse_json),
+            ])
+                                 },
+                                 None::<fn(Keypress)>)
+    }
+
+    // how struct fields and enums get spelled out in JSON, for JSON HTTP clients and JSON parsers
+    fn render_json_codec_editor(&self,
+                                json_codec: &JSONCodec,
+                                onchange: impl Fn(JSONCodec) + 'static)
+                                -> T::DrawResult {
+        let onchange = Rc::new(onchange);
+        self.ui_toolkit.draw_all(&[
+            &|| {
+                let onchange = Rc::clone(&onchange);
+                let json_codec1 = json_codec.clone();
+                self.ui_toolkit.draw_combo_box_with_label("Field names",
+                                                          |style| *style == json_codec.field_names,
+                                                          |style| style.to_display().to_owned(),
+                                                          &NAME_STYLE_LIST,
+                                                          move |new_style| {
+                                                              onchange(JSONCodec { field_names: *new_style,
+                                                                                   ..json_codec1.clone() })
+                                                          })
+            },
+            &|| {
+                let onchange = Rc::clone(&onchange);
+                let json_codec1 = json_codec.clone();
+                self.ui_toolkit.draw_combo_box_with_label("Enum variant names",
+                                                          |style| *style == json_codec.variant_names,
+                                                          |style| style.to_display().to_owned(),
+                                                          &NAME_STYLE_LIST,
+                                                          move |new_style| {
+                                                              onchange(JSONCodec { variant_names: *new_style,
+                                                                                   ..json_codec1.clone() })
+                                                          })
+            },
+            &|| {
+                let onchange = Rc::clone(&onchange);
+                let json_codec1 = json_codec.clone();
+                self.ui_toolkit.draw_combo_box_with_label("Enum tagging",
+                                                          |tagging| tagging.is_same_kind(&json_codec.enum_tagging),
+                                                          |tagging| tagging.to_display().to_owned(),
+                                                          &ENUM_TAGGING_LIST.iter().collect_vec(),
+                                                          move |new_tagging| {
+                                                              onchange(JSONCodec { enum_tagging: new_tagging.clone(),
+                                                                                   ..json_codec1.clone() })
+                                                          })
+            },
+            &|| self.render_enum_tag_names_editor(json_codec, Rc::clone(&onchange)),
+        ])
+    }
+
+    // only internally and adjacently tagged enums have names to pick
+    fn render_enum_tag_names_editor<F>(&self,
+                                       json_codec: &JSONCodec,
+                                       onchange: Rc<F>)
+                                       -> T::DrawResult
+        where F: Fn(JSONCodec) + 'static
+    {
+        let (tag, content) = match &json_codec.enum_tagging {
+            EnumTagging::Internal { tag } => (tag, None),
+            EnumTagging::Adjacent { tag, content } => (tag, Some(content)),
+            EnumTagging::External | EnumTagging::Untagged => return self.ui_toolkit.draw_all(&[]),
+        };
+        self.ui_toolkit.draw_all(&[
+            &|| {
+                let onchange = Rc::clone(&onchange);
+                let json_codec1 = json_codec.clone();
+                self.ui_toolkit.draw_text_input_with_label("Tag field",
+                                                           tag,
+                                                           move |new_tag| {
+                                                               let mut json_codec = json_codec1.clone();
+                                                               match &mut json_codec.enum_tagging {
+                                                                   EnumTagging::Internal { tag }
+                                                                   | EnumTagging::Adjacent { tag, .. } => *tag = new_tag.to_owned(),
+                                                                   EnumTagging::External
+                                                                   | EnumTagging::Untagged => (),
+                                                               }
+                                                               onchange(json_codec)
+                                                           },
+                                                           || {})
+            },
+            &|| {
+                let content = match content {
+                    Some(content) => content,
+                    None => return self.ui_toolkit.draw_all(&[]),
+                };
+                let onchange = Rc::clone(&onchange);
+                let json_codec1 = json_codec.clone();
+                self.ui_toolkit.draw_text_input_with_label("Content field",
+                                                           content,
+                                                           move |new_content| {
+                                                               let mut json_codec = json_codec1.clone();
+                                                               if let EnumTagging::Adjacent { content, .. } = &mut json_codec.enum_tagging {
+                                                                   *content = new_content.to_owned();
+                                                               }
+                                                               onchange(json_codec)
+                                                           },
+                                                           || {})
+            },
+        ])
+    }
+
     fn render_edit_structs(&self) -> T::DrawResult {
         let structs =
             self.list_open_typespecs()
@@ -1413,6 +3074,27 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                                                                          &window)))
     }
 
+    fn render_webhook_programs(&self) -> T::DrawResult {
+        let programs =
+            self.list_open_functions()
+                .filter_map(|(func, window)| Some((func.downcast_ref::<WebhookProgram>()?, window)));
+        draw_all_iter!(T::self.ui_toolkit,
+                       programs.map(|(program, window)| move || {
+                                   self.render_webhook_program(program, &window)
+                               }))
+    }
+
+    fn render_scheduled_programs(&self) -> T::DrawResult {
+        let programs = self.list_open_functions().filter_map(|(func, window)| {
+                                                     Some((func.downcast_ref::<ScheduledProgram>()?,
+                                                           window))
+                                                 });
+        draw_all_iter!(T::self.ui_toolkit,
+                       programs.map(|(program, window)| move || {
+                                   self.render_scheduled_program(program, &window)
+                               }))
+    }
+
     // TODO: should window_name go inside of Window?
     fn draw_managed_window(&self,
                            window: &Window,
@@ -1425,6 +3107,7 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
         self.ui_toolkit.draw_window(window_name,
                                     window.size,
                                     window.pos(),
+                                    window.scroll_y,
                                     draw_fn,
                                     handle_keypress,
                                     Some(move || {
@@ -1433,7 +3116,8 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                                       controller.close_window(window_id);
                                                   })
                                     }),
-                                    onwindowchange(Rc::clone(&self.command_buffer), window.id))
+                                    onwindowchange(Rc::clone(&self.command_buffer), window.id),
+                                    onscroll(Rc::clone(&self.command_buffer), window.id))
     }
 
     fn render_chat_program(&self, chat_program: &ChatProgram, window: &Window) -> T::DrawResult {
@@ -1448,9 +3132,26 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                  &|| {
                                      self.ui_toolkit.draw_all(&[
                 &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.ui_toolkit.draw_combo_box_with_label("Trigger",
+                                                              |trigger| *trigger == chat_program.trigger,
+                                                              |trigger| trigger.to_display().to_owned(),
+                                                              &CHAT_TRIGGER_LIST,
+                                                              move |newtrigger| {
+                                                                  let newtrigger = *newtrigger;
+                                                                  cmd_buffer.borrow_mut().change_chat_program(chat_program_id, move |ct| {
+                                                                      ct.trigger = newtrigger;
+                                                                  })
+                                                              })
+                },
+                &|| {
+                    let label = match chat_program.trigger.prefix_label() {
+                        Some(label) => label,
+                        None => return self.ui_toolkit.draw_all(&[]),
+                    };
                     let cmd_buffer2 = Rc::clone(&self.command_buffer);
                     self.ui_toolkit.draw_text_input_with_label(
-                                                               "Bot command",
+                                                               label,
                                                                &chat_program.prefix,
                                                                move |newvalue| {
                                                                    let newvalue =
@@ -1464,6 +3165,10 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                                                &|| {},
                     )
                 },
+                &|| match chat_program.trigger_error() {
+                    Some(e) => self.ui_toolkit.draw_wrapped_text(colorscheme!(danger_color), &e),
+                    None => self.ui_toolkit.draw_all(&[]),
+                },
                 &|| self.render_code(chat_program.code.id),
             ])
                                  },
@@ -1471,6 +3176,117 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
         )
     }
 
+    fn render_webhook_program(&self,
+                              webhook_program: &WebhookProgram,
+                              window: &Window)
+                              -> T::DrawResult {
+        let webhook_program_id = webhook_program.id;
+        self.draw_managed_window(
+                                 window,
+                                 &format!("Edit webhook program: {}###{}",
+                                          webhook_program.name(),
+                                          webhook_program.id()),
+                                 &|| {
+                                     self.ui_toolkit.draw_all(&[
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.ui_toolkit.draw_text_input_with_label(
+                                                               "Route",
+                                                               &webhook_program.route,
+                                                               move |newvalue| {
+                                                                   let newvalue =
+                                                                       newvalue.to_string();
+                                                                   cmd_buffer
+                                    .borrow_mut()
+                                    .change_webhook_program(webhook_program_id, move |wp| {
+                                        wp.route = newvalue.to_string()
+                                    })
+                                                               },
+                                                               &|| {},
+                    )
+                },
+                &|| self.render_code(webhook_program.code.id),
+            ])
+                                 },
+                                 None::<fn(Keypress)>,
+        )
+    }
+
+    fn render_scheduled_program(&self,
+                                scheduled_program: &ScheduledProgram,
+                                window: &Window)
+                                -> T::DrawResult {
+        let scheduled_program_id = scheduled_program.id;
+        self.draw_managed_window(
+                                 window,
+                                 &format!("Edit scheduled program: {}###{}",
+                                          scheduled_program.name(),
+                                          scheduled_program.id()),
+                                 &|| {
+                                     self.ui_toolkit.draw_all(&[
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.ui_toolkit.draw_text_input_with_label("Name",
+                                                               &scheduled_program.name,
+                                                               move |newvalue| {
+                                                                   let newvalue = newvalue.to_string();
+                                                                   cmd_buffer.borrow_mut()
+                                                                             .change_scheduled_program(scheduled_program_id, move |sp| {
+                                                                                 sp.name = newvalue.clone()
+                                                                             })
+                                                               },
+                                                               &|| {})
+                },
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.ui_toolkit.draw_text_input_with_label("Schedule (cron, UTC)",
+                                                               &scheduled_program.schedule,
+                                                               move |newvalue| {
+                                                                   let newvalue = newvalue.to_string();
+                                                                   cmd_buffer.borrow_mut()
+                                                                             .change_scheduled_program(scheduled_program_id, move |sp| {
+                                                                                 sp.schedule = newvalue.clone()
+                                                                             })
+                                                               },
+                                                               &|| {})
+                },
+                &|| match scheduled_program.parse_schedule() {
+                    Ok(_) => self.ui_toolkit.draw_all(&[]),
+                    Err(e) => self.ui_toolkit.draw_wrapped_text(colorscheme!(danger_color), &e),
+                },
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.ui_toolkit.draw_text_input_with_label("Service (irc, discord or slack)",
+                                                               &scheduled_program.service,
+                                                               move |newvalue| {
+                                                                   let newvalue = newvalue.to_string();
+                                                                   cmd_buffer.borrow_mut()
+                                                                             .change_scheduled_program(scheduled_program_id, move |sp| {
+                                                                                 sp.service = newvalue.clone()
+                                                                             })
+                                                               },
+                                                               &|| {})
+                },
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.ui_toolkit.draw_text_input_with_label("Channel",
+                                                               &scheduled_program.channel,
+                                                               move |newvalue| {
+                                                                   let newvalue = newvalue.to_string();
+                                                                   cmd_buffer.borrow_mut()
+                                                                             .change_scheduled_program(scheduled_program_id, move |sp| {
+                                                                                 sp.channel = newvalue.clone()
+                                                                             })
+                                                               },
+                                                               &|| {})
+                },
+                &|| self.render_code(scheduled_program.code.id),
+            ])
+                                 },
+                                 None::<fn(Keypress)>,
+        )
+    }
+
     fn render_json_http_client_builders(&self) -> T::DrawResult {
         let builders = self.controller.list_json_http_client_builders();
         draw_all_iter!(T::self.ui_toolkit,
@@ -1527,6 +3343,15 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                 &|| self.ui_toolkit.draw_text("URL params:"),
                 &|| self.render_code(client.gen_url_params_code.id),
                 &|| self.ui_toolkit.draw_separator(),
+                &|| {
+                    let cmd_buffer = Rc::clone(&self.command_buffer);
+                    self.render_json_codec_editor(&client.json_codec, move |json_codec| {
+                        cmd_buffer.borrow_mut().change_http_client(client_id, move |http_client| {
+                            http_client.json_codec = json_codec.clone();
+                        })
+                    })
+                },
+                &|| self.ui_toolkit.draw_separator(),
                 &|| self.ui_toolkit.draw_text("Test out this client below, and we'll try and figure out the response schema"),
                 &|| {
                     self.render_code(client.test_code.id)
@@ -1950,6 +3775,14 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                                                        .load_typespec(newstrukt)
                                                               })
                             },
+                            &|| {
+                                let cont1 = Rc::clone(&self.command_buffer);
+                                let target = RenameTarget::StructField { struct_id: strukt.id,
+                                                                         field_id: field.id };
+                                self.ui_toolkit.draw_button("Rename...",
+                                                            colorscheme!(action_color),
+                                                            move || cont1.borrow_mut().open_rename_dialog(target))
+                            },
                             &|| {
                                 let cont1 = Rc::clone(&self.command_buffer);
                                 let strukt_id = strukt.id;
@@ -1985,14 +3818,28 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
     fn render_general_struct_menu(&self, strukt: &structs::Struct) -> T::DrawResult {
         self.ui_toolkit.draw_all(&[&|| {
                                      let cont1 = Rc::clone(&self.command_buffer);
-                                     let strukt_id = strukt.id;
+                                     let target = DeletionTarget::TypeSpec(strukt.id);
                                      self.ui_toolkit.draw_button("\u{f1f8} Delete Struct",
                                                                  colorscheme!(danger_color),
                                                                  move || {
                                                                      cont1.borrow_mut()
-                                                                          .remove_typespec(strukt_id);
+                                                                          .request_deletion(target);
                                                                  })
-                                 }])
+                                 },
+                                   &|| {
+                                       self.render_find_references_button(ReferenceTarget::TypeSpec(strukt.id))
+                                   }])
+    }
+
+    fn render_find_references_button(&self, target: ReferenceTarget) -> T::DrawResult {
+        let cmd_buffer = Rc::clone(&self.command_buffer);
+        self.ui_toolkit
+            .draw_button("Find references", colorscheme!(action_color), move || {
+                cmd_buffer.borrow_mut()
+                          .add_controller_command(move |controller| {
+                              controller.show_references(target)
+                          });
+            })
     }
 
     fn render_edit_enum(&self, eneom: &enums::Enum, window: &Window) -> T::DrawResult {
@@ -2030,6 +3877,7 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                                                &|| {})
                 },
                 &|| self.render_enum_variants_selector(eneom),
+                &|| self.render_find_references_button(ReferenceTarget::TypeSpec(eneom.id)),
                 // TODO: why is this commented out? lol
                 //                    self.render_general_struct_menu(eneom),
             ])
@@ -2102,6 +3950,14 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                                     },
                                 )
                             },
+                            &|| {
+                                let cont1 = Rc::clone(&self.command_buffer);
+                                let target = RenameTarget::EnumVariant { enum_id: eneom.id,
+                                                                         variant_id: variant.id };
+                                self.ui_toolkit.draw_button("Rename...", colorscheme!(action_color), move || {
+                                    cont1.borrow_mut().open_rename_dialog(target)
+                                })
+                            },
                             &|| {
                                 let eneom1 = eneom.clone();
                                 let cont1 = Rc::clone(&self.command_buffer);
@@ -2138,7 +3994,33 @@ impl<'a, T: UiToolkit> Renderer<'a, T> {
                 let func_id = func.id();
                 self.ui_toolkit
                     .draw_button("Delete", colorscheme!(danger_color), move || {
-                        cont1.borrow_mut().remove_function(func_id);
+                        cont1.borrow_mut()
+                             .request_deletion(DeletionTarget::Function(func_id));
+                    })
+            },
+            &|| self.render_find_references_button(ReferenceTarget::Function(func.id())),
+            &|| {
+                if self.controller.project.path.is_none() {
+                    return self.ui_toolkit.draw_all(&[]);
+                }
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                let func_id = func.id();
+                self.ui_toolkit.draw_button("Changes since last save",
+                                            colorscheme!(cool_color),
+                                            move || {
+                                                cmd_buffer.borrow_mut()
+                                                          .compare_with_saved(Some(func_id))
+                                            })
+            },
+            &|| {
+                if self.env_genie.get_code_func(func.id()).is_none() {
+                    return self.ui_toolkit.draw_all(&[]);
+                }
+                let cmd_buffer = Rc::clone(&self.command_buffer);
+                let target = RenameTarget::Function(func.id());
+                self.ui_toolkit
+                    .draw_button("Rename...", colorscheme!(action_color), move || {
+                        cmd_buffer.borrow_mut().open_rename_dialog(target);
                     })
             },
             // TODO: temporarily(?) disable function test section
@@ -2520,6 +4402,43 @@ fn onwindowchange(cmd_buffer: Rc<RefCell<CommandBuffer>>,
     }
 }
 
+// saves to the project's file, asking where to put it first if it doesn't have one yet
+fn save_project<T: UiToolkit>(cmd_buffer: Rc<RefCell<CommandBuffer>>,
+                              path: Option<PathBuf>,
+                              then: Option<ProjectAction>) {
+    match path {
+        Some(path) => cmd_buffer.borrow_mut().save_project(path, then),
+        None => T::choose_file_to_save(move |path| {
+            cmd_buffer.borrow_mut().save_project(path, then.clone())
+        }),
+    }
+}
+
+// shows what uploading would change. the server's copy gets fetched in the background, so the
+// window says it's loading until it shows up
+fn compare_with_server(cmd_buffer: Rc<RefCell<CommandBuffer>>) {
+    let cmd_buffer2 = Rc::clone(&cmd_buffer);
+    cmd_buffer.borrow_mut()
+              .add_integrating_command(move |controller, _, async_executor, _| {
+                  controller.show_diff_view(DiffView::loading(UPLOAD_DIFF_DESCRIPTION.to_owned()));
+                  async_executor.exec(async move {
+                                    let server_world =
+                                        fetch_the_code().await.map_err(|e| e.to_string());
+                                    cmd_buffer2.borrow_mut().show_upload_diff(server_world);
+                                    Ok::<(), ()>(())
+                                })
+              })
+}
+
+fn onscroll(cmd_buffer: Rc<RefCell<CommandBuffer>>, window_id: lang::ID) -> impl Fn(f32) + 'static {
+    move |scroll_y| {
+        cmd_buffer.borrow_mut()
+                  .add_controller_command(move |controller| {
+                      controller.set_window_scroll(window_id, scroll_y)
+                  })
+    }
+}
+
 fn format_typespec_select(ts: &Box<dyn lang::TypeSpec>, nesting_level: Option<&[usize]>) -> String {
     let indent = match nesting_level {
         Some(nesting_level) => iter::repeat("\t").take(nesting_level.len() + 1).join(""),
@@ -2541,7 +4460,23 @@ fn run_test<F: lang::Function>(command_buffer: &Rc<RefCell<CommandBuffer>>, func
                                });
 }
 
-fn save_world(cont: &Controller, env: &env::ExecutionEnvironment) -> code_loading::TheWorld {
+fn describe_edit_token(token_info: &EditTokenInfo) -> String {
+    let scope = match &token_info.scope {
+        EditScope::ReadOnly => "read-only".to_owned(),
+        EditScope::Write { program_ids: None } => "can change everything".to_owned(),
+        EditScope::Write { program_ids } => {
+            let count = program_ids.as_ref()
+                                   .map_or(0, |program_ids| program_ids.len());
+            format!("can change {} program(s)", count)
+        }
+    };
+    format!("Edit link from {}, {}, expires in {}h",
+            token_info.issued_by,
+            scope,
+            token_info.expires_in_secs / 3600)
+}
+
+pub fn save_world(cont: &Controller, env: &env::ExecutionEnvironment) -> code_loading::TheWorld {
     code_loading::TheWorld { scripts: cont.script_by_id.values().cloned().collect(),
                              tests: cont.test_by_id.values().cloned().collect(),
                              // save all non-builtin functions and typespecs
@@ -2562,6 +4497,25 @@ fn save_world(cont: &Controller, env: &env::ExecutionEnvironment) -> code_loadin
                                            .collect() }
 }
 
+pub fn load_world(world: code_loading::TheWorld,
+                  cont: &mut Controller,
+                  env: &mut env::ExecutionEnvironment) {
+    for script in world.scripts {
+        cont.load_script(script)
+    }
+    for test in world.tests {
+        cont.load_test(test);
+    }
+
+    // TODO: this is duped in irctest.rs
+    for function in world.functions {
+        env.add_function_box(function);
+    }
+    for typespec in world.typespecs {
+        env.add_typespec_box(typespec);
+    }
+}
+
 pub fn run<F: FnOnce(lang::Value) + 'static>(mut interpreter: Interpreter,
                                              async_executor: &mut async_executor::AsyncExecutor,
                                              code_node: lang::CodeNode,
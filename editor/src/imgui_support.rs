@@ -8,7 +8,9 @@ use crate::colorscheme;
 use imgui_winit_support;
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
 
-pub fn run<F: FnMut(&Ui, Option<Keypress>) -> bool>(title: String, mut run_ui: F) {
+// run_ui gets told when the window's close button was hit, and it's up to it whether we actually
+// exit
+pub fn run<F: FnMut(&Ui, Option<Keypress>, bool) -> bool>(title: String, mut run_ui: F) {
     use glium::{Display, Surface};
     use imgui_glium_renderer::Renderer;
 
@@ -198,6 +200,7 @@ pub fn run<F: FnMut(&Ui, Option<Keypress>) -> bool>(title: String, mut run_ui: F
         style.colors[StyleColor::FrameBg as usize] = colorscheme!(input_bg_color).into();
 
         let mut keypress: Option<Keypress> = None;
+        let mut close_requested = false;
 
         event_loop.run_return(|event, _, control_flow| match event {
                       Event::NewEvents(_) => {
@@ -213,10 +216,11 @@ pub fn run<F: FnMut(&Ui, Option<Keypress>) -> bool>(title: String, mut run_ui: F
                       }
                       Event::RedrawRequested(_) => {
                           let ui = imgui.frame();
-                          if !run_ui(&ui, keypress) {
+                          if !run_ui(&ui, keypress, close_requested) {
                               *control_flow = ControlFlow::Exit;
                           }
                           keypress = None;
+                          close_requested = false;
                           let gl_window = display.gl_window();
                           let mut target = display.draw();
                           target.clear_color_srgb(1.0, 1.0, 1.0, 1.0);
@@ -226,7 +230,7 @@ pub fn run<F: FnMut(&Ui, Option<Keypress>) -> bool>(title: String, mut run_ui: F
                           target.finish().unwrap();
                       }
                       Event::WindowEvent { event: WindowEvent::CloseRequested,
-                                           .. } => *control_flow = ControlFlow::Exit,
+                                           .. } => close_requested = true,
                       event => {
                           let gl_window = display.gl_window();
                           platform.handle_event(imgui.io_mut(), &gl_window.window(), &event);
@@ -271,6 +275,7 @@ fn map_key(key: VirtualKeyCode) -> Option<AppKey> {
         VirtualKeyCode::X => Some(AppKey::X),
         VirtualKeyCode::R => Some(AppKey::R),
         VirtualKeyCode::O => Some(AppKey::O),
+        VirtualKeyCode::P => Some(AppKey::P),
         VirtualKeyCode::U => Some(AppKey::U),
         VirtualKeyCode::V => Some(AppKey::V),
         VirtualKeyCode::Delete => Some(AppKey::Delete),
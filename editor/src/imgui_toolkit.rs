@@ -27,6 +27,7 @@ use std::fmt::Debug;
 use std::fs::File;
 use std::hash::{Hash, Hasher};
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -51,6 +52,7 @@ struct Window {
     // than our size, in which case scrollbars would activate if enabled on the window
     content_size: (f32, f32),
     remaining_size: (f32, f32),
+    scroll_y: f32,
     flex: u8,
 }
 
@@ -264,8 +266,11 @@ impl TkCache {
 }
 
 pub fn draw_app(app: Rc<RefCell<App>>, mut async_executor: async_executor::AsyncExecutor) {
-    imgui_support::run("cs".to_string(), move |ui, keypress| {
+    imgui_support::run("cs".to_string(), move |ui, keypress, close_requested| {
         let mut app = app.borrow_mut();
+        if close_requested {
+            app.request_exit();
+        }
         app.flush_commands(&mut async_executor);
         async_executor.turn();
 
@@ -289,6 +294,15 @@ fn buf(text: &str) -> ImString {
     imstr
 }
 
+// None if the user backed out of the dialog
+fn chosen_file(response: nfd::Response) -> Option<PathBuf> {
+    match response {
+        nfd::Response::Okay(file_path) => Some(file_path.into()),
+        nfd::Response::OkayMultiple(file_paths) => file_paths.into_iter().nth(0).map(Into::into),
+        nfd::Response::Cancel => None,
+    }
+}
+
 impl State {
     fn new() -> Self {
         State { used_labels: HashMap::new(),
@@ -544,37 +558,34 @@ impl<'a> UiToolkit for ImguiToolkit<'a> {
     //        self.ui.columns(1, &self.imlabel("columnsend"), false)
     //    }
     fn open_file_open_dialog(callback: impl Fn(&[u8]) + 'static) {
-        let result = nfd::open_file_dialog(None, None).unwrap();
-        let filename = match result {
-            nfd::Response::Okay(file_path) => Some(file_path),
-            nfd::Response::OkayMultiple(file_paths) => file_paths.into_iter().nth(0),
-            nfd::Response::Cancel => None,
-        };
-        if filename.is_none() {
-            return;
-        }
-        let filename = filename.unwrap();
-        let mut read_buffer = Vec::new();
-        File::open(&filename).unwrap()
-                             .read_to_end(&mut read_buffer)
-                             .unwrap();
-        callback(&read_buffer);
+        Self::choose_file_to_open(move |filename| {
+            let mut read_buffer = Vec::new();
+            File::open(&filename).unwrap()
+                                 .read_to_end(&mut read_buffer)
+                                 .unwrap();
+            callback(&read_buffer);
+        })
     }
 
     fn open_file_save_dialog(_filename_suggestion: &str, contents: &[u8], _mimetype: &str) {
-        let result = nfd::open_save_dialog(None, None).unwrap();
-        let filename = match result {
-            nfd::Response::Okay(file_path) => Some(file_path),
-            nfd::Response::OkayMultiple(file_paths) => file_paths.into_iter().nth(0),
-            nfd::Response::Cancel => None,
-        };
-        if filename.is_none() {
-            return;
+        let contents = contents.to_vec();
+        Self::choose_file_to_save(move |filename| {
+            File::create(&filename).unwrap()
+                                   .write_all(&contents)
+                                   .unwrap();
+        })
+    }
+
+    fn choose_file_to_open(callback: impl Fn(PathBuf) + 'static) {
+        if let Some(filename) = chosen_file(nfd::open_file_dialog(None, None).unwrap()) {
+            callback(filename)
+        }
+    }
+
+    fn choose_file_to_save(callback: impl Fn(PathBuf) + 'static) {
+        if let Some(filename) = chosen_file(nfd::open_save_dialog(None, None).unwrap()) {
+            callback(filename)
         }
-        let filename = filename.unwrap();
-        File::create(&filename).unwrap()
-                               .write_all(&contents)
-                               .unwrap();
     }
 
     fn callback_when_drag_intersects<F: Fn() + 'static>(&self,
@@ -1031,20 +1042,24 @@ impl<'a> UiToolkit for ImguiToolkit<'a> {
             .text(["|", "/", "-", "\\"][(time / 0.05) as usize & 3])
     }
 
-    fn draw_window<F: Fn(Keypress) + 'static, G: Fn() + 'static, H>(&self,
-                                                                    window_name: &str,
-                                                                    size: (usize, usize),
-                                                                    pos: (isize, isize),
-                                                                    draw_window_contents: &dyn Fn(),
-                                                                    handle_keypress: Option<F>,
-                                                                    onclose: Option<G>,
-                                                                    onwindowchange: H)
-        where H: Fn((isize, isize), (usize, usize)) + 'static
+    fn draw_window<F: Fn(Keypress) + 'static, G: Fn() + 'static, H, I>(&self,
+                                                                       window_name: &str,
+                                                                       size: (usize, usize),
+                                                                       pos: (isize, isize),
+                                                                       scroll_y: f32,
+                                                                       draw_window_contents: &dyn Fn(),
+                                                                       handle_keypress: Option<F>,
+                                                                       onclose: Option<G>,
+                                                                       onwindowchange: H,
+                                                                       onscroll: I)
+        where H: Fn((isize, isize), (usize, usize)) + 'static,
+              I: Fn(f32) + 'static
     {
         let window_name = self.imlabel(window_name);
         let window_name_str: &str = window_name.as_ref();
 
         let window_after_prev_draw = TkCache::get_window(window_name_str);
+        let is_first_draw = window_after_prev_draw.is_none();
         let mut window_builder = imgui::Window::new(&window_name).movable(true)
                                                                  .scrollable(true);
 
@@ -1071,6 +1086,11 @@ impl<'a> UiToolkit for ImguiToolkit<'a> {
         }
 
         window_builder.build(self.ui, &|| {
+                          // the first time around, put the window back where it was scrolled to
+                          // last session
+                          if is_first_draw && scroll_y > 0. {
+                              self.ui.set_scroll_y(scroll_y);
+                          }
                           TkCache::set_current_window_label(&window_name);
                           TkCache::set_current_window_flex(0);
                           self.ui.group(draw_window_contents);
@@ -1087,6 +1107,7 @@ impl<'a> UiToolkit for ImguiToolkit<'a> {
                                        size: (window_size[0], window_size[1]),
                                        remaining_size: (remaining_size[0], remaining_size[1]),
                                        content_size: (content_size[0], content_size[1]),
+                                       scroll_y: self.ui.scroll_y(),
                                        flex: cache.current_flex };
                           if prev_window.is_some() && prev_window.unwrap() != &drawn_window {
                               onwindowchange((drawn_window.pos.0 as isize,
@@ -1094,6 +1115,12 @@ impl<'a> UiToolkit for ImguiToolkit<'a> {
                                              (drawn_window.size.0 as usize,
                                               drawn_window.size.1 as usize))
                           }
+                          if prev_window.map_or(false, |prev_window| {
+                                            prev_window.scroll_y != drawn_window.scroll_y
+                                        })
+                          {
+                              onscroll(drawn_window.scroll_y)
+                          }
                           cache.windows
                                .insert(window_name_str.to_string(), drawn_window);
 
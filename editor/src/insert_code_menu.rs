@@ -17,6 +17,8 @@ use crate::code_editor::{
 use cs::builtins;
 use cs::builtins::get_success_type_from_option_or_result_typ;
 use cs::chat_program::ChatProgram;
+use cs::scheduled_program::ScheduledProgram;
+use cs::webhook_program::WebhookProgram;
 use cs::code_generation::new_anon_func;
 use cs::lang::{arg_and_return_typs_for_anon_func, ArgumentDefinition, TypeSpec};
 
@@ -496,9 +498,12 @@ fn find_functions_ignoring_wraps_type<'a>(
                      env_genie.types_match(&func.returns(), return_type.unwrap())
                  }
              })
-             // filter out ChatPrograms... we don't want them to show up in autocomplete and possibly
+             // filter out ChatPrograms, WebhookPrograms and ScheduledPrograms... we don't want them
+             // to show up in autocomplete and possibly
              // TODO don't even want them to be functions
              .filter(|f| f.downcast_ref::<ChatProgram>().is_none())
+             .filter(|f| f.downcast_ref::<WebhookProgram>().is_none())
+             .filter(|f| f.downcast_ref::<ScheduledProgram>().is_none())
              .map(|func| func.as_ref())
 }
 
@@ -0,0 +1,193 @@
+// small diffs between two JSON values, so undo history can keep track of what changed instead of
+// holding onto a copy of everything. objects get diffed key by key, and arrays get the unchanged
+// stuff at the front and back trimmed off, so inserting one expression into a block only stores
+// that one expression
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Change {
+    // None means the key isn't there
+    Set {
+        path: Vec<PathSegment>,
+        before: Option<Value>,
+        after: Option<Value>,
+    },
+    Splice {
+        path: Vec<PathSegment>,
+        index: usize,
+        removed: Vec<Value>,
+        inserted: Vec<Value>,
+    },
+}
+
+impl Change {
+    fn inverse(&self) -> Self {
+        match self {
+            Change::Set { path,
+                          before,
+                          after, } => Change::Set { path: path.clone(),
+                                                    before: after.clone(),
+                                                    after: before.clone() },
+            Change::Splice { path,
+                             index,
+                             removed,
+                             inserted, } => Change::Splice { path: path.clone(),
+                                                             index: *index,
+                                                             removed: inserted.clone(),
+                                                             inserted: removed.clone() },
+        }
+    }
+
+    fn apply(&self, value: &mut Value) -> Option<()> {
+        match self {
+            Change::Set { path, after, .. } => {
+                let (last, parent_path) = match path.split_last() {
+                    Some(split) => split,
+                    None => {
+                        *value = after.clone().unwrap_or(Value::Null);
+                        return Some(());
+                    }
+                };
+                let parent = find_mut(value, parent_path)?;
+                match (last, parent, after) {
+                    (PathSegment::Key(key), Value::Object(map), Some(after)) => {
+                        map.insert(key.clone(), after.clone());
+                    }
+                    (PathSegment::Key(key), Value::Object(map), None) => {
+                        map.remove(key);
+                    }
+                    (PathSegment::Index(index), Value::Array(array), Some(after)) => {
+                        *array.get_mut(*index)? = after.clone();
+                    }
+                    _ => return None,
+                }
+            }
+            Change::Splice { path,
+                             index,
+                             removed,
+                             inserted, } => {
+                let array = find_mut(value, path)?.as_array_mut()?;
+                if *index + removed.len() > array.len() {
+                    return None;
+                }
+                array.splice(*index..*index + removed.len(), inserted.iter().cloned());
+            }
+        }
+        Some(())
+    }
+}
+
+fn find_mut<'a>(value: &'a mut Value, path: &[PathSegment]) -> Option<&'a mut Value> {
+    let mut value = value;
+    for segment in path {
+        value = match segment {
+            PathSegment::Key(key) => value.as_object_mut()?.get_mut(key)?,
+            PathSegment::Index(index) => value.as_array_mut()?.get_mut(*index)?,
+        };
+    }
+    Some(value)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Diff {
+    changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn between(before: &Value, after: &Value) -> Self {
+        let mut changes = vec![];
+        diff_into(&mut vec![], before, after, &mut changes);
+        Self { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    // turns the before value into the after value. None if the value doesn't look like what the
+    // diff was made from, in which case it could be left partway changed
+    pub fn apply(&self, value: &mut Value) -> Option<()> {
+        for change in &self.changes {
+            change.apply(value)?;
+        }
+        Some(())
+    }
+
+    // turns the after value back into the before value
+    pub fn revert(&self, value: &mut Value) -> Option<()> {
+        for change in self.changes.iter().rev() {
+            change.inverse().apply(value)?;
+        }
+        Some(())
+    }
+}
+
+fn diff_into(path: &mut Vec<PathSegment>,
+             before: &Value,
+             after: &Value,
+             changes: &mut Vec<Change>) {
+    if before == after {
+        return;
+    }
+    match (before, after) {
+        (Value::Object(before_map), Value::Object(after_map)) => {
+            for (key, before_value) in before_map {
+                path.push(PathSegment::Key(key.clone()));
+                match after_map.get(key) {
+                    Some(after_value) => diff_into(path, before_value, after_value, changes),
+                    None => changes.push(Change::Set { path: path.clone(),
+                                                       before: Some(before_value.clone()),
+                                                       after: None }),
+                }
+                path.pop();
+            }
+            for (key, after_value) in after_map {
+                if !before_map.contains_key(key) {
+                    let mut path = path.clone();
+                    path.push(PathSegment::Key(key.clone()));
+                    changes.push(Change::Set { path,
+                                               before: None,
+                                               after: Some(after_value.clone()) });
+                }
+            }
+        }
+        (Value::Array(before_array), Value::Array(after_array)) => {
+            let prefix = before_array.iter()
+                                     .zip(after_array.iter())
+                                     .take_while(|(b, a)| b == a)
+                                     .count();
+            let max_suffix = before_array.len().min(after_array.len()) - prefix;
+            let suffix = before_array.iter()
+                                     .rev()
+                                     .zip(after_array.iter().rev())
+                                     .take(max_suffix)
+                                     .take_while(|(b, a)| b == a)
+                                     .count();
+            let removed = &before_array[prefix..before_array.len() - suffix];
+            let inserted = &after_array[prefix..after_array.len() - suffix];
+            if removed.len() == inserted.len() {
+                // same shape, so dig into the elements that changed
+                for (i, (before_value, after_value)) in removed.iter().zip(inserted).enumerate() {
+                    path.push(PathSegment::Index(prefix + i));
+                    diff_into(path, before_value, after_value, changes);
+                    path.pop();
+                }
+            } else {
+                changes.push(Change::Splice { path: path.clone(),
+                                              index: prefix,
+                                              removed: removed.to_vec(),
+                                              inserted: inserted.to_vec() });
+            }
+        }
+        _ => changes.push(Change::Set { path: path.clone(),
+                                        before: Some(before.clone()),
+                                        after: Some(after.clone()) }),
+    }
+}
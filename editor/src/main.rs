@@ -21,7 +21,9 @@ mod code_editor;
 mod code_editor_renderer;
 mod code_rendering;
 mod code_validation;
+mod deletion;
 mod color_schemes;
+mod command_palette;
 mod edit_types;
 mod editor;
 #[cfg(not(target_arch = "wasm32"))]
@@ -31,11 +33,17 @@ mod imgui_toolkit;
 mod insert_code_menu;
 mod insert_code_menu_renderer;
 mod json2;
+mod json_diff;
 mod json_http_client_builder;
+mod merge;
 mod opener;
+mod project;
+mod references;
+mod rename;
 mod save_state;
 mod schema_builder;
 mod send_to_server_overlay;
+mod structural_diff;
 mod theme_editor_renderer;
 mod ui_toolkit;
 mod undo;
@@ -0,0 +1,73 @@
+// resolving a merge the git merge driver couldn't finish on its own (see src/bin/merge_worlds.rs).
+// the conflicts are listed in a window, the user picks a side for each one, and then the merged
+// world gets loaded in place of whatever was open
+use cs::code_loading;
+use cs::code_loading::TheWorld;
+use cs::world_merge::{Conflict, PendingMerge, Side};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+type Error = Box<dyn std::error::Error>;
+
+// how much of each side of a conflict gets shown
+const PREVIEW_LENGTH: usize = 300;
+
+pub struct MergeView {
+    // the file the pending merge came from, which is where the merged world belongs
+    pub path: PathBuf,
+    pending_merge: PendingMerge,
+    conflicts: Vec<Conflict>,
+    resolutions: HashMap<String, Side>,
+}
+
+impl MergeView {
+    pub fn new(path: PathBuf, pending_merge: PendingMerge) -> Self {
+        let conflicts = pending_merge.merge(&HashMap::new()).conflicts;
+        Self { path,
+               pending_merge,
+               conflicts,
+               resolutions: HashMap::new() }
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    pub fn resolution(&self, conflict_id: &str) -> Option<Side> {
+        self.resolutions.get(conflict_id).cloned()
+    }
+
+    pub fn resolve(&mut self, conflict_id: String, side: Side) {
+        self.resolutions.insert(conflict_id, side);
+    }
+
+    pub fn num_unresolved(&self) -> usize {
+        self.conflicts
+            .iter()
+            .filter(|conflict| !self.resolutions.contains_key(&conflict.id))
+            .count()
+    }
+
+    pub fn finish(&self) -> Result<TheWorld, Error> {
+        let merge = self.pending_merge.merge(&self.resolutions);
+        if !merge.conflicts.is_empty() {
+            return Err(format!("{} conflict(s) still need resolving", merge.conflicts.len()).into());
+        }
+        code_loading::deserialize(&merge.merged.to_string())
+    }
+}
+
+// one side of a conflict, short enough to fit in the window
+pub fn preview(conflict: &Conflict, side: Side) -> String {
+    let value = match conflict.side(side) {
+        Some(value) => value,
+        None => return "(deleted)".to_owned(),
+    };
+    let json = value.to_string();
+    if json.chars().count() > PREVIEW_LENGTH {
+        format!("{}...",
+                json.chars().take(PREVIEW_LENGTH).collect::<String>())
+    } else {
+        json
+    }
+}
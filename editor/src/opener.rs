@@ -1,6 +1,9 @@
+use crate::command_palette;
 use crate::editor::CommandBuffer;
 use crate::editor::Controller;
+use crate::editor::Keypress;
 use cs::env_genie::EnvGenie;
+use cs::lang;
 use cs::lang::Function;
 use cs::lang::TypeSpec;
 use itertools::Itertools;
@@ -11,7 +14,10 @@ use std::iter;
 lazy_static! {
     static ref CATEGORIES: Vec<Box<dyn MenuCategory + Send + Sync>> = vec![Box::new(Scripts {}),
         Box::new(ChatPrograms {}),
+        Box::new(WebhookPrograms {}),
+        Box::new(ScheduledPrograms {}),
              Box::new(JSONHTTPClients {}),
+             Box::new(JSONParsers {}),
              Box::new(Functions {}),
              Box::new(Enums {}),
              Box::new(Structs {}),];
@@ -20,12 +26,25 @@ lazy_static! {
 pub struct Opener {
     pub input_str: String,
     pub selected_index: isize,
+    mode: OpenerMode,
+}
+
+// the command palette is the opener, except it lists things to do instead of things to open
+enum OpenerMode {
+    OpenItems,
+    RunCommands,
 }
 
 impl Opener {
     pub fn new() -> Self {
         Self { input_str: "".to_string(),
-               selected_index: 0 }
+               selected_index: 0,
+               mode: OpenerMode::OpenItems }
+    }
+
+    pub fn command_palette() -> Self {
+        Self { mode: OpenerMode::RunCommands,
+               ..Self::new() }
     }
 
     // XXX: copy and paste from insert_code_menu.rs
@@ -110,46 +129,143 @@ impl<'a> OptionsLister<'a> {
 
     // TODO: hax to make it work
     fn vec(&self) -> Vec<MenuItem> {
-        Iterator::flatten(
+        match self.opener.mode {
+            OpenerMode::OpenItems => self.items_to_open(),
+            OpenerMode::RunCommands => self.commands(),
+        }
+    }
+
+    fn items_to_open(&self) -> Vec<MenuItem> {
+        let recent = self.recent_items();
+        let recent_section = if recent.is_empty() {
+            None
+        } else {
+            Some(iter::once(MenuItem::Heading("Recent")).chain(recent.into_iter()))
+        };
+        let category_sections =
             CATEGORIES.iter().filter_map(move |category| {
-            let items = filter_matches(&self.opener.input_str, category.items(self))
-                .collect_vec();
-            if items.is_empty() {
-                return None
-            }
-            Some(iter::once(MenuItem::Heading(category.label()))
-                .chain(items.into_iter()))
-            })
-        ).collect()
+                                 self.section(category.label(), category.items(self))
+                             });
+        recent_section.into_iter()
+                      .flatten()
+                      .chain(Iterator::flatten(category_sections))
+                      .collect()
+    }
+
+    fn commands(&self) -> Vec<MenuItem> {
+        command_palette::sections(self.controller, self.env_genie)
+            .into_iter()
+            .filter_map(|(label, items)| self.section(label, items.into_iter()))
+            .flatten()
+            .collect()
+    }
+
+    // None when nothing in there matches what's been typed in
+    fn section(&self,
+               label: &'static str,
+               items: impl Iterator<Item = MenuItem>)
+               -> Option<impl Iterator<Item = MenuItem>> {
+        let items = filter_matches(&self.opener.input_str, items).collect_vec();
+        if items.is_empty() {
+            return None;
+        }
+        Some(iter::once(MenuItem::Heading(label)).chain(items.into_iter()))
+    }
+
+    // the things that were opened most recently, out of every category, most recent first. they
+    // also still show up under their own categories
+    fn recent_items(&self) -> Vec<MenuItem> {
+        let recent_ids = self.controller.recent_opener_items();
+        let all_items = CATEGORIES.iter()
+                                  .flat_map(|category| category.items(self))
+                                  .collect_vec();
+        filter_matches(&self.opener.input_str, all_items.into_iter())
+            .filter_map(|item| Some((recent_ids.iter().position(|id| *id == item.id()?)?, item)))
+            .sorted_by_key(|(recency, _)| *recency)
+            .map(|(_, item)| item)
+            .collect()
     }
 }
 
-fn filter_matches<'a>(input_str: &'a str,
-                      items: impl Iterator<Item = MenuItem>)
-                      -> impl Iterator<Item = MenuItem> {
-    // TODO: add fuzzy finding
+// best matches first
+fn filter_matches(input_str: &str,
+                  items: impl Iterator<Item = MenuItem>)
+                  -> impl Iterator<Item = MenuItem> {
     let input = input_str.trim().to_lowercase();
-    items.filter(move |item| match item {
-             MenuItem::Selectable { label, .. } => label.to_lowercase().contains(&input),
-             MenuItem::Heading(_) => panic!("this method shouldn't ever see a Heading"),
+    items.filter_map(move |item| {
+             let score = match &item {
+                 MenuItem::Selectable { label, .. } => fuzzy_match_score(label, &input)?,
+                 MenuItem::Heading(_) => panic!("this method shouldn't ever see a Heading"),
+             };
+             Some((score, item))
          })
+         .sorted_by_key(|(score, _)| *score)
+         .map(|(_, item)| item)
+}
+
+// every character typed in has to show up in the label, in the same order. lower scores are
+// better: it's how far into the label the match starts plus how many characters got skipped
+// over along the way, so typing the start of the label scores 0
+fn fuzzy_match_score(label: &str, input: &str) -> Option<usize> {
+    let label = label.to_lowercase();
+    let mut label_chars = label.chars().enumerate();
+    let mut score = 0;
+    let mut next_pos = 0;
+    for input_char in input.chars() {
+        let (pos, _) = label_chars.find(|(_, label_char)| *label_char == input_char)?;
+        score += pos - next_pos;
+        next_pos = pos + 1;
+    }
+    Some(score)
 }
 
 pub enum MenuItem {
     Heading(&'static str),
     Selectable {
+        // what gets opened, for keeping track of recently opened things. commands don't have one
+        id: Option<lang::ID>,
         label: String,
+        // the hotkey that does the same thing, to show next to the label
+        keybinding: Option<Keypress>,
         when_selected: Box<dyn Fn(&mut CommandBuffer)>,
         is_selected: bool,
     },
 }
 
 impl MenuItem {
-    fn selectable(label: String, when_selected: impl Fn(&mut CommandBuffer) + 'static) -> Self {
-        MenuItem::Selectable { label,
+    fn selectable(id: lang::ID,
+                  label: String,
+                  when_selected: impl Fn(&mut CommandBuffer) + 'static)
+                  -> Self {
+        MenuItem::Selectable { id: Some(id),
+                               label,
+                               keybinding: None,
+                               when_selected: Box::new(move |command_buffer| {
+                                   command_buffer.add_controller_command(move |controller| {
+                                                     controller.remember_opened(id)
+                                                 });
+                                   when_selected(command_buffer)
+                               }),
+                               is_selected: false }
+    }
+
+    pub fn command(label: &str,
+                   keybinding: Option<Keypress>,
+                   when_selected: impl Fn(&mut CommandBuffer) + 'static)
+                   -> Self {
+        MenuItem::Selectable { id: None,
+                               label: label.to_owned(),
+                               keybinding,
                                when_selected: Box::new(when_selected),
                                is_selected: false }
     }
+
+    fn id(&self) -> Option<lang::ID> {
+        match self {
+            MenuItem::Selectable { id, .. } => *id,
+            MenuItem::Heading(_) => None,
+        }
+    }
 }
 
 trait MenuCategory {
@@ -178,7 +294,8 @@ impl MenuCategory for ChatPrograms {
                 // ID instead of the whole trigger
                 let ct2 = ct.clone();
                 Some(MenuItem::selectable(
-                    ct.prefix.clone(),
+                    ct.id,
+                    ct.name().to_owned(),
                     move |command_buffer| {
                         let ct2 = ct2.clone();
                         command_buffer.load_chat_program(ct2)
@@ -188,6 +305,62 @@ impl MenuCategory for ChatPrograms {
     }
 }
 
+struct WebhookPrograms;
+
+impl MenuCategory for WebhookPrograms {
+    fn label(&self) -> &'static str {
+        "Webhook programs"
+    }
+
+    fn items<'a>(&'a self,
+                 options_lister: &'a OptionsLister<'a>)
+                 -> Box<dyn Iterator<Item = MenuItem> + 'a> {
+        Box::new(options_lister.env_genie.list_webhook_programs()
+            .filter_map(move |wp| {
+                if options_lister.controller.is_builtin(wp.id) {
+                    return None
+                }
+                let wp2 = wp.clone();
+                Some(MenuItem::selectable(
+                    wp.id,
+                    wp.route.clone(),
+                    move |command_buffer| {
+                        let wp2 = wp2.clone();
+                        command_buffer.load_webhook_program(wp2)
+                    }
+                ))
+            }))
+    }
+}
+
+struct ScheduledPrograms;
+
+impl MenuCategory for ScheduledPrograms {
+    fn label(&self) -> &'static str {
+        "Scheduled programs"
+    }
+
+    fn items<'a>(&'a self,
+                 options_lister: &'a OptionsLister<'a>)
+                 -> Box<dyn Iterator<Item = MenuItem> + 'a> {
+        Box::new(options_lister.env_genie.list_scheduled_programs()
+            .filter_map(move |sp| {
+                if options_lister.controller.is_builtin(sp.id) {
+                    return None
+                }
+                let sp2 = sp.clone();
+                Some(MenuItem::selectable(
+                    sp.id,
+                    sp.name.clone(),
+                    move |command_buffer| {
+                        let sp2 = sp2.clone();
+                        command_buffer.load_scheduled_program(sp2)
+                    }
+                ))
+            }))
+    }
+}
+
 struct Functions;
 
 impl MenuCategory for Functions {
@@ -207,7 +380,8 @@ impl MenuCategory for Functions {
                                    // TODO: we could avoid this clone by having load_chat_program take the
                                    // ID instead of the whole trigger
                                    let cf2 = cf.clone();
-                                   Some(MenuItem::selectable(cf.name.clone(),
+                                   Some(MenuItem::selectable(cf.id(),
+                                                             cf.name.clone(),
                                                              move |command_buffer| {
                                                                  let cf2 = cf2.clone();
                                                                  command_buffer.load_code_func(cf2)
@@ -235,6 +409,7 @@ impl MenuCategory for JSONHTTPClients {
                 // ID instead of the whole trigger
                 let cf2 = cf.clone();
                 Some(MenuItem::selectable(
+                    cf.id(),
                     cf.name.clone(),
                     move |command_buffer| {
                         let cf2 = cf2.clone();
@@ -245,6 +420,34 @@ impl MenuCategory for JSONHTTPClients {
     }
 }
 
+struct JSONParsers;
+
+impl MenuCategory for JSONParsers {
+    fn label(&self) -> &'static str {
+        "JSON Parsers"
+    }
+
+    fn items<'a>(&'a self,
+                 options_lister: &'a OptionsLister<'a>)
+                 -> Box<dyn Iterator<Item = MenuItem> + 'a> {
+        Box::new(options_lister.env_genie
+                               .list_parse_jsons()
+                               .filter(move |parse_json| {
+                                   !options_lister.controller.is_builtin(parse_json.id())
+                               })
+                               .map(|parse_json| {
+                                   let parse_json_id = parse_json.id();
+                                   MenuItem::selectable(parse_json_id,
+                                                        parse_json.name.clone(),
+                                                        move |command_buffer| {
+                                                            command_buffer.add_controller_command(move |controller| {
+                                                                controller.open_window(parse_json_id)
+                                                            })
+                                                        })
+                               }))
+    }
+}
+
 struct Scripts;
 
 impl MenuCategory for Scripts {
@@ -264,6 +467,7 @@ impl MenuCategory for Scripts {
                                .map(|script| {
                                    let script_id = script.id();
                                    MenuItem::selectable(
+                script_id,
                 script.name.clone(),
                 move |command_buffer| {
                     command_buffer.add_controller_command(move |cont| {
@@ -295,6 +499,7 @@ impl MenuCategory for Enums {
                 // ID instead of the whole trigger
                 let eneom2 = eneom.clone();
                 Some(MenuItem::selectable(
+                    eneom.id,
                     eneom.name.clone(),
                     move |command_buffer| {
                         let eneom2 = eneom2.clone();
@@ -326,6 +531,7 @@ impl MenuCategory for Structs {
                 // ID instead of the whole trigger
                 let strukt2 = strukt.clone();
                 Some(MenuItem::selectable(
+                    strukt.id,
                     strukt.name.clone(),
                     move |command_buffer| {
                         let strukt = strukt2.clone();
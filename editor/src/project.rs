@@ -0,0 +1,204 @@
+// the file the world gets saved to. a world that hasn't been saved anywhere yet is untitled.
+// unsaved changes are noticed by fingerprinting the world (see undo::fingerprint) and comparing
+// that to the fingerprint from when it was last saved or opened
+use std::path::PathBuf;
+
+use cs::code_loading;
+use cs::code_loading::TheWorld;
+use cs::env::ExecutionEnvironment;
+use cs::world_merge;
+
+use crate::editor::{load_world, save_world, Controller};
+use crate::merge::MergeView;
+use crate::save_state;
+use crate::save_state::RecoveryDeserialize;
+use crate::undo;
+
+type Error = Box<dyn std::error::Error>;
+
+// things that throw away the world that's open, so the user gets asked about unsaved changes
+// before they happen
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProjectAction {
+    New,
+    Open(PathBuf),
+    Exit,
+}
+
+pub struct Project {
+    pub path: Option<PathBuf>,
+    // None if what's open has never been saved anywhere, like after recovering unsaved changes
+    saved_fingerprint: Option<u64>,
+    fingerprint: u64,
+    // the last thing that went wrong opening or saving, for showing the user
+    pub error: Option<String>,
+    // the world changed since the recovery file was last written
+    recovery_out_of_date: bool,
+    last_recovery_save_secs: f64,
+}
+
+impl Project {
+    pub fn new(path: Option<PathBuf>, fingerprint: u64) -> Self {
+        Self { path,
+               saved_fingerprint: Some(fingerprint),
+               fingerprint,
+               error: None,
+               recovery_out_of_date: false,
+               last_recovery_save_secs: 0. }
+    }
+
+    // for a world that isn't what's in the file, like after recovering unsaved changes or
+    // finishing a merge
+    fn unsaved(path: Option<PathBuf>, fingerprint: u64) -> Self {
+        Self { saved_fingerprint: None,
+               ..Self::new(path, fingerprint) }
+    }
+
+    pub fn name(&self) -> String {
+        self.path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Untitled".to_owned())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.saved_fingerprint != Some(self.fingerprint)
+    }
+
+    pub fn world_changed(&mut self, fingerprint: u64) {
+        self.fingerprint = fingerprint;
+    }
+}
+
+fn current_fingerprint(controller: &Controller, env: &ExecutionEnvironment) -> u64 {
+    undo::fingerprint(&undo::take_snapshot(controller, env))
+}
+
+pub fn save(path: PathBuf,
+            controller: &mut Controller,
+            env: &ExecutionEnvironment)
+            -> Result<(), Error> {
+    code_loading::save(&path.to_string_lossy(), &save_world(controller, env))?;
+    controller.project = Project::new(Some(path.clone()), current_fingerprint(controller, env));
+    controller.remember_project(path);
+    save_state::remove_recovery();
+    Ok(())
+}
+
+// the file gets read before anything is thrown away, so a file that won't open doesn't cost the
+// user the world they've got open. a file the git merge driver left conflicts in opens up the merge
+// window instead, and the world gets swapped out once they're resolved
+pub fn open(path: PathBuf,
+            controller: &mut Controller,
+            env: &mut ExecutionEnvironment)
+            -> Result<(), Error> {
+    let contents = std::fs::read_to_string(&path)?;
+    if let Some(pending_merge) = world_merge::read_pending_merge(&contents) {
+        controller.start_merge(MergeView::new(path, pending_merge));
+        return Ok(());
+    }
+    let world = code_loading::deserialize(&contents)?;
+    replace_world(world, controller, env);
+    controller.project = Project::new(Some(path.clone()), current_fingerprint(controller, env));
+    controller.remember_project(path);
+    save_state::remove_recovery();
+    Ok(())
+}
+
+pub fn open_untitled(world: TheWorld, controller: &mut Controller, env: &mut ExecutionEnvironment) {
+    replace_world(world, controller, env);
+    controller.project = Project::new(None, current_fingerprint(controller, env));
+    save_state::remove_recovery();
+}
+
+// brings back the changes that didn't get saved last time. they still haven't been saved, so the
+// project starts out dirty
+pub fn recover(recovery: RecoveryDeserialize,
+               controller: &mut Controller,
+               env: &mut ExecutionEnvironment)
+               -> Result<(), Error> {
+    let world = code_loading::deserialize(&recovery.world.to_string())?;
+    replace_world(world, controller, env);
+    controller.project =
+        Project::unsaved(recovery.project_path, current_fingerprint(controller, env));
+    Ok(())
+}
+
+// the merged world hasn't been written over the pending merge yet, so it starts out unsaved
+pub fn finish_merge(world: TheWorld,
+                    path: PathBuf,
+                    controller: &mut Controller,
+                    env: &mut ExecutionEnvironment) {
+    replace_world(world, controller, env);
+    controller.project = Project::unsaved(Some(path.clone()), current_fingerprint(controller, env));
+    controller.remember_project(path);
+}
+
+// what's in the project's file, for seeing what's changed since
+pub fn read_saved_world(project: &Project) -> Result<TheWorld, Error> {
+    let path = project.path.as_ref().ok_or("This hasn't been saved yet")?;
+    code_loading::deserialize(&std::fs::read_to_string(path)?)
+}
+
+fn replace_world(world: TheWorld, controller: &mut Controller, env: &mut ExecutionEnvironment) {
+    controller.clear_world(env);
+    load_world(world, controller, env);
+}
+
+// for once unsaved changes have been saved, or the user said they don't want them
+pub fn perform(action: ProjectAction, controller: &mut Controller, env: &mut ExecutionEnvironment) {
+    controller.close_unsaved_changes_dialog();
+    match action {
+        ProjectAction::New => open_untitled(TheWorld::default(), controller, env),
+        ProjectAction::Open(path) => {
+            if let Err(e) = open(path.clone(), controller, env) {
+                controller.project.error = Some(format!("Couldn't open {}: {}", path.display(), e));
+            }
+        }
+        ProjectAction::Exit => std::process::exit(0),
+    }
+}
+
+// writing out the whole world after every keystroke is too much, so the recovery file gets
+// written at most this often
+const RECOVERY_SAVE_INTERVAL_SECS: f64 = 5.;
+
+// keeps the recovery file in step with the world: a copy of it while there's anything unsaved,
+// and nothing once there isn't
+pub fn autosave(controller: &mut Controller, env: &ExecutionEnvironment) {
+    controller.project.recovery_out_of_date = true;
+    autosave_if_due(controller, env)
+}
+
+// called every time around, so a copy that had to wait still gets written once the user stops
+// typing
+pub fn autosave_if_due(controller: &mut Controller, env: &ExecutionEnvironment) {
+    if !controller.project.recovery_out_of_date {
+        return;
+    }
+    if !controller.project.is_dirty() {
+        controller.project.recovery_out_of_date = false;
+        return save_state::remove_recovery();
+    }
+    let now = now_secs();
+    if now - controller.project.last_recovery_save_secs < RECOVERY_SAVE_INTERVAL_SECS {
+        return;
+    }
+    controller.project.recovery_out_of_date = false;
+    controller.project.last_recovery_save_secs = now;
+    save_state::save_recovery(controller.project.path.as_deref(),
+                              &save_world(controller, env))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now_secs() -> f64 {
+    stdweb::web::Date::now() / 1000.
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_secs() -> f64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                                .unwrap()
+                                .as_secs_f64()
+}
@@ -0,0 +1,304 @@
+// finding every place in the world that refers to a function, a type, a struct field or an enum
+// variant, across every code location: code functions, programs, scripts, tests and JSON HTTP
+// client blocks. also the call hierarchy for functions, built on top of the same search
+use cs::env_genie::EnvGenie;
+use cs::lang;
+use cs::lang::{CodeNode, Function};
+use itertools::Itertools;
+use std::collections::HashSet;
+
+use crate::code_editor::CodeLocation;
+use crate::code_validation::all_code;
+use crate::editor::Controller;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ReferenceTarget {
+    Function(lang::ID),
+    TypeSpec(lang::ID),
+    StructField(lang::ID),
+    EnumVariant(lang::ID),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Reference {
+    pub location: CodeLocation,
+    pub node_id: lang::ID,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CallDirection {
+    Callers,
+    Callees,
+}
+
+// what the references window is showing. the call trees are keyed by the path of function IDs
+// from the top of the tree, because the same function can show up in more than one branch
+pub struct ReferencesView {
+    pub target: ReferenceTarget,
+    pub expanded: HashSet<(CallDirection, Vec<lang::ID>)>,
+}
+
+impl ReferencesView {
+    pub fn new(target: ReferenceTarget) -> Self {
+        Self { target,
+               expanded: HashSet::new() }
+    }
+
+    pub fn is_expanded(&self, direction: CallDirection, path: &[lang::ID]) -> bool {
+        self.expanded.contains(&(direction, path.to_vec()))
+    }
+
+    pub fn toggle_expanded(&mut self, direction: CallDirection, path: Vec<lang::ID>) {
+        let key = (direction, path);
+        if !self.expanded.remove(&key) {
+            self.expanded.insert(key);
+        }
+    }
+}
+
+// the thing the user right clicked on, if it refers to something we can look up
+pub fn target_for_code_node(code_node: &CodeNode, env_genie: &EnvGenie) -> Option<ReferenceTarget> {
+    match code_node {
+        CodeNode::FunctionCall(function_call) => {
+            let function_id = function_call.function_reference().function_id;
+            env_genie.find_function(function_id)?;
+            Some(ReferenceTarget::Function(function_id))
+        }
+        CodeNode::FunctionReference(function_reference) => {
+            env_genie.find_function(function_reference.function_id)?;
+            Some(ReferenceTarget::Function(function_reference.function_id))
+        }
+        CodeNode::StructLiteral(struct_literal) => {
+            Some(ReferenceTarget::TypeSpec(struct_literal.struct_id))
+        }
+        CodeNode::StructFieldGet(lang::StructFieldGet { struct_field_id, .. })
+        | CodeNode::StructLiteralField(lang::StructLiteralField { struct_field_id, .. }) => {
+            Some(ReferenceTarget::StructField(*struct_field_id))
+        }
+        CodeNode::EnumVariantLiteral(evl) => Some(ReferenceTarget::EnumVariant(evl.variant_id)),
+        _ => None,
+    }
+}
+
+pub fn describe_target(target: ReferenceTarget, env_genie: &EnvGenie) -> String {
+    match target {
+        ReferenceTarget::Function(id) => {
+            format!("function {}",
+                    function_name(id, env_genie).unwrap_or_default())
+        }
+        ReferenceTarget::TypeSpec(id) => {
+            let name = env_genie.find_typespec(id)
+                                .map(|typespec| typespec.readable_name().to_owned())
+                                .unwrap_or_default();
+            format!("type {}", name)
+        }
+        ReferenceTarget::StructField(id) => match env_genie.find_struct_and_field(id) {
+            Some((strukt, field)) => format!("field {}.{}", strukt.name, field.name),
+            None => "a deleted field".to_owned(),
+        },
+        ReferenceTarget::EnumVariant(id) => match env_genie.find_enum_variant(id) {
+            Some((eneom, variant)) => format!("variant {}.{}", eneom.name, variant.name),
+            None => "a deleted variant".to_owned(),
+        },
+    }
+}
+
+// every code location there is, including tests, which only live inside of their code editors
+pub fn all_code_including_tests(controller: &Controller,
+                                env_genie: &EnvGenie)
+                                -> Vec<(CodeLocation, CodeNode)> {
+    let tests = controller.list_all_tests().filter_map(|test| {
+                                               let editor = controller.get_editor(test.code_id())?;
+                                               Some((CodeLocation::Test(test.id),
+                                                     editor.get_code().clone()))
+                                           });
+    all_code(env_genie, controller).map(|(location, block)| {
+                                       (location, CodeNode::Block(block.clone()))
+                                   })
+                                   .chain(tests)
+                                   .collect()
+}
+
+pub fn find_references(target: ReferenceTarget,
+                       controller: &Controller,
+                       env_genie: &EnvGenie)
+                       -> Vec<Reference> {
+    let mut references = vec![];
+    for (location, code) in all_code_including_tests(controller, env_genie) {
+        for code_node in code.all_children_dfs_iter() {
+            if refers_to(code_node, target) {
+                references.push(Reference { location,
+                                            node_id: code_node.id() });
+            }
+        }
+    }
+    references
+}
+
+pub fn refers_to(code_node: &CodeNode, target: ReferenceTarget) -> bool {
+    match (code_node, target) {
+        // the function reference inside of a call would count the call twice
+        (CodeNode::FunctionCall(function_call), ReferenceTarget::Function(function_id)) => {
+            function_call.function_reference().function_id == function_id
+        }
+        (CodeNode::StructLiteral(struct_literal), ReferenceTarget::TypeSpec(typespec_id)) => {
+            struct_literal.struct_id == typespec_id
+        }
+        (CodeNode::Placeholder(placeholder), ReferenceTarget::TypeSpec(typespec_id)) => {
+            type_mentions(&placeholder.typ, typespec_id)
+        }
+        (CodeNode::ListLiteral(list_literal), ReferenceTarget::TypeSpec(typespec_id)) => {
+            type_mentions(&list_literal.element_type, typespec_id)
+        }
+        (CodeNode::MapLiteral(map_literal), ReferenceTarget::TypeSpec(typespec_id)) => {
+            type_mentions(&map_literal.from_type, typespec_id)
+            || type_mentions(&map_literal.to_type, typespec_id)
+        }
+        (CodeNode::AnonymousFunction(anon_func), ReferenceTarget::TypeSpec(typespec_id)) => {
+            type_mentions(&anon_func.takes_arg.arg_type, typespec_id)
+            || type_mentions(&anon_func.returns, typespec_id)
+        }
+        (CodeNode::EnumVariantLiteral(evl), ReferenceTarget::TypeSpec(typespec_id)) => {
+            type_mentions(&evl.typ, typespec_id)
+        }
+        (CodeNode::StructFieldGet(lang::StructFieldGet { struct_field_id, .. }),
+         ReferenceTarget::StructField(field_id))
+        | (CodeNode::StructLiteralField(lang::StructLiteralField { struct_field_id, .. }),
+         ReferenceTarget::StructField(field_id)) => *struct_field_id == field_id,
+        (CodeNode::EnumVariantLiteral(evl), ReferenceTarget::EnumVariant(variant_id)) => {
+            evl.variant_id == variant_id
+        }
+        (CodeNode::Match(mach), ReferenceTarget::EnumVariant(variant_id)) => {
+            mach.branch_by_variant_id.contains_key(&variant_id)
+        }
+        _ => false,
+    }
+}
+
+pub fn type_mentions(typ: &lang::Type, typespec_id: lang::ID) -> bool {
+    typ.typespec_id == typespec_id
+    || typ.params
+          .iter()
+          .any(|param| type_mentions(param, typespec_id))
+}
+
+// the function, program or script that a piece of code belongs to
+pub fn owner_id(location: CodeLocation) -> lang::ID {
+    match location {
+        CodeLocation::Function(id)
+        | CodeLocation::Script(id)
+        | CodeLocation::Test(id)
+        | CodeLocation::JSONHTTPClientURL(id)
+        | CodeLocation::JSONHTTPClientURLParams(id)
+        | CodeLocation::JSONHTTPClientTestSection(id)
+        | CodeLocation::JSONHTTPClientTransform(id)
+        | CodeLocation::ChatProgram(id)
+        | CodeLocation::WebhookProgram(id)
+        | CodeLocation::ScheduledProgram(id) => id,
+    }
+}
+
+// the window the code shows up in. tests don't have windows of their own
+pub fn window_id_for_location(location: CodeLocation) -> Option<lang::ID> {
+    match location {
+        CodeLocation::Test(_) => None,
+        _ => Some(owner_id(location)),
+    }
+}
+
+fn function_name(id: lang::ID, env_genie: &EnvGenie) -> Option<String> {
+    env_genie.find_function(id)
+             .map(|func| func.name().to_owned())
+}
+
+// for showing the user where code is
+pub fn describe_location(location: CodeLocation,
+                         controller: &Controller,
+                         env_genie: &EnvGenie)
+                         -> String {
+    let name_of = |id| function_name(id, env_genie).unwrap_or_default();
+    match location {
+        CodeLocation::Function(id) => format!("Function {}", name_of(id)),
+        CodeLocation::Script(id) => {
+            let script_name = controller.find_script(id)
+                                        .map(|script| script.name.clone())
+                                        .unwrap_or_default();
+            format!("Script {}", script_name)
+        }
+        CodeLocation::Test(id) => {
+            let test_name = controller.get_test(id)
+                                      .map(|test| test.name.clone())
+                                      .unwrap_or_default();
+            format!("Test {}", test_name)
+        }
+        CodeLocation::JSONHTTPClientURL(id) => format!("{} URL", name_of(id)),
+        CodeLocation::JSONHTTPClientURLParams(id) => format!("{} URL params", name_of(id)),
+        CodeLocation::JSONHTTPClientTestSection(id) => format!("{} test", name_of(id)),
+        CodeLocation::JSONHTTPClientTransform(id) => format!("{} transform", name_of(id)),
+        CodeLocation::ChatProgram(id) => format!("Chat program {}", name_of(id)),
+        CodeLocation::WebhookProgram(id) => format!("Webhook {}", name_of(id)),
+        CodeLocation::ScheduledProgram(id) => format!("Scheduled program {}", name_of(id)),
+    }
+}
+
+// one level of the call hierarchy. callers are whatever has code calling the function, which
+// includes scripts and tests as well as functions. callees are the functions called from the
+// function's code
+pub fn call_tree_children(direction: CallDirection,
+                          function_id: lang::ID,
+                          controller: &Controller,
+                          env_genie: &EnvGenie)
+                          -> Vec<lang::ID> {
+    match direction {
+        CallDirection::Callers => {
+            find_references(ReferenceTarget::Function(function_id),
+                            controller,
+                            env_genie).into_iter()
+                                      .map(|reference| owner_id(reference.location))
+                                      .unique()
+                                      .collect()
+        }
+        CallDirection::Callees => {
+            let mut callees = vec![];
+            for (location, code) in all_code_including_tests(controller, env_genie) {
+                if owner_id(location) != function_id {
+                    continue;
+                }
+                for code_node in code.all_children_dfs_iter() {
+                    if let Ok(function_call) = code_node.as_function_call() {
+                        callees.push(function_call.function_reference().function_id);
+                    }
+                }
+            }
+            callees.into_iter().unique().collect()
+        }
+    }
+}
+
+// what goes on a line of the call tree: a function's name, or whatever other code is calling it
+pub fn describe_call_tree_entry(id: lang::ID,
+                                controller: &Controller,
+                                env_genie: &EnvGenie)
+                                -> String {
+    if let Some(name) = function_name(id, env_genie) {
+        return name;
+    }
+    if let Some(script) = controller.find_script(id) {
+        return format!("Script {}", script.name);
+    }
+    if let Some(test) = controller.get_test(id) {
+        return format!("Test {}", test.name);
+    }
+    "(deleted)".to_owned()
+}
+
+// the first code location belonging to a function, program or script, for jumping to it from the
+// call tree. builtins don't have any code, so there's nowhere to go for them
+pub fn location_for_owner(id: lang::ID,
+                          controller: &Controller,
+                          env_genie: &EnvGenie)
+                          -> Option<CodeLocation> {
+    all_code_including_tests(controller, env_genie).into_iter()
+                                                   .map(|(location, _)| location)
+                                                   .find(|location| owner_id(*location) == id)
+}
@@ -0,0 +1,274 @@
+// renaming variables, functions, struct fields and enum variants. code refers to all of these by
+// ID, so a rename only ever touches the definition, but we still dig up every usage so the user
+// can see what's going to look different afterwards
+use cs::code_function::CodeFunction;
+use cs::env::ExecutionEnvironment;
+use cs::env_genie::EnvGenie;
+use cs::lang;
+use cs::lang::CodeNode;
+use std::collections::HashSet;
+
+use crate::code_editor::{update_code_in_env, CodeGenie};
+use crate::editor::Controller;
+use crate::references;
+use crate::references::{Reference, ReferenceTarget};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RenameTarget {
+    // variables only live inside of one code editor, so that's how we find them
+    Variable {
+        editor_id: lang::ID,
+        assignment_id: lang::ID,
+    },
+    Function(lang::ID),
+    StructField {
+        struct_id: lang::ID,
+        field_id: lang::ID,
+    },
+    EnumVariant {
+        enum_id: lang::ID,
+        variant_id: lang::ID,
+    },
+}
+
+// struct field names double as JSON keys, so renaming a field can change what goes out over the
+// wire, or what we expect to come back
+#[derive(Clone, Debug)]
+pub struct WireNameChange {
+    pub mapped_by: String,
+    pub old_wire_name: String,
+    pub new_wire_name: String,
+}
+
+// the thing the user right clicked on, if it's something that can be renamed
+pub fn target_for_code_node(code_node: &CodeNode,
+                            editor_id: lang::ID,
+                            code_genie: &CodeGenie,
+                            env_genie: &EnvGenie)
+                            -> Option<RenameTarget> {
+    match code_node {
+        CodeNode::Assignment(assignment) => Some(RenameTarget::Variable { editor_id,
+                                                                          assignment_id:
+                                                                              assignment.id }),
+        CodeNode::VariableReference(lang::VariableReference { assignment_id, .. })
+        | CodeNode::Reassignment(lang::Reassignment { assignment_id, .. })
+        | CodeNode::ReassignListIndex(lang::ReassignListIndex { assignment_id, .. }) => {
+            // could also be the variable from a for loop or an anonymous function's argument,
+            // and those don't have their names in an assignment
+            code_genie.find_node(*assignment_id)?.as_assignment().ok()?;
+            Some(RenameTarget::Variable { editor_id,
+                                          assignment_id: *assignment_id })
+        }
+        CodeNode::FunctionCall(function_call) => {
+            let function_id = function_call.function_reference().function_id;
+            env_genie.get_code_func(function_id)?;
+            Some(RenameTarget::Function(function_id))
+        }
+        CodeNode::StructFieldGet(lang::StructFieldGet { struct_field_id, .. })
+        | CodeNode::StructLiteralField(lang::StructLiteralField { struct_field_id, .. }) => {
+            let (strukt, _) = env_genie.find_struct_and_field(*struct_field_id)?;
+            Some(RenameTarget::StructField { struct_id: strukt.id,
+                                             field_id: *struct_field_id })
+        }
+        CodeNode::EnumVariantLiteral(evl) => {
+            let (eneom, _) = env_genie.find_enum_variant(evl.variant_id)?;
+            Some(RenameTarget::EnumVariant { enum_id: eneom.id,
+                                             variant_id: evl.variant_id })
+        }
+        _ => None,
+    }
+}
+
+pub fn current_name(target: RenameTarget,
+                    controller: &Controller,
+                    env_genie: &EnvGenie)
+                    -> Option<String> {
+    match target {
+        RenameTarget::Variable { editor_id,
+                                 assignment_id, } => {
+            let editor = controller.get_editor(editor_id)?;
+            let assignment = editor.code_genie.find_node(assignment_id)?;
+            Some(assignment.as_assignment().ok()?.name.clone())
+        }
+        RenameTarget::Function(function_id) => {
+            Some(env_genie.get_code_func(function_id)?.name.clone())
+        }
+        RenameTarget::StructField { struct_id,
+                                    field_id, } => {
+            let strukt = env_genie.find_struct(struct_id)?;
+            Some(strukt.field_by_id().get(&field_id)?.name.clone())
+        }
+        RenameTarget::EnumVariant { enum_id,
+                                    variant_id, } => {
+            let eneom = env_genie.find_enum(enum_id)?;
+            Some(eneom.get_variant_by_id(variant_id)?.name.clone())
+        }
+    }
+}
+
+// variables can only be used inside of their own code editor, but everything else can be used
+// from anywhere
+pub fn find_usages(target: RenameTarget,
+                   controller: &Controller,
+                   env_genie: &EnvGenie)
+                   -> Vec<Reference> {
+    let reference_target = match target {
+        RenameTarget::Variable { editor_id,
+                                 assignment_id, } => {
+            return find_variable_usages(editor_id, assignment_id, controller)
+        }
+        RenameTarget::Function(function_id) => ReferenceTarget::Function(function_id),
+        RenameTarget::StructField { field_id, .. } => ReferenceTarget::StructField(field_id),
+        RenameTarget::EnumVariant { variant_id, .. } => ReferenceTarget::EnumVariant(variant_id),
+    };
+    references::find_references(reference_target, controller, env_genie)
+}
+
+fn find_variable_usages(editor_id: lang::ID,
+                        assignment_id: lang::ID,
+                        controller: &Controller)
+                        -> Vec<Reference> {
+    let editor = match controller.get_editor(editor_id) {
+        Some(editor) => editor,
+        None => return vec![],
+    };
+    let location = match editor.location {
+        Some(location) => location,
+        None => return vec![],
+    };
+    editor.get_code()
+          .all_children_dfs_iter()
+          .filter(|code_node| uses_variable(code_node, assignment_id))
+          .map(|code_node| Reference { location,
+                                       node_id: code_node.id() })
+          .collect()
+}
+
+fn uses_variable(code_node: &CodeNode, assignment_id: lang::ID) -> bool {
+    match code_node {
+        CodeNode::VariableReference(lang::VariableReference { assignment_id: id, .. })
+        | CodeNode::Reassignment(lang::Reassignment { assignment_id: id, .. })
+        | CodeNode::ReassignListIndex(lang::ReassignListIndex { assignment_id: id, .. }) => {
+            *id == assignment_id
+        }
+        _ => false,
+    }
+}
+
+// every JSON HTTP client and Parse JSON function whose type has this struct somewhere inside of
+// it, and what the field's key would turn into under its naming style
+pub fn wire_name_changes(target: RenameTarget,
+                         new_name: &str,
+                         env_genie: &EnvGenie)
+                         -> Vec<WireNameChange> {
+    let (struct_id, field_id) = match target {
+        RenameTarget::StructField { struct_id,
+                                    field_id, } => (struct_id, field_id),
+        _ => return vec![],
+    };
+    let old_name = match env_genie.get_struct_and_field(struct_id, field_id) {
+        Some((_, field)) => &field.name,
+        None => return vec![],
+    };
+
+    let json_mappers = env_genie.list_json_http_clients()
+                                .map(|client| {
+                                    let types = vec![&client.intermediate_parse_schema,
+                                                     &client.return_type_after_transform];
+                                    (format!("JSON HTTP client {}", client.name),
+                                     types,
+                                     &client.json_codec)
+                                })
+                                .chain(env_genie.list_parse_jsons().map(|parse_json| {
+                                           (format!("{} ({})",
+                                                    parse_json.name,
+                                                    env_genie.get_name_for_type(&parse_json.into_type)
+                                                             .unwrap_or_default()),
+                                            vec![&parse_json.into_type],
+                                            &parse_json.json_codec)
+                                       }));
+    json_mappers.filter(|(_, types, _)| {
+                    types.iter()
+                         .any(|typ| type_contains_struct(typ, struct_id, env_genie))
+                })
+                .filter_map(|(mapped_by, _, json_codec)| {
+                    let old_wire_name = json_codec.field_names.apply(old_name);
+                    let new_wire_name = json_codec.field_names.apply(new_name);
+                    if old_wire_name == new_wire_name {
+                        None
+                    } else {
+                        Some(WireNameChange { mapped_by,
+                                              old_wire_name,
+                                              new_wire_name })
+                    }
+                })
+                .collect()
+}
+
+fn type_contains_struct(typ: &lang::Type, struct_id: lang::ID, env_genie: &EnvGenie) -> bool {
+    let mut seen = HashSet::new();
+    let mut to_check = vec![typ.clone()];
+    while let Some(typ) = to_check.pop() {
+        if typ.typespec_id == struct_id {
+            return true;
+        }
+        if !seen.insert(typ.typespec_id) {
+            continue;
+        }
+        if let Some(strukt) = env_genie.find_struct(typ.typespec_id) {
+            to_check.extend(strukt.fields.iter().map(|field| field.field_type.clone()));
+        }
+        to_check.extend(typ.params);
+    }
+    false
+}
+
+// changes the name at the definition, and gives back what it used to be. None if the thing being
+// renamed is gone
+pub fn rename(target: RenameTarget,
+              new_name: String,
+              controller: &mut Controller,
+              env: &mut ExecutionEnvironment)
+              -> Option<String> {
+    match target {
+        RenameTarget::Variable { editor_id,
+                                 assignment_id, } => {
+            let editor = controller.get_editor_mut(editor_id)?;
+            let mut code = editor.get_code().clone();
+            let mut assignment = code.find_node(assignment_id)?.as_assignment().ok()?.clone();
+            let old_name = std::mem::replace(&mut assignment.name, new_name);
+            code.replace(CodeNode::Assignment(assignment));
+            editor.replace_code(code.clone());
+            let location = editor.location?;
+            update_code_in_env(location, code, controller, env);
+            Some(old_name)
+        }
+        RenameTarget::Function(function_id) => {
+            let mut code_func: CodeFunction =
+                EnvGenie::new(env).get_code_func(function_id)?.clone();
+            let old_name = std::mem::replace(&mut code_func.name, new_name);
+            env.add_function(code_func);
+            Some(old_name)
+        }
+        RenameTarget::StructField { struct_id,
+                                    field_id, } => {
+            let mut strukt = env.find_struct(struct_id)?.clone();
+            let field = strukt.fields
+                              .iter_mut()
+                              .find(|field| field.id == field_id)?;
+            let old_name = std::mem::replace(&mut field.name, new_name);
+            env.add_typespec(strukt);
+            Some(old_name)
+        }
+        RenameTarget::EnumVariant { enum_id,
+                                    variant_id, } => {
+            let mut eneom = env.find_enum(enum_id)?.clone();
+            let variant = eneom.variants
+                               .iter_mut()
+                               .find(|variant| variant.id == variant_id)?;
+            let old_name = std::mem::replace(&mut variant.name, new_name);
+            env.add_typespec(eneom);
+            Some(old_name)
+        }
+    }
+}
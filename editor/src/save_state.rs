@@ -1,7 +1,12 @@
 use super::window_positions::WindowPositions;
 use crate::code_editor::CodeLocation;
+use crate::undo::{UndoTreeNode, WorldHistoryChange, WorldUndoHistory};
 use cfg_if::cfg_if;
+use cs::code_loading::TheWorld;
+use cs::lang;
 use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
 
 cfg_if! {
     if #[cfg(target_arch = "wasm32")] {
@@ -11,28 +16,207 @@ cfg_if! {
     }
 }
 
+// bump this whenever the state changes shape. a state file from a different version gets ignored
+// and we start over with the default layout, instead of failing halfway through loading it
+const STATE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OpenCodeEditor {
+    pub location: CodeLocation,
+    pub selected_node_ids: Vec<lang::ID>,
+}
+
 // separated into two classes so we can save without
 // allocating
 #[derive(Serialize)]
 pub struct StateSerialize<'a> {
+    version: u32,
     window_positions: &'a WindowPositions,
-    open_code_editors: &'a [CodeLocation],
+    open_code_editors: &'a [OpenCodeEditor],
+    recent_opener_items: &'a [lang::ID],
+    recent_projects: &'a [PathBuf],
 }
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct StateDeserialize {
+    #[serde(default)]
+    pub version: u32,
     pub window_positions: WindowPositions,
-    pub open_code_editors: Vec<CodeLocation>,
+    pub open_code_editors: Vec<OpenCodeEditor>,
+    pub recent_opener_items: Vec<lang::ID>,
+    #[serde(default)]
+    pub recent_projects: Vec<PathBuf>,
+}
+
+impl StateDeserialize {
+    fn for_this_version(self) -> Self {
+        if self.version == STATE_VERSION {
+            self
+        } else {
+            Self::default()
+        }
+    }
+}
+
+thread_local! {
+    static LAST_SAVED_STATE: RefCell<String> = RefCell::new(String::new());
+}
+
+// this gets called after every batch of commands, and most of those don't touch the state, so it
+// only gets written out when it's different from last time
+pub fn save(window_positions: &WindowPositions,
+            open_code_editors: &[OpenCodeEditor],
+            recent_opener_items: &[lang::ID],
+            recent_projects: &[PathBuf]) {
+    let state = serde_json::to_string_pretty(&StateSerialize { version: STATE_VERSION,
+                                                               window_positions,
+                                                               open_code_editors,
+                                                               recent_opener_items,
+                                                               recent_projects }).unwrap();
+    LAST_SAVED_STATE.with(|last_saved| {
+                        if *last_saved.borrow() != state {
+                            save_state(&state);
+                            *last_saved.borrow_mut() = state;
+                        }
+                    })
+}
+
+// the undo history goes in its own file because it gets saved after every edit. it's kept as a
+// log, one JSON entry per line: the whole history every once in a while, and then each edit or
+// move around the tree gets added onto the end. the fingerprint is of the world at the history's
+// current spot, so a history that doesn't belong to the world that got loaded gets thrown out
+// instead of undoing things that aren't there
+#[derive(Serialize)]
+pub enum WorldHistoryEntrySerialize<'a> {
+    Whole {
+        world_fingerprint: u64,
+        history: &'a WorldUndoHistory,
+    },
+    NewEdit {
+        world_fingerprint: u64,
+        node: &'a UndoTreeNode,
+    },
+    Moved {
+        world_fingerprint: u64,
+        current: Option<usize>,
+    },
+}
+
+#[derive(Deserialize)]
+pub enum WorldHistoryEntryDeserialize {
+    Whole {
+        world_fingerprint: u64,
+        history: WorldUndoHistory,
+    },
+    NewEdit {
+        world_fingerprint: u64,
+        node: UndoTreeNode,
+    },
+    Moved {
+        world_fingerprint: u64,
+        current: Option<usize>,
+    },
+}
+
+pub struct WorldHistoryDeserialize {
+    pub world_fingerprint: u64,
+    pub history: WorldUndoHistory,
+}
+
+// after this many entries get added on, the whole thing gets written out fresh, so moving around
+// the tree doesn't grow the log forever
+const MAX_WORLD_HISTORY_LOG_ENTRIES: usize = 500;
+
+thread_local! {
+    static WORLD_HISTORY_LOG_ENTRIES: Cell<usize> = Cell::new(0);
+}
+
+pub fn save_world_history(world_fingerprint: u64,
+                          history: &WorldUndoHistory,
+                          change: WorldHistoryChange) {
+    let num_entries = WORLD_HISTORY_LOG_ENTRIES.with(|num| {
+                                                   num.set(num.get() + 1);
+                                                   num.get()
+                                               });
+    if num_entries > MAX_WORLD_HISTORY_LOG_ENTRIES {
+        return rewrite_world_history(world_fingerprint, history);
+    }
+    let current = history.current();
+    let entry = match change {
+        WorldHistoryChange::Rewritten => return rewrite_world_history(world_fingerprint, history),
+        WorldHistoryChange::NewEdit => {
+            WorldHistoryEntrySerialize::NewEdit { world_fingerprint,
+                                                  node: history.node(current.unwrap()) }
+        }
+        WorldHistoryChange::Moved => WorldHistoryEntrySerialize::Moved { world_fingerprint,
+                                                                         current },
+    };
+    append_world_history_entry(&serde_json::to_string(&entry).unwrap())
+}
+
+fn rewrite_world_history(world_fingerprint: u64, history: &WorldUndoHistory) {
+    WORLD_HISTORY_LOG_ENTRIES.with(|num| num.set(0));
+    let entry = WorldHistoryEntrySerialize::Whole { world_fingerprint,
+                                                    history };
+    save_world_history_log(&serde_json::to_string(&entry).unwrap())
+}
+
+// plays the log back. anything that doesn't fit, like a line that only got partway written, means
+// the saved history is no good
+fn replay_world_history(log: &str) -> Option<WorldHistoryDeserialize> {
+    let mut lines = log.lines().filter(|line| !line.is_empty());
+    let mut replayed = match serde_json::from_str(lines.next()?).ok()? {
+        WorldHistoryEntryDeserialize::Whole { world_fingerprint,
+                                              history, } => {
+            WorldHistoryDeserialize { world_fingerprint,
+                                      history }
+        }
+        _ => return None,
+    };
+    for line in lines {
+        match serde_json::from_str(line).ok()? {
+            WorldHistoryEntryDeserialize::Whole { .. } => return None,
+            WorldHistoryEntryDeserialize::NewEdit { world_fingerprint,
+                                                    node, } => {
+                replayed.history.replay_new_edit(node)?;
+                replayed.world_fingerprint = world_fingerprint;
+            }
+            WorldHistoryEntryDeserialize::Moved { world_fingerprint,
+                                                  current, } => {
+                replayed.history.replay_move(current)?;
+                replayed.world_fingerprint = world_fingerprint;
+            }
+        }
+    }
+    Some(replayed)
+}
+
+// a copy of the world with changes that haven't been saved to the project yet, in case we don't
+// make it to saving them. the world is kept as plain JSON because TheWorld gets deserialized by
+// hand, see code_loading::deserialize
+#[derive(Serialize)]
+pub struct RecoverySerialize<'a> {
+    project_path: Option<&'a Path>,
+    world: &'a TheWorld,
+}
+
+#[derive(Deserialize)]
+pub struct RecoveryDeserialize {
+    pub project_path: Option<PathBuf>,
+    pub world: serde_json::Value,
 }
 
-pub fn save(window_positions: &WindowPositions, open_code_editors: &[CodeLocation]) {
-    save_state(&StateSerialize { window_positions,
-                                 open_code_editors })
+pub fn save_recovery(project_path: Option<&Path>, world: &TheWorld) {
+    save_recovery_state(&RecoverySerialize { project_path,
+                                             world })
 }
 
 #[cfg(target_arch = "wasm32")]
 mod js {
-    use super::{StateDeserialize, StateSerialize};
+    use super::{
+        replay_world_history, RecoveryDeserialize, RecoverySerialize, StateDeserialize,
+        WorldHistoryDeserialize,
+    };
     use lazy_static::lazy_static;
     use stdweb::web::{window, Storage};
 
@@ -41,39 +225,62 @@ mod js {
     }
 
     pub fn load() -> StateDeserialize {
-        StateDeserialize::default()
-    }
-
-    // TODO: don't deserialize, just load the default. i don't want state rn because i want to test
-    // the default state
-    // TODO: same for native version below
-    //    pub fn load() -> StateDeserialize {
-    //        let deserialized_state: Result<_, Box<dyn std::error::Error>> = try {
-    //            let stored = STORAGE.get("state").ok_or("no such key state")?;
-    //            serde_json::from_str(&stored)?
-    //        };
-    //        if let Ok(ds) = deserialized_state {
-    //            return ds;
-    //        }
-    //        let default = StateDeserialize::default();
-    //        STORAGE.insert("state", &serde_json::to_string(&default).unwrap())
-    //               .unwrap();
-    //        default
-    //    }
-
-    pub fn save_state(state_serialize: &StateSerialize) {
-        STORAGE.insert("state", &serde_json::to_string(state_serialize).unwrap())
+        STORAGE.get("state")
+               .and_then(|stored| serde_json::from_str::<StateDeserialize>(&stored).ok())
+               .map(StateDeserialize::for_this_version)
+               .unwrap_or_default()
+    }
+
+    pub fn save_state(state: &str) {
+        STORAGE.insert("state", state).unwrap()
+    }
+
+    pub fn load_world_history() -> Option<WorldHistoryDeserialize> {
+        replay_world_history(&STORAGE.get("world_history")?)
+    }
+
+    // localStorage fills up, and the undo history is the biggest thing in there. losing it is
+    // better than crashing, and a half-saved one is worse than none at all
+    pub fn save_world_history_log(log: &str) {
+        if STORAGE.insert("world_history", log).is_err() {
+            STORAGE.remove("world_history");
+        }
+    }
+
+    // there's no appending in localStorage, so this is still a whole rewrite
+    pub fn append_world_history_entry(entry: &str) {
+        match STORAGE.get("world_history") {
+            Some(log) => save_world_history_log(&format!("{}\n{}", log, entry)),
+            None => save_world_history_log(entry),
+        }
+    }
+
+    pub fn load_recovery() -> Option<RecoveryDeserialize> {
+        serde_json::from_str(&STORAGE.get("recovery")?).ok()
+    }
+
+    pub fn save_recovery_state(recovery_serialize: &RecoverySerialize) {
+        STORAGE.insert("recovery",
+                       &serde_json::to_string(recovery_serialize).unwrap())
                .unwrap()
     }
 
+    pub fn remove_recovery() {
+        STORAGE.remove("recovery");
+    }
+
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 mod native {
-    use crate::save_state::{StateDeserialize, StateSerialize};
+    use crate::save_state::{
+        replay_world_history, RecoveryDeserialize, RecoverySerialize, StateDeserialize,
+        WorldHistoryDeserialize,
+    };
     use directories::ProjectDirs;
     use lazy_static::lazy_static;
-    use std::fs::{create_dir_all, File};
+    use std::fs::{create_dir_all, read_to_string, remove_file, write, File, OpenOptions};
+    use std::io::{BufReader, BufWriter, Write};
     use std::path::PathBuf;
 
     lazy_static! {
@@ -81,37 +288,61 @@ mod native {
             ProjectDirs::from("org", "sumeet", "cs").unwrap()
                 .config_dir().into()
         };
-        // HAXXXXXXXXXXXXX
-        static ref STATE_FILE_NAME : PathBuf = {
-            CONFIG_DIR.with_file_name("state.json").into()
-        };
+        // these go inside of the config dir, which might not exist yet
+        static ref STATE_FILE_NAME : PathBuf = CONFIG_DIR.join("state.json");
+        static ref WORLD_HISTORY_FILE_NAME : PathBuf = CONFIG_DIR.join("world_history.json");
+        static ref RECOVERY_FILE_NAME : PathBuf = CONFIG_DIR.join("recovery.json");
     }
 
+    // a missing or unreadable state file just means starting with the default layout
     pub fn load() -> StateDeserialize {
-        StateDeserialize::default()
-    }
-
-    // TODO: don't deserialize, just load the default. i don't want state rn because i want to test
-    // the default state
-    // TODO: same for wasm version above
-    pub fn _load() -> StateDeserialize {
-        let deserialized_state: Result<_, Box<dyn std::error::Error>> = try {
+        let deserialized_state: Result<StateDeserialize, Box<dyn std::error::Error>> = try {
             let file = File::open(&*STATE_FILE_NAME)?;
-            serde_json::from_reader(file)?
+            serde_json::from_reader(BufReader::new(file))?
         };
-        if let Ok(ds) = deserialized_state {
-            return ds;
-        }
-        let default = StateDeserialize::default();
+        deserialized_state.map(StateDeserialize::for_this_version)
+                          .unwrap_or_default()
+    }
+
+    pub fn save_state(state: &str) {
+        create_dir_all(CONFIG_DIR.as_path()).unwrap();
+        write(&*STATE_FILE_NAME, state).unwrap()
+    }
+
+    pub fn load_world_history() -> Option<WorldHistoryDeserialize> {
+        replay_world_history(&read_to_string(&*WORLD_HISTORY_FILE_NAME).ok()?)
+    }
+
+    pub fn save_world_history_log(log: &str) {
+        create_dir_all(CONFIG_DIR.as_path()).unwrap();
+        let mut f = BufWriter::new(File::create(&*WORLD_HISTORY_FILE_NAME).unwrap());
+        writeln!(f, "{}", log).unwrap();
+        f.flush().unwrap()
+    }
+
+    pub fn append_world_history_entry(entry: &str) {
+        create_dir_all(CONFIG_DIR.as_path()).unwrap();
+        let mut f = OpenOptions::new().create(true)
+                                      .append(true)
+                                      .open(&*WORLD_HISTORY_FILE_NAME)
+                                      .unwrap();
+        writeln!(f, "{}", entry).unwrap()
+    }
+
+    pub fn load_recovery() -> Option<RecoveryDeserialize> {
+        let file = File::open(&*RECOVERY_FILE_NAME).ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    pub fn save_recovery_state(recovery_serialize: &RecoverySerialize) {
         create_dir_all(CONFIG_DIR.as_path()).unwrap();
-        let f = File::create(&*STATE_FILE_NAME).unwrap();
-        serde_json::to_writer_pretty(f, &default).unwrap();
-        default
+        let mut f = BufWriter::new(File::create(&*RECOVERY_FILE_NAME).unwrap());
+        serde_json::to_writer(&mut f, recovery_serialize).unwrap();
+        f.flush().unwrap()
     }
 
-    // JANK
-    pub fn save_state(state_serialize: &StateSerialize) {
-        let f = File::create(&*STATE_FILE_NAME).unwrap();
-        serde_json::to_writer_pretty(f, state_serialize).unwrap()
+    // it's fine if there wasn't one
+    pub fn remove_recovery() {
+        remove_file(&*RECOVERY_FILE_NAME).ok();
     }
 }
@@ -1,10 +1,19 @@
+use cs::edit_token::EditTokenInfo;
+
 pub struct SendToServerOverlay {
     pub status: SendToServerOverlayStatus,
+    // what the server said our edit link lets us do, the last time we asked
+    pub token_info: Option<EditTokenInfo>,
 }
 
 impl SendToServerOverlay {
     pub fn new() -> Self {
-        Self { status: SendToServerOverlayStatus::Ready }
+        Self { status: SendToServerOverlayStatus::Ready,
+               token_info: None }
+    }
+
+    pub fn set_token_info(&mut self, token_info: EditTokenInfo) {
+        self.token_info = Some(token_info);
     }
 
     pub fn mark_as_submitting(&mut self) {
@@ -0,0 +1,411 @@
+// diffs between two versions of the same code. code nodes keep their IDs when they get edited, so
+// instead of comparing how the code looks, nodes get lined up by ID and marked as inserted, moved
+// or changed. deleted nodes get held onto along with where they used to be, so they can be drawn
+// in alongside the new version (see CodeEditorRenderer::with_diff)
+use std::collections::{HashMap, HashSet};
+
+use cs::chat_program::ChatProgram;
+use cs::code_function::CodeFunction;
+use cs::code_loading::TheWorld;
+use cs::json_http_client::JSONHTTPClient;
+use cs::lang;
+use cs::lang::CodeNode;
+use cs::scheduled_program::ScheduledProgram;
+use cs::webhook_program::WebhookProgram;
+use serde_json::{Map, Value};
+
+use crate::code_editor::{CodeEditor, CodeLocation};
+use crate::references;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeChange {
+    Inserted,
+    Moved,
+    // something about the node itself, not counting anything nested inside of it
+    Changed,
+}
+
+// where a deleted node gets drawn in the new version of the code
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Anchor {
+    // right after this expression in the block, or at the top if it's None
+    InBlock {
+        block_id: lang::ID,
+        after: Option<lang::ID>,
+    },
+    // next to whatever took its place
+    Beside(lang::ID),
+}
+
+pub struct StructuralDiff {
+    changes: HashMap<lang::ID, NodeChange>,
+    deletions: Vec<(Anchor, CodeNode)>,
+}
+
+impl StructuralDiff {
+    pub fn between(old: &CodeNode, new: &CodeNode) -> Self {
+        let old_positions = positions(old);
+        let new_positions = positions(new);
+
+        let mut changes = HashMap::new();
+        for (id, new_position) in &new_positions {
+            let old_position = match old_positions.get(id) {
+                Some(old_position) => old_position,
+                None => {
+                    // only the outermost new node gets marked, everything inside comes with it
+                    let parent_is_new =
+                        new_position.parent.map_or(false, |parent| {
+                                               !old_positions.contains_key(&parent.id())
+                                           });
+                    if !parent_is_new {
+                        changes.insert(*id, NodeChange::Inserted);
+                    }
+                    continue;
+                }
+            };
+            if old_position.parent_id() != new_position.parent_id() {
+                changes.insert(*id, NodeChange::Moved);
+            } else if shallow(old_position.node) != shallow(new_position.node) {
+                changes.insert(*id, NodeChange::Changed);
+            }
+            if let (CodeNode::Block(old_block), CodeNode::Block(new_block)) =
+                (old_position.node, new_position.node)
+            {
+                for id in reordered(old_block, new_block) {
+                    changes.insert(id, NodeChange::Moved);
+                }
+            }
+        }
+
+        // going through the old code in order, so deletions in the same spot stay in order too
+        let mut deletions = vec![];
+        for node in old.all_children_dfs_iter() {
+            if new_positions.contains_key(&node.id()) {
+                continue;
+            }
+            // only the outermost deleted node gets kept, everything inside goes with it
+            let parent = match old_positions[&node.id()].parent {
+                Some(parent) if new_positions.contains_key(&parent.id()) => parent,
+                _ => continue,
+            };
+            let anchor = match parent {
+                CodeNode::Block(old_block) => {
+                    let after = survivor_before(old_block, node.id(), &new_positions);
+                    Anchor::InBlock { block_id: old_block.id,
+                                      after }
+                }
+                _ => {
+                    let new_parent = new_positions[&parent.id()].node;
+                    let replacement =
+                        new_parent.immediate_children_iter()
+                                  .find(|child| !old_positions.contains_key(&child.id()))
+                                  .unwrap_or(new_parent);
+                    Anchor::Beside(replacement.id())
+                }
+            };
+            deletions.push((anchor, node.clone()));
+        }
+
+        Self { changes, deletions }
+    }
+
+    pub fn change(&self, id: lang::ID) -> Option<NodeChange> {
+        self.changes.get(&id).cloned()
+    }
+
+    pub fn deleted_in_block(&self, block_id: lang::ID, after: Option<lang::ID>) -> Vec<&CodeNode> {
+        self.deleted_at(Anchor::InBlock { block_id, after })
+    }
+
+    pub fn deleted_beside(&self, id: lang::ID) -> Vec<&CodeNode> {
+        self.deleted_at(Anchor::Beside(id))
+    }
+
+    fn deleted_at(&self, anchor: Anchor) -> Vec<&CodeNode> {
+        self.deletions
+            .iter()
+            .filter(|(deleted_at, _)| *deleted_at == anchor)
+            .map(|(_, node)| node)
+            .collect()
+    }
+
+    pub fn summary(&self) -> String {
+        let count = |change| self.changes.values().filter(|c| **c == change).count();
+        format!("{} inserted, {} deleted, {} moved, {} changed",
+                count(NodeChange::Inserted),
+                self.deletions.len(),
+                count(NodeChange::Moved),
+                count(NodeChange::Changed))
+    }
+}
+
+// where a node sits in the code
+struct Position<'a> {
+    node: &'a CodeNode,
+    parent: Option<&'a CodeNode>,
+}
+
+impl<'a> Position<'a> {
+    fn parent_id(&self) -> Option<lang::ID> {
+        self.parent.map(|parent| parent.id())
+    }
+}
+
+fn positions(root: &CodeNode) -> HashMap<lang::ID, Position> {
+    let mut positions = HashMap::new();
+    positions.insert(root.id(),
+                     Position { node: root,
+                                parent: None });
+    for node in root.self_with_all_children_dfs() {
+        for child in node.immediate_children_iter() {
+            positions.insert(child.id(),
+                             Position { node: child,
+                                        parent: Some(node) });
+        }
+    }
+    positions
+}
+
+// expressions that are in the block both before and after, but not in the same order. the ones
+// that kept their order relative to each other stay put, and the rest count as moved
+fn reordered(old_block: &lang::Block, new_block: &lang::Block) -> Vec<lang::ID> {
+    let old_ids = old_block.expressions
+                           .iter()
+                           .map(CodeNode::id)
+                           .collect::<HashSet<_>>();
+    let new_ids = new_block.expressions
+                           .iter()
+                           .map(CodeNode::id)
+                           .collect::<HashSet<_>>();
+    let old_order = old_block.expressions
+                             .iter()
+                             .map(CodeNode::id)
+                             .filter(|id| new_ids.contains(id))
+                             .collect::<Vec<_>>();
+    let new_order = new_block.expressions
+                             .iter()
+                             .map(CodeNode::id)
+                             .filter(|id| old_ids.contains(id))
+                             .collect::<Vec<_>>();
+    let in_order = longest_common_subsequence(&old_order, &new_order);
+    new_order.into_iter()
+             .filter(|id| !in_order.contains(id))
+             .collect()
+}
+
+fn longest_common_subsequence(a: &[lang::ID], b: &[lang::ID]) -> HashSet<lang::ID> {
+    // lengths[i][j] is how long the longest common subsequence of a[i..] and b[j..] is
+    let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut common = HashSet::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            common.insert(a[i]);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    common
+}
+
+// the closest expression before the deleted one that's still in the same block
+fn survivor_before(old_block: &lang::Block,
+                   deleted_id: lang::ID,
+                   new_positions: &HashMap<lang::ID, Position>)
+                   -> Option<lang::ID> {
+    old_block.expressions
+             .iter()
+             .map(CodeNode::id)
+             .take_while(|id| *id != deleted_id)
+             .filter(|id| {
+                 new_positions.get(id)
+                              .map_or(false, |position| position.parent_id() == Some(old_block.id))
+             })
+             .last()
+}
+
+// the node serialized with everything nested inside of it taken out, so a node only counts as
+// changed when something about it in particular changed
+fn shallow(node: &CodeNode) -> Value {
+    let child_ids = node.immediate_children_iter()
+                        .map(|child| serde_json::to_value(child.id()).unwrap())
+                        .collect::<Vec<_>>();
+    let mut value = serde_json::to_value(node).unwrap();
+    strip_children(&mut value, &child_ids);
+    value
+}
+
+fn strip_children(value: &mut Value, child_ids: &[Value]) {
+    match value {
+        Value::Object(map) => {
+            *map = std::mem::take(map).into_iter()
+                                      .filter(|(_, value)| !is_child(value, child_ids))
+                                      .collect();
+            for value in map.values_mut() {
+                strip_children(value, child_ids)
+            }
+        }
+        Value::Array(values) => {
+            values.retain(|value| !is_child(value, child_ids));
+            for value in values {
+                strip_children(value, child_ids)
+            }
+        }
+        _ => (),
+    }
+}
+
+// code nodes are serialized like {"FunctionCall": {"id": ..., ...}}, or {"NullLiteral": id}
+fn is_child(value: &Value, child_ids: &[Value]) -> bool {
+    match value.as_object() {
+        Some(map) if map.len() == 1 => {
+            let inner = map.values().next().unwrap();
+            child_ids.contains(inner.get("id").unwrap_or(inner))
+        }
+        _ => false,
+    }
+}
+
+// one piece of code that's different between two worlds
+pub struct CodeDiff {
+    pub location: CodeLocation,
+    // these are only around for drawing the code. nothing ever edits them
+    pub old_editor: CodeEditor,
+    pub new_editor: CodeEditor,
+    pub diff: StructuralDiff,
+}
+
+impl CodeDiff {
+    fn new(location: CodeLocation, old: lang::Block, new: lang::Block) -> Self {
+        let old = CodeNode::Block(old);
+        let new = CodeNode::Block(new);
+        Self { location,
+               diff: StructuralDiff::between(&old, &new),
+               old_editor: CodeEditor::new(old, location),
+               new_editor: CodeEditor::new(new, location) }
+    }
+}
+
+pub enum Comparison {
+    Loading,
+    Failed(String),
+    Done(Vec<CodeDiff>),
+}
+
+pub struct DiffView {
+    pub description: String,
+    pub comparison: Comparison,
+}
+
+impl DiffView {
+    pub fn loading(description: String) -> Self {
+        Self { description,
+               comparison: Comparison::Loading }
+    }
+
+    pub fn failed(description: String, error: String) -> Self {
+        Self { description,
+               comparison: Comparison::Failed(error) }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        match self.comparison {
+            Comparison::Loading => true,
+            _ => false,
+        }
+    }
+
+    // only is for narrowing it down to the code belonging to one function, script or test
+    pub fn between(description: String,
+                   old: &TheWorld,
+                   new: &TheWorld,
+                   only: Option<lang::ID>)
+                   -> Self {
+        let code_diffs = compare_worlds(old, new).into_iter()
+                                                 .filter(|code_diff| {
+                                                     only.map_or(true, |id| {
+                                                         references::owner_id(code_diff.location)
+                                                         == id
+                                                     })
+                                                 })
+                                                 .collect();
+        Self { description,
+               comparison: Comparison::Done(code_diffs) }
+    }
+}
+
+// every piece of code that's different. code that's only in one of the worlds gets diffed against
+// an empty block, so it shows up as entirely inserted or entirely deleted
+fn compare_worlds(old: &TheWorld, new: &TheWorld) -> Vec<CodeDiff> {
+    let old_code = all_code(old);
+    let new_code = all_code(new);
+    let find = |code: &[(CodeLocation, lang::Block)], location: CodeLocation| {
+        code.iter()
+            .find(|(l, _)| *l == location)
+            .map(|(_, block)| block.clone())
+    };
+    let only_in_old = old_code.iter()
+                              .filter(|(location, _)| find(&new_code, *location).is_none());
+    new_code.iter()
+            .chain(only_in_old)
+            .filter_map(|(location, block)| {
+                let old_block = find(&old_code, *location).unwrap_or_else(|| empty_block(block.id));
+                let new_block = find(&new_code, *location).unwrap_or_else(|| empty_block(block.id));
+                if old_block == new_block {
+                    None
+                } else {
+                    Some(CodeDiff::new(*location, old_block, new_block))
+                }
+            })
+            .collect()
+}
+
+fn empty_block(id: lang::ID) -> lang::Block {
+    lang::Block { expressions: vec![],
+                  id }
+}
+
+// like code_validation::all_code, but for a world that isn't loaded
+fn all_code(world: &TheWorld) -> Vec<(CodeLocation, lang::Block)> {
+    let mut code = vec![];
+    for function in &world.functions {
+        let id = function.id();
+        if let Some(code_func) = function.downcast_ref::<CodeFunction>() {
+            code.push((CodeLocation::Function(id), code_func.block.clone()));
+        } else if let Some(chat_program) = function.downcast_ref::<ChatProgram>() {
+            code.push((CodeLocation::ChatProgram(id), chat_program.code.clone()));
+        } else if let Some(webhook_program) = function.downcast_ref::<WebhookProgram>() {
+            code.push((CodeLocation::WebhookProgram(id), webhook_program.code.clone()));
+        } else if let Some(scheduled_program) = function.downcast_ref::<ScheduledProgram>() {
+            code.push((CodeLocation::ScheduledProgram(id), scheduled_program.code.clone()));
+        } else if let Some(client) = function.downcast_ref::<JSONHTTPClient>() {
+            code.push((CodeLocation::JSONHTTPClientURLParams(id),
+                       client.gen_url_params_code.clone()));
+            code.push((CodeLocation::JSONHTTPClientURL(id), client.gen_url_code.clone()));
+            code.push((CodeLocation::JSONHTTPClientTestSection(id), client.test_code.clone()));
+            code.push((CodeLocation::JSONHTTPClientTransform(id), client.transform_code.clone()));
+        }
+    }
+    for script in &world.scripts {
+        code.push((CodeLocation::Script(script.id()), script.code.clone()));
+    }
+    for test in &world.tests {
+        if let CodeNode::Block(block) = test.code() {
+            code.push((CodeLocation::Test(test.id), block));
+        }
+    }
+    code
+}
@@ -38,6 +38,7 @@ impl<'a, T: UiToolkit> ThemeEditorRenderer<'a, T> {
             &|| render_theme_color_picker_with_label!(self, "Window bg color", window_bg_color),
             &|| render_theme_color_picker_with_label!(self, "Action color", action_color),
             &|| render_theme_color_picker_with_label!(self, "Adding color", adding_color),
+            &|| render_theme_color_picker_with_label!(self, "Removing color", removing_color),
             &|| render_theme_color_picker_with_label!(self, "Cool color", cool_color),
             &|| {
                 render_theme_color_picker_with_label!(self,
@@ -5,6 +5,7 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 lazy_static! {
@@ -46,6 +47,9 @@ pub trait UiToolkit {
     fn handle_global_keypress(&self, handle_keypress: impl Fn(Keypress) + 'static);
     fn open_file_open_dialog(callback: impl Fn(&[u8]) + 'static);
     fn open_file_save_dialog(filename_suggestion: &str, contents: &[u8], mimetype: &str);
+    // like the above, but for when we need to know where the file is and not just what's in it
+    fn choose_file_to_open(callback: impl Fn(PathBuf) + 'static);
+    fn choose_file_to_save(callback: impl Fn(PathBuf) + 'static);
     fn draw_code_line_separator(&self, width: f32, height: f32) -> Self::DrawResult;
     fn replace_on_hover(&self,
                         draw_when_not_hovered: &dyn Fn() -> Self::DrawResult,
@@ -82,16 +86,21 @@ pub trait UiToolkit {
                                                       -> Self::DrawResult;
     fn draw_top_left_overlay(&self, draw_fn: &dyn Fn() -> Self::DrawResult) -> Self::DrawResult;
     fn draw_top_right_overlay(&self, draw_fn: &dyn Fn() -> Self::DrawResult) -> Self::DrawResult;
-    fn draw_window<F: Fn(Keypress) + 'static, G: Fn() + 'static, H>(&self,
-                                                                    window_name: &str,
-                                                                    size: (usize, usize),
-                                                                    pos: (isize, isize),
-                                                                    draw_fn: &dyn Fn() -> Self::DrawResult,
-                                                                    handle_keypress: Option<F>,
-                                                                    onclose: Option<G>,
-                                                                    onwindowchange: H)
-                                                                    -> Self::DrawResult
-        where H: Fn((isize, isize), (usize, usize)) + 'static;
+    // scroll_y is where to scroll to when the window first shows up, and onscroll gets told
+    // whenever the user scrolls it somewhere else
+    fn draw_window<F: Fn(Keypress) + 'static, G: Fn() + 'static, H, I>(&self,
+                                                                       window_name: &str,
+                                                                       size: (usize, usize),
+                                                                       pos: (isize, isize),
+                                                                       scroll_y: f32,
+                                                                       draw_fn: &dyn Fn() -> Self::DrawResult,
+                                                                       handle_keypress: Option<F>,
+                                                                       onclose: Option<G>,
+                                                                       onwindowchange: H,
+                                                                       onscroll: I)
+                                                                       -> Self::DrawResult
+        where H: Fn((isize, isize), (usize, usize)) + 'static,
+              I: Fn(f32) + 'static;
     fn with_y_padding(&self, amount_px: u32, draw_fn: DrawFnRef<Self>) -> Self::DrawResult;
     fn draw_child_region<F: Fn(Keypress) + 'static, G: Fn() + 'static>(&self,
                                                                        bg: Color,
@@ -1,4 +1,16 @@
+use cs::code_loading::TheWorld;
+use cs::env::ExecutionEnvironment;
+use cs::env_genie::EnvGenie;
 use cs::lang;
+use cs::lang::CodeNode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::code_validation::all_code;
+use crate::editor::Controller;
+use crate::json_diff::Diff;
+use crate::references;
 
 #[derive(Clone, Debug)]
 pub struct UndoHistoryCell {
@@ -6,43 +18,660 @@ pub struct UndoHistoryCell {
     pub cursor_position: Vec<lang::ID>,
 }
 
+// a change from one state of the code to the next, and where the cursor was on either side of it
+#[derive(Clone, Debug)]
+struct UndoStep {
+    diff: Diff,
+    cursor_before: Vec<lang::ID>,
+    cursor_after: Vec<lang::ID>,
+}
+
+// the undo history for a single code editor. only the most recently recorded state is kept around
+// whole, everything before that is diffs
 #[derive(Debug, Clone)]
 pub struct UndoHistory {
-    undo_stack: Vec<UndoHistoryCell>,
-    redo_stack: Vec<UndoHistoryCell>,
+    last_recorded: Option<UndoHistoryCell>,
+    undo_stack: Vec<UndoStep>,
+    redo_stack: Vec<UndoStep>,
 }
 
 impl UndoHistory {
     pub fn new() -> Self {
-        Self { undo_stack: vec![],
+        Self { last_recorded: None,
+               undo_stack: vec![],
                redo_stack: vec![] }
     }
 
     pub fn record_previous_state(&mut self, root: &lang::CodeNode, cursor_position: Vec<lang::ID>) {
-        let root = root.clone();
-        self.undo_stack.push(UndoHistoryCell { root,
-                                               cursor_position });
+        self.finish_last_recorded(root, cursor_position.clone());
+        self.last_recorded = Some(UndoHistoryCell { root: root.clone(),
+                                                    cursor_position });
         self.redo_stack.clear();
     }
 
+    // the last recorded state becomes a diff once we know what came after it
+    fn finish_last_recorded(&mut self, current_root: &lang::CodeNode, cursor: Vec<lang::ID>) {
+        if let Some(last_recorded) = self.last_recorded.take() {
+            let diff = Diff::between(&to_json(&last_recorded.root), &to_json(current_root));
+            if !diff.is_empty() {
+                self.undo_stack.push(UndoStep { diff,
+                                                cursor_before: last_recorded.cursor_position,
+                                                cursor_after: cursor });
+            }
+        }
+    }
+
     pub fn undo(&mut self,
                 current_root: &lang::CodeNode,
                 cursor_position: Vec<lang::ID>)
                 -> Option<UndoHistoryCell> {
-        self.redo_stack
-            .push(UndoHistoryCell { root: current_root.clone(),
-                                    cursor_position });
-        self.undo_stack.pop()
+        self.finish_last_recorded(current_root, cursor_position);
+        let step = self.undo_stack.pop()?;
+        let mut root = to_json(current_root);
+        step.diff.revert(&mut root)?;
+        let cell = UndoHistoryCell { root: serde_json::from_value(root).ok()?,
+                                     cursor_position: step.cursor_before.clone() };
+        self.redo_stack.push(step);
+        Some(cell)
     }
 
     pub fn redo(&mut self,
                 current_root: &lang::CodeNode,
-                cursor_position: Vec<lang::ID>)
+                _cursor_position: Vec<lang::ID>)
                 -> Option<UndoHistoryCell> {
-        let redone_state = self.redo_stack.pop()?;
-        self.undo_stack
-            .push(UndoHistoryCell { root: current_root.clone(),
-                                    cursor_position });
-        Some(redone_state)
+        let step = self.redo_stack.pop()?;
+        let mut root = to_json(current_root);
+        step.diff.apply(&mut root)?;
+        let cell = UndoHistoryCell { root: serde_json::from_value(root).ok()?,
+                                     cursor_position: step.cursor_after.clone() };
+        self.undo_stack.push(step);
+        Some(cell)
+    }
+}
+
+fn to_json<T: Serialize>(t: &T) -> Value {
+    serde_json::to_value(t).unwrap()
+}
+
+// the pieces of the world that the global undo history keeps track of. tests aren't in here
+// because their code only lives inside of their code editors, which have their own undo
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WorldItem {
+    Function(lang::ID),
+    TypeSpec(lang::ID),
+    Script(lang::ID),
+}
+
+impl WorldItem {
+    fn id(&self) -> lang::ID {
+        match self {
+            WorldItem::Function(id) | WorldItem::TypeSpec(id) | WorldItem::Script(id) => *id,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            WorldItem::Function(_) => "function",
+            WorldItem::TypeSpec(_) => "type",
+            WorldItem::Script(_) => "script",
+        }
+    }
+}
+
+// everything in the world that isn't a builtin, serialized
+pub type WorldSnapshot = BTreeMap<WorldItem, Value>;
+
+pub fn take_snapshot(controller: &Controller, env: &ExecutionEnvironment) -> WorldSnapshot {
+    let functions = env.functions
+                       .values()
+                       .filter(|func| !controller.is_builtin(func.id()))
+                       .map(|func| (WorldItem::Function(func.id()), to_json(func)));
+    let typespecs = env.typespecs
+                       .values()
+                       .filter(|typespec| !controller.is_builtin(typespec.id()))
+                       .map(|typespec| (WorldItem::TypeSpec(typespec.id()), to_json(typespec)));
+    let scripts = controller.list_scripts()
+                            .map(|script| (WorldItem::Script(script.id()), to_json(script)));
+    functions.chain(typespecs).chain(scripts).collect()
+}
+
+// for checking that a saved undo history still lines up with the world that got loaded. this gets
+// saved, so it's FNV-1a by hand instead of std's hashers, which can change between rust releases
+pub fn fingerprint(snapshot: &WorldSnapshot) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (item, value) in snapshot {
+        let item = serde_json::to_string(item).unwrap();
+        for byte in item.bytes().chain(value.to_string().bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+    hash
+}
+
+// one item's worth of a world edit. a missing item diffs as null, so adding and deleting things
+// is just another change
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ItemChange {
+    pub item: WorldItem,
+    pub diff: Diff,
+}
+
+pub fn changes_between(before: &WorldSnapshot, after: &WorldSnapshot) -> Vec<ItemChange> {
+    let items = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+    items.into_iter()
+         .filter_map(|item| {
+             let diff = Diff::between(before.get(item).unwrap_or(&Value::Null),
+                                      after.get(item).unwrap_or(&Value::Null));
+             if diff.is_empty() {
+                 None
+             } else {
+                 Some(ItemChange { item: *item, diff })
+             }
+         })
+         .collect()
+}
+
+// for when nobody said what an edit was for, e.g. typing into a code editor
+fn describe_changes(changes: &[ItemChange],
+                    before: &WorldSnapshot,
+                    after: &WorldSnapshot)
+                    -> String {
+    match changes {
+        [change] => {
+            let verb = match (before.get(&change.item), after.get(&change.item)) {
+                (None, _) => "Add",
+                (_, None) => "Delete",
+                _ => "Change",
+            };
+            let name = after.get(&change.item)
+                            .or_else(|| before.get(&change.item))
+                            .and_then(find_name)
+                            .unwrap_or("");
+            format!("{} {} {}", verb, change.item.kind(), name)
+        }
+        _ => format!("Change {} things", changes.len()),
+    }
+}
+
+// functions and typespecs are serialized with their type wrapped around them, so the name could be
+// one level down
+fn find_name(value: &Value) -> Option<&str> {
+    let map = value.as_object()?;
+    if let Some(name) = map.get("name").and_then(|name| name.as_str()) {
+        return Some(name);
+    }
+    if map.len() == 1 {
+        return find_name(map.values().next()?);
+    }
+    None
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WorldEdit {
+    pub description: String,
+    pub changes: Vec<ItemChange>,
+}
+
+impl WorldEdit {
+    pub fn describe(&self) -> String {
+        self.description.clone()
+    }
+}
+
+// how to get from one spot in the undo tree to another
+pub enum WorldHistoryStep {
+    Undo(WorldEdit),
+    Redo(WorldEdit),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UndoTreeNode {
+    pub parent: Option<usize>,
+    pub edit: WorldEdit,
+}
+
+// every change to the world, kept as a tree: undoing and then making a different change starts a
+// new branch instead of throwing away what was undone. a node of None is the very beginning,
+// before any edits
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WorldUndoHistory {
+    nodes: Vec<UndoTreeNode>,
+    current: Option<usize>,
+    #[serde(skip)]
+    next_description: Option<String>,
+    // where we were before moving around the tree, so the changes that come from moving don't
+    // get recorded as new edits
+    #[serde(skip)]
+    moved_from: Option<Option<usize>>,
+    // set when the whole world got swapped out, so there's nothing to compare it against
+    #[serde(skip)]
+    started_over: bool,
+    // set when nodes got thrown out or renumbered, so what's been saved so far doesn't line up
+    // anymore
+    #[serde(skip)]
+    needs_rewrite: bool,
+}
+
+// past this many edits, the oldest ones get forgotten
+const MAX_WORLD_HISTORY_NODES: usize = 1000;
+
+// what changed in the history since the last time it was recorded, so the saved copy can be
+// added onto instead of being written out from scratch every time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldHistoryChange {
+    NewEdit,
+    Moved,
+    Rewritten,
+}
+
+impl WorldUndoHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // for when a whole different world gets swapped in. there's nothing to undo back to, and the
+    // swap itself isn't an edit
+    pub fn start_over(&mut self) {
+        *self = Self { started_over: true,
+                       needs_rewrite: true,
+                       ..Self::default() };
+    }
+
+    // for commands that know better than we do what the next edit is
+    pub fn label_next_edit(&mut self, description: String) {
+        self.next_description = Some(description);
+    }
+
+    // returns what there is to save, if anything
+    pub fn record(&mut self,
+                  before: &WorldSnapshot,
+                  after: &WorldSnapshot)
+                  -> Option<WorldHistoryChange> {
+        let next_description = self.next_description.take();
+        let moved_from = self.moved_from.take();
+        if std::mem::replace(&mut self.started_over, false) {
+            return Some(self.change_to_save(WorldHistoryChange::Moved));
+        }
+        // anything else that changed alongside a move is still an edit of its own
+        let changes = match moved_from {
+            Some(from) => changes_between(&self.take_steps_on(before, from), after),
+            None => changes_between(before, after),
+        };
+        if changes.is_empty() {
+            return moved_from.map(|_| self.change_to_save(WorldHistoryChange::Moved));
+        }
+        let description =
+            next_description.unwrap_or_else(|| describe_changes(&changes, before, after));
+        self.nodes.push(UndoTreeNode { parent: self.current,
+                                       edit: WorldEdit { description,
+                                                         changes } });
+        self.current = Some(self.nodes.len() - 1);
+        self.prune();
+        Some(self.change_to_save(WorldHistoryChange::NewEdit))
+    }
+
+    fn change_to_save(&mut self, change: WorldHistoryChange) -> WorldHistoryChange {
+        if std::mem::replace(&mut self.needs_rewrite, false) {
+            WorldHistoryChange::Rewritten
+        } else {
+            change
+        }
+    }
+
+    // forgets the oldest edit on the way to where we are, along with any branches that split off
+    // before it, until the history's small enough again. if we're all the way back at the
+    // beginning, the oldest branch goes instead
+    fn prune(&mut self) {
+        while self.nodes.len() > MAX_WORLD_HISTORY_NODES {
+            let ancestors = self.ancestors(self.current);
+            match ancestors.iter().rev().nth(1) {
+                Some(Some(oldest)) => {
+                    let oldest = *oldest;
+                    let keep = (0..self.nodes.len()).map(|index| {
+                                                        index != oldest
+                                                        && self.ancestors(Some(index))
+                                                               .contains(&Some(oldest))
+                                                    })
+                                                    .collect::<Vec<_>>();
+                    self.retain_nodes(&keep, Some(oldest));
+                }
+                _ => {
+                    let oldest = match self.children(None).next() {
+                        Some(oldest) => oldest,
+                        None => return,
+                    };
+                    let keep = (0..self.nodes.len()).map(|index| {
+                                                        !self.ancestors(Some(index))
+                                                             .contains(&Some(oldest))
+                                                    })
+                                                    .collect::<Vec<_>>();
+                    self.retain_nodes(&keep, None);
+                }
+            }
+            self.needs_rewrite = true;
+        }
+    }
+
+    // renumbers the nodes that are kept. children of new_beginning hang off of the very
+    // beginning from now on
+    fn retain_nodes(&mut self, keep: &[bool], new_beginning: Option<usize>) {
+        let mut new_index = vec![None; self.nodes.len()];
+        let mut count = 0;
+        for (index, kept) in keep.iter().enumerate() {
+            if *kept {
+                new_index[index] = Some(count);
+                count += 1;
+            }
+        }
+        let nodes = std::mem::replace(&mut self.nodes, vec![]);
+        self.nodes = nodes.into_iter()
+                          .enumerate()
+                          .filter(|(index, _)| keep[*index])
+                          .map(|(_, node)| {
+                              let parent = match node.parent {
+                                  parent if parent == new_beginning => None,
+                                  Some(parent) => new_index[parent],
+                                  None => None,
+                              };
+                              UndoTreeNode { parent, ..node }
+                          })
+                          .collect();
+        self.current = self.current.and_then(|current| new_index[current]);
+    }
+
+    // for loading a saved history back up, one saved change at a time. returns None if the
+    // change doesn't fit, in which case the whole saved history is no good
+    pub fn replay_new_edit(&mut self, node: UndoTreeNode) -> Option<()> {
+        let len = self.nodes.len();
+        if node.parent.map_or(false, |parent| parent >= len) {
+            return None;
+        }
+        self.nodes.push(node);
+        self.current = Some(self.nodes.len() - 1);
+        Some(())
+    }
+
+    pub fn replay_move(&mut self, current: Option<usize>) -> Option<()> {
+        let len = self.nodes.len();
+        if current.map_or(false, |current| current >= len) {
+            return None;
+        }
+        self.current = current;
+        Some(())
+    }
+
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn node(&self, index: usize) -> &UndoTreeNode {
+        &self.nodes[index]
+    }
+
+    pub fn children(&self, parent: Option<usize>) -> impl Iterator<Item = usize> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(move |(_, node)| node.parent == parent)
+            .map(|(index, _)| index)
+    }
+
+    pub fn next_undo(&self) -> Option<&WorldEdit> {
+        Some(&self.nodes[self.current?].edit)
+    }
+
+    // redo follows the newest branch
+    pub fn next_redo(&self) -> Option<&WorldEdit> {
+        Some(&self.nodes[self.newest_child(self.current)?].edit)
+    }
+
+    fn newest_child(&self, parent: Option<usize>) -> Option<usize> {
+        self.children(parent).last()
+    }
+
+    pub fn undo(&mut self) -> Vec<WorldHistoryStep> {
+        match self.current {
+            Some(current) => self.go_to(self.nodes[current].parent),
+            None => vec![],
+        }
+    }
+
+    pub fn redo(&mut self) -> Vec<WorldHistoryStep> {
+        match self.newest_child(self.current) {
+            Some(child) => self.go_to(Some(child)),
+            None => vec![],
+        }
+    }
+
+    // undoes back up to where the two branches meet, then redoes down to the target
+    pub fn go_to(&mut self, target: Option<usize>) -> Vec<WorldHistoryStep> {
+        let steps = self.steps_between(self.current, target);
+        if self.moved_from.is_none() {
+            self.moved_from = Some(self.current);
+        }
+        self.current = target;
+        steps
+    }
+
+    // what the snapshot turns into after moving from one spot in the tree to where we are now
+    fn take_steps_on(&self, snapshot: &WorldSnapshot, from: Option<usize>) -> WorldSnapshot {
+        let mut snapshot = snapshot.clone();
+        for step in self.steps_between(from, self.current) {
+            let (edit, is_undo) = match step {
+                WorldHistoryStep::Undo(edit) => (edit, true),
+                WorldHistoryStep::Redo(edit) => (edit, false),
+            };
+            let mut changes = edit.changes;
+            if is_undo {
+                changes.reverse();
+            }
+            for change in changes {
+                let mut value = snapshot.get(&change.item).cloned().unwrap_or(Value::Null);
+                let applied = if is_undo {
+                    change.diff.revert(&mut value)
+                } else {
+                    change.diff.apply(&mut value)
+                };
+                match applied {
+                    None => (),
+                    Some(_) if value.is_null() => {
+                        snapshot.remove(&change.item);
+                    }
+                    Some(_) => {
+                        snapshot.insert(change.item, value);
+                    }
+                }
+            }
+        }
+        snapshot
+    }
+
+    fn steps_between(&self, from: Option<usize>, to: Option<usize>) -> Vec<WorldHistoryStep> {
+        let from = self.ancestors(from);
+        let to = self.ancestors(to);
+        // the very beginning is in both, so they always meet somewhere
+        let meeting_point = *from.iter().find(|node| to.contains(node)).unwrap();
+        let undos = from.iter()
+                        .take_while(|node| **node != meeting_point)
+                        .filter_map(|node| *node)
+                        .map(|index| WorldHistoryStep::Undo(self.nodes[index].edit.clone()));
+        let redos = to.iter()
+                      .take_while(|node| **node != meeting_point)
+                      .filter_map(|node| *node)
+                      .collect::<Vec<_>>()
+                      .into_iter()
+                      .rev()
+                      .map(|index| WorldHistoryStep::Redo(self.nodes[index].edit.clone()));
+        undos.chain(redos).collect()
+    }
+
+    // what an item looked like at another spot in the tree, worked out backwards (or forwards)
+    // from how it looks now. null means it wasn't there
+    fn item_at(&self, node: Option<usize>, item: WorldItem, current: &Value) -> Value {
+        let mut value = current.clone();
+        for step in self.steps_between(self.current, node) {
+            let (edit, is_undo) = match step {
+                WorldHistoryStep::Undo(edit) => (edit, true),
+                WorldHistoryStep::Redo(edit) => (edit, false),
+            };
+            let mut changes = edit.changes;
+            if is_undo {
+                changes.reverse();
+            }
+            for change in changes.iter().filter(|change| change.item == item) {
+                if is_undo {
+                    change.diff.revert(&mut value);
+                } else {
+                    change.diff.apply(&mut value);
+                }
+            }
+        }
+        value
+    }
+
+    // the things an edit touched, from right before and right after it, for seeing what it did
+    pub fn worlds_around_edit(&self,
+                              node: usize,
+                              snapshot: &WorldSnapshot)
+                              -> (TheWorld, TheWorld) {
+        let mut before = TheWorld::default();
+        let mut after = TheWorld::default();
+        for change in &self.nodes[node].edit.changes {
+            let current = snapshot.get(&change.item).cloned().unwrap_or(Value::Null);
+            add_to_world(&mut before,
+                         change.item,
+                         self.item_at(self.nodes[node].parent, change.item, &current));
+            add_to_world(&mut after,
+                         change.item,
+                         self.item_at(Some(node), change.item, &current));
+        }
+        (before, after)
+    }
+
+    // from the node up to the very beginning, both included
+    fn ancestors(&self, node: Option<usize>) -> Vec<Option<usize>> {
+        let mut ancestors = vec![node];
+        let mut node = node;
+        while let Some(index) = node {
+            node = self.nodes[index].parent;
+            ancestors.push(node);
+        }
+        ancestors
+    }
+
+    // the tree laid out for showing, with how far each node is indented. the oldest branch
+    // carries on at the same indentation, and newer ones get nudged over
+    pub fn tree_rows(&self) -> Vec<(usize, Option<usize>)> {
+        let mut rows = vec![];
+        self.push_tree_rows(None, 0, &mut rows);
+        rows
+    }
+
+    fn push_tree_rows(&self,
+                      node: Option<usize>,
+                      depth: usize,
+                      rows: &mut Vec<(usize, Option<usize>)>) {
+        rows.push((depth, node));
+        for (i, child) in self.children(node)
+                              .collect::<Vec<_>>()
+                              .into_iter()
+                              .enumerate()
+        {
+            let child_depth = if i == 0 { depth } else { depth + 1 };
+            self.push_tree_rows(Some(child), child_depth, rows);
+        }
+    }
+}
+
+pub fn take_steps(steps: Vec<WorldHistoryStep>,
+                  controller: &mut Controller,
+                  env: &mut ExecutionEnvironment) {
+    let mut changed_ids = vec![];
+    for step in steps {
+        let (edit, is_undo) = match step {
+            WorldHistoryStep::Undo(edit) => (edit, true),
+            WorldHistoryStep::Redo(edit) => (edit, false),
+        };
+        let mut changes = edit.changes;
+        if is_undo {
+            changes.reverse();
+        }
+        for change in changes {
+            apply_change(&change, is_undo, controller, env);
+            changed_ids.push(change.item.id());
+        }
+    }
+    reload_code_editors(&changed_ids, controller, env);
+}
+
+fn add_to_world(world: &mut TheWorld, item: WorldItem, value: Value) {
+    if value.is_null() {
+        return;
+    }
+    match item {
+        WorldItem::Function(_) => world.functions.extend(serde_json::from_value(value).ok()),
+        WorldItem::TypeSpec(_) => world.typespecs.extend(serde_json::from_value(value).ok()),
+        WorldItem::Script(_) => world.scripts.extend(serde_json::from_value(value).ok()),
+    }
+}
+
+// if the item doesn't look like what the change was made from, something outside of the history
+// must have changed it, so it's left alone rather than being mangled
+fn apply_change(change: &ItemChange,
+                is_undo: bool,
+                controller: &mut Controller,
+                env: &mut ExecutionEnvironment) {
+    let snapshot_value = match change.item {
+        WorldItem::Function(id) => env.find_function(id).map(to_json),
+        WorldItem::TypeSpec(id) => env.find_typespec(id).map(to_json),
+        WorldItem::Script(id) => controller.find_script(id).map(to_json),
+    };
+    let mut value = snapshot_value.unwrap_or(Value::Null);
+    let applied = if is_undo {
+        change.diff.revert(&mut value)
+    } else {
+        change.diff.apply(&mut value)
+    };
+    if applied.is_none() {
+        return;
+    }
+    match (change.item, value) {
+        (WorldItem::Function(id), Value::Null) => env.delete_function(id),
+        (WorldItem::TypeSpec(id), Value::Null) => env.delete_typespec(id),
+        (WorldItem::Script(id), Value::Null) => controller.remove_script(id),
+        (WorldItem::Function(_), value) => {
+            if let Ok(func) = serde_json::from_value(value) {
+                env.add_function_box(func)
+            }
+        }
+        (WorldItem::TypeSpec(_), value) => {
+            if let Ok(typespec) = serde_json::from_value(value) {
+                env.add_typespec_box(typespec)
+            }
+        }
+        (WorldItem::Script(_), value) => {
+            if let Ok(script) = serde_json::from_value(value) {
+                controller.load_script(script)
+            }
+        }
+    }
+}
+
+// code editors keep their own copy of the code, so they have to be told about the new version
+fn reload_code_editors(changed_ids: &[lang::ID],
+                       controller: &mut Controller,
+                       env: &ExecutionEnvironment) {
+    let code_to_reload = {
+        let env_genie = EnvGenie::new(env);
+        all_code(&env_genie, controller).filter(|(location, block)| {
+                                            changed_ids.contains(&references::owner_id(*location))
+                                            && controller.get_editor(block.id).is_some()
+                                        })
+                                        .map(|(location, block)| (location, block.clone()))
+                                        .collect::<Vec<_>>()
+    };
+    for (location, block) in code_to_reload {
+        controller.load_code(CodeNode::Block(block), location);
     }
 }
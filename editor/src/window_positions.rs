@@ -11,6 +11,14 @@ lazy_static! {
         uuid::Uuid::parse_str("1f5dbdf2-c8b7-4594-bc3e-9a4ca4c6184b").unwrap();
     pub static ref THEME_EDITOR_WINDOW_ID: lang::ID =
         uuid::Uuid::parse_str("5532875b-ab0e-4ac0-af22-dcfd364b7d7a").unwrap();
+    pub static ref REFERENCES_WINDOW_ID: lang::ID =
+        uuid::Uuid::parse_str("0b6e3f3e-5b1d-4c55-9a57-7f3c2a8d41e6").unwrap();
+    pub static ref UNDO_TREE_WINDOW_ID: lang::ID =
+        uuid::Uuid::parse_str("c4a1d6e2-7f3b-4e58-9d2a-61b0f8e5a937").unwrap();
+    pub static ref MERGE_WINDOW_ID: lang::ID =
+        uuid::Uuid::parse_str("5e2b8a7d-3c91-4f06-b4d8-9a1e7c3f6b20").unwrap();
+    pub static ref DIFF_WINDOW_ID: lang::ID =
+        uuid::Uuid::parse_str("9d47c2b1-6e0a-4f8d-a3b5-2c81e7f05d64").unwrap();
 }
 
 // go under the title bar
@@ -20,6 +28,8 @@ const INITIAL_WINDOW_POSITION: (isize, isize) = (5, 30);
 const INITIAL_WINDOW_SIZE: (usize, usize) = (550, 650);
 
 const QUICK_START_WINDOW_SIZE: (usize, usize) = (300, 300);
+// so each window's title bar still peeks out from under the next one
+const RESET_LAYOUT_CASCADE_OFFSET: usize = 30;
 const _CHAT_TEST_WINDOW_SIZE: (usize, usize) = (300, 300);
 
 #[derive(Deserialize, Serialize)]
@@ -58,7 +68,23 @@ impl WindowPositions {
                                  Window { id: window_id,
                                           x: initial_pos.0,
                                           y: initial_pos.1,
-                                          size: INITIAL_WINDOW_SIZE });
+                                          size: INITIAL_WINDOW_SIZE,
+                                          scroll_y: 0. });
+    }
+
+    // keeps the same windows open, but stacks them back up where new windows go, at the size new
+    // windows get
+    pub fn reset_layout(&mut self) {
+        let initial_pos = self.get_next_window_position();
+        let mut windows = self.open_windows.values_mut().collect::<Vec<_>>();
+        windows.sort_by_key(|window| (window.y, window.x));
+        for (i, window) in windows.into_iter().enumerate() {
+            let offset = (i * RESET_LAYOUT_CASCADE_OFFSET) as isize;
+            window.x = initial_pos.0 + offset;
+            window.y = initial_pos.1 + offset;
+            window.size = INITIAL_WINDOW_SIZE;
+            window.scroll_y = 0.;
+        }
     }
 
     pub fn set_window(&mut self, window_id: lang::ID, pos: (isize, isize), size: (usize, usize)) {
@@ -72,6 +98,12 @@ impl WindowPositions {
         win.y = pos.1;
     }
 
+    pub fn set_window_scroll(&mut self, window_id: lang::ID, scroll_y: f32) {
+        if let Some(win) = self.open_windows.get_mut(&window_id) {
+            win.scroll_y = scroll_y;
+        }
+    }
+
     // TODO: i don't think this is used except for getting the first window pos
     fn get_next_window_position(&self) -> (isize, isize) {
         // TODO: some calculationz
@@ -102,6 +134,7 @@ pub struct Window {
     pub x: isize,
     pub y: isize,
     pub size: (usize, usize),
+    pub scroll_y: f32,
 }
 
 impl Window {
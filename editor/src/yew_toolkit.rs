@@ -23,6 +23,7 @@ use spinner::Spinner;
 use text::{symbolize_text, Text};
 
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use itertools::Itertools;
@@ -163,6 +164,11 @@ impl UiToolkit for YewToolkit {
         js! { saveFile(@{bytes}, @{filename_suggestion}, @{mimetype}); }
     }
 
+    // the browser never tells us where files are, so project files are native only
+    fn choose_file_to_open(_callback: impl Fn(PathBuf) + 'static) {}
+
+    fn choose_file_to_save(_callback: impl Fn(PathBuf) + 'static) {}
+
     fn draw_color_picker_with_label(&self,
                                     label: &str,
                                     existing_value: Color,
@@ -485,17 +491,21 @@ impl UiToolkit for YewToolkit {
     }
 
     // TODO: wasm needs to call back into the app and tell it the window positions
-    fn draw_window<F: Fn(Keypress) + 'static, G: Fn() + 'static, H>(&self,
-                                                                    window_name: &str,
-                                                                    size: (usize, usize),
-                                                                    pos: (isize, isize),
-                                                                    f: &dyn Fn()
-                                                                            -> Self::DrawResult,
-                                                                    handle_keypress: Option<F>,
-                                                                    onclose: Option<G>,
-                                                                    onwindowchange: H)
-                                                                    -> Self::DrawResult
-        where H: Fn((isize, isize), (usize, usize)) + 'static
+    // TODO: scroll positions aren't saved or restored on wasm yet
+    fn draw_window<F: Fn(Keypress) + 'static, G: Fn() + 'static, H, I>(&self,
+                                                                       window_name: &str,
+                                                                       size: (usize, usize),
+                                                                       pos: (isize, isize),
+                                                                       _scroll_y: f32,
+                                                                       f: &dyn Fn()
+                                                                               -> Self::DrawResult,
+                                                                       handle_keypress: Option<F>,
+                                                                       onclose: Option<G>,
+                                                                       onwindowchange: H,
+                                                                       _onscroll: I)
+                                                                       -> Self::DrawResult
+        where H: Fn((isize, isize), (usize, usize)) + 'static,
+              I: Fn(f32) + 'static
     {
         // TODO: i should just be able to move onwindowchange... i wonder why we have to wrap it in
         // RC :/
@@ -1510,6 +1520,7 @@ fn map_key(key: &str) -> Option<AppKey> {
         "x" => Some(AppKey::X),
         "r" => Some(AppKey::R),
         "o" => Some(AppKey::O),
+        "p" => Some(AppKey::P),
         "u" => Some(AppKey::U),
         "v" => Some(AppKey::V),
         "tab" => Some(AppKey::Tab),
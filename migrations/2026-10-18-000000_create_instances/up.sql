@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE instances (
+  id SERIAL PRIMARY KEY,
+  disabled BOOLEAN NOT NULL DEFAULT FALSE,
+  token_generation INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+SELECT diesel_manage_updated_at('instances');
+
+-- up until now, instances only existed as the instance_id on their service configs and code
+INSERT INTO instances (id)
+  SELECT instance_id FROM service_configs
+  UNION
+  SELECT instance_id FROM codes;
+SELECT setval('instances_id_seq', COALESCE(MAX(id), 0) + 1, false) FROM instances;
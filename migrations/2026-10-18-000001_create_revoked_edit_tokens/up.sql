@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE revoked_edit_tokens (
+  token_id TEXT PRIMARY KEY,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+SELECT diesel_manage_updated_at('revoked_edit_tokens');
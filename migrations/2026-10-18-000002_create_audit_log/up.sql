@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE audit_log (
+  id SERIAL PRIMARY KEY,
+  instance_id INTEGER NOT NULL,
+  program_id TEXT NOT NULL,
+  program_name TEXT NOT NULL,
+  change TEXT NOT NULL,
+  changed_by TEXT NOT NULL,
+  edit_token_id TEXT NOT NULL,
+  changed_at BIGINT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX audit_log_instance_id ON audit_log (instance_id);
+
+SELECT diesel_manage_updated_at('audit_log');
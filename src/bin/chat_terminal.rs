@@ -0,0 +1,160 @@
+// talk to a saved world's chat programs from the terminal, without connecting to any chat service
+//
+//     chat_terminal <world.json> [--sender <name>]
+//
+// every line typed in is a message from the sender, and the bot's replies are printed back.
+//
+//     chat_terminal <world.json> --script <transcript> [--sender <name>]
+//
+// plays back a transcript and checks that the bot replied the way it says. in a transcript, a line
+// like `> !weather` is a message from the sender, `<alice> !weather` is a message from alice, and
+// every other line is a reply the bot is expected to make, in order. blank lines are skipped. a
+// line starting with # is a comment, unless it comes after a message with no blank line in
+// between, so that replies like `#general is quiet today` still get checked. exits with an error
+// if any of the replies didn't match
+extern crate cs;
+
+use cs::chat_format;
+use cs::chat_format::Flavor;
+use cs::chat_program::message_received;
+use cs::env::Interpreter;
+use cs::{asynk, code_loading};
+use std::io::BufRead;
+use tokio::runtime::current_thread::Runtime;
+
+const DEFAULT_SENDER: &str = "you";
+const BOT_NAME: &str = "bot";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let world_filename =
+        args.next()
+            .expect("usage: chat_terminal <world.json> [--script <transcript>] [--sender <name>]");
+    let mut script_filename = None;
+    let mut sender = DEFAULT_SENDER.to_owned();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--script" => {
+                script_filename = Some(args.next().expect("expected a transcript filename"))
+            }
+            "--sender" => sender = args.next().expect("expected a sender name"),
+            _ => panic!("unknown argument: {}", arg),
+        }
+    }
+
+    let world_json = std::fs::read_to_string(&world_filename).unwrap();
+    let the_world = code_loading::deserialize(&world_json).unwrap();
+    let interp = cs::init_interpreter_with_world(&the_world).unwrap();
+    let mut runtime = Runtime::new().unwrap();
+
+    match script_filename {
+        Some(script_filename) => {
+            let transcript = std::fs::read_to_string(&script_filename).unwrap();
+            let mismatches = play_transcript(&mut runtime, &interp, &transcript, &sender);
+            if mismatches > 0 {
+                println!("{} message(s) didn't get the replies the transcript expected",
+                         mismatches);
+                std::process::exit(1);
+            }
+            println!("all replies matched");
+        }
+        None => {
+            let stdin = std::io::stdin();
+            for line in stdin.lock().lines() {
+                let line = line.unwrap();
+                for reply in send_message(&mut runtime, &interp, &sender, &line) {
+                    println!("<{}> {}", BOT_NAME, reply);
+                }
+            }
+        }
+    }
+}
+
+// runs the message through the chat programs, and returns what the bot said back
+fn send_message(runtime: &mut Runtime,
+                interp: &Interpreter,
+                sender: &str,
+                text: &str)
+                -> Vec<String> {
+    let triggered = message_received(interp, sender.to_owned(), text.to_owned());
+    let replies = runtime.block_on(asynk::backward(async { Ok::<_, ()>(triggered.await) }))
+                         .unwrap();
+    replies.iter()
+           .map(|reply| chat_format::render_with_target(reply, Flavor::Plain))
+           .collect()
+}
+
+// a message to send to the bot, and the replies it should send back
+struct Exchange<'a> {
+    line_number: usize,
+    sender: &'a str,
+    text: &'a str,
+    expected_replies: Vec<&'a str>,
+}
+
+fn parse_transcript<'a>(transcript: &'a str, default_sender: &'a str) -> Vec<Exchange<'a>> {
+    let mut exchanges: Vec<Exchange> = vec![];
+    let mut in_replies = false;
+    for (i, line) in transcript.lines().enumerate() {
+        if line.trim().is_empty() {
+            in_replies = false;
+            continue;
+        }
+        if line.starts_with('#') && !in_replies {
+            continue;
+        }
+        let message = if let Some(text) = line.strip_prefix("> ") {
+            Some((default_sender, text))
+        } else if line.starts_with('<') {
+            line.find("> ").map(|end| (&line[1..end], &line[end + 2..]))
+        } else {
+            None
+        };
+        match message {
+            Some((sender, text)) => {
+                in_replies = true;
+                exchanges.push(Exchange { line_number: i + 1,
+                                          sender,
+                                          text,
+                                          expected_replies: vec![] })
+            }
+            None => exchanges.last_mut()
+                             .unwrap_or_else(|| {
+                                 panic!("line {}: expected a message before any replies", i + 1)
+                             })
+                             .expected_replies
+                             .push(line),
+        }
+    }
+    exchanges
+}
+
+// returns how many messages got different replies than expected
+fn play_transcript(runtime: &mut Runtime,
+                   interp: &Interpreter,
+                   transcript: &str,
+                   default_sender: &str)
+                   -> usize {
+    let mut mismatches = 0;
+    for exchange in parse_transcript(transcript, default_sender) {
+        let replies = send_message(runtime, interp, exchange.sender, exchange.text);
+        // cards and code blocks come out over a few lines, and so do they in the transcript
+        let replies = replies.iter()
+                             .flat_map(|reply| reply.lines())
+                             .collect::<Vec<_>>();
+        if replies == exchange.expected_replies {
+            continue;
+        }
+        mismatches += 1;
+        println!("line {}: <{}> {}",
+                 exchange.line_number, exchange.sender, exchange.text);
+        for expected in &exchange.expected_replies {
+            println!("- {}", expected);
+        }
+        for reply in &replies {
+            println!("+ {}", reply);
+        }
+        println!();
+    }
+    mismatches
+}
@@ -3,36 +3,34 @@ extern crate cs;
 use std::pin::Pin;
 
 use cs::asynk::{backward, forward, OldFuture};
-use cs::builtins::ChatReply;
-use cs::code_loading::TheWorld;
+use cs::code_loading::{world_changes, TheWorld, WorldChange};
 use cs::env;
-use diesel::prelude::*;
-use diesel::query_dsl::QueryDsl;
+use cs::logging;
+use cs::metrics;
 use futures::future::join_all;
 use futures_channel::mpsc;
-use futures_util::compat::Stream01CompatExt;
 use hyper::service::service_fn;
 use hyper::{Body, Request, Response, Server};
-use irc::client::prelude::*;
-use irc::client::PackedIrcClient;
-use irc_proto::Command;
 use itertools::Itertools;
-use noob;
 use serde::Deserialize;
 use serde_derive::{Deserialize as Deserializeable, Serialize as Serializeable};
+use serde_json::json;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use tokio::prelude::*;
+use tokio::runtime::current_thread;
 use tokio::runtime::current_thread::Runtime;
 
 fn main() {
     use std::fs::File;
     use std::io::BufReader;
 
-    let _guard =
-        sentry::init("https://d8b23e4125944accaee73d0aa4663f0d@o407424.ingest.sentry.io/5276333");
+    // panics only get reported if there's somewhere to report them to
+    let _guard = config::get("SENTRY_DSN").map(sentry::init);
 
     let mut runtime = Runtime::new().unwrap();
 
@@ -48,382 +46,446 @@ fn main() {
         std::process::exit(0);
     }
 
-    let service_configs_by_instance_id = runtime.block_on(backward(load_instances())).unwrap();
+    if main_arg == Some("admin".to_string()) {
+        let command = args.collect_vec();
+        match runtime.block_on(backward(run_admin_command(&command))) {
+            Ok(()) => std::process::exit(0),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let instances = runtime.block_on(backward(load_instances())).unwrap();
 
     if main_arg == Some("list_instance_urls".to_string()) {
-        for (instance_id, service_configs) in service_configs_by_instance_id.iter() {
-            let url = GenerateProgramBotUrl::new(*instance_id).generate_url()
-                                                              .unwrap();
+        for (instance, service_configs) in instances.iter() {
+            let token = EditToken::new(instance.id,
+                                       instance.token_generation,
+                                       "list_instance_urls".to_owned(),
+                                       unix_now(),
+                                       DEFAULT_EDIT_TOKEN_TTL_SECS,
+                                       EditScope::Write { program_ids: None });
+            let url = edit_url(&token).unwrap();
             let nicknames = service_configs.iter().map(|sc| &sc.nickname).join(", ");
-            println!("instance {}: {}", instance_id, nicknames);
+            println!("instance {}: {}", instance.id, nicknames);
             println!("{}", url);
             println!();
         }
         std::process::exit(0);
     }
 
-    let mut new_code_sender_by_instance_id = HashMap::new();
-
-    let mut threads = service_configs_by_instance_id.into_iter().map(|(instance_id, service_configs)| {
-        // GHETTO: this is for sending worlds from the web interface into the interp
-        let (tx, rx) = mpsc::unbounded::<TheWorld>();
-        new_code_sender_by_instance_id.insert(instance_id, tx);
-        thread::spawn(move || {
-            start_new_interpreter_instance_with_services(instance_id, &service_configs, rx);
-        })
-    }).collect_vec();
+    let running_instances = instances.into_iter()
+                                     .filter(|(instance, _)| !instance.disabled)
+                                     .map(|(instance, service_configs)| {
+                                         (instance.id, start_instance(&instance, service_configs))
+                                     })
+                                     .collect();
+    let running_instances: RunningInstances = Arc::new(Mutex::new(running_instances));
+
+    // instances come and go from the admin API, and each one takes its thread down with it when it
+    // stops, so the http server is the only thing we wait on
+    let http_thread = thread::spawn(move || {
+        let mut runtime = Runtime::new().unwrap();
+        runtime.block_on(backward(http_server(running_instances)))
+               .unwrap();
+    });
+    http_thread.join().unwrap();
+}
 
-    threads.push(thread::spawn(move || {
-                     let mut runtime = Runtime::new().unwrap();
-                     runtime.block_on(backward(http_server(new_code_sender_by_instance_id)))
-                            .unwrap();
-                 }));
+// everything the http server needs to get at an instance that's running in its own thread
+struct InstanceHandle {
+    senders: InstanceSenders,
+    // the instance's thread stops when this is sent on, or dropped
+    shutdown: oneshot::Sender<()>,
+    // and this goes off once it's stopped and its connections are closed
+    stopped: oneshot::Receiver<()>,
+    token_generation: Arc<AtomicI32>,
+    // keyed by service config ID
+    service_statuses: HashMap<i32, SharedConnectionStatus>,
+}
 
-    for thread in threads {
-        thread.join().unwrap();
+impl InstanceHandle {
+    // waits for the thread to be done, so a new one for the same instance doesn't end up connected
+    // alongside it with the same nickname and token
+    async fn stop(self) {
+        self.shutdown.send(()).ok();
+        self.stopped.await.ok();
     }
 }
 
+type RunningInstances = Arc<Mutex<HashMap<i32, InstanceHandle>>>;
+
+fn start_instance(instance: &Instance, service_configs: Vec<ServiceConfig>) -> InstanceHandle {
+    // GHETTO: this is for sending worlds from the web interface into the interp
+    let (tx, rx) = mpsc::unbounded::<TheWorld>();
+    // and this is for handing webhook requests from the http server to the interp
+    let (webhook_tx, webhook_rx) = mpsc::unbounded::<WebhookCall>();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+    let token_generation = Arc::new(AtomicI32::new(instance.token_generation));
+    let service_statuses =
+        service_configs.iter()
+                       .map(|sc| (sc.id, Arc::new(Mutex::new(ConnectionStatus::Connecting))))
+                       .collect::<HashMap<_, _>>();
+
+    let instance_id = instance.id;
+    let thread_token_generation = Arc::clone(&token_generation);
+    let thread_service_statuses = service_statuses.clone();
+    thread::spawn(move || {
+        start_new_interpreter_instance_with_services(instance_id,
+                                                     thread_token_generation,
+                                                     &service_configs,
+                                                     &thread_service_statuses,
+                                                     rx,
+                                                     webhook_rx,
+                                                     shutdown_rx);
+        stopped_tx.send(()).ok();
+    });
+    InstanceHandle { senders: InstanceSenders { new_code: tx,
+                                                webhook: webhook_tx },
+                     shutdown: shutdown_tx,
+                     stopped: stopped_rx,
+                     token_generation,
+                     service_statuses }
+}
+
 fn start_new_interpreter_instance_with_services(instance_id: i32,
+                                                token_generation: Arc<AtomicI32>,
                                                 service_configs: &[ServiceConfig],
-                                                new_code_receiver: mpsc::UnboundedReceiver<TheWorld>)
-{
+                                                service_statuses: &HashMap<i32, SharedConnectionStatus>,
+                                                new_code_receiver: mpsc::UnboundedReceiver<TheWorld>,
+                                                webhook_receiver: mpsc::UnboundedReceiver<WebhookCall>,
+                                                shutdown_receiver: oneshot::Receiver<()>) {
+    logging::set_instance_id(instance_id);
     let mut runtime = Runtime::new().unwrap();
-    let chat_thingy = Rc::new(RefCell::new(ChatThingy::new(instance_id)));
+    let chat_thingy = Rc::new(RefCell::new(ChatThingy::new(instance_id, token_generation)));
 
     runtime.block_on(backward(load_code_from_the_db_into(Rc::clone(&chat_thingy), instance_id)))
            .unwrap();
     let mut futures = service_configs.iter()
                                      .map(|service_config| {
+                                         let status =
+                                             Arc::clone(&service_statuses[&service_config.id]);
                                          let b: Box<dyn OldFuture<Item = (), Error = ()>> =
                                              Box::new(backward(new_conn(service_config,
+                                                                        status,
                                                                         Rc::clone(&chat_thingy))));
                                          b
                                      })
                                      .collect_vec();
-    futures.push(Box::new(backward(receive_code(chat_thingy, new_code_receiver))));
-    let joined = join_all(futures);
-    runtime.block_on(joined).unwrap();
+    futures.push(Box::new(backward(receive_code(Rc::clone(&chat_thingy), new_code_receiver))));
+    futures.push(Box::new(backward(receive_webhooks(Rc::clone(&chat_thingy), webhook_receiver))));
+    futures.push(Box::new(backward(report_queue_metrics(Rc::clone(&chat_thingy)))));
+    futures.push(Box::new(backward(run_scheduled_programs(chat_thingy))));
+    let joined = forward(join_all(futures));
+    // dropping the runtime on the way out takes the connections down with it
+    runtime.block_on(backward(async move {
+                         select(joined, shutdown_receiver).await;
+                         Ok::<(), ()>(())
+                     }))
+           .unwrap();
+    logging::info("instance stopped", json!({}));
 }
 
 async fn receive_code(chat_thingy: Rc<RefCell<ChatThingy>>,
                       mut rx: mpsc::UnboundedReceiver<TheWorld>)
                       -> Result<(), ()> {
     while let Some(world) = rx.next().await {
-        chat_thingy.borrow_mut().load_world(&world);
+        chat_thingy.borrow_mut().swap_world(&world);
+    }
+    Ok::<(), ()>(())
+}
+
+// the route, the request, and where to send the response. the response is None if there's no
+// webhook program for that route
+type WebhookCall = (String, WebhookRequest, oneshot::Sender<Option<WebhookResponse>>);
+
+#[derive(Clone)]
+struct InstanceSenders {
+    new_code: mpsc::UnboundedSender<TheWorld>,
+    webhook: mpsc::UnboundedSender<WebhookCall>,
+}
+
+async fn receive_webhooks(chat_thingy: Rc<RefCell<ChatThingy>>,
+                          mut rx: mpsc::UnboundedReceiver<WebhookCall>)
+                          -> Result<(), ()> {
+    while let Some((route, request, response_sender)) = rx.next().await {
+        let response = chat_thingy.borrow().webhook_received(&route, request);
+        let respond = async move {
+            let response = match response {
+                Some(response) => Some(response.await),
+                None => None,
+            };
+            // if this fails, the http side already hung up, so there's nobody to tell
+            response_sender.send(response).ok();
+            Ok::<(), ()>(())
+        };
+        // each one runs on its own, so a slow program doesn't hold up the webhooks behind it
+        current_thread::spawn(backward(respond));
     }
     Ok::<(), ()>(())
 }
 
+async fn run_scheduled_programs(chat_thingy: Rc<RefCell<ChatThingy>>) -> Result<(), ()> {
+    let mut last_minute_run = None;
+    loop {
+        let now = unix_now();
+        // the timer can fire a hair early, so make sure we never run the same minute twice
+        if last_minute_run == Some(now / 60) {
+            forward(tokio_timer::sleep(Duration::from_secs(1))).await
+                                                               .unwrap();
+            continue;
+        }
+        last_minute_run = Some(now / 60);
+
+        // the borrow has to be let go of before awaiting, or swapping in a new world or connecting
+        // a service while the programs are running would panic
+        let tick = chat_thingy.borrow()
+                              .scheduled_tick(UtcTime::from_unix_secs(now as i64));
+        let chat_thingy_for_replies = Rc::clone(&chat_thingy);
+        let run_tick = async move {
+            for reply in tick.await {
+                chat_thingy_for_replies.borrow().send_scheduled_reply(reply);
+            }
+            Ok::<(), ()>(())
+        };
+        // runs on its own, so a run that takes longer than a minute doesn't make us miss the
+        // minutes after it
+        current_thread::spawn(backward(run_tick));
+
+        let until_next_minute = 60 - unix_now() % 60;
+        forward(tokio_timer::sleep(Duration::from_secs(until_next_minute))).await
+                                                                           .unwrap();
+    }
+}
+
+// so we can tell when an instance is getting more chat than it can keep up with
+async fn report_queue_metrics(chat_thingy: Rc<RefCell<ChatThingy>>) -> Result<(), ()> {
+    loop {
+        forward(tokio_timer::sleep(Duration::from_secs(60))).await
+                                                            .unwrap();
+        let metrics = chat_thingy.borrow().work_queue.metrics();
+        logging::info("queue metrics",
+                      json!({ "depth": metrics.depth,
+                              "capacity": metrics.capacity,
+                              "high_water_mark": metrics.high_water_mark,
+                              "handled": metrics.handled }));
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                                .unwrap()
+                                .as_secs()
+}
+
 async fn new_conn(service_config: &ServiceConfig,
+                  status: SharedConnectionStatus,
                   chat_thingy: Rc<RefCell<ChatThingy>>)
                   -> Result<(), ()> {
-    match service_config.service_type.as_str() {
-        "irc" => {
-            println!("new irc conn");
-            new_irc_conn(service_config.irc_config().unwrap(), chat_thingy).await
+    let service_type = &service_config.service_type;
+    logging::info("connecting", json!({ "service_type": service_type }));
+    let service = match build_service(service_type,
+                                      &service_config.nickname,
+                                      &service_config.config)
+    {
+        Ok(service) => service,
+        // the instance's other services keep going without it
+        Err(e) => {
+            logging::error("couldn't set up service",
+                           json!({ "service_type": service_type, "error": format!("{:?}", e) }));
+            *status.lock().unwrap() = ConnectionStatus::Failed;
+            return Err(());
         }
-        "discord" => {
-            println!("new discord conn");
-            new_discord_conn(service_config.discord_token().unwrap(), chat_thingy).await
-        }
-        "slack" => {
-            println!("new slack conn");
-            new_slack_conn(service_config.slack_token().unwrap(), chat_thingy).await
-        }
-        _ => panic!("unknown service type: {}", service_config.service_type),
-    }
+    };
+
+    let outbox = chat_thingy.borrow_mut().new_outbox(service_type);
+    let work_queue = Rc::clone(&chat_thingy.borrow().work_queue);
+    chat_service::run(service, outbox, work_queue, status, move |event, origin| {
+        chat_thingy.borrow().event_received(event, origin)
+    }).await
+      .map_err(|e| {
+          logging::error("service stopped",
+                         json!({ "service_type": service_type, "error": format!("{:?}", e) }))
+      })
 }
 
-#[derive(Serializeable, Deserializeable, Clone)]
-struct GenerateProgramBotUrl {
-    instance_id: i32,
+fn build_service(service_type: &str,
+                 nickname: &str,
+                 config: &serde_json::Value)
+                 -> chat_service::Result<Box<dyn ChatService>> {
+    ChatServiceRegistry::with_builtin_services().build(service_type, nickname, config)
 }
 
-impl GenerateProgramBotUrl {
-    fn new(instance_id: i32) -> Self {
-        Self { instance_id }
-    }
+// how many chat events an instance can have going at once, before it stops reading new ones
+fn work_queue_capacity() -> usize {
+    config::get("CHAT_QUEUE_CAPACITY").map(|capacity| {
+                                          capacity.parse()
+                                                  .expect("CHAT_QUEUE_CAPACITY must be an integer")
+                                      })
+                                      .unwrap_or(DEFAULT_WORK_QUEUE_CAPACITY)
+}
 
-    fn generate_url(&self) -> Result<url::Url, Box<dyn std::error::Error>> {
-        let token = NewCodeIntent::token(self.instance_id)?;
-        Ok(config::edit_code_url(&token)?)
-    }
+const DEFAULT_WORK_QUEUE_CAPACITY: usize = 64;
+
+// edit links are good for this long, unless whoever made them through the admin API said otherwise
+const DEFAULT_EDIT_TOKEN_TTL_SECS: u64 = 24 * 60 * 60;
+
+fn edit_url(token: &EditToken) -> Result<url::Url, Box<dyn std::error::Error>> {
+    Ok(config::edit_code_url(&encode_edit_token(token)?)?)
 }
 
 struct ChatThingy {
     interp: env::Interpreter,
-    reply_buffer: Arc<Mutex<Vec<String>>>,
     instance_id: i32,
+    // bumped by the admin API to make all of the old edit URLs stop working
+    token_generation: Arc<AtomicI32>,
+    // for sending messages that aren't replies to anything, like the output of scheduled programs.
+    // keyed by service type
+    outboxes: Vec<(String, mpsc::UnboundedSender<OutgoingMessage>)>,
+    // shared by all of this instance's services
+    work_queue: Rc<WorkQueue>,
 }
 
 impl ChatThingy {
-    pub fn new(instance_id: i32) -> Self {
-        let reply_buffer = Arc::new(Mutex::new(vec![]));
-        let interp = cs::init_interpreter();
-
-        let reply_function = ChatReply::new(Arc::clone(&reply_buffer));
-        interp.env.borrow_mut().add_function(reply_function);
-
-        Self { interp,
-               reply_buffer,
-               instance_id }
+    pub fn new(instance_id: i32, token_generation: Arc<AtomicI32>) -> Self {
+        Self { interp: cs::init_interpreter(),
+               instance_id,
+               token_generation,
+               outboxes: vec![],
+               work_queue: WorkQueue::new(work_queue_capacity()) }
     }
 
-    // TODO: this is duped from lib.rs
-    pub fn load_world(&self, world: &TheWorld) {
-        let mut env = self.interp.env.borrow_mut();
-        for function in &world.functions {
-            env.add_function_box(function.clone());
-        }
-        for typespec in &world.typespecs {
-            env.add_typespec_box(typespec.clone());
-        }
+    pub fn new_outbox(&mut self, service_type: &str) -> mpsc::UnboundedReceiver<OutgoingMessage> {
+        let (tx, rx) = mpsc::unbounded();
+        self.outboxes.push((service_type.to_owned(), tx));
+        rx
     }
 
-    pub fn message_received(&self,
-                            sender: String,
-                            text: String)
-                            -> Pin<Box<dyn std::future::Future<Output = ()>>> {
-        if text == "!letmeprogramyou" {
-            let program_url = GenerateProgramBotUrl::new(self.instance_id).generate_url()
-                                                                          .unwrap();
-            self.reply_buffer
-                .lock()
-                .unwrap()
-                .push(program_url.to_string());
-            return Box::pin(async { () });
+    pub fn send_scheduled_reply(&self, reply: ScheduledReply) {
+        let outboxes = self.outboxes
+                           .iter()
+                           .filter(|(service_type, _)| *service_type == reply.service)
+                           .collect_vec();
+        if outboxes.is_empty() {
+            logging::warn("no connection to send scheduled reply to",
+                          json!({ "service_type": reply.service, "channel": reply.channel }));
         }
-
-        message_received(&self.interp, sender, text)
-    }
-}
-
-async fn new_irc_conn(mut config: Config, chat_thingy: Rc<RefCell<ChatThingy>>) -> Result<(), ()> {
-    config.version = Some("cs: program me!".to_string());
-    config.alt_nicks =
-        Some((1..6).map(|n| {
-                       let underscores = std::iter::repeat("_").take(n).join("");
-                       format!("{}{}", config.nickname.as_ref().unwrap(), underscores)
-                   })
-                   .collect());
-    let irc_client_future = IrcClient::new_future(config).unwrap();
-    let PackedIrcClient(client, irc_future) = forward(irc_client_future).await.unwrap();
-
-    // must send cap_req before identify because identify sends cap_end
-    client.send_cap_req(&[Capability::Custom("twitch.tv/membership")])
-          .unwrap();
-    client.identify().unwrap();
-    let irc_future = backward(async move {
-        forward(irc_future).await
-                           .map_err(|e| println!("irc error: {:?}", e))
-                           .ok();
-        Ok::<(), ()>(())
-    });
-
-    forward(backward(irc_interaction_future(client, chat_thingy)).join(irc_future)).await
-                                                                                   .ok();
-    Ok::<(), ()>(())
-}
-
-async fn irc_interaction_future(client: IrcClient,
-                                chat_thingy: Rc<RefCell<ChatThingy>>)
-                                -> Result<(), ()> {
-    let mut stream = client.stream().compat();
-    while let Some(message) = stream.next().await {
-        if message.is_err() {
-            println!("there was an error: {:?}", message)
-        } else {
-            let message = message.unwrap();
-            println!("{:?}", message);
-            if let Command::PRIVMSG(sender, text) = &message.command {
-                if let Some(response_target) = message.response_target() {
-                    chat_thingy.borrow_mut()
-                               .message_received(sender.clone(), text.clone())
-                               .await;
-                    for reply in chat_thingy.borrow_mut()
-                                            .reply_buffer
-                                            .lock()
-                                            .unwrap()
-                                            .drain(..)
-                    {
-                        client.send_privmsg(response_target, &reply)
-                              .map_err(|err| println!("error sending msg: {:?}", err))
-                              .ok();
-                    }
-                }
-            }
+        for (_, outbox) in outboxes {
+            outbox.unbounded_send((ReplyOrigin::channel(reply.channel.clone()),
+                                   reply.message.clone()))
+                  .map_err(|e| {
+                      logging::error("error sending to outbox",
+                                     json!({ "error": format!("{:?}", e) }))
+                  })
+                  .ok();
         }
     }
-    Ok::<(), ()>(())
-}
 
-async fn new_discord_conn(token: &str, chat_thingy: Rc<RefCell<ChatThingy>>) -> Result<(), ()> {
-    'outer: loop {
-        let (client, stream) =
-            forward(noob::Client::connect(token)).await
-                                                 .unwrap_or_else(|e| {
-                                                     panic!("error connecting to discord: {:?}", e)
-                                                 });
-        let mut stream = stream.compat();
-        while let Some(event) = stream.next().await {
-            match event {
-                Ok(noob::Event::MessageCreate(msg)) => {
-                    chat_thingy.borrow_mut()
-                               .message_received(msg.author.username, msg.content)
-                               .await;
-
-                    for reply in chat_thingy.borrow_mut()
-                                            .reply_buffer
-                                            .lock()
-                                            .unwrap()
-                                            .drain(..)
-                    {
-                        forward(client.send_message(&noob::MessageBuilder::new(&reply), &msg.channel_id)).await
-                            .map_err(|e| println!("error sending discord message: {:?}", e)).ok();
-                    }
-                }
-                Err(e) => {
-                    println!("there was a discord error, and we must reconnect: {:?}", e);
-                    forward(tokio_timer::sleep(Duration::from_secs(1))).await
-                                                                       .unwrap();
-                    continue 'outer;
-                }
-                _ => (),
-            }
-        }
+    pub fn scheduled_tick(&self,
+                          time: UtcTime)
+                          -> Pin<Box<dyn std::future::Future<Output = Vec<ScheduledReply>>>> {
+        scheduled_program::tick(&self.interp, time)
     }
-    //Ok::<(), ()>(())
-}
-
-async fn new_slack_conn(token: &str, chat_thingy: Rc<RefCell<ChatThingy>>) -> Result<(), ()> {
-    use futures::future::{ok, FutureResult};
-    use slack::api::MessageStandard;
-    use slack::future::client::{Client, EventHandler};
-    use slack::{Event, Message};
-
-    struct MyHandler {
-        chat_thingy: Rc<RefCell<ChatThingy>>,
-    };
 
-    impl EventHandler for MyHandler {
-        type EventFut = Box<dyn OldFuture<Item = (), Error = ()>>;
-        type OnCloseFut = FutureResult<(), ()>;
-        type OnConnectFut = FutureResult<(), ()>;
-
-        fn on_event(&mut self, cli: &Client, event: Event) -> Self::EventFut {
-            // print out the event
-            // do something if it's a `Message::Standard`
-            if let Event::Message(ref message) = event {
-                if let Message::Standard(MessageStandard { ref channel,
-                                                           ref user,
-                                                           ref text,
-                                                           ts: _,
-                                                           .. }) = **message
-                {
-                    if let (Some(channel), Some(text), Some(user)) = (channel, text, user) {
-                        let msg_sender = cli.sender().clone();
-                        let sender = user.clone();
-                        let text = text.clone();
-                        let channel = channel.clone();
-
-                        let chat_thingy = Rc::clone(&self.chat_thingy);
-                        return Box::new(backward(async move {
-                                            chat_thingy.borrow_mut()
-                                                       .message_received(sender, text)
-                                                       .await;
-
-                                            for reply in chat_thingy.borrow_mut()
-                                                                    .reply_buffer
-                                                                    .lock()
-                                                                    .unwrap()
-                                                                    .drain(..)
-                                            {
-                                                msg_sender.send_message(&channel, &reply)
-                                          .map_err(|err| {
-                                              println!("error sending slack message: {:?}", err)
-                                          })
-                                          .ok();
-                                            }
-                                            Ok(())
-                                        }));
-                    }
-                }
+    // replaces the whole world with this one, leaving the connections alone. anything that's still
+    // running keeps going in the old interpreter until it's done, and everything that happens from
+    // here on out runs in the new one
+    pub fn swap_world(&mut self, world: &TheWorld) {
+        match cs::init_interpreter_with_world(world) {
+            Ok(interp) => {
+                self.interp = interp;
+                logging::info("swapped in a new world", json!({}));
             }
-            Box::new(ok(()))
+            Err(e) => logging::error("keeping the old world", json!({ "error": e.to_string() })),
         }
+    }
 
-        fn on_close(&mut self, _cli: &Client) -> Self::OnCloseFut {
-            println!("on_close");
-            ok(())
+    pub fn event_received(&self,
+                          event: ChatEvent,
+                          origin: ReplyOrigin)
+                          -> Pin<Box<dyn std::future::Future<Output = Vec<OutgoingChatMessage>>>>
+    {
+        if event.kind == ChatEventKind::Message && event.text == "!letmeprogramyou" {
+            let token = EditToken::new(self.instance_id,
+                                       self.token_generation.load(Ordering::SeqCst),
+                                       event.sender.clone(),
+                                       unix_now(),
+                                       DEFAULT_EDIT_TOKEN_TTL_SECS,
+                                       EditScope::Write { program_ids: None });
+            let program_url = edit_url(&token).unwrap();
+            // anyone who gets ahold of the link can change the bot's programs, so it only ever
+            // goes to the person who asked for it
+            let target = if event.is_direct_message {
+                ReplyTarget::Origin
+            } else {
+                ReplyTarget::DirectMessage(event.sender.clone())
+            };
+            let reply = OutgoingChatMessage { target,
+                                              content:
+                                                  MessageContent::Text(program_url.to_string()) };
+            return Box::pin(async { vec![reply] });
         }
 
-        fn on_connect(&mut self, _cli: &Client) -> Self::OnConnectFut {
-            println!("on_connect");
-            ok(())
-        }
+        event_received(&self.interp, event, origin)
     }
 
-    forward(Client::login_and_run(token, MyHandler { chat_thingy })).await
-        .map_err(|e| println!("slack error: {:?}", e)).ok();
-    Ok::<(), ()>(())
+    pub fn webhook_received(&self,
+                            route: &str,
+                            request: WebhookRequest)
+                            -> Option<Pin<Box<dyn std::future::Future<Output = WebhookResponse>>>>
+    {
+        request_received(&self.interp, route, request)
+    }
 }
 
 // database shit
-use diesel;
-use futures_cpupool::CpuPool;
-//use diesel::prelude::*;
 use cs::config;
-use cs::schema::{codes, service_configs};
-use diesel::query_dsl::RunQueryDsl;
-use diesel::r2d2;
-use diesel::{Insertable, Queryable};
+use cs::storage;
+use cs::storage::{
+    Instance, NewAuditEntry, NewCode, NewServiceConfig, ServiceConfig, Storage, StorageError,
+};
+use futures_cpupool::CpuPool;
 use lazy_static::lazy_static;
 
-pub type Conn = diesel::pg::PgConnection;
-pub type Pool = r2d2::Pool<r2d2::ConnectionManager<Conn>>;
-
 lazy_static! {
-    static ref DIESEL_CONN_POOL: Pool = connect();
+    static ref STORAGE: Box<dyn Storage> = storage::from_config().expect("couldn't open storage");
 }
 
-fn connect() -> Pool {
-    let db_url = config::get("DATABASE_URL").expect("couldn't find DATABASE_URL");
-    let manager = r2d2::ConnectionManager::<Conn>::new(db_url);
-    r2d2::Pool::builder().build(manager)
-                         .expect("Failed to create pool")
-}
-
-pub fn exec_async<T, E, F, R>(f: F) -> impl Future<Item = T, Error = E>
+// storage calls block, so they run on a thread pool
+pub fn exec_async<T, F>(f: F) -> impl Future<Item = T, Error = StorageError>
     where T: Send + 'static,
-          E: std::error::Error + Send + 'static,
-          F: FnOnce(&Conn) -> R + Send + 'static,
-          R: IntoFuture<Item = T, Error = E> + Send + 'static,
-          <R as IntoFuture>::Future: Send
+          F: FnOnce(&dyn Storage) -> Result<T, StorageError> + Send + 'static
 {
     lazy_static! {
         static ref THREAD_POOL: CpuPool = CpuPool::new_num_cpus();
     }
 
-    let pool = DIESEL_CONN_POOL.clone();
-    THREAD_POOL.spawn_fn(move || {
-                   pool.get()
-                       // TODO: this is still super fucked. we'll crash any time the insert fails lol
-                       .map_err(|_err| panic!("ugh fuck this thing"))
-                       .map(|conn| f(&conn))
-                       .into_future()
-                       .and_then(|f| f)
-               })
+    THREAD_POOL.spawn_fn(move || f(&**STORAGE))
 }
 
 use branca::Branca;
-use cs::chat_program::message_received;
+use cs::chat_format::{MessageContent, OutgoingChatMessage, ReplyTarget};
+use cs::chat_program::{event_received, ChatEvent, ChatEventKind};
+use cs::chat_service;
+use cs::chat_service::{
+    ChatService, ChatServiceRegistry, ConnectionStatus, OutgoingMessage, ReplyOrigin,
+    SharedConnectionStatus, WorkQueue,
+};
+use cs::cron::UtcTime;
+use cs::edit_token::{EditScope, EditToken};
+use cs::scheduled_program;
+use cs::scheduled_program::ScheduledReply;
+use cs::webhook_program::{request_received, WebhookRequest, WebhookResponse};
+use futures_channel::oneshot;
+use futures_util::future::select;
 use futures_util::stream::StreamExt;
 use http_fs::config::StaticFileConfig;
 use http_fs::StaticFiles;
 use hyper::service::Service;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 #[derive(Clone)]
@@ -452,135 +514,89 @@ pub fn serve_static(
     forward(static_files.call(req))
 }
 
-#[derive(Insertable)]
-#[table_name = "codes"]
-struct NewCode {
-    added_by: String,
-    code: serde_json::Value,
-    instance_id: i32,
-}
-
-#[derive(Queryable)]
-// for some reason, Queryable requires that we have all DB fields even if we don't use them
-#[allow(dead_code)]
-struct Code {
-    id: i32,
-    added_by: String,
-    code: serde_json::Value,
-    instance_id: i32,
-    created_at: std::time::SystemTime,
-    updated_at: std::time::SystemTime,
-}
-
-#[derive(Insertable, Serializeable, Deserializeable)]
-#[table_name = "service_configs"]
-struct NewServiceConfig {
-    instance_id: i32,
-    nickname: String,
-    service_type: String,
-    config: serde_json::Value,
-}
-
-#[derive(Queryable, Debug)]
-// for some reason, Queryable requires that we have all DB fields even if we don't use them
-#[allow(dead_code)]
-struct ServiceConfig {
-    id: i32,
-    nickname: String,
-    instance_id: i32,
-    service_type: String,
-    created_at: std::time::SystemTime,
-    updated_at: std::time::SystemTime,
-    config: serde_json::Value,
-}
-
-impl ServiceConfig {
-    pub fn discord_token(&self) -> Result<&str, Box<dyn std::error::Error>> {
-        Ok(self.config
-               .get("token")
-               .ok_or("discord token not found in config")?
-               .as_str()
-               .ok_or("discord token not a string")?)
-    }
-
-    pub fn slack_token(&self) -> Result<&str, Box<dyn std::error::Error>> {
-        Ok(self.config
-               .get("token")
-               .ok_or("slack token not found in config")?
-               .as_str()
-               .ok_or("slack token not a string")?)
-    }
-
-    pub fn irc_config(&self) -> Result<Config, Box<dyn std::error::Error>> {
-        Ok(serde_json::from_value(self.config.clone())?)
-    }
-}
-
 fn insert_new_code(
     code: &TheWorld,
-    instance_id: i32)
+    instance_id: i32,
+    added_by: String)
     -> impl OldFuture<Error = impl std::error::Error + std::fmt::Debug + 'static> {
-    use cs::schema::codes::dsl::codes;
-    let newcode = NewCode { added_by: "sumeet".to_string(),
-                            code: serde_json::to_value(code).unwrap(),
-                            instance_id };
-    exec_async(|conn| diesel::insert_into(codes).values(newcode).execute(conn))
+    let new_code = NewCode { added_by,
+                             code: serde_json::to_value(code).unwrap(),
+                             instance_id };
+    exec_async(move |storage| storage.insert_code(new_code))
 }
 
 fn insert_new_service_configs(
     configs: Vec<NewServiceConfig>)
     -> impl OldFuture<Error = impl std::error::Error + std::fmt::Debug + 'static> {
-    use cs::schema::service_configs::dsl::service_configs;
-    exec_async(move |conn| {
-        diesel::insert_into(service_configs).values(configs)
-                                            .execute(conn)
+    exec_async(move |storage| {
+        for config in configs {
+            storage.insert_service_config(config)?;
+        }
+        Ok(())
     })
 }
 
-async fn load_instances() -> Result<HashMap<i32, Vec<ServiceConfig>>, Box<dyn std::error::Error>> {
-    use crate::service_configs::dsl::*;
-
-    let all_service_configs =
-        forward(exec_async(|conn| service_configs.load::<ServiceConfig>(conn))).await?;
+type InstanceWithServiceConfigs = (Instance, Vec<ServiceConfig>);
+
+// every instance, along with its service configs
+async fn load_instances() -> Result<Vec<InstanceWithServiceConfigs>, Box<dyn std::error::Error>> {
+    let all_instances = forward(exec_async(|storage| storage.load_instances())).await?;
+    let all_service_configs = forward(exec_async(|storage| storage.load_service_configs())).await?;
+
+    let mut service_configs_by_instance_id =
+        all_service_configs.into_iter()
+                           .map(|service_config| (service_config.instance_id, service_config))
+                           .into_group_map();
+    Ok(all_instances.into_iter()
+                    .map(|instance| {
+                        let service_configs = service_configs_by_instance_id.remove(&instance.id)
+                                                                            .unwrap_or_default();
+                        (instance, service_configs)
+                    })
+                    .collect())
+}
 
-    Ok(all_service_configs.into_iter()
-                          .map(|service_config| (service_config.instance_id, service_config))
-                          .into_group_map())
+async fn load_instance(for_instance_id: i32)
+                       -> Result<InstanceWithServiceConfigs, Box<dyn std::error::Error>> {
+    let instance =
+        forward(exec_async(move |storage| storage.load_instance(for_instance_id))).await?;
+    let instance_service_configs =
+        forward(exec_async(move |storage| {
+                    storage.load_service_configs_for_instance(for_instance_id)
+                })).await?;
+    Ok((instance, instance_service_configs))
 }
 
 async fn load_code_from_the_db_into(chat_thingy: Rc<RefCell<ChatThingy>>,
                                     for_instance_id: i32)
                                     -> Result<(), ()> {
-    use crate::codes::dsl::*;
-
-    let code_rows = forward(exec_async(move |conn| {
-                                codes.filter(instance_id.eq(for_instance_id))
-                                     .load::<Code>(conn)
-                            })).await
-                               .unwrap();
-    for code_row in code_rows {
-        let the_world = serde_json::from_value(code_row.code);
-        match the_world {
-            Ok(ref the_world) => {
-                println!("loading smth from the world");
-                chat_thingy.borrow().load_world(the_world);
-            }
-            Err(e) => println!("error deserializing world: {:?}", e),
-        }
+    match load_latest_world(for_instance_id).await {
+        Ok(Some(ref the_world)) => chat_thingy.borrow_mut().swap_world(the_world),
+        Ok(None) => (),
+        Err(e) => logging::error("error loading world", json!({ "error": e.to_string() })),
     }
-    println!("done loading from db");
+    logging::debug("done loading from storage", json!({}));
     Ok::<(), ()>(())
 }
 
-async fn http_server(new_code_sender_by_instance_id: HashMap<i32,
-                             mpsc::UnboundedSender<TheWorld>>)
-                     -> Result<(), ()> {
+// every row is a whole world, so only the newest one counts
+async fn load_latest_world(for_instance_id: i32)
+                           -> Result<Option<TheWorld>, Box<dyn std::error::Error>> {
+    let code_row =
+        forward(exec_async(move |storage| storage.load_latest_code(for_instance_id))).await?;
+    match code_row {
+        Some(code_row) => Ok(Some(serde_json::from_value(code_row.code)?)),
+        None => Ok(None),
+    }
+}
+
+async fn http_server(running_instances: RunningInstances) -> Result<(), ()> {
     let port = config::get("PORT").expect("PORT envvar not set")
                                   .parse()
                                   .expect("PORT must be an integer");
     forward(Server::bind(&([0, 0, 0, 0], port).into())
         .executor(tokio::runtime::current_thread::TaskExecutor::current())
-        .serve(move || service_fn(http_handler(new_code_sender_by_instance_id.clone())))).await.unwrap();
+        .serve(move || service_fn(http_handler(Arc::clone(&running_instances))))).await.unwrap();
     Ok::<(), ()>(())
 }
 
@@ -590,45 +606,69 @@ async fn deserialize<T>(req: Request<Body>)
 {
     let (parts, body) = req.into_parts();
     let body = forward(body.concat2()).await?;
-    println!("{}", std::str::from_utf8(&body).as_ref().unwrap());
+    logging::debug("request body",
+                   json!({ "path": parts.uri.path(), "body": String::from_utf8_lossy(&body) }));
     let body = serde_json::from_slice(&body)?;
     Ok(Request::from_parts(parts, body))
 }
 
 fn http_handler(
-    new_code_sender_by_instance_id: HashMap<i32, mpsc::UnboundedSender<TheWorld>>)
+    running_instances: RunningInstances)
     -> impl Fn(Request<Body>) -> Box<dyn OldFuture<Item = Response<Body>, Error = hyper::Error>> {
     move |request| {
         let uri = request.uri();
-        let new_code_intent = extract_intent(uri);
-        let new_code_sender =
-            new_code_intent.as_ref().and_then(|intent| {
-                                        new_code_sender_by_instance_id.get(&intent.instance_id)
-                                    });
-        if uri.path() == "/postthecode" && new_code_sender.is_some() {
-            let mut new_code_sender = new_code_sender.unwrap().clone();
-            let new_code_intent = new_code_intent.unwrap();
-            Box::new(backward(async move {
-                         let body = deserialize::<TheWorld>(request).await;
-                         if let Err(e) = body {
-                             println!("error: {:?}", e);
-                             return Ok(validation_error("ur world sucked"));
-                         }
+        if uri.path() == "/metrics" {
+            return Box::new(backward(async move { Ok(handle_metrics(request)) }));
+        }
 
-                         let the_world = body.unwrap().into_body();
-                         forward(insert_new_code(&the_world, new_code_intent.instance_id)).await
-                                                                                          .unwrap();
+        if uri.path().starts_with("/admin/") {
+            let running_instances = Arc::clone(&running_instances);
+            return Box::new(backward(async move {
+                                Ok(handle_admin(running_instances, request).await)
+                            }));
+        }
 
-                         use futures_util::sink::SinkExt;
-                         new_code_sender.send(the_world).await.unwrap();
+        if let Some((instance_id, route)) = extract_webhook_route(uri) {
+            let webhook_sender = running_instances.lock()
+                                                  .unwrap()
+                                                  .get(&instance_id)
+                                                  .map(|instance| instance.senders.webhook.clone());
+            return Box::new(backward(async move {
+                                let webhook_sender = match webhook_sender {
+                                    Some(webhook_sender) => webhook_sender,
+                                    None => return Ok(not_found()),
+                                };
+                                Ok(handle_webhook(webhook_sender, route, request).await)
+                            }));
+        }
 
-                         Ok(Response::new(Body::from("던지다")))
-                     }))
+        if uri.path() == "/postthecode" || uri.path() == "/tokeninfo" || uri.path() == "/thecode" {
+            let edit_token = extract_edit_token(uri);
+            let running_instances = Arc::clone(&running_instances);
+            return Box::new(backward(async move {
+                                let edit_token = match edit_token {
+                                    Some(edit_token) => edit_token,
+                                    None => return Ok(forbidden("that edit link is no good")),
+                                };
+                                match request.uri().path() {
+                                    "/tokeninfo" => {
+                                        Ok(token_info(&running_instances, &edit_token).await)
+                                    }
+                                    "/thecode" => {
+                                        Ok(the_code(&running_instances, &edit_token).await)
+                                    }
+                                    _ => Ok(post_the_code(&running_instances,
+                                                          &edit_token,
+                                                          request).await),
+                                }
+                            }));
         } else {
             Box::new(backward(async move {
                          // oh jesus christ, the unimplemented
                          serve_static(request).await.map_err(|e| {
-                                                        println!("{:?}", e);
+                                                        let error = format!("{:?}", e);
+                                                        logging::error("error serving static file",
+                                                                       json!({ "error": error }));
                                                         unimplemented!()
                                                     })
                      }))
@@ -636,25 +676,225 @@ fn http_handler(
     }
 }
 
-fn extract_intent(uri: &http::Uri) -> Option<NewCodeIntent> {
-    Some(NewCodeIntent::decode(uri.query()?).ok()?)
+async fn handle_webhook(mut webhook_sender: mpsc::UnboundedSender<WebhookCall>,
+                        route: String,
+                        request: Request<Body>)
+                        -> Response<Body> {
+    let (parts, body) = request.into_parts();
+    let body = match forward(body.concat2()).await {
+        Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+        Err(e) => {
+            logging::warn("error reading webhook body",
+                          json!({ "error": e.to_string() }));
+            return validation_error("couldn't read the request body");
+        }
+    };
+    let query = parts.uri
+                     .query()
+                     .map(|query| {
+                         url::form_urlencoded::parse(query.as_bytes()).into_owned()
+                                                                      .collect_vec()
+                     })
+                     .unwrap_or_else(Vec::new);
+    let headers = parts.headers
+                       .iter()
+                       .filter_map(|(name, value)| {
+                           Some((name.as_str().to_lowercase(), value.to_str().ok()?.to_owned()))
+                       })
+                       .collect_vec();
+    let webhook_request = WebhookRequest { method: parts.method.to_string(),
+                                           path: parts.uri.path().to_owned(),
+                                           query,
+                                           headers,
+                                           body };
+
+    let (response_sender, response_receiver) = oneshot::channel();
+    use futures_util::sink::SinkExt;
+    // the instance's thread is gone if this fails, so there's nothing to handle the webhook
+    let sent = webhook_sender.send((route, webhook_request, response_sender))
+                             .await;
+    if sent.is_err() {
+        return Response::builder().status(503)
+                                  .body("the instance isn't running".into())
+                                  .unwrap();
+    }
+    match response_receiver.await {
+        Ok(Some(response)) => Response::builder().status(response.status_code)
+                                                 .body(response.body.into())
+                                                 .unwrap(),
+        Ok(None) => not_found(),
+        Err(_) => Response::builder().status(500)
+                                     .body("the interpreter went away".into())
+                                     .unwrap(),
+    }
 }
 
-fn validation_error(str: &'static str) -> Response<Body> {
-    Response::builder().status(400).body(str.into()).unwrap()
+// webhooks live at /hooks/<instance id>/<route>
+fn extract_webhook_route(uri: &http::Uri) -> Option<(i32, String)> {
+    let mut segments = uri.path().trim_start_matches('/').splitn(3, '/');
+    if segments.next()? != "hooks" {
+        return None;
+    }
+    let instance_id = segments.next()?.parse().ok()?;
+    let route = segments.next().unwrap_or("").to_owned();
+    Some((instance_id, route))
 }
 
-#[derive(Serializeable, Deserializeable)]
-struct NewCodeIntent {
-    instance_id: i32,
+async fn post_the_code(running_instances: &RunningInstances,
+                       edit_token: &EditToken,
+                       request: Request<Body>)
+                       -> Response<Body> {
+    let mut new_code_sender = match check_edit_token(running_instances, edit_token).await {
+        Ok(new_code_sender) => new_code_sender,
+        Err(problem) => return forbidden(&problem),
+    };
+
+    let body = deserialize::<TheWorld>(request).await;
+    if let Err(e) = body {
+        logging::warn("couldn't read posted world",
+                      json!({ "instance_id": edit_token.instance_id, "error": e.to_string() }));
+        return validation_error("ur world sucked");
+    }
+    let the_world = body.unwrap().into_body();
+
+    let current_world = match load_latest_world(edit_token.instance_id).await {
+        Ok(current_world) => current_world.unwrap_or_default(),
+        Err(e) => {
+            logging::error("error loading world",
+                           json!({ "instance_id": edit_token.instance_id,
+                                   "error": e.to_string() }));
+            return Response::builder().status(500)
+                                      .body("couldn't load the current world".into())
+                                      .unwrap();
+        }
+    };
+    if let Err(problem) = edit_token.check_world_change(&current_world, &the_world) {
+        return forbidden(&problem);
+    }
+    // check it over here, so the editor hears about it if it's no good
+    if let Err(e) = cs::init_interpreter_with_world(&the_world) {
+        return Response::builder().status(400)
+                                  .body(e.to_string().into())
+                                  .unwrap();
+    }
+
+    forward(insert_new_code(&the_world,
+                            edit_token.instance_id,
+                            edit_token.issued_by.clone())).await
+                                                          .unwrap();
+    let audit_entries = audit_entries(edit_token, &current_world, &the_world);
+    if let Err(e) =
+        forward(exec_async(move |storage| storage.insert_audit_entries(audit_entries))).await
+    {
+        logging::error("couldn't write to the audit log",
+                       json!({ "instance_id": edit_token.instance_id, "error": e.to_string() }));
+    }
+    use futures_util::sink::SinkExt;
+    new_code_sender.send(the_world).await.unwrap();
+
+    Response::new(Body::from("던지다"))
+}
+
+// one entry for every function and typespec the new world changes
+fn audit_entries(edit_token: &EditToken,
+                 current_world: &TheWorld,
+                 new_world: &TheWorld)
+                 -> Vec<NewAuditEntry> {
+    let changed_at = unix_now() as i64;
+    let to_audit_entry =
+        |change: WorldChange| NewAuditEntry { instance_id: edit_token.instance_id,
+                                              program_id: change.id.to_string(),
+                                              program_name: change.name,
+                                              change: change.kind.as_str().to_owned(),
+                                              changed_by: edit_token.issued_by.clone(),
+                                              edit_token_id: edit_token.id.to_string(),
+                                              changed_at };
+    world_changes(current_world, new_world).into_iter()
+                                           .map(to_audit_entry)
+                                           .collect()
+}
+
+// so the editor can show what its edit link lets it do, and why it doesn't work if it doesn't
+async fn token_info(running_instances: &RunningInstances,
+                    edit_token: &EditToken)
+                    -> Response<Body> {
+    let problem = check_edit_token(running_instances, edit_token).await.err();
+    let info = edit_token.info(unix_now(), problem);
+    Response::builder().header("Content-Type", "application/json")
+                       .body(serde_json::to_string(&info).unwrap().into())
+                       .unwrap()
 }
 
-impl NewCodeIntent {
-    fn token(instance_id: i32) -> Result<String, Box<dyn std::error::Error>> {
-        Ok(Self { instance_id }.encode()?)
+// what's running right now, so the editor can show what an upload would change
+async fn the_code(running_instances: &RunningInstances, edit_token: &EditToken) -> Response<Body> {
+    if let Err(problem) = check_edit_token(running_instances, edit_token).await {
+        return forbidden(&problem);
+    }
+    match load_latest_world(edit_token.instance_id).await {
+        Ok(world) => {
+            let world = serde_json::to_string(&world.unwrap_or_default()).unwrap();
+            Response::builder().header("Content-Type", "application/json")
+                               .body(world.into())
+                               .unwrap()
+        }
+        Err(e) => {
+            logging::error("error loading world",
+                           json!({ "instance_id": edit_token.instance_id,
+                                   "error": e.to_string() }));
+            Response::builder().status(500)
+                               .body("couldn't load the current world".into())
+                               .unwrap()
+        }
     }
 }
 
+// returns where to send new code for the token's instance, or why the token can't be used
+async fn check_edit_token(running_instances: &RunningInstances,
+                          edit_token: &EditToken)
+                          -> Result<mpsc::UnboundedSender<TheWorld>, String> {
+    if edit_token.is_expired(unix_now()) {
+        return Err("this edit link expired. ask the bot for a new one".to_owned());
+    }
+    match is_edit_token_revoked(edit_token).await {
+        Ok(false) => (),
+        Ok(true) => return Err("this edit link was revoked".to_owned()),
+        Err(e) => return Err(format!("couldn't check if this edit link was revoked: {}", e)),
+    }
+    let running_instances = running_instances.lock().unwrap();
+    let instance = running_instances.get(&edit_token.instance_id)
+                                    .ok_or("this instance isn't running")?;
+    // tokens from before the instance's last token rotation don't get you anywhere
+    if instance.token_generation.load(Ordering::SeqCst) != edit_token.token_generation {
+        return Err("this edit link was replaced by a newer one".to_owned());
+    }
+    Ok(instance.senders.new_code.clone())
+}
+
+async fn is_edit_token_revoked(edit_token: &EditToken) -> Result<bool, Box<dyn std::error::Error>> {
+    let token_id = edit_token.id.to_string();
+    Ok(forward(exec_async(move |storage| storage.is_edit_token_revoked(token_id))).await?)
+}
+
+fn forbidden(reason: &str) -> Response<Body> {
+    Response::builder().status(403)
+                       .body(reason.to_owned().into())
+                       .unwrap()
+}
+
+fn not_found() -> Response<Body> {
+    Response::builder().status(404)
+                       .body("not found".into())
+                       .unwrap()
+}
+
+fn extract_edit_token(uri: &http::Uri) -> Option<EditToken> {
+    Some(decode_edit_token(uri.query()?).ok()?)
+}
+
+fn validation_error(str: &'static str) -> Response<Body> {
+    Response::builder().status(400).body(str.into()).unwrap()
+}
+
 lazy_static! {
     static ref SIGNING_TOKEN: Branca = {
         let signing_secret = config::get("SIGNING_SECRET").expect("SIGNING_SECRET");
@@ -662,12 +902,345 @@ lazy_static! {
     };
 }
 
-impl NewCodeIntent {
-    fn encode(&self) -> Result<String, Box<dyn std::error::Error>> {
-        Ok(SIGNING_TOKEN.encode(&serde_json::to_string(self)?)?)
+fn encode_edit_token(edit_token: &EditToken) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(SIGNING_TOKEN.encode(&serde_json::to_string(edit_token)?)?)
+}
+
+// branca gets a TTL of 0 (don't check), because every token has its own expiration inside of it.
+// tokens from before that, which only had an instance ID, don't decode anymore
+fn decode_edit_token(str: &str) -> Result<EditToken, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_str(&SIGNING_TOKEN.decode(str, 0)?)?)
+}
+
+// prometheus metrics, from the metrics module. if METRICS_TOKEN is set, scrapers need
+// `Authorization: Bearer <METRICS_TOKEN>`
+fn handle_metrics(request: Request<Body>) -> Response<Body> {
+    if let Some(metrics_token) = config::get("METRICS_TOKEN") {
+        if !has_bearer_token(&request, &metrics_token) {
+            return Response::builder().status(401)
+                                      .body("unauthorized".into())
+                                      .unwrap();
+        }
     }
+    Response::builder().header("Content-Type", "text/plain; version=0.0.4")
+                       .body(metrics::render().into())
+                       .unwrap()
+}
 
-    fn decode(str: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(serde_json::from_str(&SIGNING_TOKEN.decode(str, 0)?)?)
+// whether the request has `Authorization: Bearer <token>`. the comparison takes just as long no
+// matter how much of the token was right, so it can't be guessed a byte at a time
+fn has_bearer_token(request: &Request<Body>, token: &str) -> bool {
+    let authorization = match request.headers().get("Authorization") {
+        Some(authorization) => authorization.as_bytes(),
+        None => return false,
+    };
+    let expected = format!("Bearer {}", token);
+    let expected = expected.as_bytes();
+    if authorization.len() != expected.len() {
+        return false;
     }
+    authorization.iter()
+                 .zip(expected)
+                 .fold(0, |difference, (a, b)| difference | (a ^ b))
+    == 0
 }
+
+// admin API: everything under /admin/ needs `Authorization: Bearer <ADMIN_TOKEN>`, and the whole
+// thing is turned off if ADMIN_TOKEN isn't set. `irctest admin` is the CLI for it
+type AdminResult = Result<serde_json::Value, Box<dyn std::error::Error>>;
+
+async fn handle_admin(running_instances: RunningInstances,
+                      request: Request<Body>)
+                      -> Response<Body> {
+    let admin_token = match config::get("ADMIN_TOKEN") {
+        Some(admin_token) => admin_token,
+        None => return not_found(),
+    };
+    if !has_bearer_token(&request, &admin_token) {
+        return Response::builder().status(401)
+                                  .body("unauthorized".into())
+                                  .unwrap();
+    }
+
+    let method = request.method().to_string();
+    let path = request.uri()
+                      .path()
+                      .trim_start_matches("/admin/")
+                      .to_owned();
+    let segments = path.trim_end_matches('/').split('/').collect_vec();
+    let result = match (method.as_str(), segments.as_slice()) {
+        ("GET", ["instances"]) => admin_list_instances(&running_instances).await,
+        ("POST", ["instances"]) => admin_create_instance(&running_instances).await,
+        ("GET", ["instances", id]) => admin_show_instance(&running_instances, id).await,
+        ("POST", ["instances", id, "disable"]) => {
+            admin_set_disabled(&running_instances, id, true).await
+        }
+        ("POST", ["instances", id, "enable"]) => {
+            admin_set_disabled(&running_instances, id, false).await
+        }
+        ("POST", ["instances", id, "restart"]) => admin_restart(&running_instances, id).await,
+        ("POST", ["instances", id, "rotate_token"]) => {
+            admin_rotate_token(&running_instances, id).await
+        }
+        ("POST", ["service_configs"]) => {
+            admin_add_service_config(&running_instances, request).await
+        }
+        ("DELETE", ["service_configs", id]) => {
+            admin_remove_service_config(&running_instances, id).await
+        }
+        ("POST", ["instances", id, "tokens"]) => admin_issue_token(id, request).await,
+        ("GET", ["instances", id, "audit_log"]) => admin_audit_log(id).await,
+        ("POST", ["tokens", id, "revoke"]) => admin_revoke_token(id).await,
+        _ => return not_found(),
+    };
+    match result {
+        Ok(json) => Response::builder().header("Content-Type", "application/json")
+                                       .body(json.to_string().into())
+                                       .unwrap(),
+        Err(e) => Response::builder().status(400)
+                                     .body(e.to_string().into())
+                                     .unwrap(),
+    }
+}
+
+fn parse_id(id: &str) -> Result<i32, Box<dyn std::error::Error>> {
+    Ok(id.parse().map_err(|_| format!("{} isn't an ID", id))?)
+}
+
+fn instance_json(running_instances: &RunningInstances,
+                 instance: &Instance,
+                 service_configs: &[ServiceConfig])
+                 -> serde_json::Value {
+    let running_instances = running_instances.lock().unwrap();
+    let running_instance = running_instances.get(&instance.id);
+    let services = service_configs.iter()
+                                  .map(|sc| {
+                                      let status = running_instance.and_then(|running_instance| {
+                                                       running_instance.service_statuses.get(&sc.id)
+                                                   })
+                                                   .map(|status| *status.lock().unwrap())
+                                                   .unwrap_or(ConnectionStatus::Stopped);
+                                      json!({ "id": sc.id,
+                                              "nickname": sc.nickname,
+                                              "service_type": sc.service_type,
+                                              "status": status })
+                                  })
+                                  .collect_vec();
+    json!({ "id": instance.id,
+            "disabled": instance.disabled,
+            "running": running_instance.is_some(),
+            "services": services })
+}
+
+async fn admin_list_instances(running_instances: &RunningInstances) -> AdminResult {
+    let instances = load_instances().await?;
+    Ok(instances.iter()
+                .map(|(instance, service_configs)| {
+                    instance_json(running_instances, instance, service_configs)
+                })
+                .collect())
+}
+
+async fn admin_show_instance(running_instances: &RunningInstances, id: &str) -> AdminResult {
+    let (instance, service_configs) = load_instance(parse_id(id)?).await?;
+    Ok(instance_json(running_instances,
+                     &instance,
+                     &service_configs))
+}
+
+async fn admin_create_instance(running_instances: &RunningInstances) -> AdminResult {
+    let instance = forward(exec_async(|storage| storage.create_instance())).await?;
+    let url = edit_url(&admin_edit_token(&instance))?;
+    running_instances.lock()
+                     .unwrap()
+                     .insert(instance.id, start_instance(&instance, vec![]));
+    Ok(json!({ "id": instance.id, "edit_url": url.to_string() }))
+}
+
+async fn admin_set_disabled(running_instances: &RunningInstances,
+                            id: &str,
+                            set_disabled: bool)
+                            -> AdminResult {
+    let for_instance_id = parse_id(id)?;
+    forward(exec_async(move |storage| {
+                storage.set_instance_disabled(for_instance_id, set_disabled)
+            })).await?;
+    restart_instance(running_instances, for_instance_id).await
+}
+
+async fn admin_restart(running_instances: &RunningInstances, id: &str) -> AdminResult {
+    restart_instance(running_instances, parse_id(id)?).await
+}
+
+// stops the instance's thread if it's running, and starts up a new one with whatever's in the DB,
+// unless it's been disabled
+async fn restart_instance(running_instances: &RunningInstances, instance_id: i32) -> AdminResult {
+    let (instance, service_configs) = load_instance(instance_id).await?;
+    let old_handle = running_instances.lock().unwrap().remove(&instance_id);
+    if let Some(old_handle) = old_handle {
+        old_handle.stop().await;
+    }
+    if !instance.disabled {
+        let handle = start_instance(&instance, service_configs);
+        running_instances.lock()
+                         .unwrap()
+                         .insert(instance_id, handle);
+    }
+    admin_show_instance(running_instances, &instance_id.to_string()).await
+}
+
+async fn admin_rotate_token(running_instances: &RunningInstances, id: &str) -> AdminResult {
+    let for_instance_id = parse_id(id)?;
+    let instance =
+        forward(exec_async(move |storage| storage.bump_token_generation(for_instance_id))).await?;
+    if let Some(running_instance) = running_instances.lock().unwrap().get(&instance.id) {
+        running_instance.token_generation
+                        .store(instance.token_generation, Ordering::SeqCst);
+    }
+    let url = edit_url(&admin_edit_token(&instance))?;
+    Ok(json!({ "id": instance.id, "edit_url": url.to_string() }))
+}
+
+fn admin_edit_token(instance: &Instance) -> EditToken {
+    EditToken::new(instance.id,
+                   instance.token_generation,
+                   "admin".to_owned(),
+                   unix_now(),
+                   DEFAULT_EDIT_TOKEN_TTL_SECS,
+                   EditScope::Write { program_ids: None })
+}
+
+#[derive(Deserializeable)]
+struct NewEditTokenRequest {
+    issued_by: String,
+    ttl_secs: Option<u64>,
+    #[serde(default)]
+    read_only: bool,
+    // if these are here, the token can only change these programs
+    program_ids: Option<Vec<cs::lang::ID>>,
+}
+
+async fn admin_issue_token(id: &str, request: Request<Body>) -> AdminResult {
+    let (instance, _) = load_instance(parse_id(id)?).await?;
+    let token_request = deserialize::<NewEditTokenRequest>(request).await?
+                                                                   .into_body();
+    let scope = if token_request.read_only {
+        EditScope::ReadOnly
+    } else {
+        EditScope::Write { program_ids: token_request.program_ids }
+    };
+    let token = EditToken::new(instance.id,
+                               instance.token_generation,
+                               token_request.issued_by,
+                               unix_now(),
+                               token_request.ttl_secs
+                                            .unwrap_or(DEFAULT_EDIT_TOKEN_TTL_SECS),
+                               scope);
+    let url = edit_url(&token)?;
+    Ok(json!({ "token_id": token.id, "expires_at": token.expires_at, "edit_url": url.to_string() }))
+}
+
+async fn admin_audit_log(id: &str) -> AdminResult {
+    let for_instance_id = parse_id(id)?;
+    let audit_log =
+        forward(exec_async(move |storage| storage.load_audit_log(for_instance_id))).await?;
+    Ok(serde_json::to_value(audit_log)?)
+}
+
+async fn admin_revoke_token(id: &str) -> AdminResult {
+    let revoked_token_id = cs::lang::ID::parse_str(id)?.to_string();
+    forward(exec_async(move |storage| storage.revoke_edit_token(revoked_token_id))).await?;
+    Ok(json!({ "revoked": id }))
+}
+
+async fn admin_add_service_config(running_instances: &RunningInstances,
+                                  request: Request<Body>)
+                                  -> AdminResult {
+    let new_service_config = deserialize::<NewServiceConfig>(request).await?.into_body();
+    // a config that doesn't work gets turned away here, instead of being saved and then breaking
+    // the instance every time it starts
+    let NewServiceConfig { service_type,
+                           nickname,
+                           config,
+                           .. } = &new_service_config;
+    if let Err(e) = build_service(service_type, nickname, config) {
+        return Err(format!("couldn't set up {} service: {}", service_type, e).into());
+    }
+    let service_config = forward(exec_async(move |storage| {
+                                     storage.insert_service_config(new_service_config)
+                                 })).await?;
+    // the new service only gets connected when the instance starts back up
+    restart_instance(running_instances, service_config.instance_id).await?;
+    Ok(json!({ "id": service_config.id }))
+}
+
+async fn admin_remove_service_config(running_instances: &RunningInstances,
+                                     id: &str)
+                                     -> AdminResult {
+    let service_config_id = parse_id(id)?;
+    let service_config =
+        forward(exec_async(move |storage| storage.delete_service_config(service_config_id))).await?;
+    restart_instance(running_instances, service_config.instance_id).await
+}
+
+// the CLI side of the admin API. talks to the server at SERVER_LISTEN_URL
+async fn run_admin_command(command: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let command = command.iter().map(|arg| arg.as_str()).collect_vec();
+    let (method, path, body) = match command.as_slice() {
+        ["list_instances"] => ("GET", "instances".to_owned(), "".to_owned()),
+        ["create_instance"] => ("POST", "instances".to_owned(), "".to_owned()),
+        ["show_instance", id] => ("GET", format!("instances/{}", id), "".to_owned()),
+        ["disable_instance", id] => ("POST", format!("instances/{}/disable", id), "".to_owned()),
+        ["enable_instance", id] => ("POST", format!("instances/{}/enable", id), "".to_owned()),
+        ["restart_instance", id] => ("POST", format!("instances/{}/restart", id), "".to_owned()),
+        ["rotate_token", id] => ("POST", format!("instances/{}/rotate_token", id), "".to_owned()),
+        // the file is a single service config, like the ones load_service_configs takes
+        ["add_service_config", filename] => {
+            ("POST", "service_configs".to_owned(), std::fs::read_to_string(filename)?)
+        }
+        ["remove_service_config", id] => {
+            ("DELETE", format!("service_configs/{}", id), "".to_owned())
+        }
+        // the file looks like {"issued_by": "...", "ttl_secs": 3600, "read_only": false,
+        // "program_ids": ["..."]}, and everything besides issued_by is optional
+        ["issue_token", id, filename] => {
+            ("POST", format!("instances/{}/tokens", id), std::fs::read_to_string(filename)?)
+        }
+        ["revoke_token", token_id] => {
+            ("POST", format!("tokens/{}/revoke", token_id), "".to_owned())
+        }
+        ["audit_log", id] => ("GET", format!("instances/{}/audit_log", id), "".to_owned()),
+        _ => return Err(ADMIN_USAGE.into()),
+    };
+
+    let url = config::server_listen_url()?.join("/admin/")?.join(&path)?;
+    let admin_token = config::get_or_err("ADMIN_TOKEN")?;
+    let request =
+        http::Request::builder().method(method)
+                                .uri(url.as_str())
+                                .header("Authorization", format!("Bearer {}", admin_token))
+                                .header("Content-Type", "application/json")
+                                .body(body)?;
+    let response = cs::http_client::fetch(request).await?;
+    if !response.status().is_success() {
+        return Err(format!("{}: {}", response.status(), response.body()).into());
+    }
+    println!("{}", response.body());
+    Ok(())
+}
+
+const ADMIN_USAGE: &str = "usage: irctest admin <command>
+
+commands:
+    list_instances
+    create_instance
+    show_instance <instance id>
+    disable_instance <instance id>
+    enable_instance <instance id>
+    restart_instance <instance id>
+    rotate_token <instance id>
+    add_service_config <service config.json>
+    remove_service_config <service config id>
+    issue_token <instance id> <token request.json>
+    revoke_token <token id>
+    audit_log <instance id>";
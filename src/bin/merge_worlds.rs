@@ -0,0 +1,67 @@
+// a git merge driver for world files, which merges them by ID instead of line by line. to use it:
+//
+//   .gitattributes
+//     codesample.json merge=cs-world
+//
+//   .git/config
+//     [merge "cs-world"]
+//         name = merge CodeMaestro worlds by ID
+//         driver = merge_worlds %O %A %B
+//
+// git hands us the common ancestor, our version and their version, and takes whatever we leave in
+// our version as the result. if anything conflicts, our version gets replaced with a pending merge
+// instead, which the editor asks about resolving when the file's opened, and we exit with 1 so git
+// knows the file still needs work
+extern crate cs;
+
+use cs::world_merge;
+use cs::world_merge::PendingMerge;
+use std::collections::HashMap;
+use std::process::exit;
+
+fn main() {
+    let filenames = std::env::args().skip(1).collect::<Vec<_>>();
+    if filenames.len() != 3 {
+        eprintln!("usage: merge_worlds BASE OURS THEIRS");
+        exit(2);
+    }
+    let base = read_world(&filenames[0]);
+    let ours = read_world(&filenames[1]);
+    let theirs = read_world(&filenames[2]);
+
+    let merge = world_merge::merge(&base, &ours, &theirs, &HashMap::new());
+    if merge.conflicts.is_empty() {
+        write_world(&filenames[1], &merge.merged);
+        return;
+    }
+    for conflict in &merge.conflicts {
+        eprintln!("conflict: {}", conflict.describe());
+    }
+    write_world(&filenames[1],
+                &PendingMerge { base, ours, theirs }.to_json());
+    exit(1);
+}
+
+// the ancestor is an empty file when both sides added the world on their own
+fn read_world(filename: &str) -> serde_json::Value {
+    let contents = std::fs::read_to_string(filename).unwrap_or_else(|e| {
+                                                        eprintln!("couldn't read {}: {}",
+                                                                  filename, e);
+                                                        exit(2)
+                                                    });
+    if contents.trim().is_empty() {
+        return serde_json::Value::Null;
+    }
+    serde_json::from_str(&contents).unwrap_or_else(|e| {
+                                       eprintln!("{} isn't a world file: {}", filename, e);
+                                       exit(2)
+                                   })
+}
+
+fn write_world(filename: &str, world: &serde_json::Value) {
+    let contents = serde_json::to_string_pretty(world).unwrap();
+    std::fs::write(filename, contents).unwrap_or_else(|e| {
+                                          eprintln!("couldn't write {}: {}", filename, e);
+                                          exit(2)
+                                      })
+}
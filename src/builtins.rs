@@ -16,12 +16,20 @@ use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::sync::{Arc, Mutex};
 
+mod chat;
 mod http_request;
+mod json;
 
+use crate::chat_format::OutgoingChatMessage;
+use crate::chat_program;
 use crate::env::ExecutionError;
 use crate::lang::{typ_for_anonymous_function, FunctionRenderingStyle};
+pub use chat::{
+    Bold, CodeBlock, InlineCode, Link, ReplyInThread, ReplyWithCard, SendDirectMessage, SendToChannel,
+};
 pub use http_request::HTTPRequest;
 pub use http_request::HTTP_RESPONSE_STRUCT_ID;
+pub use json::{ParseJSON, ToJSON};
 
 lazy_static! {
     pub static ref HTTP_REQUEST_FUNC_ID: uuid::Uuid =
@@ -42,6 +50,10 @@ lazy_static! {
         uuid::Uuid::parse_str("b6566a28-8257-46a9-aa29-39d9add25173").unwrap();
     pub static ref MESSAGE_STRUCT_ID: uuid::Uuid =
         uuid::Uuid::parse_str("cc430c68-1eba-4dd7-a3a8-0ee8e202ee83").unwrap();
+    pub static ref WEBHOOK_REQUEST_STRUCT_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("a3c5e7f9-1b2d-4f6a-8c0e-2d4f6a8c0e1b").unwrap();
+    pub static ref WEBHOOK_RESPONSE_STRUCT_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("f4e6d8c0-2a4b-4c6d-9e8f-0a2b4c6d8e1f").unwrap();
     pub static ref CHAT_REPLY_FUNC_ID: uuid::Uuid =
         uuid::Uuid::parse_str("36052afd-cf12-4146-bbc7-f9df04148b73").unwrap();
     pub static ref CHAT_REPLY_MESSAGE_ARG_ID: uuid::Uuid =
@@ -106,15 +118,6 @@ pub fn new_struct_value(struct_id: lang::ID, values: lang::StructValues) -> lang
     lang::Value::Struct { struct_id, values }
 }
 
-pub fn new_message(sender: String, argument_text: String, full_text: String) -> lang::Value {
-    new_struct_value(*MESSAGE_STRUCT_ID,
-                     lang::StructValues(btreemap! {
-                                            uuid::Uuid::parse_str("e01e6346-5c8f-4b1b-9723-cde0abf77ec0").unwrap() => lang::Value::String(sender),
-                                            uuid::Uuid::parse_str("d0d3b2b3-1d25-4d3d-bdca-fe34022eadf2").unwrap() => lang::Value::String(argument_text),
-                                            uuid::Uuid::parse_str("9a8d9059-a729-4660-b440-8ee7c411e70a").unwrap() => lang::Value::String(full_text),
-                                        }))
-}
-
 pub fn some_option_value(value: lang::Value) -> lang::Value {
     lang::Value::EnumVariant { variant_id: *OPTION_SOME_VARIANT_ID,
                                value: Box::new(value) }
@@ -339,7 +342,7 @@ pub fn get_error_type_from_result_type(result_type: &lang::Type) -> &lang::Type
 
 #[derive(Clone)]
 pub struct ChatReply {
-    pub output_buffer: Arc<Mutex<Vec<String>>>,
+    pub output_buffer: Arc<Mutex<Vec<OutgoingChatMessage>>>,
 }
 
 impl<'de> DeserializeTrait<'de> for ChatReply {
@@ -360,7 +363,7 @@ impl SerializeTrait for ChatReply {
 }
 
 impl ChatReply {
-    pub fn new(output_buffer: Arc<Mutex<Vec<String>>>) -> Self {
+    pub fn new(output_buffer: Arc<Mutex<Vec<OutgoingChatMessage>>>) -> Self {
         Self { output_buffer }
     }
 }
@@ -368,14 +371,14 @@ impl ChatReply {
 #[typetag::serde]
 impl lang::Function for ChatReply {
     fn call(&self,
-            _interpreter: env::Interpreter,
+            interpreter: env::Interpreter,
             mut args: HashMap<lang::ID, lang::Value>)
             -> lang::Value {
         let text_to_send = args.remove(&CHAT_REPLY_MESSAGE_ARG_ID)
                                .unwrap()
                                .into_string()
                                .unwrap();
-        self.output_buffer.lock().unwrap().push(text_to_send);
+        chat_program::send_reply(&interpreter, OutgoingChatMessage::reply(text_to_send));
         lang::Value::Null
     }
 
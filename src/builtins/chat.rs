@@ -0,0 +1,340 @@
+use crate::builtins::{convert_lang_option_to_rust_option, get_args, get_string, new_option};
+use crate::chat_format;
+use crate::chat_format::{Card, MessageContent, OutgoingChatMessage, ReplyTarget};
+use crate::chat_program;
+use crate::env::Interpreter;
+use crate::lang;
+use crate::lang::{ArgumentDefinition, Type, Value, ID};
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+lazy_static! {
+    static ref REPLY_IN_THREAD_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("7a1c3e5f-2b4d-4f6a-8c9e-0d1f3a5b7c92").unwrap();
+    static ref REPLY_IN_THREAD_MESSAGE_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("3e5a7c9f-1b2d-4e6f-a8c0-d2e4f6a8b0c1").unwrap();
+    static ref SEND_TO_CHANNEL_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("c8e0a2b4-6d8f-4a1c-9e3b-5d7f9a1c3e54").unwrap();
+    static ref SEND_TO_CHANNEL_CHANNEL_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("1d3f5b7d-9f1b-4d3f-b5d7-f9b1d3f5b7d9").unwrap();
+    static ref SEND_TO_CHANNEL_MESSAGE_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("6f8a0c2e-4a6c-4e8a-8c0e-2a4c6e8a0c2e").unwrap();
+    static ref SEND_DIRECT_MESSAGE_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("2c4e6a8c-0e2a-4c6e-9a0c-4e6a8c0e2a4c").unwrap();
+    static ref SEND_DIRECT_MESSAGE_USER_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("8a0c2e4a-6c8e-4a0c-8e2a-6c8e0a2c4e6a").unwrap();
+    static ref SEND_DIRECT_MESSAGE_MESSAGE_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("4e6a8c0e-2a4c-4e6a-8c0e-a2c4e6a8c0e2").unwrap();
+    static ref REPLY_WITH_CARD_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("9b1d3f5b-7d9f-4b1d-9f5b-d7f9b1d3f5b7").unwrap();
+    static ref REPLY_WITH_CARD_TITLE_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("5b7d9f1b-3d5f-4b7d-9b1d-3f5b7d9f1b3d").unwrap();
+    static ref REPLY_WITH_CARD_TEXT_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("0d2f4b6d-8f0b-4d2f-8b6d-f0b2d4f6b8d0").unwrap();
+    static ref REPLY_WITH_CARD_URL_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("e4f6a8c0-2e4a-4f6a-8c2e-4a6c8e0a2c4e").unwrap();
+    static ref BOLD_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("f1a3c5e7-9b1d-4f3a-8c5e-7a9c1e3f5a7b").unwrap();
+    static ref BOLD_TEXT_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("2d4f6b8d-0f2b-4d4f-9b8d-2f4b6d8f0b2d").unwrap();
+    static ref INLINE_CODE_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("6c8e0a2c-4e6a-4c8e-8a2c-6e8a0c2e4a6c").unwrap();
+    static ref INLINE_CODE_TEXT_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("b3d5f7b9-1d3f-4b5d-9f7b-3d5f7b9d1f3b").unwrap();
+    static ref CODE_BLOCK_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("a7c9e1a3-5c7e-4a9c-8e1a-5c7e9a1c3e5a").unwrap();
+    static ref CODE_BLOCK_TEXT_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("7e9a1c3e-5a7c-4e9a-8c3e-7a9c1e3a5c7e").unwrap();
+    static ref LINK_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("3a5c7e9a-1c3e-4a5c-9e9a-1c3e5a7c9e1a").unwrap();
+    static ref LINK_TEXT_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("c5e7a9c1-3e5a-4c7e-8a9c-3e5a7c9e1a3c").unwrap();
+    static ref LINK_URL_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("9e1a3c5e-7a9c-4e1a-9c5e-7a9c1e3a5c7e").unwrap();
+}
+
+fn send(interpreter: &Interpreter, message: OutgoingChatMessage) -> Value {
+    chat_program::send_reply(interpreter, message);
+    Value::Null
+}
+
+fn string_arg(id: ID, short_name: &str) -> ArgumentDefinition {
+    ArgumentDefinition::new_with_id(id,
+                                    lang::Type::from_spec(&*lang::STRING_TYPESPEC),
+                                    short_name.to_string())
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplyInThread {}
+
+#[typetag::serde]
+impl lang::Function for ReplyInThread {
+    fn call(&self, interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [message] = get_args(args, [*REPLY_IN_THREAD_MESSAGE_ARG_ID]).unwrap();
+        send(&interpreter,
+             OutgoingChatMessage { target: ReplyTarget::Thread,
+                                   content: MessageContent::Text(get_string(message).unwrap()) })
+    }
+
+    fn name(&self) -> &str {
+        "Reply in thread"
+    }
+
+    fn description(&self) -> &str {
+        "Reply in a thread off of the message that started this program. On chat services without threads, this is the same as Reply."
+    }
+
+    fn id(&self) -> ID {
+        *REPLY_IN_THREAD_FUNC_ID
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![string_arg(*REPLY_IN_THREAD_MESSAGE_ARG_ID, "Message")]
+    }
+
+    fn returns(&self) -> Type {
+        lang::Type::from_spec(&*lang::NULL_TYPESPEC)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SendToChannel {}
+
+#[typetag::serde]
+impl lang::Function for SendToChannel {
+    fn call(&self, interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [channel, message] = get_args(args,
+                                          [*SEND_TO_CHANNEL_CHANNEL_ARG_ID,
+                                           *SEND_TO_CHANNEL_MESSAGE_ARG_ID]).unwrap();
+        send(&interpreter,
+             OutgoingChatMessage { target: ReplyTarget::Channel(get_string(channel).unwrap()),
+                                   content: MessageContent::Text(get_string(message).unwrap()) })
+    }
+
+    fn name(&self) -> &str {
+        "Send to channel"
+    }
+
+    fn description(&self) -> &str {
+        "Send a message to a specific channel, on the same chat service this program was started from"
+    }
+
+    fn id(&self) -> ID {
+        *SEND_TO_CHANNEL_FUNC_ID
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![string_arg(*SEND_TO_CHANNEL_CHANNEL_ARG_ID, "Channel"),
+             string_arg(*SEND_TO_CHANNEL_MESSAGE_ARG_ID, "Message")]
+    }
+
+    fn returns(&self) -> Type {
+        lang::Type::from_spec(&*lang::NULL_TYPESPEC)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SendDirectMessage {}
+
+#[typetag::serde]
+impl lang::Function for SendDirectMessage {
+    fn call(&self, interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [user, message] = get_args(args,
+                                       [*SEND_DIRECT_MESSAGE_USER_ARG_ID,
+                                        *SEND_DIRECT_MESSAGE_MESSAGE_ARG_ID]).unwrap();
+        send(&interpreter,
+             OutgoingChatMessage { target: ReplyTarget::DirectMessage(get_string(user).unwrap()),
+                                   content: MessageContent::Text(get_string(message).unwrap()) })
+    }
+
+    fn name(&self) -> &str {
+        "Send direct message"
+    }
+
+    fn description(&self) -> &str {
+        "Send a private message to someone. Pass in the sender of a Chat Message to message whoever started this program."
+    }
+
+    fn id(&self) -> ID {
+        *SEND_DIRECT_MESSAGE_FUNC_ID
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![string_arg(*SEND_DIRECT_MESSAGE_USER_ARG_ID, "User"),
+             string_arg(*SEND_DIRECT_MESSAGE_MESSAGE_ARG_ID, "Message")]
+    }
+
+    fn returns(&self) -> Type {
+        lang::Type::from_spec(&*lang::NULL_TYPESPEC)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplyWithCard {}
+
+#[typetag::serde]
+impl lang::Function for ReplyWithCard {
+    fn call(&self, interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [title, text, url] = get_args(args,
+                                          [*REPLY_WITH_CARD_TITLE_ARG_ID,
+                                           *REPLY_WITH_CARD_TEXT_ARG_ID,
+                                           *REPLY_WITH_CARD_URL_ARG_ID]).unwrap();
+        let url = convert_lang_option_to_rust_option(url).map(|url| get_string(url).unwrap());
+        let card = Card { title: get_string(title).unwrap(),
+                          text: get_string(text).unwrap(),
+                          url };
+        send(&interpreter,
+             OutgoingChatMessage { target: ReplyTarget::Origin,
+                                   content: MessageContent::Card(card) })
+    }
+
+    fn name(&self) -> &str {
+        "Reply with card"
+    }
+
+    fn description(&self) -> &str {
+        "Reply with a box that has a title and some text in it. This becomes an embed on Discord and a block on Slack, and plain text on IRC."
+    }
+
+    fn id(&self) -> ID {
+        *REPLY_WITH_CARD_FUNC_ID
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![string_arg(*REPLY_WITH_CARD_TITLE_ARG_ID, "Title"),
+             string_arg(*REPLY_WITH_CARD_TEXT_ARG_ID, "Text"),
+             ArgumentDefinition::new_with_id(*REPLY_WITH_CARD_URL_ARG_ID,
+                                             new_option(lang::Type::from_spec(&*lang::STRING_TYPESPEC)),
+                                             "Link".to_string())]
+    }
+
+    fn returns(&self) -> Type {
+        lang::Type::from_spec(&*lang::NULL_TYPESPEC)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bold {}
+
+#[typetag::serde]
+impl lang::Function for Bold {
+    fn call(&self, _interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [text] = get_args(args, [*BOLD_TEXT_ARG_ID]).unwrap();
+        Value::String(chat_format::bold(&get_string(text).unwrap()))
+    }
+
+    fn name(&self) -> &str {
+        "Bold"
+    }
+
+    fn description(&self) -> &str {
+        "Makes text bold when it's sent to chat"
+    }
+
+    fn id(&self) -> ID {
+        *BOLD_FUNC_ID
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![string_arg(*BOLD_TEXT_ARG_ID, "Text")]
+    }
+
+    fn returns(&self) -> Type {
+        lang::Type::from_spec(&*lang::STRING_TYPESPEC)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InlineCode {}
+
+#[typetag::serde]
+impl lang::Function for InlineCode {
+    fn call(&self, _interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [text] = get_args(args, [*INLINE_CODE_TEXT_ARG_ID]).unwrap();
+        Value::String(chat_format::code(&get_string(text).unwrap()))
+    }
+
+    fn name(&self) -> &str {
+        "Code"
+    }
+
+    fn description(&self) -> &str {
+        "Shows text in a monospace font when it's sent to chat"
+    }
+
+    fn id(&self) -> ID {
+        *INLINE_CODE_FUNC_ID
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![string_arg(*INLINE_CODE_TEXT_ARG_ID, "Text")]
+    }
+
+    fn returns(&self) -> Type {
+        lang::Type::from_spec(&*lang::STRING_TYPESPEC)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CodeBlock {}
+
+#[typetag::serde]
+impl lang::Function for CodeBlock {
+    fn call(&self, _interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [text] = get_args(args, [*CODE_BLOCK_TEXT_ARG_ID]).unwrap();
+        Value::String(chat_format::code_block(&get_string(text).unwrap()))
+    }
+
+    fn name(&self) -> &str {
+        "Code block"
+    }
+
+    fn description(&self) -> &str {
+        "Shows multiple lines of text in a monospace box when it's sent to chat"
+    }
+
+    fn id(&self) -> ID {
+        *CODE_BLOCK_FUNC_ID
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![string_arg(*CODE_BLOCK_TEXT_ARG_ID, "Text")]
+    }
+
+    fn returns(&self) -> Type {
+        lang::Type::from_spec(&*lang::STRING_TYPESPEC)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Link {}
+
+#[typetag::serde]
+impl lang::Function for Link {
+    fn call(&self, _interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [text, url] = get_args(args, [*LINK_TEXT_ARG_ID, *LINK_URL_ARG_ID]).unwrap();
+        Value::String(chat_format::link(&get_string(text).unwrap(), &get_string(url).unwrap()))
+    }
+
+    fn name(&self) -> &str {
+        "Link"
+    }
+
+    fn description(&self) -> &str {
+        "Turns text into a link when it's sent to chat"
+    }
+
+    fn id(&self) -> ID {
+        *LINK_FUNC_ID
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![string_arg(*LINK_TEXT_ARG_ID, "Text"),
+             string_arg(*LINK_URL_ARG_ID, "URL")]
+    }
+
+    fn returns(&self) -> Type {
+        lang::Type::from_spec(&*lang::STRING_TYPESPEC)
+    }
+}
@@ -33,7 +33,7 @@ lazy_static! {
 
 #[typetag::serde]
 impl lang::Function for HTTPRequest {
-    fn call(&self, _interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+    fn call(&self, interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
         let [arg1, arg2] = get_args(args, [*HTTP_METHOD_ARG_ID, *URL_ARG_ID]).unwrap();
         let [http_method, url] = [get_string(arg1).unwrap(), get_string(arg2).unwrap()];
 
@@ -43,9 +43,10 @@ impl lang::Function for HTTPRequest {
         request_builder.method(http_method.as_str());
         // TODO: make body a parameter
         let request = request_builder.body("".to_string()).unwrap();
+        let program_id = interpreter.program_id;
 
         lang::Value::new_future(async move {
-            let response = http_client::fetch(request).await;
+            let response = http_client::fetch_for_program(request, program_id).await;
             match response {
                 Ok(resp) => {
                     let status_code = resp.status().as_u16();
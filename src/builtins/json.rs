@@ -0,0 +1,130 @@
+use crate::builtins::{get_args, get_string, new_result, ok_result_value};
+use crate::env::Interpreter;
+use crate::json_codec;
+use crate::json_codec::JSONCodec;
+use crate::lang;
+use crate::lang::{ArgumentDefinition, Type, Value, ID};
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+lazy_static! {
+    static ref TO_JSON_FUNC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("0e3a7c1d-9a53-4f0e-8c6b-5d2e6f1b7a94").unwrap();
+    static ref TO_JSON_VALUE_ARG_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("6c1f0b2e-4d7a-4b89-9e35-a8f2c3d41e57").unwrap();
+    static ref TO_JSON_GENERIC_ID: uuid::Uuid =
+        uuid::Uuid::parse_str("b2d94e6a-71c5-4f3b-8a0d-3e9c5f7b1a26").unwrap();
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ToJSON {}
+
+#[typetag::serde]
+impl lang::Function for ToJSON {
+    fn call(&self, interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [value] = get_args(args, [*TO_JSON_VALUE_ARG_ID]).unwrap();
+        let env = interpreter.env.borrow();
+        match json_codec::to_string(&value, &JSONCodec::default(), &env) {
+            Ok(string) => ok_result_value(Value::String(string)),
+            Err(e) => super::err_result_string(e),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "To JSON"
+    }
+
+    fn description(&self) -> &str {
+        "Converts any value into a JSON string"
+    }
+
+    fn id(&self) -> ID {
+        *TO_JSON_FUNC_ID
+    }
+
+    fn defines_generics(&self) -> Vec<lang::GenericParamTypeSpec> {
+        vec![lang::GenericParamTypeSpec::new(*TO_JSON_GENERIC_ID)]
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![lang::ArgumentDefinition::new_with_id(*TO_JSON_VALUE_ARG_ID,
+                                                   lang::Type::from_spec_id(*TO_JSON_GENERIC_ID,
+                                                                            vec![]),
+                                                   "Value".to_string())]
+    }
+
+    fn returns(&self) -> Type {
+        new_result(lang::Type::from_spec(&*lang::STRING_TYPESPEC),
+                   lang::Type::from_spec(&*lang::STRING_TYPESPEC))
+    }
+
+    fn autocomplete_also_matches(&self) -> &[&str] {
+        &["serialize", "stringify", "encode"]
+    }
+}
+
+// unlike ToJSON, the type we're parsing into has to be known ahead of time, because functions
+// don't get to see the types they're called with. so every ParseJSON in the world is its own
+// function, tied to a single type, the same way a JSONHTTPClient is
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParseJSON {
+    pub id: lang::ID,
+    pub name: String,
+    pub description: String,
+    pub into_type: lang::Type,
+    pub json_arg: lang::ArgumentDefinition,
+    #[serde(default)]
+    pub json_codec: JSONCodec,
+}
+
+impl ParseJSON {
+    pub fn new(into_type: lang::Type) -> Self {
+        Self { id: lang::new_id(),
+               name: "Parse JSON".to_string(),
+               description: "".to_string(),
+               into_type,
+               json_arg:
+                   lang::ArgumentDefinition::new(lang::Type::from_spec(&*lang::STRING_TYPESPEC),
+                                                 "JSON".to_string()),
+               json_codec: JSONCodec::default() }
+    }
+}
+
+#[typetag::serde]
+impl lang::Function for ParseJSON {
+    fn call(&self, interpreter: Interpreter, args: HashMap<ID, Value>) -> Value {
+        let [json] = get_args(args, [self.json_arg.id]).unwrap();
+        let json = get_string(json).unwrap();
+        let env = interpreter.env.borrow();
+        match json_codec::parse(&json, &self.into_type, &self.json_codec, &env) {
+            Ok(value) => ok_result_value(value),
+            Err(e) => super::err_result_string(e),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn id(&self) -> ID {
+        self.id
+    }
+
+    fn takes_args(&self) -> Vec<ArgumentDefinition> {
+        vec![self.json_arg.clone()]
+    }
+
+    fn returns(&self) -> Type {
+        new_result(self.into_type.clone(),
+                   lang::Type::from_spec(&*lang::STRING_TYPESPEC))
+    }
+
+    fn autocomplete_also_matches(&self) -> &[&str] {
+        &["deserialize", "decode"]
+    }
+}
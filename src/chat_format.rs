@@ -0,0 +1,234 @@
+// chat programs write formatted text in a tiny subset of markdown, and each chat service gets it
+// translated into whatever that service understands:
+//
+// **bold**, `code`, ```code block``` and [link text](https://url)
+//
+// there's no nesting. anything that doesn't parse is sent along as is
+use serde_json::json;
+
+// what comes out of a chat program, and where it should be sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutgoingChatMessage {
+    pub target: ReplyTarget,
+    pub content: MessageContent,
+}
+
+impl OutgoingChatMessage {
+    pub fn reply(text: String) -> Self {
+        Self { target: ReplyTarget::Origin,
+               content: MessageContent::Text(text) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplyTarget {
+    // wherever the program was triggered from. for scheduled programs, that's their channel
+    Origin,
+    // a thread off of the triggering message, or the thread it was already in. services without
+    // threads get it in the origin instead
+    Thread,
+    Channel(String),
+    DirectMessage(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageContent {
+    Text(String),
+    Card(Card),
+}
+
+// a box with a title and some text in it. slack blocks, discord embeds, and a few lines of text
+// everywhere else
+#[derive(Debug, Clone, PartialEq)]
+pub struct Card {
+    pub title: String,
+    pub text: String,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flavor {
+    // no formatting at all, for the chat test window and logs
+    Plain,
+    Irc,
+    Slack,
+    Discord,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Span<'a> {
+    Text(&'a str),
+    Bold(&'a str),
+    Code(&'a str),
+    CodeBlock(&'a str),
+    Link { text: &'a str, url: &'a str },
+}
+
+pub fn bold(text: &str) -> String {
+    format!("**{}**", text)
+}
+
+pub fn code(text: &str) -> String {
+    format!("`{}`", text)
+}
+
+pub fn code_block(text: &str) -> String {
+    format!("```\n{}\n```", text)
+}
+
+pub fn link(text: &str, url: &str) -> String {
+    format!("[{}]({})", text, url)
+}
+
+pub fn render(content: &MessageContent, flavor: Flavor) -> String {
+    match content {
+        MessageContent::Text(text) => render_text(text, flavor),
+        MessageContent::Card(card) => render_card_as_text(card, flavor),
+    }
+}
+
+// for places with only one room, like the chat test window and the terminal, where replies that
+// would've gone somewhere else get a note saying where
+pub fn render_with_target(message: &OutgoingChatMessage, flavor: Flavor) -> String {
+    let destination = match &message.target {
+        ReplyTarget::Origin => "".to_string(),
+        ReplyTarget::Thread => "(in thread) ".to_string(),
+        ReplyTarget::Channel(channel) => format!("(to {}) ", channel),
+        ReplyTarget::DirectMessage(user) => format!("(DM to {}) ", user),
+    };
+    format!("{}{}", destination, render(&message.content, flavor))
+}
+
+pub fn render_text(text: &str, flavor: Flavor) -> String {
+    parse(text).into_iter()
+               .map(|span| render_span(span, flavor))
+               .collect()
+}
+
+fn render_span(span: Span, flavor: Flavor) -> String {
+    match (span, flavor) {
+        // the text the program wrote goes out as is, so slack's own <@U123> mentions and
+        // <!here>s still work. only what gets put inside of a link is escaped
+        (Span::Text(text), _) => text.to_owned(),
+
+        (Span::Bold(text), Flavor::Plain) => text.to_owned(),
+        // ^B toggles bold in pretty much every IRC client
+        (Span::Bold(text), Flavor::Irc) => format!("\x02{}\x02", text),
+        (Span::Bold(text), Flavor::Slack) => format!("*{}*", text),
+        (Span::Bold(text), Flavor::Discord) => bold(text),
+
+        (Span::Code(text), Flavor::Plain) | (Span::Code(text), Flavor::Irc) => text.to_owned(),
+        (Span::Code(text), Flavor::Slack) | (Span::Code(text), Flavor::Discord) => code(text),
+
+        (Span::CodeBlock(text), Flavor::Plain) | (Span::CodeBlock(text), Flavor::Irc) => {
+            text.to_owned()
+        }
+        (Span::CodeBlock(text), Flavor::Slack) => format!("```{}```", text),
+        (Span::CodeBlock(text), Flavor::Discord) => code_block(text),
+
+        (Span::Link { text, url }, Flavor::Slack) => format!("<{}|{}>", url, escape_slack(text)),
+        // discord only supports markdown links inside of embeds. the angle brackets keep it from
+        // unfurling a preview of every link
+        (Span::Link { text, url }, Flavor::Discord) => format!("{} (<{}>)", text, url),
+        (Span::Link { text, url }, _) => format!("{} ({})", text, url),
+    }
+}
+
+fn render_card_as_text(card: &Card, flavor: Flavor) -> String {
+    let title = match &card.url {
+        Some(url) => render_text(&format!("{} ({})", bold(&card.title), url), flavor),
+        None => render_text(&bold(&card.title), flavor),
+    };
+    format!("{}\n{}", title, render_text(&card.text, flavor))
+}
+
+// https://api.slack.com/reference/block-kit/blocks
+pub fn slack_blocks(card: &Card) -> serde_json::Value {
+    let title = match &card.url {
+        Some(url) => format!("*<{}|{}>*", url, escape_slack(&card.title)),
+        None => format!("*{}*", card.title),
+    };
+    json!([{
+        "type": "section",
+        "text": {
+            "type": "mrkdwn",
+            "text": format!("{}\n{}", title, render_text(&card.text, Flavor::Slack)),
+        },
+    }])
+}
+
+// https://discord.com/developers/docs/resources/channel#embed-object
+pub fn discord_embed(card: &Card) -> serde_json::Value {
+    let mut embed = json!({
+        "title": card.title,
+        "description": render_text(&card.text, Flavor::Discord),
+    });
+    if let Some(url) = &card.url {
+        embed["url"] = json!(url);
+    }
+    embed
+}
+
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn parse(text: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut plain_start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let found = if rest.starts_with("```") {
+            between(rest, "```", "```").map(|(inner, len)| {
+                                           (Span::CodeBlock(inner.trim_matches('\n')), len)
+                                       })
+        } else if rest.starts_with("**") {
+            between(rest, "**", "**").map(|(inner, len)| (Span::Bold(inner), len))
+        } else if rest.starts_with('`') {
+            between(rest, "`", "`").map(|(inner, len)| (Span::Code(inner), len))
+        } else if rest.starts_with('[') {
+            parse_link(rest)
+        } else {
+            None
+        };
+
+        match found {
+            Some((span, len)) => {
+                if plain_start < i {
+                    spans.push(Span::Text(&text[plain_start..i]));
+                }
+                spans.push(span);
+                i += len;
+                plain_start = i;
+            }
+            None => i += rest.chars().next().unwrap().len_utf8(),
+        }
+    }
+    if plain_start < text.len() {
+        spans.push(Span::Text(&text[plain_start..]));
+    }
+    spans
+}
+
+// returns what's between the opening and closing markers, and the length of the whole thing
+// including the markers
+fn between<'a>(s: &'a str, open: &str, close: &str) -> Option<(&'a str, usize)> {
+    if !s.starts_with(open) {
+        return None;
+    }
+    let after_open = &s[open.len()..];
+    let end = after_open.find(close)?;
+    if end == 0 {
+        return None;
+    }
+    Some((&after_open[..end], open.len() + end + close.len()))
+}
+
+fn parse_link(s: &str) -> Option<(Span, usize)> {
+    let (text, text_len) = between(s, "[", "]")?;
+    let (url, url_len) = between(&s[text_len..], "(", ")")?;
+    Some((Span::Link { text, url }, text_len + url_len))
+}
@@ -1,36 +1,210 @@
 use lazy_static::lazy_static;
-use maplit::hashmap;
+use maplit::{btreemap, hashmap};
 use regex;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
 use uuid;
 
 use super::builtins::MESSAGE_STRUCT_ID;
 use super::env;
 use super::lang;
-use crate::builtins::new_message;
+use crate::builtins::{new_struct_value, rust_option_to_lang_option};
+use crate::chat_format::OutgoingChatMessage;
+use crate::chat_service::ReplyOrigin;
 use crate::env::Interpreter;
 use crate::lang::Function;
-use crate::{builtins, resolve_all_futures, validation, EnvGenie};
+use crate::logging;
+use crate::logging::ProgramRun;
+use crate::{builtins, validation, EnvGenie};
 use itertools::Itertools;
 use std::pin::Pin;
 
 lazy_static! {
     static ref MESSAGE_ARG_ID: lang::ID =
         uuid::Uuid::parse_str("159dc4f3-3f37-44da-b979-d4a41a9273cf").unwrap();
+
+    // Chat Message struct field IDs
+    static ref MESSAGE_SENDER_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("e01e6346-5c8f-4b1b-9723-cde0abf77ec0").unwrap();
+    static ref MESSAGE_ARGUMENT_TEXT_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("d0d3b2b3-1d25-4d3d-bdca-fe34022eadf2").unwrap();
+    static ref MESSAGE_FULL_TEXT_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("9a8d9059-a729-4660-b440-8ee7c411e70a").unwrap();
+    static ref MESSAGE_CHANNEL_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("2b6f1a53-8c4e-4d2a-9f71-3e5c8b0d6a14").unwrap();
+    static ref MESSAGE_PLATFORM_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("7d3e9c20-5a1b-4f6e-8b2d-c4a9e1f03b57").unwrap();
+    static ref MESSAGE_TIMESTAMP_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("a1f5c8e3-6d2b-4079-b3e4-5f8a2c1d9e60").unwrap();
+    static ref MESSAGE_THREAD_ID_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("4c9b2e71-3f8a-4d5c-a6e0-b1d7f3c95a28").unwrap();
+    static ref MESSAGE_IS_DIRECT_MESSAGE_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("e8a3d5f1-7b2c-4e9a-8d06-2f4c6b8a1e93").unwrap();
+    static ref MESSAGE_REACTION_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("5b1e7a39-c2d4-4f86-9e0b-a3c5d7f1b248").unwrap();
+    static ref MESSAGE_CAPTURES_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("93d6f2a8-1e5b-4c7d-b049-6a8e2c4f1d35").unwrap();
+}
+
+thread_local! {
+    // compiled Regex trigger regexes by program ID, along with the regex they were compiled from,
+    // so they only get compiled again when they're changed
+    static TRIGGER_REGEX_BY_PROGRAM_ID: RefCell<HashMap<lang::ID, (String, Option<regex::Regex>)>> =
+        RefCell::new(HashMap::new());
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ChatProgram {
     pub id: lang::ID,
+    // what this means depends on the trigger: it's the command for Prefix triggers, the regex for
+    // Regex triggers, and the emoji for Reaction triggers (where blank means any reaction). the
+    // other triggers ignore it
     pub prefix: String,
+    #[serde(default)]
+    pub trigger: ChatTrigger,
     pub code: lang::Block,
 }
 
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ChatTrigger {
+    Prefix,
+    Regex,
+    Mention,
+    DirectMessage,
+    Join,
+    Leave,
+    Reaction,
+}
+
+impl Default for ChatTrigger {
+    // every chat program used to be a prefix trigger, so that's what old programs get
+    fn default() -> Self {
+        ChatTrigger::Prefix
+    }
+}
+
+pub const CHAT_TRIGGER_LIST: [&ChatTrigger; 7] = [&ChatTrigger::Prefix,
+                                                  &ChatTrigger::Regex,
+                                                  &ChatTrigger::Mention,
+                                                  &ChatTrigger::DirectMessage,
+                                                  &ChatTrigger::Join,
+                                                  &ChatTrigger::Leave,
+                                                  &ChatTrigger::Reaction];
+
+impl ChatTrigger {
+    pub fn to_display(&self) -> &str {
+        match self {
+            ChatTrigger::Prefix => "Bot command",
+            ChatTrigger::Regex => "Regex",
+            ChatTrigger::Mention => "Bot mentioned",
+            ChatTrigger::DirectMessage => "Direct message",
+            ChatTrigger::Join => "User joined",
+            ChatTrigger::Leave => "User left",
+            ChatTrigger::Reaction => "Reaction",
+        }
+    }
+
+    // None if the trigger doesn't use the prefix field at all
+    pub fn prefix_label(&self) -> Option<&str> {
+        match self {
+            ChatTrigger::Prefix => Some("Bot command"),
+            ChatTrigger::Regex => Some("Regex"),
+            ChatTrigger::Reaction => Some("Emoji (blank for any)"),
+            ChatTrigger::Mention
+            | ChatTrigger::DirectMessage
+            | ChatTrigger::Join
+            | ChatTrigger::Leave => None,
+        }
+    }
+}
+
+// something that happened in a chat room that could set off a chat program
+#[derive(Debug, Clone)]
+pub struct ChatEvent {
+    pub kind: ChatEventKind,
+    pub sender: String,
+    // empty for joins and leaves, and for reactions when the service doesn't tell us what was
+    // reacted to
+    pub text: String,
+    pub channel: String,
+    // irc, discord, slack...
+    pub platform: String,
+    // seconds since the unix epoch
+    pub timestamp: i64,
+    // the service's own ID for the message, when it has one. replies in a thread hang off of this
+    // if the message isn't already in a thread
+    pub message_id: Option<String>,
+    pub thread_id: Option<String>,
+    pub is_direct_message: bool,
+    pub mentions_bot: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatEventKind {
+    Message,
+    // the emoji that was reacted with
+    Reaction(String),
+    Join,
+    Leave,
+}
+
+impl ChatEvent {
+    pub fn message(sender: String, text: String) -> Self {
+        Self { kind: ChatEventKind::Message,
+               sender,
+               text,
+               channel: "".to_string(),
+               platform: "".to_string(),
+               timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                                                      .map(|d| d.as_secs() as i64)
+                                                      .unwrap_or(0),
+               message_id: None,
+               thread_id: None,
+               is_direct_message: false,
+               mentions_bot: false }
+    }
+
+    fn into_message_value(self,
+                          argument_text: String,
+                          captures: BTreeMap<String, String>)
+                          -> lang::Value {
+        let string_type = lang::Type::from_spec(&*lang::STRING_TYPESPEC);
+        let captures = lang::Value::Map { from: string_type.clone(),
+                                          to: string_type,
+                                          value: captures.into_iter()
+                                                         .map(|(k, v)| {
+                                                             (lang::Value::String(k),
+                                                              lang::Value::String(v))
+                                                         })
+                                                         .collect() };
+        let reaction = match self.kind {
+            ChatEventKind::Reaction(reaction) => Some(lang::Value::String(reaction)),
+            _ => None,
+        };
+        new_struct_value(*MESSAGE_STRUCT_ID,
+                         lang::StructValues(btreemap! {
+                                                *MESSAGE_SENDER_FIELD_ID => lang::Value::String(self.sender),
+                                                *MESSAGE_ARGUMENT_TEXT_FIELD_ID => lang::Value::String(argument_text),
+                                                *MESSAGE_FULL_TEXT_FIELD_ID => lang::Value::String(self.text),
+                                                *MESSAGE_CHANNEL_FIELD_ID => lang::Value::String(self.channel),
+                                                *MESSAGE_PLATFORM_FIELD_ID => lang::Value::String(self.platform),
+                                                *MESSAGE_TIMESTAMP_FIELD_ID => lang::Value::Number(self.timestamp as i128),
+                                                *MESSAGE_THREAD_ID_FIELD_ID => rust_option_to_lang_option(self.thread_id.map(lang::Value::String)),
+                                                *MESSAGE_IS_DIRECT_MESSAGE_FIELD_ID => lang::Value::Boolean(self.is_direct_message),
+                                                *MESSAGE_REACTION_FIELD_ID => rust_option_to_lang_option(reaction),
+                                                *MESSAGE_CAPTURES_FIELD_ID => captures,
+                                            }))
+    }
+}
+
 impl ChatProgram {
     pub fn new() -> Self {
         Self { id: lang::new_id(),
                prefix: "".to_string(),
+               trigger: ChatTrigger::Prefix,
                code: lang::Block::new() }
     }
 
@@ -48,14 +222,88 @@ impl ChatProgram {
         regex::Regex::new(&regex_str).unwrap()
     }
 
+    // None if the regex doesn't compile
+    fn trigger_regex(&self) -> Option<regex::Regex> {
+        TRIGGER_REGEX_BY_PROGRAM_ID.with(|cache| {
+                                       let mut cache = cache.borrow_mut();
+                                       match cache.get(&self.id) {
+                                           Some((pattern, regex)) if *pattern == self.prefix => {
+                                               return regex.clone()
+                                           }
+                                           _ => (),
+                                       }
+                                       let regex = regex::Regex::new(&self.prefix).ok();
+                                       cache.insert(self.id, (self.prefix.clone(), regex.clone()));
+                                       regex
+                                   })
+    }
+
+    // a problem with how the trigger is set up, for showing in the editor
+    pub fn trigger_error(&self) -> Option<String> {
+        match self.trigger {
+            ChatTrigger::Regex => regex::Regex::new(&self.prefix).err().map(|e| e.to_string()),
+            _ => None,
+        }
+    }
+
+    // if this program should be triggered by the event, returns the argument text and the regex
+    // captures to go in the Message
+    fn match_event(&self, event: &ChatEvent) -> Option<(String, BTreeMap<String, String>)> {
+        let is_message = event.kind == ChatEventKind::Message;
+        match self.trigger {
+            ChatTrigger::Prefix if is_message && self.prefix_re().is_match(&event.text) => {
+                Some((self.strip_prefix(&event.text), BTreeMap::new()))
+            }
+            ChatTrigger::Regex if is_message => {
+                // a regex that doesn't compile never matches. the editor shows the error
+                let regex = self.trigger_regex()?;
+                let captures = regex.captures(&event.text)?;
+                let mut captures_by_name = captures.iter()
+                                                   .enumerate()
+                                                   .filter_map(|(i, capture)| {
+                                                       Some((i.to_string(),
+                                                             capture?.as_str().to_owned()))
+                                                   })
+                                                   .collect::<BTreeMap<_, _>>();
+                for name in regex.capture_names().filter_map(|name| name) {
+                    if let Some(capture) = captures.name(name) {
+                        captures_by_name.insert(name.to_owned(), capture.as_str().to_owned());
+                    }
+                }
+                // everything after the match, the same as what a Prefix trigger would give
+                let argument_text = event.text[captures.get(0).unwrap().end()..].trim()
+                                                                                .to_owned();
+                Some((argument_text, captures_by_name))
+            }
+            ChatTrigger::Mention if is_message && event.mentions_bot => {
+                Some((event.text.clone(), BTreeMap::new()))
+            }
+            ChatTrigger::DirectMessage if is_message && event.is_direct_message => {
+                Some((event.text.clone(), BTreeMap::new()))
+            }
+            ChatTrigger::Join if event.kind == ChatEventKind::Join => {
+                Some(("".to_owned(), BTreeMap::new()))
+            }
+            ChatTrigger::Leave if event.kind == ChatEventKind::Leave => {
+                Some(("".to_owned(), BTreeMap::new()))
+            }
+            ChatTrigger::Reaction => match &event.kind {
+                ChatEventKind::Reaction(reaction)
+                    if self.prefix.is_empty() || self.prefix == *reaction =>
+                {
+                    Some((event.text.clone(), BTreeMap::new()))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn try_to_trigger(&self,
                           interpreter: env::Interpreter,
-                          sender: String,
-                          message_text: String)
+                          event: ChatEvent)
                           -> Option<lang::Value> {
-        if !self.prefix_re().is_match(&message_text) {
-            return None;
-        }
+        let (argument_text, captures) = self.match_event(&event)?;
 
         let can_be_run = {
             let env = interpreter.env.borrow();
@@ -63,17 +311,13 @@ impl ChatProgram {
             validation::can_be_run(self, &env_genie)
         };
         if !can_be_run {
-            // TODO: send a message here saying that the code had an issue with it, and would've
-            // otherwise run
-            println!("would run this chat program, but deciding not to because it would crash");
-            let env = interpreter.env.borrow();
-            let env_genie = EnvGenie::new(&env);
-            append_to_chat_buffer(&env_genie, "The code you're trying to run has some issues and cannot be run. Please get in touch with the author or the administrator.".to_owned());
+            logging::warn("not running a chat program that would crash",
+                          json!({ "program_id": self.id, "program_name": self.name() }));
+            send_reply(&interpreter, OutgoingChatMessage::reply("The code you're trying to run has some issues and cannot be run. Please get in touch with the author or the administrator.".to_owned()));
             return None;
         }
 
-        let argument_text = self.strip_prefix(&message_text);
-        let message_struct = new_message(sender, argument_text, message_text);
+        let message_struct = event.into_message_value(argument_text, captures);
         Some(self.call(interpreter, hashmap! {*MESSAGE_ARG_ID => message_struct}))
     }
 }
@@ -96,7 +340,10 @@ impl lang::Function for ChatProgram {
     }
 
     fn name(&self) -> &str {
-        &self.prefix
+        match self.trigger.prefix_label() {
+            Some(_) if !self.prefix.is_empty() => &self.prefix,
+            _ => self.trigger.to_display(),
+        }
     }
 
     fn description(&self) -> &str {
@@ -127,7 +374,18 @@ impl lang::Function for ChatProgram {
 pub fn message_received(interp: &Interpreter,
                         sender: String,
                         text: String)
-                        -> Pin<Box<dyn std::future::Future<Output = ()>>> {
+                        -> Pin<Box<dyn std::future::Future<Output = Vec<OutgoingChatMessage>>>> {
+    let event = ChatEvent::message(sender, text);
+    let origin = ReplyOrigin::from_event(&event);
+    event_received(interp, event, origin)
+}
+
+// runs every chat program the event sets off, and returns everything they replied with. replies to
+// the origin go back to wherever the event came from
+pub fn event_received(interp: &Interpreter,
+                      event: ChatEvent,
+                      origin: ReplyOrigin)
+                      -> Pin<Box<dyn std::future::Future<Output = Vec<OutgoingChatMessage>>>> {
     // this is mandatory or else we'll borrow the Env for too long
     // TODO: make this a better comment
     // BorrowMutError
@@ -136,29 +394,81 @@ pub fn message_received(interp: &Interpreter,
         let env_genie = EnvGenie::new(&env);
         env_genie.list_chat_programs().cloned().collect::<Vec<_>>()
     };
-    let triggered_values =
-        chat_programs.iter()
-                     .filter_map(|cp| {
-                         cp.try_to_trigger(interp.new_stack_frame(), sender.clone(), text.clone())
-                     })
-                     .collect_vec();
+    let reply_context = ReplyContext::new(event.platform.clone(), origin);
+    let interp = interp.new_stack_frame()
+                       .with_reply_context(reply_context.clone());
+    let triggered = chat_programs.iter()
+                                 .filter_map(|cp| {
+                                     let value = cp.try_to_trigger(interp.new_stack_frame()
+                                                                         .with_program_id(cp.id),
+                                                                   event.clone())?;
+                                     let program_run =
+                                         ProgramRun::new(cp, "chat", Some(event.sender.clone()));
+                                     Some((program_run, value))
+                                 })
+                                 .collect_vec();
 
     Box::pin(async move {
-        for value in triggered_values {
-            resolve_all_futures(value).await;
+        for (program_run, value) in triggered {
+            // if one of them crashes, it's already been logged, and the rest still get to run
+            let _ = program_run.finish(value).await;
         }
+        reply_context.take_replies()
     })
 }
 
-fn append_to_chat_buffer(env_genie: &EnvGenie, reply: String) {
-    let chat_reply = env_genie.find_function(*builtins::CHAT_REPLY_FUNC_ID)
-                              .unwrap()
-                              .downcast_ref::<builtins::ChatReply>()
-                              .unwrap();
-    chat_reply.output_buffer.lock().unwrap().push(reply);
+// whether the text mentions the bot by name, like "programbot: hi" or "hey @programbot"
+pub fn mentions(text: &str, nickname: &str) -> bool {
+    if nickname.is_empty() {
+        return false;
+    }
+    let regex_str = format!(r"(?i)(?:^|\W)@?{}(?:\W|$)", regex::escape(nickname));
+    regex::Regex::new(&regex_str).unwrap().is_match(text)
+}
+
+// where an invocation's replies should go, and the replies themselves. everything that gets set off
+// from chat, or on a schedule, gets its own, so that the replies from two things happening at once
+// can't get mixed up
+#[derive(Debug, Clone)]
+pub struct ReplyContext {
+    // irc, discord, slack...
+    pub service: String,
+    pub origin: ReplyOrigin,
+    replies: Rc<RefCell<Vec<OutgoingChatMessage>>>,
+}
+
+impl ReplyContext {
+    pub fn new(service: String, origin: ReplyOrigin) -> Self {
+        Self { service,
+               origin,
+               replies: Rc::new(RefCell::new(vec![])) }
+    }
+
+    pub fn push(&self, message: OutgoingChatMessage) {
+        self.replies.borrow_mut().push(message)
+    }
+
+    pub fn take_replies(&self) -> Vec<OutgoingChatMessage> {
+        self.replies.borrow_mut().drain(..).collect()
+    }
+}
+
+// sends the message along with the rest of the replies for whatever set off this code. if nothing
+// did, like when running a script from the editor, it goes in the Reply builtin's buffer instead
+pub fn send_reply(interpreter: &Interpreter, message: OutgoingChatMessage) {
+    if let Some(reply_context) = &interpreter.reply_context {
+        reply_context.push(message);
+        return;
+    }
+    let env = interpreter.env.borrow();
+    let chat_reply = env.find_function(*builtins::CHAT_REPLY_FUNC_ID)
+                        .unwrap()
+                        .downcast_ref::<builtins::ChatReply>()
+                        .unwrap();
+    chat_reply.output_buffer.lock().unwrap().push(message);
 }
 
-pub fn flush_reply_buffer(env_genie: &EnvGenie) -> Vec<String> {
+pub fn flush_reply_buffer(env_genie: &EnvGenie) -> Vec<OutgoingChatMessage> {
     let chat_reply = env_genie.find_function(*builtins::CHAT_REPLY_FUNC_ID)
                               .unwrap()
                               .downcast_ref::<builtins::ChatReply>()
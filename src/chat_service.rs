@@ -0,0 +1,328 @@
+// a chat service is anywhere people can talk to the bot: IRC, Discord, Slack, and so on. each one
+// is a ChatService, built from its stored config by the ChatServiceRegistry, and `run` takes care
+// of the rest: passing events to the chat programs, sending back their replies, and reconnecting
+// whenever the connection drops
+use futures_util::future::{select_all, FutureExt};
+use futures_util::stream::{Stream, StreamExt};
+use serde_derive::Serialize;
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use crate::chat_format::OutgoingChatMessage;
+use crate::chat_program::ChatEvent;
+use crate::logging;
+use crate::metrics;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod discord_service;
+#[cfg(not(target_arch = "wasm32"))]
+mod irc_service;
+#[cfg(not(target_arch = "wasm32"))]
+mod slack_service;
+mod worker_pool;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use discord_service::DiscordService;
+#[cfg(not(target_arch = "wasm32"))]
+pub use irc_service::IrcService;
+#[cfg(not(target_arch = "wasm32"))]
+pub use slack_service::SlackService;
+pub use worker_pool::{QueueMetrics, WorkQueue, WorkerPool};
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+pub type ChatServiceFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+// what the bot sends on its own, without anybody asking, like the output of scheduled programs
+pub type OutgoingMessage = (ReplyOrigin, OutgoingChatMessage);
+
+pub trait ChatService {
+    // the service_type this was registered under, like "irc"
+    fn service_type(&self) -> &str;
+
+    fn connect(&mut self) -> ChatServiceFuture<'_, Result<()>>;
+
+    // waits for the next thing to happen. Ok(None) means the service is done for good and won't be
+    // reconnected, and an error means the connection dropped.
+    //
+    // this has to be safe to drop before it finishes, because `run` drops it whenever there's
+    // something to send
+    fn receive(&mut self) -> ChatServiceFuture<'_, Result<Option<(ChatEvent, ReplyOrigin)>>>;
+
+    fn send(&mut self,
+            origin: ReplyOrigin,
+            message: OutgoingChatMessage)
+            -> ChatServiceFuture<'_, Result<()>>;
+
+    // called after the connection drops. services with rate limits should wait a bit in here
+    fn reconnect(&mut self) -> ChatServiceFuture<'_, Result<()>> {
+        self.connect()
+    }
+}
+
+// where the thing that set off a program came from, so that its replies can find their way back
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplyOrigin {
+    // for IRC DMs, this is the sender
+    pub channel: String,
+    // the thread the message was in, or the message itself so a new thread can be started off of it
+    pub thread_id: Option<String>,
+    pub sender: String,
+}
+
+impl ReplyOrigin {
+    pub fn from_event(event: &ChatEvent) -> Self {
+        Self { channel: event.channel.clone(),
+               thread_id: event.thread_id.clone().or_else(|| event.message_id.clone()),
+               sender: event.sender.clone() }
+    }
+
+    // for messages that weren't set off by anybody, like the output of scheduled programs
+    pub fn channel(channel: String) -> Self {
+        Self { channel,
+               thread_id: None,
+               sender: "".to_owned() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    Reconnecting,
+    Stopped,
+    // the service's config didn't work, so it never got to connecting
+    Failed,
+}
+
+// `run` keeps this up to date, so other threads (like the admin API) can see how a service is doing
+pub type SharedConnectionStatus = Arc<Mutex<ConnectionStatus>>;
+
+// builds a service from its nickname and the config stored alongside it
+pub type ChatServiceConstructor =
+    Box<dyn Fn(&str, &serde_json::Value) -> Result<Box<dyn ChatService>>>;
+
+pub struct ChatServiceRegistry {
+    constructors: HashMap<String, ChatServiceConstructor>,
+}
+
+impl ChatServiceRegistry {
+    pub fn new() -> Self {
+        Self { constructors: HashMap::new() }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_builtin_services() -> Self {
+        let mut registry = Self::new();
+        registry.register("irc", |nickname, config| {
+                    Ok(Box::new(IrcService::from_config(nickname, config)?))
+                });
+        registry.register("discord", |nickname, config| {
+                    Ok(Box::new(DiscordService::from_config(nickname, config)?))
+                });
+        registry.register("slack", |nickname, config| {
+                    Ok(Box::new(SlackService::from_config(nickname, config)?))
+                });
+        registry
+    }
+
+    pub fn register(&mut self,
+                    service_type: &str,
+                    constructor: impl Fn(&str, &serde_json::Value) -> Result<Box<dyn ChatService>>
+                        + 'static) {
+        self.constructors
+            .insert(service_type.to_owned(), Box::new(constructor));
+    }
+
+    pub fn service_types(&self) -> impl Iterator<Item = &str> {
+        self.constructors
+            .keys()
+            .map(|service_type| service_type.as_str())
+    }
+
+    pub fn build(&self,
+                 service_type: &str,
+                 nickname: &str,
+                 config: &serde_json::Value)
+                 -> Result<Box<dyn ChatService>> {
+        let constructor = self.constructors
+                              .get(service_type)
+                              .ok_or_else(|| format!("unknown service type: {}", service_type))?;
+        constructor(nickname, config)
+    }
+}
+
+// connects to the service and keeps it going until it says it's done. every event goes to
+// `on_event`, along with where it came from, and whatever it returns is sent back there. events are
+// handled at the same time as each other, except for ones from the same channel, and the `queue`
+// says how many can be in the works at once. anything that comes out of the `outbox` is sent too
+pub async fn run<F>(mut service: Box<dyn ChatService>,
+                    mut outbox: impl Stream<Item = OutgoingMessage> + Unpin,
+                    queue: Rc<WorkQueue>,
+                    status: SharedConnectionStatus,
+                    on_event: F)
+                    -> Result<()>
+    where F: Fn(ChatEvent, ReplyOrigin) -> ChatServiceFuture<'static, Vec<OutgoingChatMessage>>
+{
+    let mut workers = WorkerPool::new(Rc::clone(&queue), on_event);
+    let mut outbox_closed = false;
+    set_status(&status, ConnectionStatus::Connecting);
+    let mut connected = service.connect().await;
+    loop {
+        if let Err(e) = connected {
+            logging::warn("couldn't connect, trying again",
+                          json!({ "service_type": service.service_type(),
+                                  "error": e.to_string() }));
+            count_reconnect(&*service);
+            set_status(&status, ConnectionStatus::Reconnecting);
+            connected = service.reconnect().await;
+            continue;
+        }
+        set_status(&status, ConnectionStatus::Connected);
+
+        let next = {
+            let mut waiting_on: Vec<ChatServiceFuture<Next>> = vec![];
+            waiting_on.push(Box::pin(workers.next_finished().map(|(origin, replies)| {
+                                                                Next::Finished(origin, replies)
+                                                            })));
+            // when the queue's full, stop taking in events until there's room for them. this goes
+            // through our own handle on the queue, because `workers` is already borrowed up above
+            if queue.is_full() {
+                waiting_on.push(Box::pin(queue.wait_for_room().map(|_| Next::Room)));
+            } else {
+                waiting_on.push(Box::pin(service.receive().map(Next::Received)));
+            }
+            if !outbox_closed {
+                waiting_on.push(Box::pin(outbox.next().map(Next::Outgoing)));
+            }
+            // whatever didn't finish gets dropped here, and they're all safe to drop
+            select_all(waiting_on).await.0
+        };
+
+        match next {
+            Next::Received(Ok(Some((event, origin)))) => {
+                metrics::inc_counter(metrics::MESSAGES_RECEIVED,
+                                     &[("service_type", service.service_type())]);
+                workers.push(event, origin)
+            }
+            Next::Received(Ok(None)) => {
+                // let whatever's still running finish up before calling it quits
+                while !workers.is_idle() {
+                    let (origin, replies) = workers.next_finished().await;
+                    send_all(&mut *service, origin, replies).await;
+                }
+                set_status(&status, ConnectionStatus::Stopped);
+                return Ok(());
+            }
+            Next::Received(Err(e)) => {
+                logging::warn("disconnected, reconnecting",
+                              json!({ "service_type": service.service_type(),
+                                      "error": e.to_string() }));
+                count_reconnect(&*service);
+                set_status(&status, ConnectionStatus::Reconnecting);
+                connected = service.reconnect().await;
+            }
+            Next::Finished(origin, replies) => send_all(&mut *service, origin, replies).await,
+            Next::Outgoing(Some((origin, message))) => send(&mut *service, origin, message).await,
+            Next::Outgoing(None) => outbox_closed = true,
+            Next::Room => (),
+        }
+    }
+}
+
+// what `run` was waiting on when it woke up
+enum Next {
+    Received(Result<Option<(ChatEvent, ReplyOrigin)>>),
+    Finished(ReplyOrigin, Vec<OutgoingChatMessage>),
+    Outgoing(Option<OutgoingMessage>),
+    Room,
+}
+
+fn count_reconnect(service: &dyn ChatService) {
+    metrics::inc_counter(metrics::CHAT_SERVICE_RECONNECTS,
+                         &[("service_type", service.service_type())]);
+}
+
+fn set_status(status: &SharedConnectionStatus, new_status: ConnectionStatus) {
+    *status.lock().unwrap() = new_status;
+}
+
+async fn send_all(service: &mut dyn ChatService,
+                  origin: ReplyOrigin,
+                  messages: Vec<OutgoingChatMessage>) {
+    for message in messages {
+        send(service, origin.clone(), message).await;
+    }
+}
+
+async fn send(service: &mut dyn ChatService, origin: ReplyOrigin, message: OutgoingChatMessage) {
+    if let Err(e) = service.send(origin, message).await {
+        logging::error("error sending message",
+                       json!({ "service_type": service.service_type(), "error": e.to_string() }));
+    }
+}
+
+// a chat service that lives entirely in memory, for trying out chat programs without connecting
+// to anything. it plays back `incoming` and then says it's done, and everything sent to it ends
+// up in `sent`
+pub struct FakeChatService {
+    pub incoming: VecDeque<ChatEvent>,
+    pub sent: Rc<RefCell<Vec<OutgoingMessage>>>,
+}
+
+impl FakeChatService {
+    pub fn new(incoming: impl IntoIterator<Item = ChatEvent>) -> Self {
+        Self { incoming: incoming.into_iter().collect(),
+               sent: Rc::new(RefCell::new(vec![])) }
+    }
+}
+
+impl ChatService for FakeChatService {
+    fn service_type(&self) -> &str {
+        "fake"
+    }
+
+    fn connect(&mut self) -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn receive(&mut self) -> ChatServiceFuture<'_, Result<Option<(ChatEvent, ReplyOrigin)>>> {
+        let received = self.incoming.pop_front().map(|event| {
+                                                    let origin = ReplyOrigin::from_event(&event);
+                                                    (event, origin)
+                                                });
+        Box::pin(async move { Ok(received) })
+    }
+
+    fn send(&mut self,
+            origin: ReplyOrigin,
+            message: OutgoingChatMessage)
+            -> ChatServiceFuture<'_, Result<()>> {
+        self.sent.borrow_mut().push((origin, message));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+// for the parts of the chat APIs that noob and the slack crate don't cover
+#[cfg(not(target_arch = "wasm32"))]
+async fn post_json_with_auth(url: &str,
+                             authorization: &str,
+                             body: &serde_json::Value)
+                             -> Result<http::Response<String>> {
+    let request = http::Request::post(url).header("Authorization", authorization)
+                                          .header("Content-Type", "application/json; charset=utf-8")
+                                          .body(body.to_string())?;
+    crate::http_client::fetch(request).await
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn get_with_auth(url: &str, authorization: &str) -> Result<http::Response<String>> {
+    let request = http::Request::get(url).header("Authorization", authorization)
+                                         .body("".to_owned())?;
+    crate::http_client::fetch(request).await
+}
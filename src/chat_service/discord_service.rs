@@ -0,0 +1,172 @@
+use futures_util::compat::Stream01CompatExt;
+use futures_util::stream::{Stream, StreamExt};
+use serde_json::json;
+use std::pin::Pin;
+use std::time::Duration;
+
+use super::{
+    get_with_auth, post_json_with_auth, ChatService, ChatServiceFuture, ReplyOrigin, Result,
+};
+use crate::asynk::forward;
+use crate::chat_format;
+use crate::chat_format::{Flavor, MessageContent, OutgoingChatMessage, ReplyTarget};
+use crate::chat_program::{mentions, ChatEvent};
+
+type EventStream = Pin<Box<dyn Stream<Item = std::result::Result<noob::Event, String>>>>;
+
+pub struct DiscordService {
+    token: String,
+    nickname: String,
+    // discord sends mentions as <@123> instead of by name, and we need to know which messages are
+    // our own. this gets looked up when connecting
+    bot_user_id: Option<String>,
+    client: Option<noob::Client>,
+    events: Option<EventStream>,
+}
+
+impl DiscordService {
+    // the config looks like {"token": "..."}
+    pub fn from_config(nickname: &str, config: &serde_json::Value) -> Result<Self> {
+        let token = config.get("token")
+                          .ok_or("discord token not found in config")?
+                          .as_str()
+                          .ok_or("discord token not a string")?;
+        Ok(Self { token: token.to_owned(),
+                  nickname: nickname.to_owned(),
+                  bot_user_id: None,
+                  client: None,
+                  events: None })
+    }
+}
+
+impl ChatService for DiscordService {
+    fn service_type(&self) -> &str {
+        "discord"
+    }
+
+    fn connect(&mut self) -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(async move {
+            if self.bot_user_id.is_none() {
+                self.bot_user_id = Some(fetch_bot_user_id(&self.token).await?);
+            }
+            let (client, stream) =
+                forward(noob::Client::connect(&self.token)).await
+                                                           .map_err(|e| {
+                                                               format!("error connecting to discord: {:?}", e)
+                                                           })?;
+            let events = stream.compat()
+                               .map(|event| event.map_err(|e| format!("{:?}", e)));
+            self.events = Some(Box::pin(events));
+            self.client = Some(client);
+            Ok(())
+        })
+    }
+
+    fn receive(&mut self) -> ChatServiceFuture<'_, Result<Option<(ChatEvent, ReplyOrigin)>>> {
+        Box::pin(async move {
+            let events = self.events.as_mut().ok_or("not connected to discord")?;
+            let bot_user_id = self.bot_user_id
+                                  .as_ref()
+                                  .ok_or("not connected to discord")?;
+            loop {
+                match events.next().await {
+                    // a program that replies to anything matching a regex could otherwise set
+                    // itself off forever with its own replies
+                    Some(Ok(noob::Event::MessageCreate(msg)))
+                        if msg.author.id.to_string() == *bot_user_id =>
+                    {
+                        continue
+                    }
+                    // noob doesn't tell us about reactions, joins or DMs, so everything from
+                    // discord is a plain old message
+                    Some(Ok(noob::Event::MessageCreate(msg))) => {
+                        // the ! is there when the mention is for the bot's nickname in the server
+                        let mentions_bot = msg.content.contains(&format!("<@{}>", bot_user_id))
+                                           || msg.content.contains(&format!("<@!{}>", bot_user_id))
+                                           || mentions(&msg.content, &self.nickname);
+                        let event = ChatEvent { channel: msg.channel_id.to_string(),
+                                                platform: "discord".to_owned(),
+                                                mentions_bot,
+                                                ..ChatEvent::message(msg.author.username,
+                                                                     msg.content) };
+                        let origin = ReplyOrigin::from_event(&event);
+                        return Ok(Some((event, origin)));
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        return Err(format!("there was a discord error, and we must reconnect: {}",
+                                           e).into())
+                    }
+                    None => return Err("discord connection closed".into()),
+                }
+            }
+        })
+    }
+
+    fn send(&mut self,
+            origin: ReplyOrigin,
+            message: OutgoingChatMessage)
+            -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let client = self.client.as_ref().ok_or("not connected to discord")?;
+            send_discord_message(client, &self.token, &origin, message).await
+        })
+    }
+
+    fn reconnect(&mut self) -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(async move {
+            forward(tokio_timer::sleep(Duration::from_secs(1))).await?;
+            self.connect().await
+        })
+    }
+}
+
+// https://discord.com/developers/docs/resources/user#get-current-user
+async fn fetch_bot_user_id(token: &str) -> Result<String> {
+    let response = get_with_auth("https://discord.com/api/v10/users/@me",
+                                 &format!("Bot {}", token)).await?;
+    if !response.status().is_success() {
+        return Err(format!("error looking up the discord bot's user: {}",
+                           response.body()).into());
+    }
+    let user: serde_json::Value = serde_json::from_str(response.body())?;
+    match user["id"].as_str() {
+        Some(id) => Ok(id.to_owned()),
+        None => Err(format!("discord didn't say what the bot's user ID is: {}", user).into()),
+    }
+}
+
+async fn send_discord_message(client: &noob::Client,
+                              token: &str,
+                              origin: &ReplyOrigin,
+                              message: OutgoingChatMessage)
+                              -> Result<()> {
+    let channel_id = match &message.target {
+        // we don't do discord threads, so those replies go right back where they came from
+        ReplyTarget::Origin | ReplyTarget::Thread => origin.channel.as_str(),
+        ReplyTarget::Channel(channel_id) => channel_id.as_str(),
+        // noob can't open up DM channels. sending it to the channel instead would show everyone
+        // something that was meant to be private
+        ReplyTarget::DirectMessage(username) => {
+            return Err(format!("can't send a direct message to {} on discord", username).into())
+        }
+    };
+    match &message.content {
+        MessageContent::Text(text) => {
+            let text = chat_format::render_text(text, Flavor::Discord);
+            forward(client.send_message(&noob::MessageBuilder::new(&text), channel_id)).await
+                .map_err(|e| format!("error sending discord message: {:?}", e))?;
+        }
+        // noob's MessageBuilder doesn't know about embeds, so these go through the REST API
+        MessageContent::Card(card) => {
+            let url = format!("https://discord.com/api/v10/channels/{}/messages",
+                              channel_id);
+            let body = json!({ "embeds": [chat_format::discord_embed(card)] });
+            let response = post_json_with_auth(&url, &format!("Bot {}", token), &body).await?;
+            if !response.status().is_success() {
+                return Err(format!("error sending discord embed: {}", response.body()).into());
+            }
+        }
+    }
+    Ok(())
+}
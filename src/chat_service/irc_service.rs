@@ -0,0 +1,166 @@
+use futures_util::compat::{Compat01As03, Stream01CompatExt};
+use futures_util::stream::StreamExt;
+use irc::client::prelude::*;
+use irc::client::{ClientStream, PackedIrcClient};
+use irc_proto::Command;
+use itertools::Itertools;
+use serde_json::json;
+use std::time::Duration;
+
+use super::{ChatService, ChatServiceFuture, ReplyOrigin, Result};
+use crate::asynk::{forward, OldFuture};
+use crate::chat_format;
+use crate::chat_format::{Flavor, OutgoingChatMessage, ReplyTarget};
+use crate::chat_program::{mentions, ChatEvent, ChatEventKind};
+use crate::logging;
+
+pub struct IrcService {
+    config: Config,
+    client: Option<IrcClient>,
+    stream: Option<Compat01As03<ClientStream>>,
+}
+
+impl IrcService {
+    // the config is the irc crate's Config, as JSON
+    pub fn from_config(nickname: &str, config: &serde_json::Value) -> Result<Self> {
+        let mut config: Config = serde_json::from_value(config.clone())?;
+        if config.nickname.is_none() {
+            config.nickname = Some(nickname.to_owned());
+        }
+        config.version = Some("cs: program me!".to_string());
+        config.alt_nicks =
+            Some((1..6).map(|n| {
+                           let underscores = std::iter::repeat("_").take(n).join("");
+                           format!("{}{}", config.nickname.as_ref().unwrap(), underscores)
+                       })
+                       .collect());
+        Ok(Self { config,
+                  client: None,
+                  stream: None })
+    }
+}
+
+impl ChatService for IrcService {
+    fn service_type(&self) -> &str {
+        "irc"
+    }
+
+    fn connect(&mut self) -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let irc_client_future = IrcClient::new_future(self.config.clone()).map_err(irc_error)?;
+            let PackedIrcClient(client, irc_future) =
+                forward(irc_client_future).await.map_err(irc_error)?;
+
+            // must send cap_req before identify because identify sends cap_end
+            client.send_cap_req(&[Capability::Custom("twitch.tv/membership")])
+                  .map_err(irc_error)?;
+            client.identify().map_err(irc_error)?;
+            // this is what actually talks to the server, and it keeps going in the background
+            // until the connection drops
+            tokio::runtime::current_thread::spawn(irc_future.map_err(log_irc_error));
+
+            self.stream = Some(client.stream().compat());
+            self.client = Some(client);
+            Ok(())
+        })
+    }
+
+    fn receive(&mut self) -> ChatServiceFuture<'_, Result<Option<(ChatEvent, ReplyOrigin)>>> {
+        Box::pin(async move {
+            let client = self.client.as_ref().ok_or("not connected to irc")?;
+            let stream = self.stream.as_mut().ok_or("not connected to irc")?;
+            while let Some(message) = stream.next().await {
+                match message {
+                    Ok(message) => {
+                        logging::debug("irc message", json!({ "message": message.to_string() }));
+                        if let Some(received) = irc_chat_event(client, &message) {
+                            return Ok(Some(received));
+                        }
+                    }
+                    Err(e) => log_irc_error(e),
+                }
+            }
+            Err("irc connection closed".into())
+        })
+    }
+
+    fn send(&mut self,
+            origin: ReplyOrigin,
+            message: OutgoingChatMessage)
+            -> ChatServiceFuture<'_, Result<()>> {
+        let result = match &self.client {
+            Some(client) => send_irc_message(client, &origin, &message),
+            None => Err("not connected to irc".into()),
+        };
+        Box::pin(async move { result })
+    }
+
+    fn reconnect(&mut self) -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(async move {
+            forward(tokio_timer::sleep(Duration::from_secs(5))).await?;
+            self.connect().await
+        })
+    }
+}
+
+// the irc crate's errors don't implement std::error::Error
+fn irc_error(e: impl std::fmt::Debug) -> Box<dyn std::error::Error> {
+    format!("irc error: {:?}", e).into()
+}
+
+fn log_irc_error(e: impl std::fmt::Debug) {
+    logging::error("irc error", json!({ "error": format!("{:?}", e) }))
+}
+
+// returns the event, and where replies to it should go
+fn irc_chat_event(client: &IrcClient,
+                  message: &irc::proto::Message)
+                  -> Option<(ChatEvent, ReplyOrigin)> {
+    let sender = message.source_nickname()?.to_owned();
+    // a program that replies to anything matching a regex could otherwise set itself off forever
+    // with its own replies
+    if sender == client.current_nickname() {
+        return None;
+    }
+    let (kind, channel, text) = match &message.command {
+        Command::PRIVMSG(target, text) => (ChatEventKind::Message, target, text.clone()),
+        Command::JOIN(channel, _, _) => (ChatEventKind::Join, channel, "".to_owned()),
+        Command::PART(channel, _) => (ChatEventKind::Leave, channel, "".to_owned()),
+        _ => return None,
+    };
+    // messages sent straight to us have our nick as the target instead of a channel
+    let is_direct_message = !(channel.starts_with('#') || channel.starts_with('&'));
+    let response_target = if is_direct_message {
+        sender.clone()
+    } else {
+        channel.clone()
+    };
+    let event = ChatEvent { kind,
+                            channel: channel.clone(),
+                            platform: "irc".to_owned(),
+                            is_direct_message,
+                            mentions_bot: mentions(&text, client.current_nickname()),
+                            ..ChatEvent::message(sender, text) };
+    let origin = ReplyOrigin { channel: response_target,
+                               ..ReplyOrigin::from_event(&event) };
+    Some((event, origin))
+}
+
+fn send_irc_message(client: &IrcClient,
+                    origin: &ReplyOrigin,
+                    message: &OutgoingChatMessage)
+                    -> Result<()> {
+    // IRC doesn't have threads, so those replies go right back where they came from
+    let target = match &message.target {
+        ReplyTarget::Origin | ReplyTarget::Thread => &origin.channel,
+        ReplyTarget::Channel(channel) => channel,
+        ReplyTarget::DirectMessage(nickname) => nickname,
+    };
+    // a PRIVMSG can't have newlines in it, so every line gets its own
+    for line in chat_format::render(&message.content, Flavor::Irc).lines()
+                                                                  .filter(|line| !line.is_empty())
+    {
+        client.send_privmsg(target, line).map_err(irc_error)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use futures::future::{ok, FutureResult};
+use futures_channel::mpsc;
+use futures_util::stream::StreamExt;
+use serde_json::json;
+use slack::api::MessageStandard;
+use slack::future::client::{Client, EventHandler};
+use slack::{Event, Item, Message};
+use std::time::Duration;
+
+use super::{post_json_with_auth, ChatService, ChatServiceFuture, ReplyOrigin, Result};
+use crate::asynk::{backward, forward};
+use crate::chat_format;
+use crate::chat_format::{Flavor, MessageContent, OutgoingChatMessage, ReplyTarget};
+use crate::chat_program::{mentions, ChatEvent, ChatEventKind};
+use crate::logging;
+
+pub struct SlackService {
+    token: String,
+    nickname: String,
+    // slack sends mentions as <@U123> instead of by name, and we need to know which messages are
+    // our own. this gets looked up when connecting
+    bot_user_id: Option<String>,
+    // the slack crate calls us back with events instead of letting us ask for them, so they get
+    // passed along through here
+    events: Option<mpsc::UnboundedReceiver<ChatEvent>>,
+}
+
+impl SlackService {
+    // the config looks like {"token": "..."}
+    pub fn from_config(nickname: &str, config: &serde_json::Value) -> Result<Self> {
+        let token = config.get("token")
+                          .ok_or("slack token not found in config")?
+                          .as_str()
+                          .ok_or("slack token not a string")?;
+        Ok(Self { token: token.to_owned(),
+                  nickname: nickname.to_owned(),
+                  bot_user_id: None,
+                  events: None })
+    }
+}
+
+impl ChatService for SlackService {
+    fn service_type(&self) -> &str {
+        "slack"
+    }
+
+    fn connect(&mut self) -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let bot_user_id = match &self.bot_user_id {
+                Some(bot_user_id) => bot_user_id.clone(),
+                None => fetch_bot_user_id(&self.token).await?,
+            };
+            self.bot_user_id = Some(bot_user_id.clone());
+            let (events_sender, events_receiver) = mpsc::unbounded();
+            let handler = MyHandler { events: events_sender,
+                                      nickname: self.nickname.clone(),
+                                      bot_user_id };
+            let token = self.token.clone();
+            // the RTM connection runs in the background. when it goes away, so does the handler,
+            // and that closes the events channel
+            tokio::runtime::current_thread::spawn(backward(async move {
+                                                      forward(Client::login_and_run(&token, handler)).await
+                                                               .map_err(log_slack_error)
+                                                               .ok();
+                                                      Ok::<(), ()>(())
+                                                  }));
+            self.events = Some(events_receiver);
+            Ok(())
+        })
+    }
+
+    fn receive(&mut self) -> ChatServiceFuture<'_, Result<Option<(ChatEvent, ReplyOrigin)>>> {
+        Box::pin(async move {
+            let events = self.events.as_mut().ok_or("not connected to slack")?;
+            let event = events.next().await.ok_or("slack connection closed")?;
+            let origin = ReplyOrigin::from_event(&event);
+            Ok(Some((event, origin)))
+        })
+    }
+
+    fn send(&mut self,
+            origin: ReplyOrigin,
+            message: OutgoingChatMessage)
+            -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(send_slack_message(self.token.clone(), origin, message))
+    }
+
+    fn reconnect(&mut self) -> ChatServiceFuture<'_, Result<()>> {
+        Box::pin(async move {
+            forward(tokio_timer::sleep(Duration::from_secs(5))).await?;
+            self.connect().await
+        })
+    }
+}
+
+struct MyHandler {
+    events: mpsc::UnboundedSender<ChatEvent>,
+    nickname: String,
+    bot_user_id: String,
+}
+
+impl EventHandler for MyHandler {
+    type EventFut = FutureResult<(), ()>;
+    type OnCloseFut = FutureResult<(), ()>;
+    type OnConnectFut = FutureResult<(), ()>;
+
+    fn on_event(&mut self, _cli: &Client, event: Event) -> Self::EventFut {
+        // turn the slack event into a chat event, if it's something we care about
+        let event = match event {
+            Event::Message(ref message) => match **message {
+                Message::Standard(MessageStandard { ref channel,
+                                                    ref user,
+                                                    ref text,
+                                                    ref thread_ts,
+                                                    ref ts,
+                                                    .. }) => {
+                    if let (Some(channel), Some(text), Some(user)) = (channel, text, user) {
+                        Some(ChatEvent { channel: channel.clone(),
+                                         platform: "slack".to_owned(),
+                                         message_id: ts.clone(),
+                                         thread_id: thread_ts.clone(),
+                                         // DM channel IDs start with a D
+                                         is_direct_message: channel.starts_with('D'),
+                                         mentions_bot: self.mentions_bot(text),
+                                         ..ChatEvent::message(user.clone(), text.clone()) })
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+            Event::ReactionAdded { ref user,
+                                   ref reaction,
+                                   ref item,
+                                   .. } => match **item {
+                Item::Message { ref channel, .. } => {
+                    Some(ChatEvent { kind: ChatEventKind::Reaction(reaction.clone()),
+                                     channel: channel.clone(),
+                                     platform: "slack".to_owned(),
+                                     ..ChatEvent::message(user.clone(), "".to_owned()) })
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        // a program that replies to anything matching a regex could otherwise set itself off
+        // forever with its own replies
+        let event = event.filter(|event| event.sender != self.bot_user_id);
+        if let Some(event) = event {
+            self.events
+                .unbounded_send(event)
+                .map_err(|e| {
+                    logging::error("error passing along slack event",
+                                   json!({ "error": e.to_string() }))
+                })
+                .ok();
+        }
+        ok(())
+    }
+
+    fn on_close(&mut self, _cli: &Client) -> Self::OnCloseFut {
+        logging::info("slack connection closed", json!({}));
+        ok(())
+    }
+
+    fn on_connect(&mut self, _cli: &Client) -> Self::OnConnectFut {
+        logging::info("slack connected", json!({}));
+        ok(())
+    }
+}
+
+impl MyHandler {
+    fn mentions_bot(&self, text: &str) -> bool {
+        text.contains(&format!("<@{}>", self.bot_user_id)) || mentions(text, &self.nickname)
+    }
+}
+
+// https://api.slack.com/methods/auth.test
+async fn fetch_bot_user_id(token: &str) -> Result<String> {
+    let response = post_json_with_auth("https://slack.com/api/auth.test",
+                                       &format!("Bearer {}", token),
+                                       &json!({})).await?;
+    let response: serde_json::Value = serde_json::from_str(response.body())?;
+    match response["user_id"].as_str() {
+        Some(user_id) => Ok(user_id.to_owned()),
+        None => Err(format!("error looking up the slack bot's user: {}", response).into()),
+    }
+}
+
+fn log_slack_error(e: impl std::fmt::Debug) {
+    logging::error("slack error", json!({ "error": format!("{:?}", e) }))
+}
+
+// everything goes through the web API instead of the RTM connection, because RTM can't do threads
+// or blocks
+async fn send_slack_message(token: String,
+                            origin: ReplyOrigin,
+                            message: OutgoingChatMessage)
+                            -> Result<()> {
+    let (channel, thread_ts) = match message.target {
+        ReplyTarget::Origin => (origin.channel, None),
+        ReplyTarget::Thread => (origin.channel, origin.thread_id),
+        ReplyTarget::Channel(channel) => (channel, None),
+        // posting to a user ID puts it in their DMs with the bot
+        ReplyTarget::DirectMessage(user) => (user, None),
+    };
+    // for cards, the text is what shows up in notifications
+    let mut body = json!({ "channel": channel,
+                           "text": chat_format::render(&message.content, Flavor::Slack) });
+    if let Some(thread_ts) = thread_ts {
+        body["thread_ts"] = json!(thread_ts);
+    }
+    if let MessageContent::Card(card) = &message.content {
+        body["blocks"] = chat_format::slack_blocks(card);
+    }
+
+    let response = post_json_with_auth("https://slack.com/api/chat.postMessage",
+                                       &format!("Bearer {}", token),
+                                       &body).await?;
+    // slack says 200 OK even when it didn't work
+    let response: serde_json::Value = serde_json::from_str(response.body())?;
+    if response["ok"] != json!(true) {
+        return Err(format!("error sending slack message: {}", response).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+// runs chat programs for a bunch of events at once, so one slow program (waiting on an HTTP call,
+// say) doesn't hold up everybody else. everything still happens on the one thread, because the
+// interpreter is all Rcs and RefCells: the running events are polled alongside the connection in
+// `run`, like a little local task set.
+//
+// events from the same channel are still handled one at a time, in the order they came in, so the
+// replies in a channel always come out in order
+use futures_util::future::{pending, poll_fn};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+use super::{ChatServiceFuture, ReplyOrigin};
+use crate::chat_format::OutgoingChatMessage;
+use crate::chat_program::ChatEvent;
+
+// how many events an instance is working on, counting the ones waiting their turn. it's shared by
+// all of the instance's services, and when it's full they stop taking in new events until some of
+// the ones in there are done
+#[derive(Debug)]
+pub struct WorkQueue {
+    capacity: usize,
+    depth: Cell<usize>,
+    high_water_mark: Cell<usize>,
+    handled: Cell<u64>,
+    waiting_for_room: RefCell<Vec<Waker>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueueMetrics {
+    pub capacity: usize,
+    pub depth: usize,
+    // the deepest the queue has ever gotten
+    pub high_water_mark: usize,
+    pub handled: u64,
+}
+
+impl WorkQueue {
+    pub fn new(capacity: usize) -> Rc<Self> {
+        Rc::new(Self { capacity,
+                       depth: Cell::new(0),
+                       high_water_mark: Cell::new(0),
+                       handled: Cell::new(0),
+                       waiting_for_room: RefCell::new(vec![]) })
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.depth.get() >= self.capacity
+    }
+
+    pub fn metrics(&self) -> QueueMetrics {
+        QueueMetrics { capacity: self.capacity,
+                       depth: self.depth.get(),
+                       high_water_mark: self.high_water_mark.get(),
+                       handled: self.handled.get() }
+    }
+
+    pub fn wait_for_room(&self) -> impl Future<Output = ()> + '_ {
+        poll_fn(move |cx| {
+            if self.is_full() {
+                self.waiting_for_room.borrow_mut().push(cx.waker().clone());
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+    }
+
+    fn enqueued(&self) {
+        let depth = self.depth.get() + 1;
+        self.depth.set(depth);
+        if depth > self.high_water_mark.get() {
+            self.high_water_mark.set(depth);
+        }
+    }
+
+    fn finished(&self) {
+        self.depth.set(self.depth.get() - 1);
+        self.handled.set(self.handled.get() + 1);
+        for waker in self.waiting_for_room.borrow_mut().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+// the channel the event came in on, where its replies go, and the replies
+type Finished = (String, ReplyOrigin, Vec<OutgoingChatMessage>);
+
+pub struct WorkerPool<F> {
+    queue: Rc<WorkQueue>,
+    on_event: F,
+    // every channel with an event running has an entry in here, holding the events that came in
+    // after it
+    waiting: HashMap<String, VecDeque<(ChatEvent, ReplyOrigin)>>,
+    running: FuturesUnordered<ChatServiceFuture<'static, Finished>>,
+}
+
+impl<F> WorkerPool<F>
+    where F: Fn(ChatEvent, ReplyOrigin) -> ChatServiceFuture<'static, Vec<OutgoingChatMessage>>
+{
+    pub fn new(queue: Rc<WorkQueue>, on_event: F) -> Self {
+        Self { queue,
+               on_event,
+               waiting: HashMap::new(),
+               running: FuturesUnordered::new() }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    pub fn push(&mut self, event: ChatEvent, origin: ReplyOrigin) {
+        self.queue.enqueued();
+        // it's the origin's channel and not the event's, because IRC DMs all come in on the bot's
+        // nick, and those shouldn't wait on each other
+        let channel = origin.channel.clone();
+        match self.waiting.get_mut(&channel) {
+            Some(waiting) => waiting.push_back((event, origin)),
+            None => {
+                self.waiting.insert(channel.clone(), VecDeque::new());
+                self.start(channel, event, origin);
+            }
+        }
+    }
+
+    // waits for the next event to be done, and returns where its replies go along with the replies.
+    // if nothing's running, this never finishes
+    //
+    // this is safe to drop before it finishes
+    pub async fn next_finished(&mut self) -> (ReplyOrigin, Vec<OutgoingChatMessage>) {
+        let (channel, origin, replies) = match self.running.next().await {
+            Some(finished) => finished,
+            None => return pending().await,
+        };
+        self.queue.finished();
+        let next_in_channel = self.waiting
+                                  .get_mut(&channel)
+                                  .and_then(|waiting| waiting.pop_front());
+        match next_in_channel {
+            Some((next_event, next_origin)) => self.start(channel, next_event, next_origin),
+            None => {
+                self.waiting.remove(&channel);
+            }
+        }
+        (origin, replies)
+    }
+
+    fn start(&mut self, channel: String, event: ChatEvent, origin: ReplyOrigin) {
+        let replies = (self.on_event)(event, origin.clone());
+        self.running
+            .push(Box::pin(async move { (channel, origin, replies.await) }));
+    }
+}
@@ -10,15 +10,18 @@ use crate::code_function::CodeFunction;
 use crate::enums::Enum;
 use crate::json_http_client::JSONHTTPClient;
 use crate::lang::BuiltInTypeSpec;
+use crate::scheduled_program::ScheduledProgram;
+use crate::webhook_program::WebhookProgram;
 use crate::structs::Struct;
 use serde_derive::{Deserialize, Serialize};
 use serde_json;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::{Arc, Mutex};
 
 type Error = Box<dyn std::error::Error>;
 
 // TODO: find a better name. til then, we're gonna save the world
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct TheWorld {
     pub scripts: Vec<scripts::Script>,
     pub tests: Vec<tests::Test>,
@@ -86,8 +89,20 @@ pub fn deserialize_fn(value: serde_json::Value) -> Result<Box<dyn lang::Function
         "Map" => Box::new(builtins::Map {}),
         "Print" => Box::new(builtins::Print {}),
         "Slice" => Box::new(builtins::Slice {}),
+        "ToJSON" => Box::new(builtins::ToJSON {}),
+        "ParseJSON" => Box::new(serde_json::from_value::<builtins::ParseJSON>(value)?),
+        "ReplyInThread" => Box::new(builtins::ReplyInThread {}),
+        "SendToChannel" => Box::new(builtins::SendToChannel {}),
+        "SendDirectMessage" => Box::new(builtins::SendDirectMessage {}),
+        "ReplyWithCard" => Box::new(builtins::ReplyWithCard {}),
+        "Bold" => Box::new(builtins::Bold {}),
+        "InlineCode" => Box::new(builtins::InlineCode {}),
+        "CodeBlock" => Box::new(builtins::CodeBlock {}),
+        "Link" => Box::new(builtins::Link {}),
         "JSONHTTPClient" => Box::new(serde_json::from_value::<JSONHTTPClient>(value)?),
         "ChatProgram" => Box::new(serde_json::from_value::<ChatProgram>(value)?),
+        "WebhookProgram" => Box::new(serde_json::from_value::<WebhookProgram>(value)?),
+        "ScheduledProgram" => Box::new(serde_json::from_value::<ScheduledProgram>(value)?),
         "CodeFunction" => Box::new(serde_json::from_value::<CodeFunction>(value)?),
         _ => panic!("don't know how to load builtin func type {}", typ),
     })
@@ -106,6 +121,75 @@ pub fn deserialize_typespec(value: serde_json::Value) -> Result<Box<dyn lang::Ty
     })
 }
 
+// a function or typespec that's different from one world to the next
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorldChange {
+    pub id: lang::ID,
+    // what it's called in the new world, or in the old one if it was removed
+    pub name: String,
+    pub kind: WorldChangeKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorldChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl WorldChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorldChangeKind::Added => "added",
+            WorldChangeKind::Changed => "changed",
+            WorldChangeKind::Removed => "removed",
+        }
+    }
+}
+
+// every function and typespec that was added, changed or removed going from `old` to `new`
+pub fn world_changes(old: &TheWorld, new: &TheWorld) -> Vec<WorldChange> {
+    let old_things = serialized_things(old);
+    let new_things = serialized_things(new);
+    let all_ids = old_things.keys()
+                            .chain(new_things.keys())
+                            .cloned()
+                            .collect::<BTreeSet<_>>();
+    all_ids.into_iter()
+           .filter_map(|id| {
+               let kind = match (old_things.get(&id), new_things.get(&id)) {
+                   (None, Some(_)) => WorldChangeKind::Added,
+                   (Some(_), None) => WorldChangeKind::Removed,
+                   (Some((_, old_value)), Some((_, new_value))) if old_value != new_value => {
+                       WorldChangeKind::Changed
+                   }
+                   _ => return None,
+               };
+               let (name, _) = new_things.get(&id).or_else(|| old_things.get(&id))?;
+               Some(WorldChange { id,
+                                  name: name.clone(),
+                                  kind })
+           })
+           .collect()
+}
+
+// the name and JSON of every function and typespec in the world, by ID
+fn serialized_things(world: &TheWorld) -> BTreeMap<lang::ID, (String, serde_json::Value)> {
+    let functions =
+        world.functions
+             .iter()
+             .map(|func| {
+                 (func.id(), (func.name().to_owned(), serde_json::to_value(func).unwrap()))
+             });
+    let typespecs = world.typespecs.iter().map(|ts| {
+                                              (ts.id(),
+                                               (ts.readable_name().to_owned(),
+                                                serde_json::to_value(ts).unwrap()))
+                                          });
+    functions.chain(typespecs).collect()
+}
+
 #[derive(Deserialize)]
 struct DeserializeTheWorld {
     scripts: Vec<scripts::Script>,
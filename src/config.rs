@@ -43,6 +43,18 @@ pub fn edit_code_url(querystring: &str) -> Result<url::Url, Box<dyn std::error::
     Ok(url)
 }
 
+pub fn token_info_url(querystring: &str) -> Result<url::Url, Box<dyn std::error::Error>> {
+    let mut url = server_listen_url()?.join("/tokeninfo")?;
+    url.set_query(Some(querystring));
+    Ok(url)
+}
+
+pub fn the_code_url(querystring: &str) -> Result<url::Url, Box<dyn std::error::Error>> {
+    let mut url = server_listen_url()?.join("/thecode")?;
+    url.set_query(Some(querystring));
+    Ok(url)
+}
+
 pub fn post_code_url(querystring: &str) -> Result<url::Url, Box<dyn std::error::Error>> {
     // XXX this /postthecode is duped in irctest.rs
     let mut url = server_listen_url()?.join("/postthecode")?;
@@ -0,0 +1,191 @@
+// parses and matches cron expressions, the five field kind:
+//
+// ┌───────────── minute (0 - 59)
+// │ ┌───────────── hour (0 - 23)
+// │ │ ┌───────────── day of the month (1 - 31)
+// │ │ │ ┌───────────── month (1 - 12, or jan - dec)
+// │ │ │ │ ┌───────────── day of the week (0 - 7, or sun - sat. both 0 and 7 are sunday)
+// │ │ │ │ │
+// * * * * *
+//
+// each field can be a *, a number, a range (1-5), a step (*/15 or 0-30/10), or a comma separated
+// list of any of those. @hourly, @daily, @weekly, @monthly and @yearly work too. everything's in UTC
+use std::str::FromStr;
+
+const MONTH_NAMES: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct",
+                               "nov", "dec"];
+const DAY_OF_WEEK_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: Field,
+    hours: Field,
+    days_of_month: Field,
+    months: Field,
+    days_of_week: Field,
+}
+
+impl CronSchedule {
+    pub fn matches(&self, time: &UtcTime) -> bool {
+        self.minutes.contains(time.minute)
+        && self.hours.contains(time.hour)
+        && self.months.contains(time.month)
+        && self.day_matches(time)
+    }
+
+    // this is the weird part of cron: if both day fields are restricted, then the day matches if
+    // *either* of them match. otherwise only the restricted one counts
+    fn day_matches(&self, time: &UtcTime) -> bool {
+        let dom_matches = self.days_of_month.contains(time.day_of_month);
+        let dow_matches = self.days_of_week.contains(time.day_of_week);
+        match (self.days_of_month.is_wildcard, self.days_of_week.is_wildcard) {
+            (false, false) => dom_matches || dow_matches,
+            _ => dom_matches && dow_matches,
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let expanded = match s {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            _ if s.starts_with('@') => return Err(format!("unknown schedule {}", s)),
+            _ => s,
+        };
+
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 fields (minute hour day month weekday), but got {}",
+                               fields.len()));
+        }
+        let mut days_of_week = Field::parse(fields[4], "day of week", 0, 7, DAY_OF_WEEK_NAMES)?;
+        // 7 is another way of saying sunday
+        if days_of_week.contains(7) {
+            days_of_week.bits |= 1;
+        }
+        Ok(Self { minutes: Field::parse(fields[0], "minute", 0, 59, &[])?,
+                  hours: Field::parse(fields[1], "hour", 0, 23, &[])?,
+                  days_of_month: Field::parse(fields[2], "day of month", 1, 31, &[])?,
+                  months: Field::parse(fields[3], "month", 1, 12, MONTH_NAMES)?,
+                  days_of_week })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    bits: u64,
+    is_wildcard: bool,
+}
+
+impl Field {
+    fn contains(&self, n: u32) -> bool {
+        n < 64 && self.bits & (1 << n) != 0
+    }
+
+    // names, when there are any, are numbered starting from min. so for months, jan is 1
+    fn parse(s: &str, field_name: &str, min: u32, max: u32, names: &[&str]) -> Result<Self, String> {
+        let mut bits = 0;
+        for part in s.split(',') {
+            let (range, step) = match part.find('/') {
+                Some(i) => {
+                    let step = part[i + 1..].parse::<u32>()
+                                            .ok()
+                                            .filter(|step| *step > 0)
+                                            .ok_or_else(|| {
+                                                format!("invalid step in {} field: {}",
+                                                        field_name, part)
+                                            })?;
+                    (&part[..i], step)
+                }
+                None => (part, 1),
+            };
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some(i) = range.find('-') {
+                (Self::parse_value(&range[..i], field_name, min, max, names)?,
+                 Self::parse_value(&range[i + 1..], field_name, min, max, names)?)
+            } else {
+                let start = Self::parse_value(range, field_name, min, max, names)?;
+                // 5/15 means "starting at 5, every 15"
+                (start, if step > 1 { max } else { start })
+            };
+            if start > end {
+                return Err(format!("backwards range in {} field: {}", field_name, part));
+            }
+            for n in (start..=end).step_by(step as usize) {
+                bits |= 1 << n;
+            }
+        }
+        Ok(Self { bits,
+                  is_wildcard: s == "*" })
+    }
+
+    fn parse_value(s: &str,
+                   field_name: &str,
+                   min: u32,
+                   max: u32,
+                   names: &[&str])
+                   -> Result<u32, String> {
+        let lowercased = s.to_lowercase();
+        let value = match names.iter().position(|name| *name == lowercased) {
+            Some(i) => i as u32 + min,
+            None => s.parse::<u32>()
+                     .map_err(|_| format!("invalid value in {} field: {}", field_name, s))?,
+        };
+        if value < min || value > max {
+            return Err(format!("{} must be between {} and {}, but got {}",
+                               field_name, min, max, value));
+        }
+        Ok(value)
+    }
+}
+
+// just the parts of a UTC timestamp that cron cares about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UtcTime {
+    pub minute: u32,
+    pub hour: u32,
+    pub day_of_month: u32,
+    pub month: u32,
+    // 0 is sunday
+    pub day_of_week: u32,
+}
+
+impl UtcTime {
+    pub fn now() -> Self {
+        let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                                               .unwrap()
+                                               .as_secs();
+        Self::from_unix_secs(secs as i64)
+    }
+
+    // from http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    pub fn from_unix_secs(secs: i64) -> Self {
+        let days = secs.div_euclid(86400);
+        let secs_of_day = secs.rem_euclid(86400);
+
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day_of_month = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+        Self { minute: (secs_of_day % 3600 / 60) as u32,
+               hour: (secs_of_day / 3600) as u32,
+               day_of_month: day_of_month as u32,
+               month: month as u32,
+               // 1970-01-01 was a thursday
+               day_of_week: (days + 4).rem_euclid(7) as u32 }
+    }
+}
@@ -0,0 +1,93 @@
+// what an edit URL lets you do. the server signs and encrypts these into the URL's token, so the
+// editor can't read them, and has to ask the server about its token instead (see EditTokenInfo)
+use serde_derive::{Deserialize, Serialize};
+
+use crate::code_loading::{world_changes, TheWorld};
+use crate::lang;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EditToken {
+    // so that a single token can be revoked without touching any of the others
+    pub id: lang::ID,
+    pub instance_id: i32,
+    // bumping the instance's token generation makes all of its old tokens stop working
+    pub token_generation: i32,
+    // whoever asked for the token, like the nick of whoever said !letmeprogramyou
+    pub issued_by: String,
+    // in unix seconds
+    pub expires_at: u64,
+    pub scope: EditScope,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum EditScope {
+    ReadOnly,
+    // if there are program IDs, those are the only programs that can be changed
+    Write { program_ids: Option<Vec<lang::ID>> },
+}
+
+impl EditScope {
+    pub fn can_change(&self, id: lang::ID) -> bool {
+        match self {
+            EditScope::ReadOnly => false,
+            EditScope::Write { program_ids } => {
+                program_ids.as_ref()
+                           .map_or(true, |program_ids| program_ids.contains(&id))
+            }
+        }
+    }
+}
+
+impl EditToken {
+    pub fn new(instance_id: i32,
+               token_generation: i32,
+               issued_by: String,
+               now: u64,
+               ttl_secs: u64,
+               scope: EditScope)
+               -> Self {
+        Self { id: lang::new_id(),
+               instance_id,
+               token_generation,
+               issued_by,
+               expires_at: now + ttl_secs,
+               scope }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    // makes sure everything that changed between the two worlds is something this token is allowed
+    // to change
+    pub fn check_world_change(&self, old: &TheWorld, new: &TheWorld) -> Result<(), String> {
+        if self.scope == EditScope::ReadOnly {
+            return Err("this edit link is read-only".to_owned());
+        }
+        match world_changes(old, new).into_iter()
+                                     .find(|change| !self.scope.can_change(change.id))
+        {
+            Some(change) => Err(format!("this edit link isn't allowed to change {}", change.name)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn info(&self, now: u64, problem: Option<String>) -> EditTokenInfo {
+        EditTokenInfo { instance_id: self.instance_id,
+                        issued_by: self.issued_by.clone(),
+                        scope: self.scope.clone(),
+                        expires_in_secs: self.expires_at.saturating_sub(now),
+                        problem }
+    }
+}
+
+// what the server tells the editor about its token
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EditTokenInfo {
+    pub instance_id: i32,
+    pub issued_by: String,
+    pub scope: EditScope,
+    pub expires_in_secs: u64,
+    // why the token can't be used, if it can't: expired, revoked, and so on
+    pub problem: Option<String>,
+}
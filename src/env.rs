@@ -12,6 +12,7 @@ use crate::builtins::{
     convert_lang_option_to_rust_option, convert_lang_value_to_rust_result, ok_result_value,
 };
 use crate::builtins::{err_result_string, err_result_value};
+use crate::chat_program::ReplyContext;
 use crate::lang::CodeNode;
 use crate::{enums, resolve_all_futures, EnvGenie};
 use failure::_core::fmt::Formatter;
@@ -44,12 +45,20 @@ impl std::hash::Hash for SharedLocals {
 pub struct Interpreter {
     pub env: Rc<RefCell<ExecutionEnvironment>>,
     pub locals: SharedLocals,
+    // for code that was set off by something in chat, this is where Reply and friends send their
+    // messages. every stack frame carries it along, so replies can't end up anywhere else
+    pub reply_context: Option<ReplyContext>,
+    // the program this code is running for, if it's running for one. carried along the same way,
+    // so things like HTTP requests can be counted per program
+    pub program_id: Option<lang::ID>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Self { env: Rc::new(RefCell::new(ExecutionEnvironment::new())),
-               locals: SharedLocals(Rc::new(RefCell::new(BTreeMap::new()))) }
+               locals: SharedLocals(Rc::new(RefCell::new(BTreeMap::new()))),
+               reply_context: None,
+               program_id: None }
     }
 
     // TODO: instead of setting local variables directly on `env`, set them on a per-interp `locals`
@@ -72,7 +81,19 @@ impl Interpreter {
 
     pub fn with_env_and_new_locals(env: Rc<RefCell<ExecutionEnvironment>>) -> Self {
         Self { env,
-               locals: SharedLocals(Rc::new(RefCell::new(BTreeMap::new()))) }
+               locals: SharedLocals(Rc::new(RefCell::new(BTreeMap::new()))),
+               reply_context: None,
+               program_id: None }
+    }
+
+    pub fn with_reply_context(self, reply_context: ReplyContext) -> Self {
+        Self { reply_context: Some(reply_context),
+               ..self }
+    }
+
+    pub fn with_program_id(self, program_id: lang::ID) -> Self {
+        Self { program_id: Some(program_id),
+               ..self }
     }
 
     pub fn env(&self) -> Rc<RefCell<ExecutionEnvironment>> {
@@ -444,12 +465,16 @@ impl Interpreter {
     }
 
     pub fn new_stack_frame(&self) -> Self {
-        Self::with_env_and_new_locals(Rc::clone(&self.env))
+        Self { reply_context: self.reply_context.clone(),
+               program_id: self.program_id,
+               ..Self::with_env_and_new_locals(Rc::clone(&self.env)) }
     }
 
     pub fn deep_clone_env(&self) -> Self {
         let env = self.env.as_ref();
-        Self::with_env_and_new_locals(Rc::new(RefCell::new(env.borrow().clone())))
+        Self { reply_context: self.reply_context.clone(),
+               program_id: self.program_id,
+               ..Self::with_env_and_new_locals(Rc::new(RefCell::new(env.borrow().clone()))) }
     }
 }
 
@@ -528,12 +553,13 @@ impl ExecutionEnvironment {
         self.functions.get(&id)
     }
 
+    // deleting something that's already gone does nothing
     pub fn delete_function(&mut self, id: lang::ID) {
-        self.functions.remove(&id).unwrap();
+        self.functions.remove(&id);
     }
 
     pub fn delete_typespec(&mut self, id: lang::ID) {
-        self.typespecs.remove(&id).unwrap();
+        self.typespecs.remove(&id);
     }
 
     pub fn list_functions(&self) -> impl Iterator<Item = &Box<dyn lang::Function>> {
@@ -593,8 +619,10 @@ pub enum ExecutionError {
     ArgumentWrongType,
     UndefinedFunction,
     PythonError,
+    PythonSerializationError,
     PythonDeserializationError,
     JavaScriptError,
+    JavaScriptSerializationError,
     JavaScriptDeserializationError,
 }
 
@@ -619,7 +647,11 @@ impl std::fmt::Display for ExecutionError {
                         ExecutionError::ArgumentWrongType => "ArgumentWrongType",
                         ExecutionError::UndefinedFunction => "UndefinedFunction",
                         ExecutionError::PythonError => "PythonError",
+                        ExecutionError::PythonSerializationError => "PythonSerializationError",
                         ExecutionError::PythonDeserializationError => "PythonDeserializationError",
+                        ExecutionError::JavaScriptSerializationError => {
+                            "JavaScriptSerializationError"
+                        }
                         ExecutionError::JavaScriptDeserializationError => {
                             "JavaScriptDeserializationError"
                         }
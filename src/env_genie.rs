@@ -1,5 +1,6 @@
 use std::collections::HashSet;
 
+use super::builtins::ParseJSON;
 use super::chat_program::ChatProgram;
 use super::code_function;
 use super::enums;
@@ -9,7 +10,9 @@ use super::jsstuff;
 use super::lang;
 use super::lang::Function;
 use super::pystuff;
+use super::scheduled_program::ScheduledProgram;
 use super::structs;
+use super::webhook_program::WebhookProgram;
 
 use crate::lang::{is_generic, TypeSpec, Value};
 use itertools::Itertools;
@@ -169,12 +172,30 @@ impl<'a> EnvGenie<'a> {
             .and_then(|f| f.downcast_ref::<JSONHTTPClient>())
     }
 
+    pub fn get_parse_json(&self, id: lang::ID) -> Option<&ParseJSON> {
+        self.env
+            .find_function(id)
+            .and_then(|f| f.downcast_ref::<ParseJSON>())
+    }
+
     pub fn get_chat_program(&self, id: lang::ID) -> Option<&ChatProgram> {
         self.env
             .find_function(id)
             .and_then(|f| f.downcast_ref::<ChatProgram>())
     }
 
+    pub fn get_webhook_program(&self, id: lang::ID) -> Option<&WebhookProgram> {
+        self.env
+            .find_function(id)
+            .and_then(|f| f.downcast_ref::<WebhookProgram>())
+    }
+
+    pub fn get_scheduled_program(&self, id: lang::ID) -> Option<&ScheduledProgram> {
+        self.env
+            .find_function(id)
+            .and_then(|f| f.downcast_ref::<ScheduledProgram>())
+    }
+
     pub fn find_struct(&self, id: lang::ID) -> Option<&structs::Struct> {
         self.env.find_struct(id)
     }
@@ -227,11 +248,26 @@ impl<'a> EnvGenie<'a> {
             .filter_map(|f| f.downcast_ref::<JSONHTTPClient>())
     }
 
+    pub fn list_parse_jsons(&self) -> impl Iterator<Item = &ParseJSON> {
+        self.all_functions()
+            .filter_map(|f| f.downcast_ref::<ParseJSON>())
+    }
+
     pub fn list_chat_programs(&self) -> impl Iterator<Item = &ChatProgram> {
         self.all_functions()
             .filter_map(|f| f.downcast_ref::<ChatProgram>())
     }
 
+    pub fn list_webhook_programs(&self) -> impl Iterator<Item = &WebhookProgram> {
+        self.all_functions()
+            .filter_map(|f| f.downcast_ref::<WebhookProgram>())
+    }
+
+    pub fn list_scheduled_programs(&self) -> impl Iterator<Item = &ScheduledProgram> {
+        self.all_functions()
+            .filter_map(|f| f.downcast_ref::<ScheduledProgram>())
+    }
+
     pub fn list_pyfuncs(&self) -> impl Iterator<Item = &pystuff::PyFunc> {
         self.env
             .list_functions()
@@ -402,6 +438,18 @@ fn get_args_for_func(
                   let chat_program = function.downcast_ref::<ChatProgram>().unwrap();
                   chat_program.takes_args()
               }),]
+    } else if let Some(webhook_program) = function.downcast_ref::<WebhookProgram>() {
+        vec![(Some(webhook_program.code.id),
+              &|function| {
+                  let webhook_program = function.downcast_ref::<WebhookProgram>().unwrap();
+                  webhook_program.takes_args()
+              }),]
+    } else if let Some(scheduled_program) = function.downcast_ref::<ScheduledProgram>() {
+        vec![(Some(scheduled_program.code.id),
+              &|function| {
+                  let scheduled_program = function.downcast_ref::<ScheduledProgram>().unwrap();
+                  scheduled_program.takes_args()
+              }),]
     } else {
         vec![(None, &|function| function.takes_args())]
     }
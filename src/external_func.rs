@@ -1,6 +1,7 @@
-use super::env;
+use super::builtins;
 use super::function;
 use super::lang;
+use crate::env::ExecutionError;
 use crate::lang::Value;
 use objekt::clone_trait_object;
 use std::collections::HashMap;
@@ -11,11 +12,15 @@ pub trait ModifyableFunc: objekt::Clone + lang::Function + function::SettableArg
 
 clone_trait_object!(ModifyableFunc);
 
-// TODO: this is a mess. we need this in JS land but now in native. lol
+// python and JS functions only have somewhere to put an error if they return a Result. otherwise
+// it's a panic, the same as when the python or JS code itself blows up
 #[allow(dead_code)]
-pub struct ValueWithEnv<'a> {
-    pub value: lang::Value,
-    pub env: &'a env::ExecutionEnvironment,
+pub fn error_value(func: &dyn lang::Function, error: ExecutionError) -> lang::Value {
+    if func.returns().typespec_id == *builtins::RESULT_ENUM_ID {
+        builtins::err_result_string(error.to_string())
+    } else {
+        std::panic::panic_any(error)
+    }
 }
 
 #[allow(dead_code)]
@@ -0,0 +1,521 @@
+use super::builtins;
+use super::env;
+use super::lang;
+use super::structs;
+use crate::EnvGenie;
+
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+use std::collections::BTreeMap;
+
+type Result<T> = std::result::Result<T, String>;
+
+// how struct field names and enum variant names are spelled out on the wire. field names in
+// CodeMaestro are display strings (e.g. "status code"), so these split on whitespace, `_`, `-`
+// and camelCase boundaries before putting the words back together
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub enum NameStyle {
+    AsIs,
+    CamelCase,
+    PascalCase,
+    SnakeCase,
+    ScreamingSnakeCase,
+    KebabCase,
+}
+
+// same idea as serde's enum representations:
+// https://serde.rs/enum-representations.html
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum EnumTagging {
+    // {"Variant": value}, or just "Variant" if there's nothing inside
+    External,
+    // {"type": "Variant", ...fields of the inner struct}
+    Internal { tag: String },
+    // {"type": "Variant", "value": value}
+    Adjacent { tag: String, content: String },
+    // just the inner value, and on the way in, the first variant that fits wins
+    Untagged,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JSONCodec {
+    pub field_names: NameStyle,
+    pub variant_names: NameStyle,
+    pub enum_tagging: EnumTagging,
+}
+
+pub const NAME_STYLE_LIST: [&NameStyle; 6] = [&NameStyle::AsIs,
+                                              &NameStyle::CamelCase,
+                                              &NameStyle::PascalCase,
+                                              &NameStyle::SnakeCase,
+                                              &NameStyle::ScreamingSnakeCase,
+                                              &NameStyle::KebabCase];
+
+lazy_static! {
+    // every kind of tagging, with the tag names serde uses out of the box
+    pub static ref ENUM_TAGGING_LIST: Vec<EnumTagging> =
+        vec![EnumTagging::External,
+             EnumTagging::Internal { tag: "type".to_owned() },
+             EnumTagging::Adjacent { tag: "type".to_owned(),
+                                     content: "value".to_owned() },
+             EnumTagging::Untagged];
+}
+
+impl Default for JSONCodec {
+    fn default() -> Self {
+        Self { field_names: NameStyle::AsIs,
+               variant_names: NameStyle::AsIs,
+               enum_tagging: EnumTagging::External }
+    }
+}
+
+impl NameStyle {
+    pub fn to_display(&self) -> &str {
+        match self {
+            NameStyle::AsIs => "As is",
+            NameStyle::CamelCase => "camelCase",
+            NameStyle::PascalCase => "PascalCase",
+            NameStyle::SnakeCase => "snake_case",
+            NameStyle::ScreamingSnakeCase => "SCREAMING_SNAKE_CASE",
+            NameStyle::KebabCase => "kebab-case",
+        }
+    }
+
+    pub fn apply(&self, name: &str) -> String {
+        let words = || split_into_words(name).into_iter();
+        match self {
+            NameStyle::AsIs => name.to_owned(),
+            NameStyle::CamelCase => words().enumerate()
+                                           .map(|(i, word)| {
+                                               if i == 0 {
+                                                   word.to_lowercase()
+                                               } else {
+                                                   capitalize(&word)
+                                               }
+                                           })
+                                           .collect(),
+            NameStyle::PascalCase => words().map(|word| capitalize(&word)).collect(),
+            NameStyle::SnakeCase => words().map(|word| word.to_lowercase()).join("_"),
+            NameStyle::ScreamingSnakeCase => words().map(|word| word.to_uppercase()).join("_"),
+            NameStyle::KebabCase => words().map(|word| word.to_lowercase()).join("-"),
+        }
+    }
+}
+
+impl EnumTagging {
+    pub fn to_display(&self) -> &str {
+        match self {
+            EnumTagging::External => "External",
+            EnumTagging::Internal { .. } => "Internal",
+            EnumTagging::Adjacent { .. } => "Adjacent",
+            EnumTagging::Untagged => "Untagged",
+        }
+    }
+
+    // whether it's the same kind of tagging, whatever the tag names are
+    pub fn is_same_kind(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+fn split_into_words(name: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut current = String::new();
+    let mut prev_was_lowercase = false;
+    for c in name.chars() {
+        if c.is_whitespace() || c == '_' || c == '-' {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_was_lowercase = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_was_lowercase && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_was_lowercase = c.is_lowercase() || c.is_numeric();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(first) => first.to_uppercase()
+                            .chain(chars.flat_map(|c| c.to_lowercase()))
+                            .collect(),
+    }
+}
+
+impl JSONCodec {
+    pub fn decode(&self,
+                  value: &serde_json::Value,
+                  into_type: &lang::Type,
+                  env: &env::ExecutionEnvironment)
+                  -> Result<lang::Value> {
+        if into_type.matches_spec(&lang::STRING_TYPESPEC) {
+            if let Some(string) = value.as_str() {
+                return Ok(lang::Value::String(string.to_owned()));
+            } else if let Some(float) = value.as_f64() {
+                return Ok(lang::Value::String(float.to_string()));
+            }
+        } else if into_type.matches_spec(&lang::NUMBER_TYPESPEC) {
+            if let Some(number) = decode_number(value) {
+                return Ok(lang::Value::Number(number));
+            }
+        } else if into_type.matches_spec(&lang::NULL_TYPESPEC) {
+            if value.is_null() {
+                return Ok(lang::Value::Null);
+            }
+        } else if into_type.matches_spec(&lang::BOOLEAN_TYPESPEC) {
+            if let Some(b) = value.as_bool() {
+                return Ok(lang::Value::Boolean(b));
+            }
+        } else if into_type.matches_spec(&lang::LIST_TYPESPEC) {
+            if let Some(vec) = value.as_array() {
+                let collection_type = into_type.params.first().unwrap();
+                let collected = vec.iter()
+                                   .map(|value| self.decode(value, collection_type, env))
+                                   .collect::<Result<Vec<_>>>()?;
+                return Ok(lang::Value::List(collection_type.clone(), collected));
+            }
+        } else if into_type.matches_spec(&lang::MAP_TYPESPEC) {
+            return self.decode_map(value, into_type, env);
+        } else if into_type.typespec_id == *builtins::OPTION_ENUM_ID {
+            return self.decode_option(value, &into_type.params[0], env);
+        } else if let Some(strukt) = env.find_struct(into_type.typespec_id) {
+            return self.decode_struct(value, strukt, env);
+        } else if env.find_enum(into_type.typespec_id).is_some() {
+            return self.decode_enum(value, into_type, env);
+        }
+        Err(format!("couldn't decode {} into {:?}", value, into_type))
+    }
+
+    // None is null and Some(x) is just x. the exception is an Option nested directly inside
+    // another Option: then Some(x) is [x], otherwise Some(None) and None would both be null
+    fn decode_option(&self,
+                     value: &serde_json::Value,
+                     some_type: &lang::Type,
+                     env: &env::ExecutionEnvironment)
+                     -> Result<lang::Value> {
+        if value.is_null() {
+            return Ok(builtins::none_option_value());
+        }
+        if some_type.typespec_id != *builtins::OPTION_ENUM_ID {
+            return Ok(builtins::some_option_value(self.decode(value, some_type, env)?));
+        }
+        match value.as_array().map(|vec| vec.as_slice()) {
+            Some([inner]) => Ok(builtins::some_option_value(self.decode(inner, some_type, env)?)),
+            _ => Err(format!("expected a nested Option ([value] or null) but got {}",
+                             value)),
+        }
+    }
+
+    // maps keyed by strings are JSON objects. anything else is a list of [key, value] pairs,
+    // because JSON object keys can only be strings
+    fn decode_map(&self,
+                  value: &serde_json::Value,
+                  into_type: &lang::Type,
+                  env: &env::ExecutionEnvironment)
+                  -> Result<lang::Value> {
+        let from = &into_type.params[0];
+        let to = &into_type.params[1];
+        let mut map = BTreeMap::new();
+        if let Some(obj) = value.as_object() {
+            for (key, value) in obj {
+                let key = if from.matches_spec(&lang::STRING_TYPESPEC) {
+                    lang::Value::String(key.clone())
+                } else if from.matches_spec(&lang::NUMBER_TYPESPEC) {
+                    lang::Value::Number(key.parse()
+                                           .map_err(|_| format!("{} isn't a number", key))?)
+                } else {
+                    return Err(format!("can't decode a map key {:?} from an object", from));
+                };
+                map.insert(key, self.decode(value, to, env)?);
+            }
+        } else if let Some(pairs) = value.as_array() {
+            for pair in pairs {
+                match pair.as_array().map(|vec| vec.as_slice()) {
+                    Some([key, value]) => {
+                        map.insert(self.decode(key, from, env)?, self.decode(value, to, env)?);
+                    }
+                    _ => return Err(format!("expected a [key, value] pair but got {}", pair)),
+                }
+            }
+        } else {
+            return Err(format!("couldn't decode {} into a map", value));
+        }
+        Ok(lang::Value::Map { from: from.clone(),
+                              to: to.clone(),
+                              value: map })
+    }
+
+    fn decode_struct(&self,
+                     value: &serde_json::Value,
+                     strukt: &structs::Struct,
+                     env: &env::ExecutionEnvironment)
+                     -> Result<lang::Value> {
+        let map =
+            value.as_object()
+                 .ok_or_else(|| {
+                     format!("expected an object for {} but got {}", strukt.name, value)
+                 })?;
+        let values =
+            strukt.fields
+                  .iter()
+                  .map(|strukt_field| {
+                      let name = self.field_names.apply(&strukt_field.name);
+                      let value = match map.get(&name) {
+                          Some(value) => self.decode(value, &strukt_field.field_type, env)?,
+                          None if strukt_field.field_type.typespec_id
+                                  == *builtins::OPTION_ENUM_ID =>
+                          {
+                              builtins::none_option_value()
+                          }
+                          None => {
+                              return Err(format!("missing field {} for {}", name, strukt.name))
+                          }
+                      };
+                      Ok((strukt_field.id, value))
+                  })
+                  .collect::<Result<_>>()?;
+        Ok(builtins::new_struct_value(strukt.id, lang::StructValues(values)))
+    }
+
+    fn decode_enum(&self,
+                   value: &serde_json::Value,
+                   into_type: &lang::Type,
+                   env: &env::ExecutionEnvironment)
+                   -> Result<lang::Value> {
+        let eneom = env.find_enum(into_type.typespec_id).unwrap();
+        let variant_types = eneom.variant_types(&into_type.params);
+        let find_variant = |name: &str| {
+            variant_types.iter()
+                         .find(|(variant, _)| self.variant_names.apply(&variant.name) == name)
+                         .ok_or_else(|| format!("{} has no variant named {}", eneom.name, name))
+        };
+        let new_variant = |variant_id, value| {
+            Ok(lang::Value::EnumVariant { variant_id,
+                                          value: Box::new(value) })
+        };
+
+        match &self.enum_tagging {
+            EnumTagging::External => {
+                if let Some(name) = value.as_str() {
+                    let (variant, typ) = find_variant(name)?;
+                    return new_variant(variant.id,
+                                       self.decode(&serde_json::Value::Null, typ, env)?);
+                }
+                match value.as_object() {
+                    Some(obj) if obj.len() == 1 => {
+                        let (name, inner) = obj.iter().next().unwrap();
+                        let (variant, typ) = find_variant(name)?;
+                        new_variant(variant.id, self.decode(inner, typ, env)?)
+                    }
+                    _ => Err(format!("couldn't decode {} into {}", value, eneom.name)),
+                }
+            }
+            EnumTagging::Internal { tag } => {
+                let name = value.get(tag)
+                                .and_then(|name| name.as_str())
+                                .ok_or_else(|| format!("{} is missing the tag {}", value, tag))?;
+                let (variant, typ) = find_variant(name)?;
+                if typ.matches_spec(&lang::NULL_TYPESPEC) {
+                    return new_variant(variant.id, lang::Value::Null);
+                }
+                let mut inner = value.clone();
+                inner.as_object_mut().unwrap().remove(tag);
+                new_variant(variant.id, self.decode(&inner, typ, env)?)
+            }
+            EnumTagging::Adjacent { tag, content } => {
+                let name = value.get(tag)
+                                .and_then(|name| name.as_str())
+                                .ok_or_else(|| format!("{} is missing the tag {}", value, tag))?;
+                let (variant, typ) = find_variant(name)?;
+                let inner = value.get(content).unwrap_or(&serde_json::Value::Null);
+                new_variant(variant.id, self.decode(inner, typ, env)?)
+            }
+            EnumTagging::Untagged => {
+                for (variant, typ) in &variant_types {
+                    if let Ok(inner) = self.decode(value, typ, env) {
+                        return new_variant(variant.id, inner);
+                    }
+                }
+                Err(format!("{} didn't match any variant of {}", value, eneom.name))
+            }
+        }
+    }
+
+    // no type needed going this way: lists and maps carry their types, structs carry their IDs,
+    // and enums can be found from the variant ID
+    pub fn encode(&self,
+                  value: &lang::Value,
+                  env: &env::ExecutionEnvironment)
+                  -> Result<serde_json::Value> {
+        Ok(match value {
+            lang::Value::Null => serde_json::Value::Null,
+            lang::Value::Boolean(b) => serde_json::Value::Bool(*b),
+            lang::Value::String(s) => serde_json::Value::String(s.clone()),
+            lang::Value::Number(n) => encode_number(*n),
+            lang::Value::List(_, vec) => {
+                serde_json::Value::Array(vec.iter()
+                                            .map(|value| self.encode(value, env))
+                                            .collect::<Result<_>>()?)
+            }
+            lang::Value::Map { from, value, .. } => {
+                if from.matches_spec(&lang::STRING_TYPESPEC) {
+                    serde_json::Value::Object(value.iter()
+                                                   .map(|(key, value)| {
+                                                       Ok((key.as_str()
+                                                              .map_err(|e| e.to_string())?
+                                                              .to_owned(),
+                                                           self.encode(value, env)?))
+                                                   })
+                                                   .collect::<Result<_>>()?)
+                } else {
+                    serde_json::Value::Array(
+                                             value.iter()
+                                                  .map(|(key, value)| {
+                                                      Ok(serde_json::Value::Array(vec![
+                            self.encode(key, env)?,
+                            self.encode(value, env)?,
+                        ]))
+                                                  })
+                                                  .collect::<Result<_>>()?,
+                    )
+                }
+            }
+            lang::Value::Struct { struct_id, values } => {
+                let strukt = env.find_struct(*struct_id)
+                                .ok_or_else(|| format!("couldn't find struct {}", struct_id))?;
+                let mut obj = serde_json::Map::new();
+                for field in &strukt.fields {
+                    if let Some(value) = values.0.get(&field.id) {
+                        obj.insert(self.field_names.apply(&field.name),
+                                   self.encode(value, env)?);
+                    }
+                }
+                serde_json::Value::Object(obj)
+            }
+            lang::Value::EnumVariant { variant_id, value } => {
+                self.encode_enum(*variant_id, value, env)?
+            }
+            lang::Value::EarlyReturn(inner) => self.encode(inner, env)?,
+            lang::Value::Future(_) | lang::Value::AnonymousFunction(_, _) => {
+                return Err(format!("can't convert {:?} into JSON", value))
+            }
+        })
+    }
+
+    fn encode_enum(&self,
+                   variant_id: lang::ID,
+                   value: &lang::Value,
+                   env: &env::ExecutionEnvironment)
+                   -> Result<serde_json::Value> {
+        if variant_id == *builtins::OPTION_NONE_VARIANT_ID {
+            return Ok(serde_json::Value::Null);
+        } else if variant_id == *builtins::OPTION_SOME_VARIANT_ID {
+            let inner = self.encode(value, env)?;
+            return Ok(if is_option_value(value) {
+                serde_json::Value::Array(vec![inner])
+            } else {
+                inner
+            });
+        }
+
+        let (_, variant) =
+            EnvGenie::new(env).find_enum_variant(variant_id)
+                              .ok_or_else(|| {
+                                  format!("couldn't find enum variant {}", variant_id)
+                              })?;
+        let name = serde_json::Value::String(self.variant_names.apply(&variant.name));
+        let is_unit = matches!(value, lang::Value::Null);
+        let inner = self.encode(value, env)?;
+        Ok(match &self.enum_tagging {
+            EnumTagging::External if is_unit => name,
+            EnumTagging::External => {
+                let mut obj = serde_json::Map::new();
+                obj.insert(name.as_str().unwrap().to_owned(), inner);
+                serde_json::Value::Object(obj)
+            }
+            EnumTagging::Internal { tag } => {
+                let mut obj = match inner {
+                    serde_json::Value::Object(obj) => obj,
+                    serde_json::Value::Null => serde_json::Map::new(),
+                    otherwise => {
+                        return Err(format!("internally tagged enums can only hold structs, \
+                                            but {} has {}",
+                                           variant.name, otherwise))
+                    }
+                };
+                obj.insert(tag.clone(), name);
+                serde_json::Value::Object(obj)
+            }
+            EnumTagging::Adjacent { tag, content } => {
+                let mut obj = serde_json::Map::new();
+                obj.insert(tag.clone(), name);
+                if !is_unit {
+                    obj.insert(content.clone(), inner);
+                }
+                serde_json::Value::Object(obj)
+            }
+            EnumTagging::Untagged => inner,
+        })
+    }
+}
+
+fn is_option_value(value: &lang::Value) -> bool {
+    match value {
+        lang::Value::EnumVariant { variant_id, .. } => {
+            *variant_id == *builtins::OPTION_SOME_VARIANT_ID
+            || *variant_id == *builtins::OPTION_NONE_VARIANT_ID
+        }
+        _ => false,
+    }
+}
+
+// numbers that don't fit into a JSON number without losing precision go out as strings, so they
+// have to be accepted as strings coming back in
+fn decode_number(value: &serde_json::Value) -> Option<i128> {
+    if let Some(int) = value.as_i64() {
+        Some(int as i128)
+    } else if let Some(uint) = value.as_u64() {
+        Some(uint as i128)
+    } else {
+        value.as_str().and_then(|s| s.parse().ok())
+    }
+}
+
+fn encode_number(number: i128) -> serde_json::Value {
+    use std::convert::TryFrom;
+    if let Ok(int) = i64::try_from(number) {
+        serde_json::Value::from(int)
+    } else if let Ok(uint) = u64::try_from(number) {
+        serde_json::Value::from(uint)
+    } else {
+        serde_json::Value::String(number.to_string())
+    }
+}
+
+pub fn parse(str: &str,
+             into_type: &lang::Type,
+             codec: &JSONCodec,
+             env: &env::ExecutionEnvironment)
+             -> Result<lang::Value> {
+    let value = serde_json::from_str(str).map_err(|e| e.to_string())?;
+    codec.decode(&value, into_type, env)
+}
+
+pub fn to_string(value: &lang::Value,
+                 codec: &JSONCodec,
+                 env: &env::ExecutionEnvironment)
+                 -> Result<String> {
+    Ok(codec.encode(value, env)?.to_string())
+}
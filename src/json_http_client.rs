@@ -7,10 +7,9 @@ use super::lang;
 use super::result::Result;
 use super::structs;
 
-use crate::builtins::{
-    get_ok_type_from_result_type, none_option_value, ok_result_value, some_option_value,
-};
+use crate::builtins::{get_ok_type_from_result_type, ok_result_value};
 use crate::code_generation;
+use crate::json_codec::JSONCodec;
 use http;
 use itertools::Itertools;
 use lazy_static::lazy_static;
@@ -102,6 +101,8 @@ pub struct JSONHTTPClient {
     pub intermediate_parse_structs: Vec<structs::Struct>,
     pub intermediate_parse_argument: lang::ArgumentDefinition,
     pub return_type_after_transform: lang::Type,
+    #[serde(default)]
+    pub json_codec: JSONCodec,
 }
 
 #[typetag::serde]
@@ -120,14 +121,16 @@ impl lang::Function for JSONHTTPClient {
         // let's start with SECONDLY just to wire the badboy up end to end
         let intermediate_parse_argument_id = self.intermediate_parse_argument.id;
         let transform_code = self.transform_code.clone();
+        let json_codec = self.json_codec.clone();
+        let program_id = interpreter.program_id;
         lang::Value::new_future(async move {
             let request = request.await;
-            match fetch_json(request).await {
+            match fetch_json(request, program_id).await {
                 Ok(json_value) => {
                     let converted_lang_value =
-                        serde_value_to_lang_value(&json_value,
-                                                  get_ok_type_from_result_type(&returns).unwrap(),
-                                                  &interpreter.env.borrow());
+                        json_codec.decode(&json_value,
+                                          get_ok_type_from_result_type(&returns).unwrap(),
+                                          &interpreter.env.borrow());
                     match converted_lang_value {
                         Ok(inner_ok_value) => {
                             let value = ok_result_value(inner_ok_value);
@@ -224,7 +227,8 @@ impl JSONHTTPClient {
                                 transform_code: lang::Block::new(),
                                 return_type_after_transform:
                                     lang::Type::from_spec(&*lang::NULL_TYPESPEC),
-                                intermediate_parse_structs: vec![] };
+                                intermediate_parse_structs: vec![],
+                                json_codec: JSONCodec::default() };
         client.test_code = client.initial_test_code();
         client
     }
@@ -265,99 +269,15 @@ pub fn serde_value_to_lang_value_wrapped_in_enum(value: &serde_json::Value,
                                                  into_type: &lang::Type,
                                                  env: &env::ExecutionEnvironment)
                                                  -> std::result::Result<lang::Value, String> {
-    Ok(ok_result_value(serde_value_to_lang_value(value, into_type, env)?))
+    Ok(ok_result_value(JSONCodec::default().decode(value,
+                                                   into_type,
+                                                   env)?))
 }
 
-fn serde_value_to_lang_value(value: &serde_json::Value,
-                             into_type: &lang::Type,
-                             env: &env::ExecutionEnvironment)
-                             -> std::result::Result<lang::Value, String> {
-    if into_type.matches_spec(&lang::STRING_TYPESPEC) {
-        if let Some(string) = value.as_str() {
-            return Ok(lang::Value::String(string.to_owned()));
-        } else if let Some(float) = value.as_f64() {
-            return Ok(lang::Value::String(float.to_string()));
-        }
-    } else if into_type.matches_spec(&lang::NUMBER_TYPESPEC) {
-        if let Some(int) = value.as_i64() {
-            return Ok(lang::Value::Number(int as i128));
-        }
-    } else if into_type.matches_spec(&lang::NULL_TYPESPEC) {
-        if value.is_null() {
-            return Ok(lang::Value::Null);
-        }
-    } else if into_type.matches_spec(&lang::BOOLEAN_TYPESPEC) {
-        if let Some(b) = value.as_bool() {
-            return Ok(lang::Value::Boolean(b));
-        }
-    } else if into_type.matches_spec(&lang::LIST_TYPESPEC) {
-        if value.is_array() {
-            // TODO: why do we need to clone here??? should our conversion methods take
-            // references?
-            let vec = value.as_array().unwrap().clone();
-            let collection_type = into_type.params.first().unwrap();
-            let collected: std::result::Result<Vec<lang::Value>, String> =
-                vec.into_iter()
-                   .map(|value| serde_value_to_lang_value(&value, collection_type, env))
-                   .collect();
-            return Ok(lang::Value::List(collection_type.clone(), collected?));
-        }
-    } else if let Some(strukt) = env.find_struct(into_type.typespec_id) {
-        if let Some(value) = serde_value_into_struct(value.clone(), strukt, env) {
-            return Ok(value);
-        }
-    } else if into_type.typespec_id == *builtins::OPTION_ENUM_ID {
-        return serde_value_into_option(value.clone(), &into_type.params[0], env).map_err(|e| {
-                                                                                    e.to_string()
-                                                                                });
-    }
-    Err(format!("couldn't decode {:?} into {:?}", value, into_type))
-}
-
-// helper function to `serde_value_to_lang_value`
-fn serde_value_into_option(value: serde_json::Value,
-                           some_type: &lang::Type,
-                           env: &env::ExecutionEnvironment)
-                           -> std::result::Result<lang::Value, Box<dyn std::error::Error>> {
-    if value.is_null() {
-        Ok(none_option_value())
-    } else {
-        Ok(some_option_value(serde_value_to_lang_value(&value,
-                                                       some_type,
-                                                       env)?))
-    }
-}
-
-fn serde_value_into_struct(mut value: serde_json::Value,
-                           strukt: &structs::Struct,
-                           env: &env::ExecutionEnvironment)
-                           -> Option<lang::Value> {
-    let value = value.as_object_mut();
-    if value.is_none() {
-        return None;
-    }
-    let map = value.unwrap();
-    let values: Option<_> =
-        strukt.fields
-              .iter()
-              .map(|strukt_field| {
-                  if strukt_field.field_type.typespec_id == *builtins::OPTION_ENUM_ID
-                     && !map.contains_key(&strukt_field.name)
-                  {
-                      return Some((strukt_field.id, none_option_value()));
-                  }
-
-                  let js_obj = map.remove(&strukt_field.name)?;
-                  Some((strukt_field.id,
-                        serde_value_to_lang_value(&js_obj, &strukt_field.field_type, env).ok()?))
-              })
-              .collect();
-    Some(lang::Value::Struct { struct_id: strukt.id,
-                               values: lang::StructValues(values?) })
-}
-
-pub async fn fetch_json(request: http::Request<String>) -> Result<serde_json::Value> {
-    let resp = http_client::fetch(request).await?;
+pub async fn fetch_json(request: http::Request<String>,
+                        program_id: Option<lang::ID>)
+                        -> Result<serde_json::Value> {
+    let resp = http_client::fetch_for_program(request, program_id).await?;
     Ok(serde_json::from_str(resp.body())?)
 }
 
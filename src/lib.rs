@@ -18,18 +18,25 @@ pub mod lang;
 pub mod structs;
 #[macro_use]
 pub mod env;
+pub mod chat_format;
 mod click_handling;
 pub mod code_loading;
 pub mod config;
+pub mod cron;
+pub mod edit_token;
 pub mod env_genie;
 pub mod http_request;
+pub mod json_codec;
 pub mod json_http_client;
+pub mod logging;
+pub mod metrics;
 mod result;
 pub mod validation;
 #[cfg(not(target_arch = "wasm32"))]
 #[macro_use]
 extern crate diesel;
 pub mod chat_program;
+pub mod chat_service;
 pub mod code_function;
 pub mod code_generation;
 pub mod external_func;
@@ -38,8 +45,13 @@ pub mod function;
 pub mod pystuff;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod schema;
+pub mod scheduled_program;
 pub mod scripts;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod storage;
 pub mod tests;
+pub mod webhook_program;
+pub mod world_merge;
 
 #[cfg(not(feature = "python"))]
 mod fakepystuff;
@@ -80,6 +92,31 @@ pub fn init_interpreter() -> env::Interpreter {
     interpreter
 }
 
+// a brand new interpreter with nothing in it besides the builtins and the world. for swapping out
+// a running world, so that anything taken out of the world goes away for real
+pub fn init_interpreter_with_world(world: &code_loading::TheWorld)
+                                   -> Result<env::Interpreter, Box<dyn std::error::Error>> {
+    let interpreter = init_interpreter();
+    load_world(&mut interpreter.env.borrow_mut(), world);
+    let problems = {
+        let env = interpreter.env.borrow();
+        validation::find_problems_in_world(&EnvGenie::new(&env))
+    };
+    if !problems.is_empty() {
+        return Err(format!("the world has problems: {:?}", problems).into());
+    }
+    Ok(interpreter)
+}
+
+pub fn load_world(env: &mut ExecutionEnvironment, world: &code_loading::TheWorld) {
+    for function in &world.functions {
+        env.add_function_box(function.clone());
+    }
+    for typespec in &world.typespecs {
+        env.add_typespec_box(typespec.clone());
+    }
+}
+
 fn load_builtins(builtins: builtins::Builtins, env: &mut ExecutionEnvironment) {
     for func in builtins.funcs.values() {
         env.add_function_box(func.clone());
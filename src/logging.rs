@@ -0,0 +1,158 @@
+// structured logs: every line is a JSON object with the time, the level, the message, and whatever
+// else goes along with it. lines logged from an instance's thread also say which instance they came
+// from. LOG_LEVEL (debug, info, warn or error) is the least important level that gets printed, and
+// it's info if it isn't set
+use futures_util::FutureExt;
+use lazy_static::lazy_static;
+use serde_derive::Serialize;
+use serde_json::json;
+use std::any::Any;
+use std::cell::Cell;
+use std::panic::AssertUnwindSafe;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::env::ExecutionError;
+use crate::lang;
+use crate::metrics;
+use crate::resolve_all_futures;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+lazy_static! {
+    static ref MIN_LEVEL: Level = match config::get("LOG_LEVEL") {
+        Some("debug") => Level::Debug,
+        Some("warn") => Level::Warn,
+        Some("error") => Level::Error,
+        _ => Level::Info,
+    };
+}
+
+thread_local! {
+    static INSTANCE_ID: Cell<Option<i32>> = Cell::new(None);
+}
+
+// everything logged from this thread from now on says it came from this instance
+pub fn set_instance_id(instance_id: i32) {
+    INSTANCE_ID.with(|id| id.set(Some(instance_id)));
+}
+
+pub fn instance_id() -> Option<i32> {
+    INSTANCE_ID.with(|id| id.get())
+}
+
+// `fields` should be a JSON object, and its keys get added to the line
+pub fn log(level: Level, message: &str, fields: serde_json::Value) {
+    if level < *MIN_LEVEL {
+        return;
+    }
+    let mut line = json!({ "time": unix_now_millis(), "level": level, "message": message });
+    if let Some(instance_id) = instance_id() {
+        line["instance_id"] = json!(instance_id);
+    }
+    if let serde_json::Value::Object(fields) = fields {
+        for (key, value) in fields {
+            line[key] = value;
+        }
+    }
+    println!("{}", line);
+}
+
+pub fn debug(message: &str, fields: serde_json::Value) {
+    log(Level::Debug, message, fields)
+}
+
+pub fn info(message: &str, fields: serde_json::Value) {
+    log(Level::Info, message, fields)
+}
+
+pub fn warn(message: &str, fields: serde_json::Value) {
+    log(Level::Warn, message, fields)
+}
+
+pub fn error(message: &str, fields: serde_json::Value) {
+    log(Level::Error, message, fields)
+}
+
+// a program getting set off by a chat event, a schedule or a webhook. `finish` runs it and logs
+// how it went
+pub struct ProgramRun {
+    program_id: lang::ID,
+    program_name: String,
+    // chat, scheduled or webhook
+    trigger: &'static str,
+    // whoever set it off, if anybody did
+    sender: Option<String>,
+    started_at: SystemTime,
+}
+
+impl ProgramRun {
+    pub fn new(program: &dyn lang::Function,
+               trigger: &'static str,
+               sender: Option<String>)
+               -> Self {
+        Self { program_id: program.id(),
+               program_name: program.name().to_owned(),
+               trigger,
+               sender,
+               started_at: SystemTime::now() }
+    }
+
+    // waits for the program to be done. if it panics, the panic is logged and returned as an
+    // error, instead of taking the whole instance down with it
+    pub async fn finish(self, value: lang::Value) -> Result<lang::Value, String> {
+        let result = AssertUnwindSafe(resolve_all_futures(value)).catch_unwind()
+                                                                 .await
+                                                                 .map_err(panic_message);
+        let duration = SystemTime::now().duration_since(self.started_at)
+                                        .unwrap_or_default();
+        let program_id = self.program_id.to_string();
+        let labels = [("program_id", program_id.as_str()),
+                      ("program_name", self.program_name.as_str()),
+                      ("trigger", self.trigger)];
+        metrics::inc_counter(metrics::PROGRAMS_TRIGGERED, &labels);
+        metrics::observe(metrics::PROGRAM_DURATION, &labels, duration.as_secs_f64());
+        if result.is_err() {
+            metrics::inc_counter(metrics::PROGRAM_ERRORS, &labels);
+        }
+        let (level, outcome, error) = match &result {
+            Ok(_) => (Level::Info, "ok", None),
+            Err(error) => (Level::Error, "error", Some(error.clone())),
+        };
+        log(level,
+            "program run",
+            json!({ "program_id": self.program_id,
+                    "program_name": self.program_name,
+                    "trigger": self.trigger,
+                    "sender": self.sender,
+                    "duration_ms": duration.as_millis() as u64,
+                    "outcome": outcome,
+                    "error": error }));
+        result
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(execution_error) = panic.downcast_ref::<ExecutionError>() {
+        format!("{:?}", execution_error)
+    } else {
+        "the program panicked".to_owned()
+    }
+}
+
+fn unix_now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+                     .map(|duration| duration.as_millis() as u64)
+                     .unwrap_or(0)
+}
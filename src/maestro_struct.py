@@ -11,3 +11,30 @@ class MaestroStruct:
         if name not in self._struct_dict:
             raise NameError
         return self._struct_dict[name]
+
+
+class MaestroObject(dict):
+    # JSON objects coming over from CodeMaestro. they're dicts, but their keys can also be gotten
+    # at as attributes, the same way as on a MaestroStruct
+
+    def __getattr__(self, name):
+        try:
+            return self[name]
+        except KeyError:
+            raise AttributeError(name)
+
+
+def _maestro_json_default(obj):
+    if isinstance(obj, MaestroStruct):
+        return obj._struct_dict
+    return vars(obj)
+
+
+def maestro_from_json(json_string):
+    import json
+    return json.loads(json_string, object_hook=MaestroObject)
+
+
+def maestro_to_json(obj):
+    import json
+    return json.dumps(obj, default=_maestro_json_default)
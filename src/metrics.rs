@@ -0,0 +1,152 @@
+// counters and histograms for the server, rendered in the prometheus text format by `render`,
+// which is what GET /metrics serves. anything counted from an instance's thread gets an
+// instance_id label, same as the logs
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use crate::logging;
+
+pub const MESSAGES_RECEIVED: &str = "cs_messages_received_total";
+pub const PROGRAMS_TRIGGERED: &str = "cs_programs_triggered_total";
+pub const PROGRAM_DURATION: &str = "cs_program_duration_seconds";
+pub const PROGRAM_ERRORS: &str = "cs_program_errors_total";
+pub const HTTP_CLIENT_REQUESTS: &str = "cs_http_client_requests_total";
+pub const HTTP_CLIENT_FAILURES: &str = "cs_http_client_failures_total";
+pub const CHAT_SERVICE_RECONNECTS: &str = "cs_chat_service_reconnects_total";
+
+// name, type, help. everything in here shows up in `render`, even before it's been counted
+const METRICS: &[(&str, &str, &str)] =
+    &[(MESSAGES_RECEIVED, "counter", "Chat events received from a chat service."),
+      (PROGRAMS_TRIGGERED, "counter", "Programs set off by a chat event, schedule or webhook."),
+      (PROGRAM_DURATION, "histogram", "How long programs took to run, in seconds."),
+      (PROGRAM_ERRORS, "counter", "Program runs that crashed."),
+      (HTTP_CLIENT_REQUESTS,
+       "counter",
+       "HTTP requests made by the server and its programs, by method and program_id."),
+      (HTTP_CLIENT_FAILURES,
+       "counter",
+       "HTTP requests that didn't go through or came back with an error status."),
+      (CHAT_SERVICE_RECONNECTS, "counter", "Times a chat service connection dropped.")];
+
+// in seconds
+const DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Default)]
+struct Histogram {
+    // one for every bucket, each counting everything at or under it
+    bucket_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Registry {
+    counters: BTreeMap<(&'static str, Labels), u64>,
+    histograms: BTreeMap<(&'static str, Labels), Histogram>,
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+}
+
+pub fn inc_counter(name: &'static str, labels: &[(&'static str, &str)]) {
+    let mut registry = REGISTRY.lock().unwrap();
+    *registry.counters
+             .entry((name, with_instance_id(labels)))
+             .or_insert(0) += 1;
+}
+
+pub fn observe(name: &'static str, labels: &[(&'static str, &str)], value: f64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    let histogram =
+        registry.histograms
+                .entry((name, with_instance_id(labels)))
+                .or_insert_with(|| Histogram { bucket_counts: vec![0; DURATION_BUCKETS.len()],
+                                               ..Histogram::default() });
+    for (bucket, count) in DURATION_BUCKETS.iter()
+                                           .zip(histogram.bucket_counts.iter_mut())
+    {
+        if value <= *bucket {
+            *count += 1;
+        }
+    }
+    histogram.sum += value;
+    histogram.count += 1;
+}
+
+fn with_instance_id(labels: &[(&'static str, &str)]) -> Labels {
+    let mut all_labels = vec![];
+    if let Some(instance_id) = logging::instance_id() {
+        all_labels.push(("instance_id", instance_id.to_string()));
+    }
+    all_labels.extend(labels.iter()
+                            .map(|(name, value)| (*name, (*value).to_owned())));
+    all_labels
+}
+
+pub fn render() -> String {
+    let registry = REGISTRY.lock().unwrap();
+    let mut out = String::new();
+    for (name, typ, help) in METRICS {
+        writeln!(out, "# HELP {} {}", name, help).unwrap();
+        writeln!(out, "# TYPE {} {}", name, typ).unwrap();
+        for ((_, labels), value) in registry.counters
+                                            .iter()
+                                            .filter(|((metric, _), _)| metric == name)
+        {
+            writeln!(out, "{}{} {}", name, render_labels(labels, None), value).unwrap();
+        }
+        for ((_, labels), histogram) in registry.histograms
+                                                .iter()
+                                                .filter(|((metric, _), _)| metric == name)
+        {
+            for (bucket, count) in DURATION_BUCKETS.iter().zip(&histogram.bucket_counts) {
+                let le = bucket.to_string();
+                writeln!(out,
+                         "{}_bucket{} {}",
+                         name,
+                         render_labels(labels, Some(&le)),
+                         count).unwrap();
+            }
+            writeln!(out,
+                     "{}_bucket{} {}",
+                     name,
+                     render_labels(labels, Some("+Inf")),
+                     histogram.count).unwrap();
+            writeln!(out,
+                     "{}_sum{} {}",
+                     name,
+                     render_labels(labels, None),
+                     histogram.sum).unwrap();
+            writeln!(out,
+                     "{}_count{} {}",
+                     name,
+                     render_labels(labels, None),
+                     histogram.count).unwrap();
+        }
+    }
+    out
+}
+
+fn render_labels(labels: &Labels, le: Option<&str>) -> String {
+    let rendered = labels.iter()
+                         .map(|(name, value)| (*name, value.as_str()))
+                         .chain(le.map(|le| ("le", le)))
+                         .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+                         .collect::<Vec<_>>();
+    if rendered.is_empty() {
+        "".to_owned()
+    } else {
+        format!("{{{}}}", rendered.join(","))
+    }
+}
+
+fn escape(label_value: &str) -> String {
+    label_value.replace('\\', "\\\\")
+               .replace('"', "\\\"")
+               .replace('\n', "\\n")
+}
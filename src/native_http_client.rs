@@ -3,11 +3,36 @@ use http::{Request, Response};
 use reqwest::r#async::Client;
 
 use super::asynk::forward;
+use super::lang;
+use super::metrics;
 
 #[allow(dead_code)] // compiler bug warns for this type alias not being used :/
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 pub async fn fetch(request: Request<String>) -> Result<Response<String>> {
+    fetch_for_program(request, None).await
+}
+
+// counts every request, and every one that fails, for /metrics. requests the server makes on its
+// own have an empty program_id
+pub async fn fetch_for_program(request: Request<String>,
+                               program_id: Option<lang::ID>)
+                               -> Result<Response<String>> {
+    let method = request.method().to_string();
+    let program_id = program_id.map(|id| id.to_string()).unwrap_or_default();
+    let labels = [("method", method.as_str()),
+                  ("program_id", program_id.as_str())];
+    metrics::inc_counter(metrics::HTTP_CLIENT_REQUESTS, &labels);
+    let response = send(request).await;
+    let failed = response.as_ref()
+                         .map_or(true, |response| !response.status().is_success());
+    if failed {
+        metrics::inc_counter(metrics::HTTP_CLIENT_FAILURES, &labels);
+    }
+    response
+}
+
+async fn send(request: Request<String>) -> Result<Response<String>> {
     let resp = forward(Client::new().request(request.method().clone(),
                                                     &request.uri().to_string())
                                            .headers(request.headers().clone())
@@ -4,18 +4,17 @@ use std::collections::HashMap;
 use std::hash;
 use std::rc::Rc;
 
-use itertools::Itertools;
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyIterator, PyObjectRef};
+use pyo3::types::{PyDict, PyObjectRef};
 use serde_derive::{Deserialize, Serialize};
 
 use super::env;
 use super::external_func;
-use super::external_func::ValueWithEnv;
 use super::function;
 use super::lang;
-use super::structs;
 use crate::env::ExecutionError;
+use crate::json_codec;
+use crate::json_codec::JSONCodec;
 
 thread_local! {
     pub static GILGUARD: Rc<GILGuard> = Rc::new(Python::acquire_gil());
@@ -69,55 +68,19 @@ pub struct PyFunc {
     pub args: Vec<lang::ArgumentDefinition>,
 }
 
-impl<'a> IntoPyObject for ValueWithEnv<'a> {
-    fn into_object(self, py: Python) -> PyObject {
-        use super::lang::Value::*;
-        match (self.env, self.value) {
-            (_, Null) => ().into_object(py),
-            (_, Boolean(b)) => b.into_object(py),
-            (_, String(s)) => s.into_object(py),
-            // not quite sure what to do with these...
-            (_, Number(i)) => i.into_object(py),
-            (env, EnumVariant { box value, .. }) => Self { value, env }.into_object(py),
-            (env, List(v)) => v.into_iter()
-                               .map(|item| {
-                                   Self { value: item,
-                                          env: env }.into_object(py)
-                               })
-                               .collect_vec()
-                               .into_object(py),
-            (env, Struct { struct_id, values }) => {
-                let strukt = env.find_struct(struct_id).unwrap();
-                let ms = py.eval("MaestroStruct", None, None).unwrap();
-                let field_by_id = strukt.field_by_id();
-                let value_by_name: HashMap<&str, ValueWithEnv> =
-                    values.into_iter()
-                          .map(|(id, value)| {
-                              let val_with_env = Self { value, env };
-                              let name = &field_by_id.get(&id).unwrap().name;
-                              (name.as_str(), val_with_env)
-                          })
-                          .collect();
-
-                ms.call1((&strukt.name, into_pyobject(value_by_name, py)))
-                  .unwrap()
-                  .to_object(py)
-            }
-            // TODO: unless we share the event loop with python, we should pry wait on the fut
-            // before passing it along to python. i suppose that would require threading the
-            // async executor through (which should be pretty doable)
-            // some info on it here https://stackoverflow.com/questions/40329421/)
-            //
-            // but if we did that, then you'd need to know on the python side that it's a future
-            //
-            // i think instead, what we can do, is write a function from lang::Value => lang::Value
-            // that recurses through the datastructure and if anything in there is a future, it'll
-            // get resolved. then we just tell the interpreter resolve any futures before passing
-            // them along to either a python function or a javascript function. i mean we're already
-            // doing said recursion in here, so it should be pretty easy to implement!
-            (_env, Future(_value_fut)) => unimplemented!(),
-        }
-    }
+// values go over to python as JSON, encoded the same way as everywhere else. JSON objects come
+// out as MaestroObjects, so struct fields can still be gotten at as attributes. anything that
+// can't be turned into JSON, like a function, is an error instead of a crash
+fn to_pyobject(value: &lang::Value,
+               env: &env::ExecutionEnvironment,
+               py: Python)
+               -> Result<PyObject, ExecutionError> {
+    let json = json_codec::to_string(value, &JSONCodec::default(), env)
+        .map_err(|_| ExecutionError::PythonSerializationError)?;
+    py.eval("maestro_from_json", None, None)
+      .and_then(|from_json| from_json.call1((json,)))
+      .map(|object| object.to_object(py))
+      .map_err(|_| ExecutionError::PythonSerializationError)
 }
 
 // TODO: i think a ref to the python interpreter could live inside of here
@@ -133,11 +96,15 @@ impl PyFunc {
 }
 
 impl PyFunc {
-    fn extract(&self, pyobjectref: &PyObjectRef, env: &env::ExecutionEnvironment) -> lang::Value {
+    fn extract(&self,
+               pyobjectref: &PyObjectRef,
+               env: &env::ExecutionEnvironment)
+               -> Result<lang::Value, ExecutionError> {
         use self::lang::Function;
         self.ex(pyobjectref, &self.returns(), env)
     }
 
+    // and they come back as JSON too
     fn ex(&self,
           pyobjectref: &PyObjectRef,
           into_type: &lang::Type,
@@ -145,54 +112,12 @@ impl PyFunc {
           -> Result<lang::Value, ExecutionError> {
         let gil = getgil();
         let py = gil.python();
-
-        // TODO: this is terrible. i should've used the FromPyObject trait instead of this
-        // verbose bullshit
-        if into_type.matches_spec(&lang::STRING_TYPESPEC) {
-            if let Ok(string) = pyobjectref.extract() {
-                return Ok(lang::Value::String(string));
-            }
-        } else if into_type.matches_spec(&lang::NUMBER_TYPESPEC) {
-            if let Ok(int) = pyobjectref.extract() {
-                return Ok(lang::Value::Number(int));
-            }
-        } else if into_type.matches_spec(&lang::NULL_TYPESPEC) {
-            if pyobjectref.is_none() {
-                return Ok(lang::Value::Null);
-            }
-        } else if into_type.matches_spec(&lang::LIST_TYPESPEC) {
-            let pyobj: PyObject = pyobjectref.extract().unwrap();
-            let collection_type = into_type.params.first().unwrap();
-            // TODO: error handlign! just figure out what's neccessary by testing it out in the
-            // GUI
-            let iter = PyIterator::from_object(py, &pyobj).unwrap();
-            let collected: Vec<lang::Value> =
-                iter.map(|pyresult| self.ex(pyresult.unwrap(), collection_type, env))
-                    .collect();
-            return Ok(lang::Value::List(collected));
-        } else if let Some(strukt) = env.find_struct(into_type.typespec_id) {
-            if let Ok(value) = self.pyobject_into_struct(pyobjectref, strukt, env) {
-                return Ok(value);
-            }
-        }
-        Err(ExecutionError::PythonDeserializationError)
-    }
-
-    fn pyobject_into_struct(&self,
-                            pyobjectref: &PyObjectRef,
-                            strukt: &structs::Struct,
-                            env: &env::ExecutionEnvironment)
-                            -> PyResult<lang::Value> {
-        let values: PyResult<HashMap<lang::ID, lang::Value>> =
-            strukt.fields
-                  .iter()
-                  .map(|strukt_field| {
-                      let field_ref = try_get_name(pyobjectref, &strukt_field.name)?;
-                      Ok((strukt_field.id, self.ex(&field_ref, &strukt_field.field_type, env)))
-                  })
-                  .collect();
-        Ok(lang::Value::Struct { struct_id: strukt.id,
-                                 values: values? })
+        let json: String = py.eval("maestro_to_json", None, None)
+                             .and_then(|to_json| to_json.call1((pyobjectref,)))
+                             .and_then(|dumped| dumped.extract())
+                             .map_err(|_| ExecutionError::PythonDeserializationError)?;
+        json_codec::parse(&json, into_type, &JSONCodec::default(), env)
+            .map_err(|_| ExecutionError::PythonDeserializationError)
     }
 
     fn py_exception_to_error(&self, pyerror: &PyErr) -> (String, String) {
@@ -252,11 +177,15 @@ impl lang::Function for PyFunc {
             return panic!(self.py_exception_to_error(&e));
         }
 
-        let named_args: HashMap<String, ValueWithEnv> =
+        let named_args: Result<HashMap<String, PyObject>, ExecutionError> =
             external_func::to_named_args(self, args).map(|(name, value)| {
-                                                        (name, ValueWithEnv { env: &env, value })
+                                                        Ok((name, to_pyobject(&value, &env, py)?))
                                                     })
                                                     .collect();
+        let named_args = match named_args {
+            Ok(named_args) => named_args,
+            Err(e) => return external_func::error_value(self, e),
+        };
 
         let args_dict = into_pyobject(named_args, py);
         let locals_with_params = mix_args_with_locals(py, locals, args_dict);
@@ -265,8 +194,10 @@ impl lang::Function for PyFunc {
             panic!(self.py_exception_to_error(&pyerr));
         }
 
-        let eval_result = eval_result.unwrap();
-        self.extract(eval_result, &env)
+        match self.extract(eval_result.unwrap(), &env) {
+            Ok(value) => value,
+            Err(e) => external_func::error_value(self, e),
+        }
     }
 
     fn name(&self) -> &str {
@@ -311,12 +242,3 @@ fn into_pyobject<K, V, H>(from: HashMap<K, V, H>, py: Python) -> PyObject
     }
     dict.into()
 }
-
-fn try_get_name<'a>(pyobjectref: &'a PyObjectRef, name: &str) -> PyResult<&'a PyObjectRef> {
-    if pyobjectref.hasattr("__getitem__")? {
-        let getitem = pyobjectref.getattr("__getitem__")?;
-        getitem.call1(name)
-    } else {
-        pyobjectref.getattr(name)
-    }
-}
@@ -0,0 +1,165 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use super::env;
+use super::lang;
+use crate::chat_format::OutgoingChatMessage;
+use crate::chat_program::ReplyContext;
+use crate::chat_service::ReplyOrigin;
+use crate::cron::{CronSchedule, UtcTime};
+use crate::env::Interpreter;
+use crate::lang::Function;
+use crate::logging;
+use crate::logging::ProgramRun;
+use crate::{validation, EnvGenie};
+
+// a program that runs on a timer instead of in response to a chat message. anything it sends with
+// ChatReply goes to `channel` on whichever chat service is named by `service` (irc, discord,
+// slack)
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ScheduledProgram {
+    pub id: lang::ID,
+    pub name: String,
+    // a cron expression, see cron.rs for what's supported
+    pub schedule: String,
+    pub service: String,
+    pub channel: String,
+    pub code: lang::Block,
+}
+
+// a reply that came out of a scheduled program, along with where it's supposed to go
+#[derive(Debug, Clone)]
+pub struct ScheduledReply {
+    pub service: String,
+    pub channel: String,
+    pub message: OutgoingChatMessage,
+}
+
+impl ScheduledProgram {
+    pub fn new() -> Self {
+        Self { id: lang::new_id(),
+               name: "New scheduled program".to_string(),
+               schedule: "0 9 * * mon-fri".to_string(),
+               service: "irc".to_string(),
+               channel: "".to_string(),
+               code: lang::Block::new() }
+    }
+
+    pub fn parse_schedule(&self) -> Result<CronSchedule, String> {
+        self.schedule.parse()
+    }
+
+    pub fn is_scheduled_at(&self, time: &UtcTime) -> bool {
+        self.parse_schedule()
+            .map(|schedule| schedule.matches(time))
+            .unwrap_or(false)
+    }
+
+    pub fn try_to_trigger(&self,
+                          interpreter: env::Interpreter,
+                          time: &UtcTime)
+                          -> Option<lang::Value> {
+        if !self.is_scheduled_at(time) {
+            return None;
+        }
+
+        let can_be_run = {
+            let env = interpreter.env.borrow();
+            let env_genie = EnvGenie::new(&env);
+            validation::can_be_run(self, &env_genie)
+        };
+        if !can_be_run {
+            logging::warn("not running a scheduled program that would crash",
+                          json!({ "program_id": self.id, "program_name": self.name }));
+            return None;
+        }
+
+        Some(self.call(interpreter, HashMap::new()))
+    }
+}
+
+#[typetag::serde]
+impl lang::Function for ScheduledProgram {
+    fn call(&self,
+            mut interpreter: env::Interpreter,
+            args: HashMap<lang::ID, lang::Value>)
+            -> lang::Value {
+        // XXX: shouldn't the caller do this???? duped with CodeFunction
+        for (id, value) in args {
+            interpreter.set_local_variable(id, value);
+        }
+
+        let code = self.code.clone();
+        lang::Value::new_future(async move { interpreter.evaluate(&lang::CodeNode::Block(code)).await })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "Runs on a schedule"
+    }
+
+    fn id(&self) -> lang::ID {
+        self.id
+    }
+
+    fn takes_args(&self) -> Vec<lang::ArgumentDefinition> {
+        vec![]
+    }
+
+    fn returns(&self) -> lang::Type {
+        lang::Type::from_spec(&*lang::NULL_TYPESPEC)
+    }
+
+    fn cs_code(&self) -> Box<dyn Iterator<Item = &lang::Block> + '_> {
+        Box::new(std::iter::once(&self.code))
+    }
+}
+
+// runs every scheduled program that's due at `time`, and returns what each one replied with, along
+// with where it's supposed to go
+pub fn tick(interp: &Interpreter,
+            time: UtcTime)
+            -> Pin<Box<dyn std::future::Future<Output = Vec<ScheduledReply>>>> {
+    // this is mandatory or else we'll borrow the Env for too long
+    let scheduled_programs = {
+        let env = interp.env.borrow();
+        let env_genie = EnvGenie::new(&env);
+        env_genie.list_scheduled_programs()
+                 .cloned()
+                 .collect::<Vec<_>>()
+    };
+    let triggered =
+        scheduled_programs.into_iter()
+                          .filter_map(|sp| {
+                              let reply_context =
+                                  ReplyContext::new(sp.service.clone(),
+                                                    ReplyOrigin::channel(sp.channel.clone()));
+                              let interp = interp.new_stack_frame()
+                                                 .with_reply_context(reply_context.clone())
+                                                 .with_program_id(sp.id);
+                              let value = sp.try_to_trigger(interp, &time)?;
+                              let program_run = ProgramRun::new(&sp, "scheduled", None);
+                              Some((reply_context, program_run, value))
+                          })
+                          .collect::<Vec<_>>();
+
+    Box::pin(async move {
+        let mut replies = vec![];
+        for (reply_context, program_run, value) in triggered {
+            // a program that crashed has already been logged, and whatever it replied before it
+            // crashed still gets sent
+            let _ = program_run.finish(value).await;
+            for message in reply_context.take_replies() {
+                replies.push(ScheduledReply { service: reply_context.service.clone(),
+                                              channel: reply_context.origin.channel.clone(),
+                                              message });
+            }
+        }
+        replies
+    })
+}
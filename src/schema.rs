@@ -1,3 +1,18 @@
+table! {
+    audit_log (id) {
+        id -> Int4,
+        instance_id -> Int4,
+        program_id -> Text,
+        program_name -> Text,
+        change -> Text,
+        changed_by -> Text,
+        edit_token_id -> Text,
+        changed_at -> Int8,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     codes (id) {
         id -> Int4,
@@ -9,6 +24,24 @@ table! {
     }
 }
 
+table! {
+    instances (id) {
+        id -> Int4,
+        disabled -> Bool,
+        token_generation -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    revoked_edit_tokens (token_id) {
+        token_id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     service_configs (id) {
         id -> Int4,
@@ -21,4 +54,4 @@ table! {
     }
 }
 
-allow_tables_to_appear_in_same_query!(codes, service_configs,);
+allow_tables_to_appear_in_same_query!(audit_log, codes, instances, revoked_edit_tokens, service_configs,);
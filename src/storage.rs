@@ -0,0 +1,183 @@
+// where the server keeps its instances, their code and their service configs. STORAGE picks the
+// backend:
+//
+// - postgres (the default): DATABASE_URL is a postgres URL, and the tables come from the diesel
+//   migrations in migrations/
+// - sqlite: DATABASE_URL is the path to the database file, which gets created (tables and all) if
+//   it isn't there yet
+// - filesystem: STORAGE_DIR is a directory with a JSON file for every row
+//
+// the methods all block, so the server runs them off on a thread pool
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::config;
+
+pub mod filesystem;
+pub mod postgres;
+pub mod sqlite;
+
+pub use filesystem::FilesystemStorage;
+pub use postgres::PostgresStorage;
+pub use sqlite::SqliteStorage;
+
+pub trait Storage: Send + Sync {
+    fn load_instances(&self) -> StorageResult<Vec<Instance>>;
+    fn load_instance(&self, instance_id: i32) -> StorageResult<Instance>;
+    fn create_instance(&self) -> StorageResult<Instance>;
+    fn set_instance_disabled(&self, instance_id: i32, disabled: bool) -> StorageResult<()>;
+    // returns the instance with its new token generation
+    fn bump_token_generation(&self, instance_id: i32) -> StorageResult<Instance>;
+
+    fn insert_code(&self, new_code: NewCode) -> StorageResult<()>;
+    // every row is a whole world, so the newest one is the only one that counts
+    fn load_latest_code(&self, instance_id: i32) -> StorageResult<Option<Code>>;
+
+    fn load_service_configs(&self) -> StorageResult<Vec<ServiceConfig>>;
+    fn load_service_configs_for_instance(&self,
+                                         instance_id: i32)
+                                         -> StorageResult<Vec<ServiceConfig>>;
+    fn insert_service_config(&self,
+                             new_service_config: NewServiceConfig)
+                             -> StorageResult<ServiceConfig>;
+    // returns the service config that was deleted
+    fn delete_service_config(&self, service_config_id: i32) -> StorageResult<ServiceConfig>;
+
+    fn insert_audit_entries(&self, new_audit_entries: Vec<NewAuditEntry>) -> StorageResult<()>;
+    // newest first
+    fn load_audit_log(&self, instance_id: i32) -> StorageResult<Vec<AuditEntry>>;
+
+    // revoking a token that's already revoked is fine
+    fn revoke_edit_token(&self, token_id: String) -> StorageResult<()>;
+    fn is_edit_token_revoked(&self, token_id: String) -> StorageResult<bool>;
+}
+
+pub fn from_config() -> StorageResult<Box<dyn Storage>> {
+    match config::get("STORAGE").unwrap_or("postgres") {
+        "postgres" => Ok(Box::new(PostgresStorage::connect(database_url()?)?)),
+        "sqlite" => Ok(Box::new(SqliteStorage::open(database_url()?)?)),
+        "filesystem" => {
+            let dir =
+                config::get("STORAGE_DIR").ok_or_else(|| StorageError::new("STORAGE_DIR not set"))?;
+            Ok(Box::new(FilesystemStorage::open(dir)?))
+        }
+        otherwise => Err(StorageError::new(format!("STORAGE should be postgres, sqlite or \
+                                                    filesystem, not {}",
+                                                   otherwise))),
+    }
+}
+
+fn database_url() -> StorageResult<&'static str> {
+    config::get("DATABASE_URL").ok_or_else(|| StorageError::new("DATABASE_URL not set"))
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct Instance {
+    pub id: i32,
+    pub disabled: bool,
+    pub token_generation: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewCode {
+    pub added_by: String,
+    pub code: serde_json::Value,
+    pub instance_id: i32,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct Code {
+    pub id: i32,
+    pub added_by: String,
+    pub code: serde_json::Value,
+    pub instance_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewServiceConfig {
+    pub instance_id: i32,
+    pub nickname: String,
+    pub service_type: String,
+    pub config: serde_json::Value,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceConfig {
+    pub id: i32,
+    pub nickname: String,
+    pub instance_id: i32,
+    pub service_type: String,
+    pub config: serde_json::Value,
+}
+
+// a record of somebody changing a program (or any other function or typespec) in an instance's
+// world
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewAuditEntry {
+    pub instance_id: i32,
+    pub program_id: String,
+    pub program_name: String,
+    // added, changed or removed
+    pub change: String,
+    // whoever the edit token was issued to
+    pub changed_by: String,
+    pub edit_token_id: String,
+    // in unix seconds
+    pub changed_at: i64,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub instance_id: i32,
+    pub program_id: String,
+    pub program_name: String,
+    pub change: String,
+    pub changed_by: String,
+    pub edit_token_id: String,
+    pub changed_at: i64,
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+// just a message, but Send, so it can come back from the thread pool
+#[derive(Debug)]
+pub struct StorageError(String);
+
+impl StorageError {
+    pub fn new(message: impl Into<String>) -> Self {
+        StorageError(message.into())
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<diesel::result::Error> for StorageError {
+    fn from(e: diesel::result::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for StorageError {
+    fn from(e: diesel::r2d2::PoolError) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
@@ -0,0 +1,267 @@
+// every row is its own JSON file, named after its ID:
+//
+//   instances/<instance id>.json
+//   codes/<instance id>/<code id>.json
+//   service_configs/<service config id>.json
+//   revoked_edit_tokens/<token id>
+//   audit_log/<instance id>/<audit entry id>.json
+//
+// each directory of rows also has a .next_id file with the ID the next row will get, so deleting
+// the newest row doesn't mean its ID gets handed out again.
+//
+// so it's easy to poke at by hand. this is for running locally, not for running a whole bunch of
+// instances
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::{
+    AuditEntry, Code, Instance, NewAuditEntry, NewCode, NewServiceConfig, ServiceConfig, Storage,
+    StorageError, StorageResult,
+};
+
+pub struct FilesystemStorage {
+    dir: PathBuf,
+    // held while reading and writing, so two writers can't hand out the same ID
+    lock: Mutex<()>,
+}
+
+impl FilesystemStorage {
+    pub fn open(dir: impl Into<PathBuf>) -> StorageResult<Self> {
+        let dir = dir.into();
+        for table in &["instances",
+                       "codes",
+                       "service_configs",
+                       "revoked_edit_tokens",
+                       "audit_log"]
+        {
+            fs::create_dir_all(dir.join(table))?;
+        }
+        Ok(Self { dir,
+                  lock: Mutex::new(()) })
+    }
+
+    fn codes_dir(&self, instance_id: i32) -> PathBuf {
+        self.dir.join("codes").join(instance_id.to_string())
+    }
+
+    fn audit_log_dir(&self, instance_id: i32) -> PathBuf {
+        self.dir.join("audit_log").join(instance_id.to_string())
+    }
+
+    fn update_instance(&self,
+                       instance_id: i32,
+                       update: impl FnOnce(&mut Instance))
+                       -> StorageResult<Instance> {
+        let _lock = self.lock.lock().unwrap();
+        let dir = self.dir.join("instances");
+        let mut instance: Instance = read_row(&dir, instance_id)?.ok_or_else(|| {
+                                         StorageError::new(format!("instance {} not found",
+                                                                   instance_id))
+                                     })?;
+        update(&mut instance);
+        write_row(&dir, instance_id, &instance)?;
+        Ok(instance)
+    }
+}
+
+impl Storage for FilesystemStorage {
+    fn load_instances(&self) -> StorageResult<Vec<Instance>> {
+        let _lock = self.lock.lock().unwrap();
+        read_all(&self.dir.join("instances"))
+    }
+
+    fn load_instance(&self, instance_id: i32) -> StorageResult<Instance> {
+        let _lock = self.lock.lock().unwrap();
+        read_row(&self.dir.join("instances"), instance_id)?
+            .ok_or_else(|| StorageError::new(format!("instance {} not found", instance_id)))
+    }
+
+    fn create_instance(&self) -> StorageResult<Instance> {
+        let _lock = self.lock.lock().unwrap();
+        let dir = self.dir.join("instances");
+        let instance = Instance { id: next_id(&dir)?,
+                                  disabled: false,
+                                  token_generation: 0 };
+        write_row(&dir, instance.id, &instance)?;
+        Ok(instance)
+    }
+
+    fn set_instance_disabled(&self, instance_id: i32, disabled: bool) -> StorageResult<()> {
+        self.update_instance(instance_id, |instance| instance.disabled = disabled)?;
+        Ok(())
+    }
+
+    fn bump_token_generation(&self, instance_id: i32) -> StorageResult<Instance> {
+        self.update_instance(instance_id, |instance| instance.token_generation += 1)
+    }
+
+    fn insert_code(&self, new_code: NewCode) -> StorageResult<()> {
+        let _lock = self.lock.lock().unwrap();
+        let dir = self.codes_dir(new_code.instance_id);
+        fs::create_dir_all(&dir)?;
+        let code = Code { id: next_id(&dir)?,
+                          added_by: new_code.added_by,
+                          code: new_code.code,
+                          instance_id: new_code.instance_id };
+        write_row(&dir, code.id, &code)
+    }
+
+    fn load_latest_code(&self, instance_id: i32) -> StorageResult<Option<Code>> {
+        let _lock = self.lock.lock().unwrap();
+        let dir = self.codes_dir(instance_id);
+        if !dir.exists() {
+            return Ok(None);
+        }
+        match ids(&dir)?.into_iter().max() {
+            Some(latest_id) => read_row(&dir, latest_id),
+            None => Ok(None),
+        }
+    }
+
+    fn load_service_configs(&self) -> StorageResult<Vec<ServiceConfig>> {
+        let _lock = self.lock.lock().unwrap();
+        read_all(&self.dir.join("service_configs"))
+    }
+
+    fn load_service_configs_for_instance(&self,
+                                         instance_id: i32)
+                                         -> StorageResult<Vec<ServiceConfig>> {
+        Ok(self.load_service_configs()?
+               .into_iter()
+               .filter(|service_config| service_config.instance_id == instance_id)
+               .collect())
+    }
+
+    fn insert_service_config(&self,
+                             new_service_config: NewServiceConfig)
+                             -> StorageResult<ServiceConfig> {
+        let _lock = self.lock.lock().unwrap();
+        let dir = self.dir.join("service_configs");
+        let service_config = ServiceConfig { id: next_id(&dir)?,
+                                             nickname: new_service_config.nickname,
+                                             instance_id: new_service_config.instance_id,
+                                             service_type: new_service_config.service_type,
+                                             config: new_service_config.config };
+        write_row(&dir, service_config.id, &service_config)?;
+        Ok(service_config)
+    }
+
+    fn delete_service_config(&self, service_config_id: i32) -> StorageResult<ServiceConfig> {
+        let _lock = self.lock.lock().unwrap();
+        let dir = self.dir.join("service_configs");
+        let service_config = read_row(&dir, service_config_id)?.ok_or_else(|| {
+                                 StorageError::new(format!("service config {} not found",
+                                                           service_config_id))
+                             })?;
+        fs::remove_file(row_path(&dir, service_config_id))?;
+        Ok(service_config)
+    }
+
+    fn insert_audit_entries(&self, new_audit_entries: Vec<NewAuditEntry>) -> StorageResult<()> {
+        let _lock = self.lock.lock().unwrap();
+        for entry in new_audit_entries {
+            let dir = self.audit_log_dir(entry.instance_id);
+            fs::create_dir_all(&dir)?;
+            let audit_entry = AuditEntry { id: next_id(&dir)?,
+                                           instance_id: entry.instance_id,
+                                           program_id: entry.program_id,
+                                           program_name: entry.program_name,
+                                           change: entry.change,
+                                           changed_by: entry.changed_by,
+                                           edit_token_id: entry.edit_token_id,
+                                           changed_at: entry.changed_at };
+            write_row(&dir, audit_entry.id, &audit_entry)?;
+        }
+        Ok(())
+    }
+
+    fn load_audit_log(&self, instance_id: i32) -> StorageResult<Vec<AuditEntry>> {
+        let _lock = self.lock.lock().unwrap();
+        let dir = self.audit_log_dir(instance_id);
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut audit_log = read_all::<AuditEntry>(&dir)?;
+        audit_log.reverse();
+        Ok(audit_log)
+    }
+
+    fn revoke_edit_token(&self, token_id: String) -> StorageResult<()> {
+        let _lock = self.lock.lock().unwrap();
+        fs::write(self.dir.join("revoked_edit_tokens").join(token_id), "")?;
+        Ok(())
+    }
+
+    fn is_edit_token_revoked(&self, token_id: String) -> StorageResult<bool> {
+        let _lock = self.lock.lock().unwrap();
+        Ok(self.dir.join("revoked_edit_tokens").join(token_id).exists())
+    }
+}
+
+fn row_path(dir: &Path, id: i32) -> PathBuf {
+    dir.join(format!("{}.json", id))
+}
+
+// the IDs of every row in the directory, going by the filenames
+fn ids(dir: &Path) -> StorageResult<Vec<i32>> {
+    let mut ids = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "json") {
+            if let Some(id) = path.file_stem()
+                                  .and_then(|stem| stem.to_str())
+                                  .and_then(|stem| stem.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+// directories from before there was a counter start counting after the rows already in there
+fn next_id(dir: &Path) -> StorageResult<i32> {
+    let counter_path = dir.join(".next_id");
+    let after_existing_rows = ids(dir)?.into_iter().max().unwrap_or(0) + 1;
+    let next_id = if counter_path.exists() {
+        let counter = fs::read_to_string(&counter_path)?;
+        let counter: i32 =
+            counter.trim()
+                   .parse()
+                   .map_err(|_| {
+                       StorageError::new(format!("bad ID counter in {}", dir.display()))
+                   })?;
+        counter.max(after_existing_rows)
+    } else {
+        after_existing_rows
+    };
+    fs::write(&counter_path, (next_id + 1).to_string())?;
+    Ok(next_id)
+}
+
+fn read_all<T: DeserializeOwned>(dir: &Path) -> StorageResult<Vec<T>> {
+    ids(dir)?.into_iter()
+             .map(|id| Ok(serde_json::from_slice(&fs::read(row_path(dir, id))?)?))
+             .collect()
+}
+
+fn read_row<T: DeserializeOwned>(dir: &Path, id: i32) -> StorageResult<Option<T>> {
+    let path = row_path(dir, id);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+}
+
+// writes to a temp file first and then moves it into place, so a crash halfway through doesn't
+// leave a broken row behind
+fn write_row<T: Serialize>(dir: &Path, id: i32, row: &T) -> StorageResult<()> {
+    let tmp_path = dir.join(format!(".{}.json.tmp", id));
+    fs::write(&tmp_path, serde_json::to_vec_pretty(row)?)?;
+    fs::rename(tmp_path, row_path(dir, id))?;
+    Ok(())
+}
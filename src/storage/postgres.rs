@@ -0,0 +1,188 @@
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::r2d2;
+
+use super::{
+    AuditEntry, Code, Instance, NewAuditEntry, NewCode, NewServiceConfig, ServiceConfig, Storage,
+    StorageResult,
+};
+use crate::schema::{audit_log, codes, instances, revoked_edit_tokens, service_configs};
+
+type Pool = r2d2::Pool<r2d2::ConnectionManager<PgConnection>>;
+
+// the created_at and updated_at columns are only for looking at the DB by hand, so they don't get
+// selected
+type InstanceColumns = (instances::id, instances::disabled, instances::token_generation);
+const INSTANCE_COLUMNS: InstanceColumns =
+    (instances::id, instances::disabled, instances::token_generation);
+
+type CodeColumns = (codes::id, codes::added_by, codes::code, codes::instance_id);
+const CODE_COLUMNS: CodeColumns = (codes::id, codes::added_by, codes::code, codes::instance_id);
+
+type ServiceConfigColumns = (service_configs::id,
+                             service_configs::nickname,
+                             service_configs::instance_id,
+                             service_configs::service_type,
+                             service_configs::config);
+const SERVICE_CONFIG_COLUMNS: ServiceConfigColumns = (service_configs::id,
+                                                      service_configs::nickname,
+                                                      service_configs::instance_id,
+                                                      service_configs::service_type,
+                                                      service_configs::config);
+
+type AuditEntryColumns = (audit_log::id,
+                          audit_log::instance_id,
+                          audit_log::program_id,
+                          audit_log::program_name,
+                          audit_log::change,
+                          audit_log::changed_by,
+                          audit_log::edit_token_id,
+                          audit_log::changed_at);
+const AUDIT_ENTRY_COLUMNS: AuditEntryColumns = (audit_log::id,
+                                                audit_log::instance_id,
+                                                audit_log::program_id,
+                                                audit_log::program_name,
+                                                audit_log::change,
+                                                audit_log::changed_by,
+                                                audit_log::edit_token_id,
+                                                audit_log::changed_at);
+
+pub struct PostgresStorage {
+    pool: Pool,
+}
+
+impl PostgresStorage {
+    pub fn connect(database_url: &str) -> StorageResult<Self> {
+        let manager = r2d2::ConnectionManager::<PgConnection>::new(database_url);
+        Ok(Self { pool: r2d2::Pool::builder().build(manager)? })
+    }
+}
+
+impl Storage for PostgresStorage {
+    fn load_instances(&self) -> StorageResult<Vec<Instance>> {
+        Ok(instances::table.select(INSTANCE_COLUMNS)
+                           .order(instances::id)
+                           .load(&*self.pool.get()?)?)
+    }
+
+    fn load_instance(&self, instance_id: i32) -> StorageResult<Instance> {
+        Ok(instances::table.find(instance_id)
+                           .select(INSTANCE_COLUMNS)
+                           .first(&*self.pool.get()?)?)
+    }
+
+    fn create_instance(&self) -> StorageResult<Instance> {
+        Ok(diesel::insert_into(instances::table).default_values()
+                                                .returning(INSTANCE_COLUMNS)
+                                                .get_result(&*self.pool.get()?)?)
+    }
+
+    fn set_instance_disabled(&self, instance_id: i32, disabled: bool) -> StorageResult<()> {
+        diesel::update(instances::table.find(instance_id)).set(instances::disabled.eq(disabled))
+                                                          .execute(&*self.pool.get()?)?;
+        Ok(())
+    }
+
+    fn bump_token_generation(&self, instance_id: i32) -> StorageResult<Instance> {
+        Ok(diesel::update(instances::table.find(instance_id))
+            .set(instances::token_generation.eq(instances::token_generation + 1))
+            .returning(INSTANCE_COLUMNS)
+            .get_result(&*self.pool.get()?)?)
+    }
+
+    fn insert_code(&self, new_code: NewCode) -> StorageResult<()> {
+        diesel::insert_into(codes::table).values((codes::added_by.eq(new_code.added_by),
+                                                  codes::code.eq(new_code.code),
+                                                  codes::instance_id.eq(new_code.instance_id)))
+                                         .execute(&*self.pool.get()?)?;
+        Ok(())
+    }
+
+    fn load_latest_code(&self, instance_id: i32) -> StorageResult<Option<Code>> {
+        Ok(codes::table.filter(codes::instance_id.eq(instance_id))
+                       .select(CODE_COLUMNS)
+                       .order(codes::id.desc())
+                       .first(&*self.pool.get()?)
+                       .optional()?)
+    }
+
+    fn load_service_configs(&self) -> StorageResult<Vec<ServiceConfig>> {
+        Ok(service_configs::table.select(SERVICE_CONFIG_COLUMNS)
+                                 .order(service_configs::id)
+                                 .load(&*self.pool.get()?)?)
+    }
+
+    fn load_service_configs_for_instance(&self,
+                                         instance_id: i32)
+                                         -> StorageResult<Vec<ServiceConfig>> {
+        Ok(service_configs::table.filter(service_configs::instance_id.eq(instance_id))
+                                 .select(SERVICE_CONFIG_COLUMNS)
+                                 .order(service_configs::id)
+                                 .load(&*self.pool.get()?)?)
+    }
+
+    fn insert_service_config(&self,
+                             new_service_config: NewServiceConfig)
+                             -> StorageResult<ServiceConfig> {
+        let NewServiceConfig { instance_id,
+                               nickname,
+                               service_type,
+                               config, } = new_service_config;
+        Ok(diesel::insert_into(service_configs::table)
+            .values((service_configs::instance_id.eq(instance_id),
+                     service_configs::nickname.eq(nickname),
+                     service_configs::service_type.eq(service_type),
+                     service_configs::config.eq(config)))
+            .returning(SERVICE_CONFIG_COLUMNS)
+            .get_result(&*self.pool.get()?)?)
+    }
+
+    fn delete_service_config(&self, service_config_id: i32) -> StorageResult<ServiceConfig> {
+        Ok(diesel::delete(service_configs::table.find(service_config_id))
+            .returning(SERVICE_CONFIG_COLUMNS)
+            .get_result(&*self.pool.get()?)?)
+    }
+
+    fn insert_audit_entries(&self, new_audit_entries: Vec<NewAuditEntry>) -> StorageResult<()> {
+        if new_audit_entries.is_empty() {
+            return Ok(());
+        }
+        let rows = new_audit_entries.into_iter()
+                                    .map(|entry| {
+                                        (audit_log::instance_id.eq(entry.instance_id),
+                                         audit_log::program_id.eq(entry.program_id),
+                                         audit_log::program_name.eq(entry.program_name),
+                                         audit_log::change.eq(entry.change),
+                                         audit_log::changed_by.eq(entry.changed_by),
+                                         audit_log::edit_token_id.eq(entry.edit_token_id),
+                                         audit_log::changed_at.eq(entry.changed_at))
+                                    })
+                                    .collect::<Vec<_>>();
+        diesel::insert_into(audit_log::table).values(rows)
+                                             .execute(&*self.pool.get()?)?;
+        Ok(())
+    }
+
+    fn load_audit_log(&self, instance_id: i32) -> StorageResult<Vec<AuditEntry>> {
+        Ok(audit_log::table.filter(audit_log::instance_id.eq(instance_id))
+                           .select(AUDIT_ENTRY_COLUMNS)
+                           .order(audit_log::id.desc())
+                           .load(&*self.pool.get()?)?)
+    }
+
+    fn revoke_edit_token(&self, token_id: String) -> StorageResult<()> {
+        diesel::insert_into(revoked_edit_tokens::table)
+            .values(revoked_edit_tokens::token_id.eq(token_id))
+            .on_conflict_do_nothing()
+            .execute(&*self.pool.get()?)?;
+        Ok(())
+    }
+
+    fn is_edit_token_revoked(&self, token_id: String) -> StorageResult<bool> {
+        let revoked = revoked_edit_tokens::table.find(token_id)
+                                                .select(revoked_edit_tokens::token_id)
+                                                .first::<String>(&*self.pool.get()?)
+                                                .optional()?;
+        Ok(revoked.is_some())
+    }
+}
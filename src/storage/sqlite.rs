@@ -0,0 +1,296 @@
+// the same tables as the postgres migrations, except JSON is kept as text, and sqlite doesn't have
+// RETURNING, so inserts look up what they inserted afterwards
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::r2d2;
+use diesel::sqlite::SqliteConnection;
+
+use super::{
+    AuditEntry, Code, Instance, NewAuditEntry, NewCode, NewServiceConfig, ServiceConfig, Storage,
+    StorageError, StorageResult,
+};
+
+mod schema {
+    table! {
+        audit_log (id) {
+            id -> Integer,
+            instance_id -> Integer,
+            program_id -> Text,
+            program_name -> Text,
+            change -> Text,
+            changed_by -> Text,
+            edit_token_id -> Text,
+            changed_at -> BigInt,
+        }
+    }
+
+    table! {
+        codes (id) {
+            id -> Integer,
+            added_by -> Text,
+            code -> Text,
+            instance_id -> Integer,
+        }
+    }
+
+    table! {
+        instances (id) {
+            id -> Integer,
+            disabled -> Bool,
+            token_generation -> Integer,
+        }
+    }
+
+    table! {
+        revoked_edit_tokens (token_id) {
+            token_id -> Text,
+        }
+    }
+
+    table! {
+        service_configs (id) {
+            id -> Integer,
+            nickname -> Text,
+            instance_id -> Integer,
+            service_type -> Text,
+            config -> Text,
+        }
+    }
+}
+
+use self::schema::{audit_log, codes, instances, revoked_edit_tokens, service_configs};
+
+const CREATE_TABLES: &str = "
+CREATE TABLE IF NOT EXISTS instances (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  disabled BOOLEAN NOT NULL DEFAULT FALSE,
+  token_generation INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS codes (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  added_by TEXT NOT NULL,
+  code TEXT NOT NULL,
+  instance_id INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS service_configs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  nickname TEXT NOT NULL,
+  instance_id INTEGER NOT NULL,
+  service_type TEXT NOT NULL,
+  config TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  instance_id INTEGER NOT NULL,
+  program_id TEXT NOT NULL,
+  program_name TEXT NOT NULL,
+  change TEXT NOT NULL,
+  changed_by TEXT NOT NULL,
+  edit_token_id TEXT NOT NULL,
+  changed_at BIGINT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_instance_id ON audit_log (instance_id);
+
+CREATE TABLE IF NOT EXISTS revoked_edit_tokens (
+  token_id TEXT PRIMARY KEY,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+";
+
+type Pool = r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>;
+type PooledConnection = r2d2::PooledConnection<r2d2::ConnectionManager<SqliteConnection>>;
+
+type CodeRow = (i32, String, String, i32);
+type ServiceConfigRow = (i32, String, i32, String, String);
+
+pub struct SqliteStorage {
+    pool: Pool,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> StorageResult<Self> {
+        let manager = r2d2::ConnectionManager::<SqliteConnection>::new(path);
+        // sqlite only lets one connection write at a time anyway, and with only one connection,
+        // looking up the last inserted row can't pick up somebody else's
+        let pool = r2d2::Pool::builder().max_size(1).build(manager)?;
+        pool.get()?.batch_execute(CREATE_TABLES)?;
+        Ok(Self { pool })
+    }
+
+    fn conn(&self) -> StorageResult<PooledConnection> {
+        Ok(self.pool.get()?)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_instances(&self) -> StorageResult<Vec<Instance>> {
+        Ok(instances::table.order(instances::id).load(&*self.conn()?)?)
+    }
+
+    fn load_instance(&self, instance_id: i32) -> StorageResult<Instance> {
+        Ok(instances::table.find(instance_id).first(&*self.conn()?)?)
+    }
+
+    fn create_instance(&self) -> StorageResult<Instance> {
+        let conn = self.conn()?;
+        conn.transaction(|| {
+                diesel::insert_into(instances::table).default_values()
+                                                     .execute(&*conn)?;
+                Ok(instances::table.order(instances::id.desc()).first(&*conn)?)
+            })
+    }
+
+    fn set_instance_disabled(&self, instance_id: i32, disabled: bool) -> StorageResult<()> {
+        diesel::update(instances::table.find(instance_id)).set(instances::disabled.eq(disabled))
+                                                          .execute(&*self.conn()?)?;
+        Ok(())
+    }
+
+    fn bump_token_generation(&self, instance_id: i32) -> StorageResult<Instance> {
+        let conn = self.conn()?;
+        conn.transaction(|| {
+                diesel::update(instances::table.find(instance_id))
+                    .set(instances::token_generation.eq(instances::token_generation + 1))
+                    .execute(&*conn)?;
+                Ok(instances::table.find(instance_id).first(&*conn)?)
+            })
+    }
+
+    fn insert_code(&self, new_code: NewCode) -> StorageResult<()> {
+        let code = serde_json::to_string(&new_code.code)?;
+        diesel::insert_into(codes::table).values((codes::added_by.eq(new_code.added_by),
+                                                  codes::code.eq(code),
+                                                  codes::instance_id.eq(new_code.instance_id)))
+                                         .execute(&*self.conn()?)?;
+        Ok(())
+    }
+
+    fn load_latest_code(&self, instance_id: i32) -> StorageResult<Option<Code>> {
+        let code_row = codes::table.filter(codes::instance_id.eq(instance_id))
+                                   .order(codes::id.desc())
+                                   .first::<CodeRow>(&*self.conn()?)
+                                   .optional()?;
+        code_row.map(code_from_row).transpose()
+    }
+
+    fn load_service_configs(&self) -> StorageResult<Vec<ServiceConfig>> {
+        service_configs::table.order(service_configs::id)
+                              .load::<ServiceConfigRow>(&*self.conn()?)?
+                              .into_iter()
+                              .map(service_config_from_row)
+                              .collect()
+    }
+
+    fn load_service_configs_for_instance(&self,
+                                         instance_id: i32)
+                                         -> StorageResult<Vec<ServiceConfig>> {
+        service_configs::table.filter(service_configs::instance_id.eq(instance_id))
+                              .order(service_configs::id)
+                              .load::<ServiceConfigRow>(&*self.conn()?)?
+                              .into_iter()
+                              .map(service_config_from_row)
+                              .collect()
+    }
+
+    fn insert_service_config(&self,
+                             new_service_config: NewServiceConfig)
+                             -> StorageResult<ServiceConfig> {
+        let NewServiceConfig { instance_id,
+                               nickname,
+                               service_type,
+                               config, } = new_service_config;
+        let config = serde_json::to_string(&config)?;
+        let conn = self.conn()?;
+        let row = conn.transaction::<_, StorageError, _>(|| {
+                          diesel::insert_into(service_configs::table)
+                              .values((service_configs::instance_id.eq(instance_id),
+                                       service_configs::nickname.eq(nickname),
+                                       service_configs::service_type.eq(service_type),
+                                       service_configs::config.eq(config)))
+                              .execute(&*conn)?;
+                          Ok(service_configs::table.order(service_configs::id.desc())
+                                                   .first::<ServiceConfigRow>(&*conn)?)
+                      })?;
+        service_config_from_row(row)
+    }
+
+    fn delete_service_config(&self, service_config_id: i32) -> StorageResult<ServiceConfig> {
+        let conn = self.conn()?;
+        let row = conn.transaction::<_, StorageError, _>(|| {
+                          let row = service_configs::table.find(service_config_id)
+                                                          .first::<ServiceConfigRow>(&*conn)?;
+                          diesel::delete(service_configs::table.find(service_config_id))
+                              .execute(&*conn)?;
+                          Ok(row)
+                      })?;
+        service_config_from_row(row)
+    }
+
+    fn insert_audit_entries(&self, new_audit_entries: Vec<NewAuditEntry>) -> StorageResult<()> {
+        let conn = self.conn()?;
+        // sqlite can only insert one row at a time
+        conn.transaction(|| {
+                for entry in new_audit_entries {
+                    diesel::insert_into(audit_log::table)
+                        .values((audit_log::instance_id.eq(entry.instance_id),
+                                 audit_log::program_id.eq(entry.program_id),
+                                 audit_log::program_name.eq(entry.program_name),
+                                 audit_log::change.eq(entry.change),
+                                 audit_log::changed_by.eq(entry.changed_by),
+                                 audit_log::edit_token_id.eq(entry.edit_token_id),
+                                 audit_log::changed_at.eq(entry.changed_at)))
+                        .execute(&*conn)?;
+                }
+                Ok(())
+            })
+    }
+
+    fn load_audit_log(&self, instance_id: i32) -> StorageResult<Vec<AuditEntry>> {
+        Ok(audit_log::table.filter(audit_log::instance_id.eq(instance_id))
+                           .order(audit_log::id.desc())
+                           .load(&*self.conn()?)?)
+    }
+
+    fn revoke_edit_token(&self, token_id: String) -> StorageResult<()> {
+        diesel::insert_or_ignore_into(revoked_edit_tokens::table)
+            .values(revoked_edit_tokens::token_id.eq(token_id))
+            .execute(&*self.conn()?)?;
+        Ok(())
+    }
+
+    fn is_edit_token_revoked(&self, token_id: String) -> StorageResult<bool> {
+        let revoked = revoked_edit_tokens::table.find(token_id)
+                                                .first::<String>(&*self.conn()?)
+                                                .optional()?;
+        Ok(revoked.is_some())
+    }
+}
+
+fn code_from_row((id, added_by, code, instance_id): CodeRow) -> StorageResult<Code> {
+    Ok(Code { id,
+              added_by,
+              code: serde_json::from_str(&code)?,
+              instance_id })
+}
+
+fn service_config_from_row((id, nickname, instance_id, service_type, config): ServiceConfigRow)
+                           -> StorageResult<ServiceConfig> {
+    Ok(ServiceConfig { id,
+                       nickname,
+                       instance_id,
+                       service_type,
+                       config: serde_json::from_str(&config)? })
+}
@@ -51,6 +51,35 @@ pub fn can_be_run(func: &dyn lang::Function, env_genie: &EnvGenie) -> bool {
                   })
 }
 
+// things that would make a world blow up once it's loaded
+#[derive(Debug)]
+pub enum WorldProblem {
+    MissingFunction {
+        caller_id: lang::ID,
+        missing_id: lang::ID,
+    },
+}
+
+pub fn find_problems_in_world(env_genie: &EnvGenie) -> Vec<WorldProblem> {
+    let mut problems = vec![];
+    for func in env_genie.all_functions() {
+        for code_block in func.cs_code() {
+            // TODO: find a way not to clone in here
+            let code = lang::CodeNode::Block(code_block.clone());
+            for code_node in code.self_with_all_children_dfs() {
+                if let Ok(function_reference) = code_node.as_function_reference() {
+                    let missing_id = function_reference.function_id;
+                    if env_genie.find_function(missing_id).is_none() {
+                        problems.push(WorldProblem::MissingFunction { caller_id: func.id(),
+                                                                      missing_id });
+                    }
+                }
+            }
+        }
+    }
+    problems
+}
+
 fn find_placeholder_nodes(root: &lang::CodeNode) -> impl Iterator<Item = lang::ID> + '_ {
     root.self_with_all_children_dfs()
         .filter_map(|code_node| match code_node {
@@ -0,0 +1,239 @@
+use lazy_static::lazy_static;
+use maplit::{btreemap, hashmap};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::pin::Pin;
+use uuid;
+
+use super::builtins::{WEBHOOK_REQUEST_STRUCT_ID, WEBHOOK_RESPONSE_STRUCT_ID};
+use super::env;
+use super::lang;
+use crate::builtins::new_struct_value;
+use crate::chat_program::ReplyContext;
+use crate::chat_service::ReplyOrigin;
+use crate::env::Interpreter;
+use crate::lang::Function;
+use crate::logging;
+use crate::logging::ProgramRun;
+use crate::{validation, EnvGenie};
+
+lazy_static! {
+    static ref REQUEST_ARG_ID: lang::ID =
+        uuid::Uuid::parse_str("3f0d4c77-52b8-4e0a-9a3e-c5a1f1f6d2b9").unwrap();
+
+    // Webhook Request struct field IDs
+    static ref REQUEST_METHOD_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("0a6b9f61-1d2c-4f4e-b5a8-7c3e2d9f8a10").unwrap();
+    static ref REQUEST_PATH_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("9c2e5a14-6b7d-4c3f-8e1a-2f4b6d8c0e35").unwrap();
+    static ref REQUEST_QUERY_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("5e8f1a2b-3c4d-4e6f-9a0b-1c2d3e4f5a67").unwrap();
+    static ref REQUEST_HEADERS_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("b7c8d9e0-f1a2-4b3c-8d4e-5f6a7b8c9d01").unwrap();
+    static ref REQUEST_BODY_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("e2f3a4b5-c6d7-4e8f-9a0b-c1d2e3f4a5b6").unwrap();
+
+    // Webhook Response struct field IDs
+    static ref RESPONSE_STATUS_CODE_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("4d5e6f70-8192-4a3b-bc4d-5e6f708192a3").unwrap();
+    static ref RESPONSE_BODY_FIELD_ID: lang::ID =
+        uuid::Uuid::parse_str("c1d2e3f4-a5b6-4c7d-8e9f-0a1b2c3d4e5f").unwrap();
+}
+
+// a program that runs when someone makes an HTTP request to
+// /hooks/<instance id>/<route>. it's handed everything about the request and whatever it
+// evaluates to is sent back as the response
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct WebhookProgram {
+    pub id: lang::ID,
+    pub route: String,
+    pub code: lang::Block,
+}
+
+// the HTTP server runs on a different thread than the interpreter, so requests and responses
+// cross over as plain rust values
+#[derive(Debug, Clone)]
+pub struct WebhookRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookResponse {
+    pub status_code: u16,
+    pub body: String,
+}
+
+impl WebhookResponse {
+    pub fn new(status_code: u16, body: String) -> Self {
+        Self { status_code, body }
+    }
+
+    fn from_value(value: &lang::Value) -> Option<Self> {
+        let (_, values) = value.as_struct()?;
+        let status_code = values.0.get(&RESPONSE_STATUS_CODE_FIELD_ID)?.as_i128()?;
+        let body = values.0.get(&RESPONSE_BODY_FIELD_ID)?.as_str().ok()?;
+        // anything else isn't an HTTP status code, and can't be sent back to whoever called
+        if !(100..=599).contains(&status_code) {
+            return Some(Self::new(500,
+                                  format!("webhook program returned an invalid status code: {}",
+                                          status_code)));
+        }
+        Some(Self::new(status_code as u16, body.to_owned()))
+    }
+}
+
+impl WebhookRequest {
+    fn into_value(self) -> lang::Value {
+        new_struct_value(*WEBHOOK_REQUEST_STRUCT_ID,
+                         lang::StructValues(btreemap! {
+                                                *REQUEST_METHOD_FIELD_ID => lang::Value::String(self.method),
+                                                *REQUEST_PATH_FIELD_ID => lang::Value::String(self.path),
+                                                *REQUEST_QUERY_FIELD_ID => string_map_value(self.query),
+                                                *REQUEST_HEADERS_FIELD_ID => string_map_value(self.headers),
+                                                *REQUEST_BODY_FIELD_ID => lang::Value::String(self.body),
+                                            }))
+    }
+}
+
+fn string_map_value(pairs: Vec<(String, String)>) -> lang::Value {
+    let string_type = lang::Type::from_spec(&*lang::STRING_TYPESPEC);
+    lang::Value::Map { from: string_type.clone(),
+                       to: string_type,
+                       value: pairs.into_iter()
+                                   .map(|(k, v)| (lang::Value::String(k), lang::Value::String(v)))
+                                   .collect::<BTreeMap<_, _>>() }
+}
+
+impl WebhookProgram {
+    pub fn new() -> Self {
+        Self { id: lang::new_id(),
+               route: "".to_string(),
+               code: lang::Block::new() }
+    }
+
+    // routes are matched case sensitively, ignoring any slashes around them
+    pub fn matches_route(&self, route: &str) -> bool {
+        !self.route.is_empty() && self.route.trim_matches('/') == route.trim_matches('/')
+    }
+
+    pub fn try_to_trigger(&self,
+                          interpreter: env::Interpreter,
+                          route: &str,
+                          request: WebhookRequest)
+                          -> Option<lang::Value> {
+        if !self.matches_route(route) {
+            return None;
+        }
+
+        let can_be_run = {
+            let env = interpreter.env.borrow();
+            let env_genie = EnvGenie::new(&env);
+            validation::can_be_run(self, &env_genie)
+        };
+        if !can_be_run {
+            logging::warn("not running a webhook program that would crash",
+                          json!({ "program_id": self.id, "route": self.route }));
+            return None;
+        }
+
+        Some(self.call(interpreter,
+                       hashmap! {*REQUEST_ARG_ID => request.into_value()}))
+    }
+}
+
+#[typetag::serde]
+impl lang::Function for WebhookProgram {
+    fn call(&self,
+            mut interpreter: env::Interpreter,
+            args: HashMap<lang::ID, lang::Value>)
+            -> lang::Value {
+        // XXX: shouldn't the caller do this???? duped with CodeFunction
+        for (id, value) in args {
+            interpreter.set_local_variable(id, value);
+        }
+
+        let code = self.code.clone();
+        lang::Value::new_future(async move { interpreter.evaluate(&lang::CodeNode::Block(code)).await })
+    }
+
+    fn name(&self) -> &str {
+        &self.route
+    }
+
+    fn description(&self) -> &str {
+        "Runs when an HTTP request is made to this webhook's route"
+    }
+
+    fn id(&self) -> lang::ID {
+        self.id
+    }
+
+    fn takes_args(&self) -> Vec<lang::ArgumentDefinition> {
+        vec![lang::ArgumentDefinition { id: *REQUEST_ARG_ID,
+                                        arg_type:
+                                            lang::Type::from_spec_id(*WEBHOOK_REQUEST_STRUCT_ID,
+                                                                     vec![]),
+                                        short_name: "Request".to_string() }]
+    }
+
+    fn returns(&self) -> lang::Type {
+        lang::Type::from_spec_id(*WEBHOOK_RESPONSE_STRUCT_ID, vec![])
+    }
+
+    fn cs_code(&self) -> Box<dyn Iterator<Item = &lang::Block> + '_> {
+        Box::new(std::iter::once(&self.code))
+    }
+}
+
+const CANT_BE_RUN_MESSAGE: &str = "The code behind this webhook has some issues and cannot be run.";
+const CRASHED_MESSAGE: &str = "The code behind this webhook crashed.";
+
+// returns None if there's no webhook program for this route, so the server can 404
+pub fn request_received(interp: &Interpreter,
+                        route: &str,
+                        request: WebhookRequest)
+                        -> Option<Pin<Box<dyn std::future::Future<Output = WebhookResponse>>>> {
+    // this is mandatory or else we'll borrow the Env for too long
+    let webhook_program = {
+        let env = interp.env.borrow();
+        let env_genie = EnvGenie::new(&env);
+        env_genie.list_webhook_programs()
+                 .find(|wp| wp.matches_route(route))
+                 .cloned()?
+    };
+    // a webhook wasn't set off from chat, so there's nowhere for replies to go. they get caught
+    // here instead of piling up in the Reply builtin's buffer forever
+    let reply_context = ReplyContext::new("".to_owned(), ReplyOrigin::channel("".to_owned()));
+    let interp = interp.new_stack_frame()
+                       .with_reply_context(reply_context.clone())
+                       .with_program_id(webhook_program.id);
+    let triggered_value = webhook_program.try_to_trigger(interp, route, request);
+    let program_run = ProgramRun::new(&webhook_program, "webhook", None);
+    let program_id = webhook_program.id;
+
+    Some(Box::pin(async move {
+             let value = match triggered_value {
+                 Some(value) => program_run.finish(value).await,
+                 None => return WebhookResponse::new(500, CANT_BE_RUN_MESSAGE.to_string()),
+             };
+             let dropped_replies = reply_context.take_replies();
+             if !dropped_replies.is_empty() {
+                 logging::warn("dropping chat replies from a webhook program",
+                               json!({ "program_id": program_id,
+                                       "num_replies": dropped_replies.len() }));
+             }
+             // the error's in the logs. it's not for whoever's calling the webhook
+             let value = match value {
+                 Ok(value) => value,
+                 Err(_) => return WebhookResponse::new(500, CRASHED_MESSAGE.to_string()),
+             };
+             WebhookResponse::from_value(&value).unwrap_or_else(|| {
+                 WebhookResponse::new(500, "webhook program didn't return a response".to_string())
+             })
+         }))
+}
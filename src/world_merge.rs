@@ -0,0 +1,436 @@
+// three-way merging of world files. everything in a world that can be edited has an ID: functions,
+// typespecs, tests, scripts (by their code's ID) and every node of code. so instead of merging
+// lines of JSON, lists of things with IDs get lined up by ID, and the things themselves get merged
+// field by field. when both sides changed the same thing in different ways, the closest thing
+// with an ID around the change becomes a conflict, and the user picks which side to keep
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+const SECTIONS: [&str; 4] = ["scripts", "tests", "functions", "typespecs"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    // the ID of the thing in conflict. resolutions are keyed by this
+    pub id: String,
+    // the function, typespec, script or test the conflict is inside of
+    pub item: String,
+    // None means that side deleted it
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+impl Conflict {
+    pub fn describe(&self) -> String {
+        let changed_both_ways = self.ours.is_some() && self.theirs.is_some();
+        let kind = self.ours
+                       .as_ref()
+                       .or(self.theirs.as_ref())
+                       .map(describe_node)
+                       .unwrap_or_else(|| "something".to_owned());
+        if changed_both_ways {
+            format!("{}: {} changed on both sides", self.item, kind)
+        } else {
+            format!("{}: {} deleted on one side and changed on the other",
+                    self.item, kind)
+        }
+    }
+
+    pub fn side(&self, side: Side) -> Option<&Value> {
+        match side {
+            Side::Ours => self.ours.as_ref(),
+            Side::Theirs => self.theirs.as_ref(),
+        }
+    }
+}
+
+pub struct Merge {
+    // conflicts that haven't been resolved are filled in with our side
+    pub merged: Value,
+    pub conflicts: Vec<Conflict>,
+}
+
+// a merge that needs the user to resolve conflicts. the git merge driver writes this out in place
+// of the world file, and the editor picks it back up from there when the file gets opened
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingMerge {
+    pub base: Value,
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PendingMergeFile {
+    pending_merge: PendingMerge,
+}
+
+impl PendingMerge {
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(&PendingMergeFile { pending_merge: self.clone() }).unwrap()
+    }
+
+    pub fn merge(&self, resolutions: &HashMap<String, Side>) -> Merge {
+        merge(&self.base, &self.ours, &self.theirs, resolutions)
+    }
+}
+
+// None if the contents are a regular world file
+pub fn read_pending_merge(contents: &str) -> Option<PendingMerge> {
+    serde_json::from_str::<PendingMergeFile>(contents).ok()
+                                                      .map(|file| file.pending_merge)
+}
+
+pub fn merge(base: &Value,
+             ours: &Value,
+             theirs: &Value,
+             resolutions: &HashMap<String, Side>)
+             -> Merge {
+    let mut merger = Merger { resolutions,
+                              conflicts: vec![],
+                              item: String::new() };
+    let mut merged = Map::new();
+    for section in SECTIONS.iter() {
+        let list = |world: &Value| {
+            world.get(section)
+                 .and_then(Value::as_array)
+                 .cloned()
+                 .unwrap_or_default()
+        };
+        let (base, ours, theirs) = (list(base), list(ours), list(theirs));
+        let merged_section = match merger.merge_list(&base, &ours, &theirs, Some(section)) {
+            Ok(merged_section) => merged_section,
+            // only happens if something in the world doesn't have an ID, in which case the
+            // whole section has to be picked from one side or the other
+            Err(Unmergeable) => {
+                merger.item = (*section).to_owned();
+                merger.conflict(section,
+                                Some(&Value::Array(base)),
+                                Some(&Value::Array(ours)),
+                                Some(&Value::Array(theirs)))
+                      .unwrap_or_default()
+                      .as_array()
+                      .cloned()
+                      .unwrap_or_default()
+            }
+        };
+        merged.insert((*section).to_owned(), Value::Array(merged_section));
+    }
+    Merge { merged: Value::Object(merged),
+            conflicts: merger.conflicts }
+}
+
+// the two sides can't be merged at this level, so it's up to the closest thing with an ID above
+// to become a conflict
+struct Unmergeable;
+
+struct Merger<'a> {
+    resolutions: &'a HashMap<String, Side>,
+    conflicts: Vec<Conflict>,
+    item: String,
+}
+
+impl<'a> Merger<'a> {
+    fn merge_value(&mut self,
+                   base: Option<&Value>,
+                   ours: Option<&Value>,
+                   theirs: Option<&Value>)
+                   -> Result<Option<Value>, Unmergeable> {
+        if ours == theirs || base == theirs {
+            return Ok(ours.cloned());
+        }
+        if base == ours {
+            return Ok(theirs.cloned());
+        }
+        let merged = match (base, ours, theirs) {
+            (Some(base), Some(ours), Some(theirs)) => self.merge_children(base, ours, theirs),
+            _ => Err(Unmergeable),
+        };
+        match merged {
+            Ok(merged) => Ok(Some(merged)),
+            Err(Unmergeable) => {
+                let id = ours.or(theirs).and_then(node_id).ok_or(Unmergeable)?;
+                Ok(self.conflict(&id, base, ours, theirs))
+            }
+        }
+    }
+
+    fn merge_children(&mut self,
+                      base: &Value,
+                      ours: &Value,
+                      theirs: &Value)
+                      -> Result<Value, Unmergeable> {
+        match (base, ours, theirs) {
+            (Value::Object(base), Value::Object(ours), Value::Object(theirs)) => {
+                // a node that turned into a different kind of node on either side
+                if is_wrapper(ours)
+                   && (ours.keys().ne(theirs.keys()) || ours.keys().ne(base.keys()))
+                {
+                    return Err(Unmergeable);
+                }
+                let keys = ours.keys()
+                               .chain(theirs.keys().filter(|key| !ours.contains_key(*key)))
+                               .cloned()
+                               .collect::<Vec<_>>();
+                let mut merged = Map::new();
+                for key in keys {
+                    if let Some(value) =
+                        self.merge_value(base.get(&key), ours.get(&key), theirs.get(&key))?
+                    {
+                        merged.insert(key, value);
+                    }
+                }
+                Ok(Value::Object(merged))
+            }
+            (Value::Array(base), Value::Array(ours), Value::Array(theirs)) => {
+                Ok(Value::Array(self.merge_list(base, ours, theirs, None)?))
+            }
+            _ => Err(Unmergeable),
+        }
+    }
+
+    // lines the lists up by ID. the order comes from our side, with anything only they have going
+    // right after whatever comes before it on their side. section is set for the lists at the top
+    // of the world, to keep track of which item conflicts are in
+    fn merge_list(&mut self,
+                  base: &[Value],
+                  ours: &[Value],
+                  theirs: &[Value],
+                  section: Option<&str>)
+                  -> Result<Vec<Value>, Unmergeable> {
+        let base = by_id(base)?;
+        let ours = by_id(ours)?;
+        let theirs = by_id(theirs)?;
+        let mut order = ours.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+        for (i, (id, _)) in theirs.iter().enumerate() {
+            if order.contains(id) {
+                continue;
+            }
+            let position =
+                theirs[..i].iter()
+                           .rev()
+                           .find_map(|(prev_id, _)| order.iter().position(|id| id == prev_id))
+                           .map_or(0, |position| position + 1);
+            order.insert(position, id.clone());
+        }
+
+        let mut merged = vec![];
+        for id in order {
+            let (base, ours, theirs) = (find(&base, &id), find(&ours, &id), find(&theirs, &id));
+            if let Some(section) = section {
+                self.item = describe_item(section, ours.or(theirs).or(base).unwrap());
+            }
+            // every element has an ID, so conflicts get caught here at the latest
+            if let Some(value) = self.merge_value(base, ours, theirs)? {
+                merged.push(value);
+            }
+        }
+        Ok(merged)
+    }
+
+    fn conflict(&mut self,
+                id: &str,
+                base: Option<&Value>,
+                ours: Option<&Value>,
+                theirs: Option<&Value>)
+                -> Option<Value> {
+        match self.resolutions.get(id) {
+            Some(Side::Ours) => ours.cloned(),
+            Some(Side::Theirs) => theirs.cloned(),
+            None => {
+                self.conflicts.push(Conflict { id: id.to_owned(),
+                                               item: self.item.clone(),
+                                               base: base.cloned(),
+                                               ours: ours.cloned(),
+                                               theirs: theirs.cloned() });
+                ours.cloned()
+            }
+        }
+    }
+}
+
+fn find<'a>(list: &[(String, &'a Value)], id: &str) -> Option<&'a Value> {
+    list.iter()
+        .find(|(list_id, _)| list_id == id)
+        .map(|(_, value)| *value)
+}
+
+fn by_id(list: &[Value]) -> Result<Vec<(String, &Value)>, Unmergeable> {
+    list.iter()
+        .map(|value| Ok((node_id(value).ok_or(Unmergeable)?, value)))
+        .collect()
+}
+
+// code nodes are serialized like {"FunctionCall": {"id": ..., ...}}, so for those the ID is one
+// level down. scripts don't have IDs of their own, they go by their code's
+fn node_id(value: &Value) -> Option<String> {
+    let obj = value.as_object()?;
+    if let Some(id) = obj.get("id").and_then(Value::as_str) {
+        return Some(id.to_owned());
+    }
+    if is_wrapper(obj) {
+        return node_id(obj.values().next()?);
+    }
+    node_id(obj.get("code")?)
+}
+
+fn is_wrapper(obj: &Map<String, Value>) -> bool {
+    obj.len() == 1 && obj.values().all(|value| value.get("id").is_some())
+}
+
+fn describe_item(section: &str, value: &Value) -> String {
+    let kind = value.get("type")
+                    .and_then(Value::as_str)
+                    .unwrap_or_else(|| section.trim_end_matches('s'));
+    let name = value.get("name")
+                    .and_then(Value::as_str)
+                    .map(ToOwned::to_owned)
+                    .or_else(|| node_id(value))
+                    .unwrap_or_default();
+    format!("{} {}", kind, name)
+}
+
+pub fn describe_node(value: &Value) -> String {
+    match value.as_object() {
+        Some(obj) if is_wrapper(obj) => obj.keys().next().unwrap().clone(),
+        Some(obj) if obj.contains_key("expressions") => "Block".to_owned(),
+        Some(obj) => obj.get("type")
+                        .and_then(Value::as_str)
+                        .unwrap_or("node")
+                        .to_owned(),
+        None => "value".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn function(id: &str, name: &str, description: &str) -> Value {
+        json!({ "id": id, "name": name, "description": description })
+    }
+
+    fn world(functions: Vec<Value>) -> Value {
+        json!({ "scripts": [], "tests": [], "functions": functions, "typespecs": [] })
+    }
+
+    fn merge_worlds(base: &Value, ours: &Value, theirs: &Value) -> Merge {
+        merge(base, ours, theirs, &HashMap::new())
+    }
+
+    #[test]
+    fn changes_to_different_things_merge_cleanly() {
+        let base = world(vec![function("a", "a", ""), function("b", "b", "")]);
+        let ours = world(vec![function("a", "ours", ""), function("b", "b", "")]);
+        let theirs = world(vec![function("a", "a", ""), function("b", "theirs", "")]);
+        let merge = merge_worlds(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged,
+                   world(vec![function("a", "ours", ""), function("b", "theirs", "")]));
+    }
+
+    #[test]
+    fn changes_to_different_fields_of_the_same_thing_merge_cleanly() {
+        let base = world(vec![function("a", "a", "")]);
+        let ours = world(vec![function("a", "ours", "")]);
+        let theirs = world(vec![function("a", "a", "theirs")]);
+        let merge = merge_worlds(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, world(vec![function("a", "ours", "theirs")]));
+    }
+
+    #[test]
+    fn the_same_change_on_both_sides_isnt_a_conflict() {
+        let base = world(vec![function("a", "a", "")]);
+        let changed = world(vec![function("a", "both", "")]);
+        let merge = merge_worlds(&base, &changed, &changed);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, changed);
+    }
+
+    #[test]
+    fn different_changes_to_the_same_thing_conflict() {
+        let base = world(vec![function("a", "a", "")]);
+        let ours = world(vec![function("a", "ours", "")]);
+        let theirs = world(vec![function("a", "theirs", "")]);
+        let merge = merge_worlds(&base, &ours, &theirs);
+        assert_eq!(merge.conflicts.len(), 1);
+        let conflict = &merge.conflicts[0];
+        assert_eq!(conflict.id, "a");
+        assert_eq!(conflict.base, Some(function("a", "a", "")));
+        assert_eq!(conflict.ours, Some(function("a", "ours", "")));
+        assert_eq!(conflict.theirs, Some(function("a", "theirs", "")));
+        // until it's resolved, our side is what's in the merged world
+        assert_eq!(merge.merged, ours);
+    }
+
+    #[test]
+    fn resolved_conflicts_take_the_chosen_side() {
+        let base = world(vec![function("a", "a", "")]);
+        let ours = world(vec![function("a", "ours", "")]);
+        let theirs = world(vec![function("a", "theirs", "")]);
+        let resolutions = vec![("a".to_owned(), Side::Theirs)].into_iter().collect();
+        let merge = merge(&base, &ours, &theirs, &resolutions);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, theirs);
+    }
+
+    #[test]
+    fn deleting_something_the_other_side_changed_conflicts() {
+        let base = world(vec![function("a", "a", ""), function("b", "b", "")]);
+        let ours = world(vec![function("b", "b", "")]);
+        let theirs = world(vec![function("a", "theirs", ""), function("b", "b", "")]);
+        let merge = merge_worlds(&base, &ours, &theirs);
+        assert_eq!(merge.conflicts.len(), 1);
+        let conflict = &merge.conflicts[0];
+        assert_eq!(conflict.id, "a");
+        assert_eq!(conflict.ours, None);
+        assert_eq!(conflict.theirs, Some(function("a", "theirs", "")));
+        assert!(conflict.describe()
+                        .ends_with("deleted on one side and changed on the other"));
+        assert_eq!(merge.merged, ours);
+    }
+
+    #[test]
+    fn deleting_something_the_other_side_didnt_touch_merges_cleanly() {
+        let base = world(vec![function("a", "a", ""), function("b", "b", "")]);
+        let ours = world(vec![function("a", "a", ""), function("b", "ours", "")]);
+        let theirs = world(vec![function("b", "b", "")]);
+        let merge = merge_worlds(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, world(vec![function("b", "ours", "")]));
+    }
+
+    #[test]
+    fn additions_on_both_sides_are_both_kept() {
+        let base = world(vec![function("a", "a", "")]);
+        let ours = world(vec![function("a", "a", ""), function("b", "b", "")]);
+        let theirs = world(vec![function("c", "c", ""), function("a", "a", "")]);
+        let merge = merge_worlds(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        // theirs goes in right after whatever came before it on their side
+        assert_eq!(merge.merged,
+                   world(vec![function("c", "c", ""),
+                              function("a", "a", ""),
+                              function("b", "b", "")]));
+    }
+
+    #[test]
+    fn pending_merges_round_trip_through_the_world_file() {
+        let pending_merge = PendingMerge { base: world(vec![]),
+                                           ours: world(vec![function("a", "a", "")]),
+                                           theirs: world(vec![function("b", "b", "")]) };
+        let contents = pending_merge.to_json().to_string();
+        let read_back = read_pending_merge(&contents).unwrap();
+        assert_eq!(read_back.ours, pending_merge.ours);
+        assert_eq!(read_back.theirs, pending_merge.theirs);
+        assert!(read_pending_merge(&world(vec![]).to_string()).is_none());
+    }
+}